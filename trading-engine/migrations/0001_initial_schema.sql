@@ -1,4 +1,6 @@
--- Database Schema
+-- Base tables
+-- Statements in these migrations are IF NOT EXISTS, so a database created by the old
+-- schema.sql bootstrap adopts them on first run without errors
 
 -- Users table
 CREATE TABLE IF NOT EXISTS users (
@@ -64,28 +66,10 @@ CREATE TABLE IF NOT EXISTS transactions (
     transaction_id VARCHAR(100) PRIMARY KEY,
     user_id BIGINT REFERENCES users(user_id),
     chain VARCHAR(20) NOT NULL,
-    type VARCHAR(20) NOT NULL, -- BUY, SELL
+    type VARCHAR(20) NOT NULL, -- BUY, SELL, TRANSFER
     token_address VARCHAR(255) NOT NULL,
     amount VARCHAR(100) NOT NULL,
     price DOUBLE PRECISION NOT NULL,
     tx_hash VARCHAR(255) NOT NULL,
     timestamp TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
 );
--- Performance Indexes Migration
--- Add indexes for frequently queried columns
-
--- Positions table indexes
-CREATE INDEX IF NOT EXISTS idx_positions_user_status ON positions(user_id, status);
-CREATE INDEX IF NOT EXISTS idx_positions_created ON positions(created_at DESC);
-CREATE INDEX IF NOT EXISTS idx_positions_user_chain ON positions(user_id, chain);
-
--- Transactions table indexes
-CREATE INDEX IF NOT EXISTS idx_transactions_user_chain ON transactions(user_id, chain);
-CREATE INDEX IF NOT EXISTS idx_transactions_timestamp ON transactions(timestamp DESC);
-CREATE INDEX IF NOT EXISTS idx_transactions_user_timestamp ON transactions(user_id, timestamp DESC);
-
--- Wallets table indexes (already has UNIQUE constraint on user_id, chain)
--- No additional index needed
-
--- Users table index
-CREATE INDEX IF NOT EXISTS idx_users_created ON users(created_at DESC);
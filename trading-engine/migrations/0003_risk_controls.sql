@@ -0,0 +1,43 @@
+-- Risk events (rejected trades, for tuning thresholds)
+CREATE TABLE IF NOT EXISTS risk_events (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    event_type VARCHAR(50) NOT NULL, -- PRICE_IMPACT, SLIPPAGE
+    token_address VARCHAR(255),
+    observed_value DOUBLE PRECISION,
+    threshold_value DOUBLE PRECISION,
+    details TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_risk_events_user_type ON risk_events(user_id, event_type);
+
+-- Grid strategy limit per user
+ALTER TABLE risk_profiles ADD COLUMN IF NOT EXISTS max_open_grids INTEGER DEFAULT 3;
+
+-- Per-user trade cooldown (seconds) and per-minute trade cap, 0 disables either
+ALTER TABLE risk_profiles ADD COLUMN IF NOT EXISTS min_seconds_between_trades INTEGER DEFAULT 1;
+ALTER TABLE risk_profiles ADD COLUMN IF NOT EXISTS max_trades_per_minute INTEGER DEFAULT 20;
+
+-- Per-user daily loss tracking, reloaded on startup so the daily loss limit survives restarts
+CREATE TABLE IF NOT EXISTS daily_stats (
+    user_id BIGINT NOT NULL,
+    date VARCHAR(10) NOT NULL,
+    total_loss_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    trade_count INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, date)
+);
+
+-- Risk engine blacklists (loaded into memory at startup)
+CREATE TABLE IF NOT EXISTS token_blacklist (
+    address VARCHAR(100) PRIMARY KEY,
+    reason TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS dev_blacklist (
+    address VARCHAR(100) PRIMARY KEY,
+    reason TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
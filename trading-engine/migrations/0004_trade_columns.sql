@@ -0,0 +1,29 @@
+-- Columns written by the trade handlers
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS profit_loss DOUBLE PRECISION;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS fee DOUBLE PRECISION; -- Platform fee taken by the swap, in its output token
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS sol_price_usd DOUBLE PRECISION; -- SOL/USD at execution, for SOL-denominated stats
+
+-- Per-position exit slippage (bps) for TP/SL sells
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS exit_slippage_bps INTEGER;
+
+-- Set when no market price was available at buy time (entry_price is a 0 placeholder)
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS price_unknown BOOLEAN DEFAULT FALSE;
+
+-- Trailing stop-loss
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS trailing_stop_percent DOUBLE PRECISION;
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS high_water_mark DOUBLE PRECISION DEFAULT 0;
+
+-- Why a position was closed outside the normal sell path (e.g. 'closed externally')
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS close_reason VARCHAR(50);
+
+-- USD spent on the tokens still held, used for realized PnL on sells
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS cost_basis_usd DOUBLE PRECISION;
+
+-- Take-profit ladder: [{"trigger_pct": 50, "close_pct": 25, "fired": false}, ...]
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS tp_ladder JSONB;
+
+-- Wallet a position was bought from (NULL = default wallet)
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS wallet_label VARCHAR(50);
+
+-- Opt-in panic sell when a position's pool liquidity is pulled
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS panic_sell_on_rug BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,18 @@
+-- Transaction bundles (survive restarts)
+CREATE TABLE IF NOT EXISTS bundles (
+    bundle_id VARCHAR(100) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    chain VARCHAR(20) NOT NULL,
+    status VARCHAR(20) NOT NULL, -- Pending, Bundling, Executing, Completed, Failed, Cancelled
+    transactions TEXT NOT NULL DEFAULT '[]', -- JSON array of pending transactions
+    created_at BIGINT NOT NULL,
+    executed_at BIGINT,
+    gas_saved DOUBLE PRECISION DEFAULT 0,
+    total_gas_cost DOUBLE PRECISION DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_bundles_user_status ON bundles(user_id, chain, status);
+
+-- Per-bundle send triggers
+ALTER TABLE bundles ADD COLUMN IF NOT EXISTS max_wait_seconds BIGINT NOT NULL DEFAULT 30;
+ALTER TABLE bundles ADD COLUMN IF NOT EXISTS min_transactions INTEGER NOT NULL DEFAULT 3;
@@ -0,0 +1,19 @@
+-- Audit trail of private key decryptions (never stores key material)
+CREATE TABLE IF NOT EXISTS key_access_log (
+    id SERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    chain VARCHAR(20) NOT NULL,
+    purpose VARCHAR(20) NOT NULL, -- trade, withdraw, export, transfer
+    ts BIGINT NOT NULL,
+    request_id VARCHAR(100) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_key_access_user_ts ON key_access_log(user_id, ts);
+
+-- Multiple wallets per chain. Trades use the is_default wallet, falling back to the
+-- oldest one (wallets created before labels have no flag)
+ALTER TABLE wallets ADD COLUMN IF NOT EXISTS label VARCHAR(50);
+ALTER TABLE wallets ADD COLUMN IF NOT EXISTS is_default BOOLEAN;
+ALTER TABLE wallets DROP CONSTRAINT IF EXISTS wallets_user_id_chain_key;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_wallets_label ON wallets (user_id, chain, label);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_wallets_default ON wallets (user_id, chain) WHERE is_default;
@@ -0,0 +1,5 @@
+-- KYC flag (enforced when REQUIRE_VERIFIED_FOR_TRADING is set)
+ALTER TABLE users ADD COLUMN IF NOT EXISTS verified BOOLEAN DEFAULT FALSE;
+
+-- Telegram chat that receives pushed notifications
+ALTER TABLE users ADD COLUMN IF NOT EXISTS telegram_chat_id BIGINT;
@@ -0,0 +1,52 @@
+-- Scheduled flatten-all exits (one-off or cron)
+CREATE TABLE IF NOT EXISTS scheduled_exits (
+    schedule_id VARCHAR(100) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    next_run BIGINT NOT NULL,
+    cron VARCHAR(100), -- NULL for one-off
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at BIGINT NOT NULL,
+    last_run BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_exits_due ON scheduled_exits(active, next_run);
+
+-- Limit orders: buy/sell once the price crosses trigger_price (BELOW = price <= trigger, ABOVE = price >= trigger)
+CREATE TABLE IF NOT EXISTS limit_orders (
+    order_id VARCHAR(100) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    chain VARCHAR(20) NOT NULL,
+    token_address VARCHAR(100) NOT NULL,
+    side VARCHAR(10) NOT NULL, -- BUY / SELL
+    trigger_price DOUBLE PRECISION NOT NULL,
+    direction VARCHAR(10) NOT NULL, -- BELOW / ABOVE
+    amount VARCHAR(50) NOT NULL, -- BUY: native amount, SELL: percent of position
+    position_id VARCHAR(100),
+    status VARCHAR(20) NOT NULL DEFAULT 'OPEN', -- OPEN, FILLED, FAILED, CANCELLED
+    tx_hash VARCHAR(255),
+    error TEXT,
+    created_at BIGINT NOT NULL,
+    filled_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_limit_orders_open ON limit_orders(chain, token_address, status);
+CREATE INDEX IF NOT EXISTS idx_limit_orders_user ON limit_orders(user_id);
+
+-- User price and balance alerts
+CREATE TABLE IF NOT EXISTS alerts (
+    alert_id VARCHAR(100) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    alert_type VARCHAR(20) NOT NULL, -- price, balance
+    chain VARCHAR(20),
+    token VARCHAR(100),
+    threshold DOUBLE PRECISION NOT NULL,
+    condition VARCHAR(10) NOT NULL, -- above, below, equals
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    repeat BOOLEAN NOT NULL DEFAULT FALSE,
+    armed BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at BIGINT NOT NULL,
+    last_triggered_at BIGINT
+);
+
+CREATE INDEX IF NOT EXISTS idx_alerts_active ON alerts(active);
+CREATE INDEX IF NOT EXISTS idx_alerts_user ON alerts(user_id);
@@ -0,0 +1,42 @@
+-- Whale alerts (lists are JSON-encoded TEXT)
+CREATE TABLE IF NOT EXISTS whale_alerts (
+    alert_id VARCHAR(100) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    min_size_usd DOUBLE PRECISION NOT NULL,
+    chains TEXT NOT NULL DEFAULT '[]',
+    tokens TEXT NOT NULL DEFAULT '[]',
+    position_types TEXT NOT NULL DEFAULT '[]',
+    active BOOLEAN DEFAULT TRUE,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_whale_alerts_user ON whale_alerts(user_id);
+
+-- Tracked whale trades (trade_type / position_type hold the enum variant names, e.g. 'Buy', 'Spot')
+CREATE TABLE IF NOT EXISTS whale_trades (
+    trade_id VARCHAR(100) PRIMARY KEY,
+    chain VARCHAR(20) NOT NULL,
+    token VARCHAR(255) NOT NULL,
+    token_symbol VARCHAR(50) NOT NULL,
+    trade_type VARCHAR(20) NOT NULL,
+    size_usd DOUBLE PRECISION NOT NULL,
+    size_native DOUBLE PRECISION NOT NULL,
+    price DOUBLE PRECISION NOT NULL,
+    timestamp BIGINT NOT NULL,
+    wallet_address VARCHAR(255) NOT NULL,
+    leverage DOUBLE PRECISION,
+    position_type VARCHAR(20) NOT NULL,
+    price_impact DOUBLE PRECISION NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_whale_trades_time ON whale_trades(timestamp DESC);
+CREATE INDEX IF NOT EXISTS idx_whale_trades_wallet ON whale_trades(wallet_address, timestamp);
+
+-- Lifetime totals per whale wallet (24h aggregates are computed from whale_trades)
+CREATE TABLE IF NOT EXISTS whale_wallets (
+    wallet_address VARCHAR(255) PRIMARY KEY,
+    first_seen BIGINT NOT NULL,
+    last_seen BIGINT NOT NULL,
+    total_trades BIGINT NOT NULL DEFAULT 0,
+    total_volume_usd DOUBLE PRECISION NOT NULL DEFAULT 0
+);
@@ -0,0 +1,29 @@
+-- Shared engine state (STATE_STORE=postgres), JSON-encoded per namespace
+CREATE TABLE IF NOT EXISTS state_store (
+    namespace VARCHAR(50) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    value TEXT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (namespace, key)
+);
+
+-- Portfolio value over time (written by the snapshot worker)
+CREATE TABLE IF NOT EXISTS portfolio_snapshots (
+    user_id BIGINT NOT NULL,
+    ts BIGINT NOT NULL,
+    total_value_usd DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (user_id, ts)
+);
+
+-- Buy/sell idempotency keys and the response each one produced (NULL while in flight)
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    user_id BIGINT NOT NULL,
+    idempotency_key VARCHAR(100) NOT NULL,
+    endpoint VARCHAR(50) NOT NULL,
+    status_code INTEGER,
+    response JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, idempotency_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created ON idempotency_keys(created_at);
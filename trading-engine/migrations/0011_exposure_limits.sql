@@ -0,0 +1,3 @@
+-- Per-user caps on capital in open positions, in total and per token (0 = no cap)
+ALTER TABLE risk_profiles ADD COLUMN IF NOT EXISTS max_total_exposure_usd DOUBLE PRECISION DEFAULT 0;
+ALTER TABLE risk_profiles ADD COLUMN IF NOT EXISTS max_token_exposure_usd DOUBLE PRECISION DEFAULT 0;
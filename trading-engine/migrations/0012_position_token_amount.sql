@@ -0,0 +1,8 @@
+-- Tokens a position holds, in whole token units. `amount` stays the native currency spent.
+-- Filled from the swap's output on buys; NULL = unknown.
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS token_amount DOUBLE PRECISION;
+
+-- Older rows never recorded it. Estimate from what was paid at the entry price
+UPDATE positions
+SET token_amount = cost_basis_usd / entry_price
+WHERE token_amount IS NULL AND cost_basis_usd IS NOT NULL AND entry_price > 0;
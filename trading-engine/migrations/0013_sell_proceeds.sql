@@ -0,0 +1,8 @@
+-- USD a sell returned, for leaderboard volume. `amount` x `price` mixed units on older rows
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS proceeds_usd DOUBLE PRECISION;
+
+-- Older sells recorded the SOL cost of what was sold as `amount`: estimate what came back as that cost plus the PnL
+UPDATE transactions
+SET proceeds_usd = amount::float8 * sol_price_usd + profit_loss
+WHERE type = 'SELL' AND proceeds_usd IS NULL AND chain = 'solana'
+  AND profit_loss IS NOT NULL AND sol_price_usd IS NOT NULL AND amount ~ '^[0-9]+(\.[0-9]+)?$';
@@ -0,0 +1,10 @@
+-- transactions.fee is now the platform fee in USD. Older rows held it in the swap's output token
+COMMENT ON COLUMN transactions.fee IS 'Platform fee taken by the swap, in USD';
+
+-- Buys took the fee in the bought token: value it at the recorded entry price
+UPDATE transactions SET fee = fee * price
+WHERE type = 'BUY' AND fee IS NOT NULL AND price > 0;
+
+-- Sells took it in the output token, which wasn't recorded: assume the default SOL output
+UPDATE transactions SET fee = fee * sol_price_usd
+WHERE type = 'SELL' AND fee IS NOT NULL AND sol_price_usd IS NOT NULL;
@@ -0,0 +1,21 @@
+-- DCA schedules: buy `amount` of a token every interval_secs until total_buys runs have happened
+CREATE TABLE IF NOT EXISTS dca_schedules (
+    schedule_id VARCHAR(100) PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    chain VARCHAR(20) NOT NULL,
+    token_address VARCHAR(100) NOT NULL,
+    amount VARCHAR(50) NOT NULL, -- Native amount spent per buy
+    interval_secs BIGINT NOT NULL,
+    total_buys INTEGER NOT NULL,
+    executions INTEGER NOT NULL DEFAULT 0, -- Buys that filled
+    failed_runs INTEGER NOT NULL DEFAULT 0, -- Buys that failed (they still use up a run)
+    wallet_label VARCHAR(50),
+    status VARCHAR(20) NOT NULL DEFAULT 'ACTIVE', -- ACTIVE, EXECUTING, COMPLETED, CANCELLED
+    next_run BIGINT NOT NULL,
+    last_tx_hash VARCHAR(255),
+    last_error TEXT,
+    created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_dca_schedules_due ON dca_schedules(status, next_run);
+CREATE INDEX IF NOT EXISTS idx_dca_schedules_user ON dca_schedules(user_id);
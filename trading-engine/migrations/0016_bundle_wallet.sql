@@ -0,0 +1,2 @@
+-- Wallet a bundle is signed with (NULL = the user's default wallet)
+ALTER TABLE bundles ADD COLUMN IF NOT EXISTS wallet_label VARCHAR(50);
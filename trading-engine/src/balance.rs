@@ -2,7 +2,13 @@
 use serde::Serialize;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::future::Future;
 use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use crate::grid_trading::{GridStrategy, OrderStatus, OrderType};
+use crate::notifications::{create_notification, Notification, NotificationQueue};
 
 #[derive(Debug, Serialize, Clone)]
 pub struct WalletBalance {
@@ -23,10 +29,215 @@ pub struct TokenBalance {
     pub balance_usd: f64,
 }
 
+// ==================== BALANCE SANITY ====================
+
+/// Last trusted balance readings per address, used to catch RPC nodes
+/// returning zero or absurd values.
+#[derive(Debug, Clone, Default)]
+pub struct BalanceCache {
+    readings: Arc<RwLock<HashMap<String, u64>>>,
+    // address -> unix timestamp of the last trade we sent from it
+    recent_activity: Arc<RwLock<HashMap<String, i64>>>,
+    notifications: NotificationQueue,
+}
+
+impl BalanceCache {
+    pub fn new(notifications: NotificationQueue) -> Self {
+        Self {
+            notifications,
+            ..Self::default()
+        }
+    }
+
+    /// Record that a transaction was sent from this address, so the next
+    /// large balance change is expected rather than anomalous.
+    pub async fn note_activity(&self, address: &str) {
+        let now = chrono::Utc::now().timestamp();
+        self.recent_activity.write().await.insert(address.to_string(), now);
+    }
+
+    async fn has_recent_activity(&self, address: &str) -> bool {
+        let window = std::env::var("BALANCE_ACTIVITY_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(300);
+        let now = chrono::Utc::now().timestamp();
+        self.recent_activity.read().await
+            .get(address)
+            .map(|ts| now - ts <= window)
+            .unwrap_or(false)
+    }
+
+    /// Validate a balance reading against the last cached value. If it
+    /// changed by more than `BALANCE_SANITY_RATIO` (default 10x) without a
+    /// recent trade, `requery` is used to confirm it against a second endpoint
+    /// and the second reading wins.
+    /// Significant trusted changes that aren't explained by a recent trade
+    /// are reported to `owner` as a "balance" notification.
+    pub async fn validate_reading<F, Fut>(
+        &self,
+        owner: i64,
+        address: &str,
+        reading: u64,
+        requery: F,
+    ) -> Result<u64, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<u64, String>>,
+    {
+        let ratio = std::env::var("BALANCE_SANITY_RATIO")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .unwrap_or(10.0);
+
+        let previous = self.readings.read().await.get(address).copied();
+
+        let trusted = match previous {
+            Some(prev) if is_anomalous_change(prev, reading, ratio) && !self.has_recent_activity(address).await => {
+                tracing::warn!(
+                    "⚠️  Balance for {} jumped {} -> {} lamports without a recent trade, re-querying",
+                    address, prev, reading
+                );
+                let confirmed = requery().await
+                    .map_err(|e| format!("Balance reading could not be verified: {}", e))?;
+                if confirmed != reading {
+                    tracing::warn!("   Second endpoint disagrees ({} lamports), using it instead", confirmed);
+                }
+                confirmed
+            }
+            _ => reading,
+        };
+
+        if let Some(prev) = previous {
+            if is_significant_change(prev, trusted, balance_alert_threshold()) && !self.has_recent_activity(address).await {
+                self.notifications.push(balance_change_notification(owner, address, prev, trusted)).await;
+            }
+        }
+
+        self.readings.write().await.insert(address.to_string(), trusted);
+        Ok(trusted)
+    }
+}
+
+/// Minimum change in lamports worth alerting on, from
+/// `BALANCE_ALERT_MIN_CHANGE_SOL` (default 0.1 SOL).
+fn balance_alert_threshold() -> u64 {
+    let sol = std::env::var("BALANCE_ALERT_MIN_CHANGE_SOL")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(0.1);
+    (sol * 1_000_000_000.0) as u64
+}
+
+pub fn is_significant_change(previous: u64, current: u64, threshold_lamports: u64) -> bool {
+    previous.abs_diff(current) >= threshold_lamports.max(1)
+}
+
+fn balance_change_notification(owner: i64, address: &str, previous: u64, current: u64) -> Notification {
+    use crate::units::{format_token_amount, SOL_DECIMALS};
+
+    let delta = format_token_amount(previous.abs_diff(current) as u128, SOL_DECIMALS);
+    let (message, priority) = if current < previous {
+        (format!("Wallet {} balance dropped by {} SOL without a trade", address, delta), "high")
+    } else {
+        (format!("Wallet {} received {} SOL", address, delta), "medium")
+    };
+
+    create_notification(owner, message, "balance".to_string(), priority.to_string())
+}
+
+// ==================== SPENDING COMMITMENTS ====================
+
+/// USD already earmarked for automated buys on a user's wallet:
+/// unfilled buy orders on grids that are still running.
+pub fn committed_usd(grids: &HashMap<String, GridStrategy>, user_id: i64, chain: &str) -> f64 {
+    let committed: f64 = grids.values()
+        .filter(|g| g.user_id == user_id && g.chain == chain && !g.status.is_terminal())
+        .flat_map(|g| g.active_orders.iter())
+        .filter(|o| matches!(o.order_type, OrderType::Buy) && matches!(o.status, OrderStatus::Active | OrderStatus::Pending))
+        .map(|o| o.amount)
+        .sum();
+    committed.max(0.0)
+}
+
+/// Lamports worth `usd` at `sol_price_usd`.
+pub fn usd_to_lamports(usd: f64, sol_price_usd: f64) -> u64 {
+    if sol_price_usd <= 0.0 {
+        return 0;
+    }
+    (usd.max(0.0) / sol_price_usd * 1_000_000_000.0) as u64
+}
+
+/// SOL kept back from manual buys, from `MIN_SOL_RESERVE` (default 0).
+pub fn sol_reserve_lamports() -> u64 {
+    let sol = std::env::var("MIN_SOL_RESERVE")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    (sol.max(0.0) * 1_000_000_000.0) as u64
+}
+
+/// Lamports a manual buy may spend once commitments and the reserve are set aside.
+pub fn available_for_buy(balance: u64, committed: u64, reserve: u64) -> u64 {
+    balance.saturating_sub(committed).saturating_sub(reserve)
+}
+
+/// Native-asset fee buffer a buy must leave on top of its amount, in base units.
+/// Configured per chain via `FEE_BUFFER_SOL`, `FEE_BUFFER_ETH` and `FEE_BUFFER_BNB`.
+pub fn fee_buffer(chain: &str) -> u128 {
+    let var = match chain {
+        "solana" | "sol" => "FEE_BUFFER_SOL",
+        "bsc" | "binance" => "FEE_BUFFER_BNB",
+        _ => "FEE_BUFFER_ETH",
+    };
+    fee_buffer_from(chain, std::env::var(var).ok().as_deref())
+}
+
+fn fee_buffer_from(chain: &str, configured: Option<&str>) -> u128 {
+    let default = match chain {
+        "solana" | "sol" => "0.01",
+        "bsc" | "binance" => "0.002",
+        _ => "0.005",
+    };
+    let decimals = crate::units::native_decimals(chain);
+    configured
+        .and_then(|v| {
+            crate::units::parse_token_amount(v, decimals)
+                .map_err(|e| tracing::warn!("⚠️  Ignoring invalid fee buffer '{}' for {}: {}", v, chain, e))
+                .ok()
+        })
+        .unwrap_or_else(|| crate::units::parse_token_amount(default, decimals).unwrap_or(0))
+}
+
+/// Ok if `available` covers `amount` plus the fee buffer, otherwise Err(required).
+pub fn check_buy_funds(available: u64, amount: u64, fee_buffer: u64) -> Result<(), u64> {
+    let required = amount.saturating_add(fee_buffer);
+    if available < required {
+        return Err(required);
+    }
+    Ok(())
+}
+
+/// Lamports to spend on a "percent of balance" buy: the share of what's available once
+/// the fee buffer is set aside, so the buy always passes `check_buy_funds`.
+pub fn percent_of_available(available: u64, fee_buffer: u64, percent: f64) -> u64 {
+    let spendable = available.saturating_sub(fee_buffer);
+    (spendable as f64 * (percent.clamp(0.0, 100.0) / 100.0)).floor() as u64
+}
+
+/// True when two balance readings differ by more than `ratio` times.
+pub fn is_anomalous_change(previous: u64, current: u64, ratio: f64) -> bool {
+    let high = previous.max(current) as f64;
+    let low = previous.min(current).max(1) as f64;
+    high / low > ratio
+}
+
 /// Get Solana balance with retry logic and fallback RPC endpoints
 pub async fn get_solana_balance(
+    owner: i64,
     address: &str,
     client: &RpcClient,
+    cache: &BalanceCache,
 ) -> Result<WalletBalance, String> {
     let pubkey = Pubkey::from_str(address)
         .map_err(|e| format!("Invalid address: {}", e))?;
@@ -50,14 +261,13 @@ pub async fn get_solana_balance(
         format!("Failed to get balance after retries: {}. Try again in a moment.", e)
     })?;
     
+    let lamports = cache.validate_reading(owner, address, lamports, || try_fallback_rpc_balance(&pubkey)).await?;
+    
     let sol_balance = lamports as f64 / 1_000_000_000.0; // Convert lamports to SOL
-    let sol_balance_str = format!("{:.9}", sol_balance);
+    let sol_balance_str = crate::units::format_token_amount(lamports as u128, crate::units::SOL_DECIMALS);
     
     // Fetch real SOL price from price module
-    let sol_price_usd = match fetch_sol_price().await {
-        Ok(price) => price,
-        Err(_) => 100.0, // Fallback price
-    };
+    let sol_price_usd = crate::price::fetch_sol_price().await.unwrap_or(100.0); // Fallback price
     let native_balance_usd = sol_balance * sol_price_usd;
     
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -66,25 +276,87 @@ pub async fn get_solana_balance(
         .unwrap()
         .as_secs() as i64;
     
-    let sol_balance_str_clone = sol_balance_str.clone();
+    // SPL and Token-2022 holdings. A failed listing degrades to SOL only
+    let token_balances = match get_token_accounts(client, &pubkey) {
+        Ok(holdings) => price_token_holdings(&holdings, dust_threshold_usd()).await,
+        Err(e) => {
+            tracing::warn!("Token accounts unavailable for {}: {}", address, e);
+            Vec::new()
+        }
+    };
+    let tokens_usd: f64 = token_balances.iter().map(|t| t.balance_usd).sum();
+
     Ok(WalletBalance {
         chain: "solana".to_string(),
         address: address.to_string(),
         native_balance: sol_balance_str,
         native_balance_usd,
-        token_balances: vec![
-            TokenBalance {
-                token: "So11111111111111111111111111111111111111112".to_string(),
-                symbol: "SOL".to_string(),
-                balance: sol_balance_str_clone,
-                balance_usd: native_balance_usd,
-            },
-        ],
-        total_usd: native_balance_usd,
+        token_balances,
+        total_usd: native_balance_usd + tokens_usd,
         last_updated: timestamp,
     })
 }
 
+/// `BALANCE_DUST_USD`: token holdings worth less than this are left out of balances (default 1.0).
+pub fn dust_threshold_usd() -> f64 {
+    std::env::var("BALANCE_DUST_USD")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|v| *v >= 0.0)
+        .unwrap_or(1.0)
+}
+
+/// Raw amount and decimals per mint, summed across a wallet's token accounts. Empty accounts are dropped.
+pub fn totals_by_mint(holdings: &[TokenAccountHolding]) -> Vec<(Pubkey, u128, u8)> {
+    let mut totals: Vec<(Pubkey, u128, u8)> = Vec::new();
+    for h in holdings.iter().filter(|h| h.amount > 0) {
+        match totals.iter_mut().find(|(mint, _, _)| *mint == h.mint) {
+            Some(entry) => entry.1 += h.amount as u128,
+            None => totals.push((h.mint, h.amount as u128, h.decimals)),
+        }
+    }
+    totals
+}
+
+/// One balance entry, or `None` for dust. Tokens without a price count as worthless.
+pub fn token_balance_entry(token: &str, raw: u128, decimals: u8, price: Option<(f64, Option<String>)>, dust_usd: f64) -> Option<TokenBalance> {
+    let (price_usd, symbol) = price?;
+    let amount = raw as f64 / 10f64.powi(decimals as i32);
+    let balance_usd = amount * price_usd;
+    if balance_usd < dust_usd {
+        return None;
+    }
+    Some(TokenBalance {
+        token: token.to_string(),
+        symbol: symbol.unwrap_or_else(|| token.chars().take(4).collect()),
+        balance: crate::units::format_token_amount(raw, decimals),
+        balance_usd,
+    })
+}
+
+/// Price every held mint concurrently and keep the non-dust ones, largest first.
+async fn price_token_holdings(holdings: &[TokenAccountHolding], dust_usd: f64) -> Vec<TokenBalance> {
+    let mut lookups = tokio::task::JoinSet::new();
+    for (mint, raw, decimals) in totals_by_mint(holdings) {
+        lookups.spawn(async move {
+            let price = crate::price::fetch_token_price("solana", &mint.to_string())
+                .await
+                .ok()
+                .map(|p| (p.price_usd, p.token_symbol));
+            token_balance_entry(&mint.to_string(), raw, decimals, price, dust_usd)
+        });
+    }
+
+    let mut balances = Vec::new();
+    while let Some(joined) = lookups.join_next().await {
+        if let Ok(Some(entry)) = joined {
+            balances.push(entry);
+        }
+    }
+    balances.sort_by(|a, b| b.balance_usd.total_cmp(&a.balance_usd));
+    balances
+}
+
 /// Retry RPC balance call with exponential backoff
 async fn retry_rpc_balance(
     client: &RpcClient,
@@ -109,21 +381,21 @@ async fn retry_rpc_balance(
 }
 
 /// Try fallback public RPC endpoints
-async fn try_fallback_rpc_balance(pubkey: &Pubkey) -> Result<u64, String> {
-    let fallback_rpcs = vec![
+pub async fn try_fallback_rpc_balance(pubkey: &Pubkey) -> Result<u64, String> {
+    let fallback_rpcs = rpc_list_from_env("SOLANA_FALLBACK_RPCS", &[
         "https://api.mainnet-beta.solana.com",
         "https://solana-api.projectserum.com",
         "https://rpc.ankr.com/solana",
-    ];
+    ]);
     
-    for rpc_url in fallback_rpcs {
+    for rpc_url in &fallback_rpcs {
         match try_single_rpc_balance(rpc_url, pubkey).await {
             Ok(balance) => {
-                tracing::info!("Successfully fetched balance from fallback RPC: {}", rpc_url);
+                tracing::info!("Successfully fetched balance from fallback RPC: {}", endpoint_label(rpc_url));
                 return Ok(balance);
             }
             Err(e) => {
-                tracing::debug!("Fallback RPC {} failed: {}", rpc_url, e);
+                tracing::warn!("Fallback RPC {} failed: {}", endpoint_label(rpc_url), e);
                 continue;
             }
         }
@@ -132,6 +404,34 @@ async fn try_fallback_rpc_balance(pubkey: &Pubkey) -> Result<u64, String> {
     Err("All fallback RPC endpoints failed".to_string())
 }
 
+/// Comma-separated endpoint list. Unset or blank falls back to `defaults`.
+pub fn parse_rpc_list(value: Option<&str>, defaults: &[&str]) -> Vec<String> {
+    let configured: Vec<String> = value
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect();
+    if configured.is_empty() {
+        defaults.iter().map(|url| url.to_string()).collect()
+    } else {
+        configured
+    }
+}
+
+fn rpc_list_from_env(var: &str, defaults: &[&str]) -> Vec<String> {
+    parse_rpc_list(std::env::var(var).ok().as_deref(), defaults)
+}
+
+/// Endpoint for logs: just the host, since paid URLs carry their API key in the path or query.
+pub fn endpoint_label(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| "<invalid url>".to_string())
+}
+
 /// Try a single RPC endpoint using HTTP request
 async fn try_single_rpc_balance(rpc_url: &str, pubkey: &Pubkey) -> Result<u64, String> {
     let client = reqwest::Client::builder()
@@ -170,83 +470,295 @@ async fn try_single_rpc_balance(rpc_url: &str, pubkey: &Pubkey) -> Result<u64, S
     Ok(result)
 }
 
-/// Fetch current SOL price
-async fn fetch_sol_price() -> Result<f64, String> {
-    // Try to fetch from DexScreener or CoinGecko
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-    
-    // Try CoinGecko first
-    let url = "https://api.coingecko.com/api/v3/simple/price?ids=solana&vs_currencies=usd";
-    match client.get(url).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                if let Ok(json) = response.json::<serde_json::Value>().await {
-                    if let Some(price) = json.get("solana")
-                        .and_then(|s| s.get("usd"))
-                        .and_then(|p| p.as_f64()) {
-                        return Ok(price);
-                    }
-                }
+// ==================== TOKEN ACCOUNTS ====================
+
+/// A non-native token account owned by a wallet.
+#[derive(Debug, Clone)]
+pub struct TokenAccountHolding {
+    pub account: Pubkey,
+    pub mint: Pubkey,
+    pub program_id: Pubkey,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+/// List every SPL Token and Token-2022 account owned by `owner`, including empty ones.
+pub fn get_token_accounts(client: &RpcClient, owner: &Pubkey) -> Result<Vec<TokenAccountHolding>, String> {
+    use solana_client::rpc_request::TokenAccountsFilter;
+
+    let mut holdings = Vec::new();
+    for program_id in [spl_token::id(), spl_token_2022::id()] {
+        let accounts = client
+            .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(program_id))
+            .map_err(|e| format!("Failed to list token accounts: {}", e))?;
+
+        for keyed in accounts {
+            // RpcClient requests jsonParsed encoding, so read the parsed info block
+            let data = serde_json::to_value(&keyed.account.data)
+                .map_err(|e| format!("Failed to read token account data: {}", e))?;
+            let info = &data["parsed"]["info"];
+
+            let account = Pubkey::from_str(&keyed.pubkey)
+                .map_err(|e| format!("Invalid token account address: {}", e))?;
+            let mint = info["mint"].as_str()
+                .and_then(|m| Pubkey::from_str(m).ok())
+                .ok_or_else(|| format!("Token account {} has no mint", account))?;
+            let amount = info["tokenAmount"]["amount"].as_str()
+                .and_then(|a| a.parse::<u64>().ok())
+                .unwrap_or(0);
+            let decimals = info["tokenAmount"]["decimals"].as_u64().unwrap_or(0) as u8;
+
+            holdings.push(TokenAccountHolding { account, mint, program_id, amount, decimals });
+        }
+    }
+
+    Ok(holdings)
+}
+
+/// Total balance of `mint` across the owner's token accounts, in token units. 0 when there's no account.
+pub fn solana_token_balance(client: &RpcClient, owner: &Pubkey, mint: &Pubkey) -> Result<f64, String> {
+    let total = get_token_accounts(client, owner)?
+        .iter()
+        .filter(|h| h.mint == *mint)
+        .map(|h| h.amount as f64 / 10f64.powi(h.decimals as i32))
+        .sum();
+    Ok(total)
+}
+
+/// Raw ERC-20 balance of `owner` for `token`.
+pub async fn evm_token_balance_raw(chain: &str, owner: &str, token: &str) -> Result<u128, String> {
+    let (rpc_url, _) = evm_rpc_urls(chain)?;
+    let owner = crate::evm::parse_address(owner)?;
+    let data = crate::evm::encode_balance_of(&owner);
+    let params = serde_json::json!([
+        { "to": token, "data": format!("0x{}", hex::encode(data)) },
+        "latest"
+    ]);
+    let output = evm_rpc_call(&rpc_url, "eth_call", params).await?;
+    let hex_out = output.as_str().unwrap_or("0x");
+    let bytes = hex::decode(hex_out.strip_prefix("0x").unwrap_or(hex_out))
+        .map_err(|e| format!("Invalid balanceOf output: {}", e))?;
+    crate::evm::decode_uint_word(&bytes, 0)
+}
+
+// ==================== ERC-20 BALANCES ====================
+
+/// Tokens checked when a balance request doesn't name any: majors and stablecoins per chain.
+pub fn known_evm_tokens(chain: &str) -> &'static [&'static str] {
+    match chain {
+        "eth" | "ethereum" => &[
+            "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", // USDC
+            "0xdAC17F958D2ee523a2206206994597C13D831ec7", // USDT
+            "0x6B175474E89094C44Da98b954EedeAC495271d0F", // DAI
+            "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2", // WETH
+            "0x6982508145454Ce325dDbE47a25d4ec3d2311933", // PEPE
+            "0x95aD61b0a150d79219dCF64E1E6Cc01f0B64C4cE", // SHIB
+        ],
+        "bsc" | "binance" => &[
+            "0x55d398326f99059fF775485246999027B3197955", // USDT
+            "0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d", // USDC
+            "0xe9e7CEA3DedcA5984780Bafc599bD69ADd087D56", // BUSD
+            "0xbb4CdB9CBd36B01bD8cBaEBF2De08d9173bc095c", // WBNB
+            "0x0E09FaBB73Bd3Ade0a17ECC321fD13a19e81cE82", // CAKE
+        ],
+        _ => &[],
+    }
+}
+
+fn eth_call_params(to: &str, data: &[u8]) -> serde_json::Value {
+    serde_json::json!([{ "to": to, "data": format!("0x{}", hex::encode(data)) }, "latest"])
+}
+
+fn decode_call_output(output: &serde_json::Value) -> Result<Vec<u8>, String> {
+    let hex_out = output.as_str().ok_or_else(|| "Invalid eth_call output".to_string())?;
+    hex::decode(hex_out.strip_prefix("0x").unwrap_or(hex_out)).map_err(|e| format!("Invalid eth_call output: {}", e))
+}
+
+/// Results of a JSON-RPC batch, in request order. Responses may arrive in any order, so
+/// they're matched on `id` (the request's index).
+pub fn parse_batch_response(json: &serde_json::Value, count: usize) -> Result<Vec<Result<serde_json::Value, String>>, String> {
+    let responses = json.as_array().ok_or_else(|| "RPC does not support batch requests".to_string())?;
+    let mut results: Vec<Result<serde_json::Value, String>> = vec![Err("Missing from batch response".to_string()); count];
+    for response in responses {
+        let Some(slot) = response["id"].as_u64().and_then(|id| results.get_mut(id as usize)) else { continue };
+        *slot = match (response.get("result"), response.get("error")) {
+            (_, Some(error)) => Err(format!("RPC error: {}", error["message"].as_str().unwrap_or("Unknown RPC error"))),
+            (Some(result), None) => Ok(result.clone()),
+            (None, None) => Err("Invalid RPC response format".to_string()),
+        };
+    }
+    Ok(results)
+}
+
+/// Send several calls as one JSON-RPC batch. Nodes that reject batches get the calls one by one.
+pub async fn evm_rpc_batch(rpc_url: &str, calls: &[(&str, serde_json::Value)]) -> Vec<Result<serde_json::Value, String>> {
+    let batch: Vec<serde_json::Value> = calls.iter().enumerate()
+        .map(|(id, (method, params))| serde_json::json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": id }))
+        .collect();
+
+    let batched = async {
+        let client = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(8))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        let response = client.post(rpc_url).json(&batch).send().await.map_err(|e| format!("RPC request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("RPC returned status: {}", response.status()));
+        }
+        let json: serde_json::Value = response.json().await.map_err(|e| format!("Failed to parse RPC response: {}", e))?;
+        parse_batch_response(&json, calls.len())
+    };
+    match batched.await {
+        Ok(results) => results,
+        Err(e) => {
+            tracing::debug!("Batch RPC to {} failed ({}), sending calls individually", endpoint_label(rpc_url), e);
+            let mut results = Vec::with_capacity(calls.len());
+            for (method, params) in calls {
+                results.push(evm_rpc_call(rpc_url, method, params.clone()).await);
             }
+            results
         }
-        Err(_) => {}
     }
-    
-    // Fallback: try DexScreener
-    let url = "https://api.dexscreener.com/latest/dex/tokens/So11111111111111111111111111111111111111112";
-    match client.get(url).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                if let Ok(json) = response.json::<serde_json::Value>().await {
-                    if let Some(pairs) = json.get("pairs").and_then(|p| p.as_array()) {
-                        if let Some(pair) = pairs.first() {
-                            if let Some(price) = pair.get("priceUsd")
-                                .and_then(|p| p.as_str())
-                                .and_then(|p| p.parse::<f64>().ok()) {
-                                return Ok(price);
-                            }
-                        }
-                    }
-                }
+}
+
+/// (raw balance, decimals) from the `balanceOf` and `decimals` call results.
+fn decode_erc20_holding(
+    balance: &Result<serde_json::Value, String>,
+    decimals: &Result<serde_json::Value, String>,
+) -> Result<(u128, u8), String> {
+    let uint = |result: &Result<serde_json::Value, String>| -> Result<u128, String> {
+        crate::evm::decode_uint_word(&decode_call_output(result.as_ref().map_err(Clone::clone)?)?, 0)
+    };
+    let raw = uint(balance)?;
+    let decimals = uint(decimals)?;
+    let decimals = u8::try_from(decimals).ok().filter(|d| *d <= 36).ok_or_else(|| format!("Unusable decimals {}", decimals))?;
+    Ok((raw, decimals))
+}
+
+/// ERC-20 holdings of `owner`, priced on DexScreener. `balanceOf` and `decimals` for every
+/// token go out in one batch. Zero balances, unpriced tokens and dust are left out.
+pub async fn evm_token_balances(rpc_url: &str, chain: &str, owner: &str, tokens: &[String]) -> Result<Vec<TokenBalance>, String> {
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    let balance_of = crate::evm::encode_balance_of(&crate::evm::parse_address(owner)?);
+    let decimals = crate::evm::encode_decimals();
+    let calls: Vec<(&str, serde_json::Value)> = tokens.iter()
+        .flat_map(|token| [("eth_call", eth_call_params(token, &balance_of)), ("eth_call", eth_call_params(token, &decimals))])
+        .collect();
+    let results = evm_rpc_batch(rpc_url, &calls).await;
+
+    let mut lookups = tokio::task::JoinSet::new();
+    let dust_usd = dust_threshold_usd();
+    for (token, pair) in tokens.iter().zip(results.chunks(2)) {
+        let (raw, decimals) = match decode_erc20_holding(&pair[0], &pair[1]) {
+            Ok((0, _)) => continue,
+            Ok(found) => found,
+            Err(e) => {
+                tracing::debug!("Skipping token {} in {} balance: {}", token, owner, e);
+                continue;
             }
+        };
+        let (chain, token) = (chain.to_string(), token.clone());
+        lookups.spawn(async move {
+            let price = crate::price::fetch_token_price(&chain, &token)
+                .await
+                .ok()
+                .map(|p| (p.price_usd, p.token_symbol));
+            token_balance_entry(&token, raw, decimals, price, dust_usd)
+        });
+    }
+
+    let mut balances = Vec::new();
+    while let Some(joined) = lookups.join_next().await {
+        if let Ok(Some(entry)) = joined {
+            balances.push(entry);
         }
-        Err(_) => {}
     }
-    
-    Err("Failed to fetch SOL price".to_string())
+    balances.sort_by(|a, b| b.balance_usd.total_cmp(&a.balance_usd));
+    Ok(balances)
 }
 
-pub async fn get_evm_balance(
-    address: &str,
-    chain: &str,
-) -> Result<WalletBalance, String> {
-    // Get primary and fallback RPC URLs
-    let (primary_rpc, fallback_rpcs) = match chain {
+/// Primary (env-configurable) and public fallback RPC URLs for an EVM chain.
+/// Fallbacks come from `ETH_FALLBACK_RPCS` / `BSC_FALLBACK_RPCS` (comma-separated) when set.
+pub fn evm_rpc_urls(chain: &str) -> Result<(String, Vec<String>), String> {
+    match chain {
         "eth" | "ethereum" => {
             let primary = std::env::var("ETH_RPC")
                 .unwrap_or_else(|_| "https://eth.llamarpc.com".to_string());
-            let fallbacks = vec![
+            let fallbacks = rpc_list_from_env("ETH_FALLBACK_RPCS", &[
                 "https://rpc.ankr.com/eth",
                 "https://eth.llamarpc.com",
                 "https://ethereum.publicnode.com",
-            ];
-            (primary, fallbacks)
+            ]);
+            Ok((primary, fallbacks))
         }
         "bsc" | "binance" => {
             let primary = std::env::var("BSC_RPC")
                 .unwrap_or_else(|_| "https://bsc-dataseed.binance.org/".to_string());
-            let fallbacks = vec![
+            let fallbacks = rpc_list_from_env("BSC_FALLBACK_RPCS", &[
                 "https://bsc-dataseed1.binance.org/",
                 "https://bsc-dataseed2.binance.org/",
                 "https://rpc.ankr.com/bsc",
-            ];
-            (primary, fallbacks)
+            ]);
+            Ok((primary, fallbacks))
         }
-        _ => return Err("Unsupported chain".to_string()),
-    };
+        _ => Err("Unsupported chain".to_string()),
+    }
+}
+
+/// Send a JSON-RPC request to an EVM node and return its `result`.
+pub async fn evm_rpc_call(rpc_url: &str, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+        "id": 1
+    });
+    
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(8))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    let response = client
+        .post(rpc_url)
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| format!("RPC request failed: {}", e))?;
+    
+    if !response.status().is_success() {
+        return Err(format!("RPC returned status: {}", response.status()));
+    }
+    
+    let mut json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse RPC response: {}", e))?;
+    
+    // Check for RPC error
+    if let Some(error) = json.get("error") {
+        let error_msg = error.get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("Unknown RPC error");
+        return Err(format!("RPC error: {}", error_msg));
+    }
+    
+    json.get_mut("result")
+        .map(serde_json::Value::take)
+        .ok_or_else(|| "Invalid RPC response format".to_string())
+}
+
+/// Native balance plus ERC-20 holdings. `tokens` are contract addresses to check;
+/// None checks `known_evm_tokens` for the chain.
+pub async fn get_evm_balance(
+    address: &str,
+    chain: &str,
+    tokens: Option<&[String]>,
+) -> Result<WalletBalance, String> {
+    // Get primary and fallback RPC URLs
+    let (primary_rpc, fallback_rpcs) = evm_rpc_urls(chain)?;
     
     // Try primary RPC first
     let mut balance_result = try_evm_rpc_balance(&primary_rpc, address).await;
@@ -257,9 +769,12 @@ pub async fn get_evm_balance(
         for fallback_rpc in &fallback_rpcs {
             if *fallback_rpc != primary_rpc {
                 balance_result = try_evm_rpc_balance(fallback_rpc, address).await;
-                if balance_result.is_ok() {
-                    tracing::info!("Successfully fetched balance from fallback RPC: {}", fallback_rpc);
-                    break;
+                match &balance_result {
+                    Ok(_) => {
+                        tracing::info!("Successfully fetched balance from fallback RPC: {}", endpoint_label(fallback_rpc));
+                        break;
+                    }
+                    Err(e) => tracing::warn!("Fallback RPC {} failed: {}", endpoint_label(fallback_rpc), e),
                 }
             }
         }
@@ -274,17 +789,8 @@ pub async fn get_evm_balance(
         format!("Failed to get balance after retries: {}. Try again in a moment.", e)
     })?;
     
-    let (native_balance, symbol, decimals) = match chain {
-        "eth" | "ethereum" => {
-            let eth = balance_wei as f64 / 1e18;
-            (format!("{:.9}", eth), "ETH", 18)
-        }
-        "bsc" | "binance" => {
-            let bnb = balance_wei as f64 / 1e18;
-            (format!("{:.9}", bnb), "BNB", 18)
-        }
-        _ => return Err("Unsupported chain".to_string()),
-    };
+    let native_balance = crate::units::format_token_amount(balance_wei, crate::units::EVM_NATIVE_DECIMALS);
+    let decimals = crate::units::EVM_NATIVE_DECIMALS as i32;
     
     // Fetch real price
     let native_price_usd = match fetch_evm_price(chain).await {
@@ -298,6 +804,17 @@ pub async fn get_evm_balance(
     
     let native_balance_f64 = balance_wei as f64 / 10_f64.powi(decimals);
     let native_balance_usd = native_balance_f64 * native_price_usd;
+
+    // Token balances are best-effort: a failed lookup leaves them out rather than failing the wallet
+    let tokens: Vec<String> = match tokens {
+        Some(list) => list.to_vec(),
+        None => known_evm_tokens(chain).iter().map(|t| t.to_string()).collect(),
+    };
+    let token_balances = evm_token_balances(&primary_rpc, chain, address, &tokens).await.unwrap_or_else(|e| {
+        tracing::warn!("⚠️ Could not load token balances for {}: {}", address, e);
+        Vec::new()
+    });
+    let tokens_usd: f64 = token_balances.iter().map(|t| t.balance_usd).sum();
     
     use std::time::{SystemTime, UNIX_EPOCH};
     let timestamp = SystemTime::now()
@@ -308,61 +825,19 @@ pub async fn get_evm_balance(
     Ok(WalletBalance {
         chain: chain.to_string(),
         address: address.to_string(),
-        native_balance: native_balance.clone(),
+        native_balance,
         native_balance_usd,
-        token_balances: vec![
-            TokenBalance {
-                token: address.to_string(),
-                symbol: symbol.to_string(),
-                balance: native_balance,
-                balance_usd: native_balance_usd,
-            },
-        ],
-        total_usd: native_balance_usd,
+        token_balances,
+        total_usd: native_balance_usd + tokens_usd,
         last_updated: timestamp,
     })
 }
 
 /// Try a single EVM RPC call
 async fn try_evm_rpc_balance(rpc_url: &str, address: &str) -> Result<u128, String> {
-    let request = serde_json::json!({
-        "jsonrpc": "2.0",
-        "method": "eth_getBalance",
-        "params": [address, "latest"],
-        "id": 1
-    });
-    
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(8))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-    
-    let response = client
-        .post(rpc_url)
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| format!("RPC request failed: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(format!("RPC returned status: {}", response.status()));
-    }
+    let json = evm_rpc_call(rpc_url, "eth_getBalance", serde_json::json!([address, "latest"])).await?;
     
-    let json: serde_json::Value = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse RPC response: {}", e))?;
-    
-    // Check for RPC error
-    if let Some(error) = json.get("error") {
-        let error_msg = error.get("message")
-            .and_then(|m| m.as_str())
-            .unwrap_or("Unknown RPC error");
-        return Err(format!("RPC error: {}", error_msg));
-    }
-    
-    let balance_hex = json.get("result")
-        .and_then(|r| r.as_str())
+    let balance_hex = json.as_str()
         .ok_or_else(|| "Invalid RPC response format".to_string())?;
     
     // Convert hex to decimal
@@ -370,6 +845,12 @@ async fn try_evm_rpc_balance(rpc_url: &str, address: &str) -> Result<u128, Strin
         .map_err(|e| format!("Failed to parse balance: {}", e))
 }
 
+/// Native balance (wei) of an EVM address from the chain's primary RPC.
+pub async fn evm_native_balance_wei(chain: &str, address: &str) -> Result<u128, String> {
+    let (primary_rpc, _) = evm_rpc_urls(chain)?;
+    try_evm_rpc_balance(&primary_rpc, address).await
+}
+
 /// Retry EVM RPC with exponential backoff
 async fn retry_evm_rpc_balance(
     rpc_url: &str,
@@ -424,3 +905,184 @@ async fn fetch_evm_price(chain: &str) -> Result<f64, String> {
     
     Err("Failed to fetch price".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_fallback_rpcs_from_env_list() {
+        let defaults = ["https://a.example", "https://b.example"];
+        assert_eq!(parse_rpc_list(None, &defaults), vec!["https://a.example", "https://b.example"]);
+        assert_eq!(parse_rpc_list(Some(" , "), &defaults), vec!["https://a.example", "https://b.example"]);
+        assert_eq!(
+            parse_rpc_list(Some("https://paid.example/?api-key=k1, https://c.example ,"), &defaults),
+            vec!["https://paid.example/?api-key=k1", "https://c.example"]
+        );
+        assert_eq!(endpoint_label("https://paid.example/?api-key=k1"), "paid.example");
+        assert_eq!(endpoint_label("https://eth-mainnet.example/v2/secret"), "eth-mainnet.example");
+    }
+
+    #[test]
+    fn test_token_holdings_summed_per_mint_and_dust_skipped() {
+        let (bonk, wif) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let holding = |mint: Pubkey, amount: u64, program_id: Pubkey| TokenAccountHolding {
+            account: Pubkey::new_unique(), mint, program_id, amount, decimals: 6,
+        };
+        let holdings = vec![
+            holding(bonk, 1_500_000, spl_token::id()),
+            holding(wif, 0, spl_token_2022::id()),
+            holding(bonk, 500_000, spl_token_2022::id()),
+        ];
+        assert_eq!(totals_by_mint(&holdings), vec![(bonk, 2_000_000, 6)]);
+
+        let entry = token_balance_entry(&bonk.to_string(), 2_000_000, 6, Some((3.0, Some("BONK".to_string()))), 1.0).unwrap();
+        assert_eq!((entry.symbol.as_str(), entry.balance.as_str(), entry.balance_usd), ("BONK", "2", 6.0));
+
+        // Below the threshold, or unpriced
+        assert!(token_balance_entry(&bonk.to_string(), 2_000_000, 6, Some((0.25, None)), 1.0).is_none());
+        assert!(token_balance_entry(&bonk.to_string(), 2_000_000, 6, None, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_solana_buy_uses_configured_fee_buffer() {
+        assert_eq!(fee_buffer_from("solana", None), 10_000_000);
+        assert_eq!(fee_buffer_from("solana", Some("0.05")), 50_000_000);
+        assert_eq!(fee_buffer_from("solana", Some("garbage")), 10_000_000);
+        assert_eq!(fee_buffer_from("eth", None), 5_000_000_000_000_000);
+
+        let buffer = fee_buffer_from("solana", Some("0.05")) as u64;
+        let amount = 1_000_000_000;
+        assert!(check_buy_funds(1_050_000_000, amount, buffer).is_ok());
+        assert_eq!(check_buy_funds(1_049_999_999, amount, buffer), Err(1_050_000_000));
+        // The old fixed 0.01 SOL buffer would have let this through
+        assert!(check_buy_funds(1_020_000_000, amount, 10_000_000).is_ok());
+        assert!(check_buy_funds(1_020_000_000, amount, buffer).is_err());
+    }
+
+    #[test]
+    fn test_percent_of_available() {
+        // 2 SOL available, 0.01 SOL buffer: half of 1.99 SOL
+        let buffer = 10_000_000;
+        let half = percent_of_available(2_000_000_000, buffer, 50.0);
+        assert_eq!(half, 995_000_000);
+        assert!(check_buy_funds(2_000_000_000, half, buffer).is_ok());
+        // 100% still leaves the buffer
+        let all = percent_of_available(2_000_000_000, buffer, 100.0);
+        assert!(check_buy_funds(2_000_000_000, all, buffer).is_ok());
+        // Less than the buffer available
+        assert_eq!(percent_of_available(5_000_000, buffer, 100.0), 0);
+    }
+
+    #[test]
+    fn test_active_grid_reduces_available_sol() {
+        let request = crate::grid_trading::CreateGridRequest {
+            user_id: 7,
+            chain: "solana".to_string(),
+            token: "Token".to_string(),
+            token_symbol: "TKN".to_string(),
+            lower_price: 1.0,
+            upper_price: 2.0,
+            grid_count: 4,
+            investment_amount: 2.0,
+            current_price: Some(1.5),
+            spacing_mode: crate::grid_trading::SpacingMode::Arithmetic,
+        };
+        let grid = crate::grid_trading::create_grid_strategy(request).unwrap();
+        let mut grids = HashMap::new();
+        grids.insert(grid.strategy_id.clone(), grid);
+
+        // $2 of buys at $100/SOL
+        let committed = usd_to_lamports(committed_usd(&grids, 7, "solana"), 100.0);
+        assert!(committed > 0);
+        assert!(committed <= 20_000_000);
+        assert_eq!(committed_usd(&grids, 8, "solana"), 0.0);
+
+        let balance = 5_000_000_000;
+        let reserve = 500_000_000;
+        assert_eq!(available_for_buy(balance, committed, reserve), balance - committed - reserve);
+        assert!(available_for_buy(balance, committed, reserve) < available_for_buy(balance, 0, reserve));
+
+        // Stopped grids no longer hold SOL back
+        grids.values_mut().for_each(|g| g.status = crate::grid_trading::GridStatus::Stopped);
+        assert_eq!(committed_usd(&grids, 7, "solana"), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_anomalous_reading_triggers_requery() {
+        let cache = BalanceCache::default();
+        let calls = AtomicUsize::new(0);
+        let requery = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(2_000_000_000)
+        };
+
+        cache.validate_reading(1, "wallet", 2_000_000_000, || async { Ok(0) }).await.unwrap();
+        // RPC suddenly reports zero - must be confirmed elsewhere
+        let trusted = cache.validate_reading(1, "wallet", 0, requery).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(trusted, 2_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_consistent_reading_skips_requery() {
+        let cache = BalanceCache::default();
+        let calls = AtomicUsize::new(0);
+        let requery = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(0)
+        };
+
+        cache.validate_reading(1, "wallet", 2_000_000_000, || async { Ok(0) }).await.unwrap();
+        let trusted = cache.validate_reading(1, "wallet", 1_500_000_000, requery).await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(trusted, 1_500_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_unexplained_drop_notifies() {
+        let queue = NotificationQueue::new();
+        let cache = BalanceCache::new(queue.clone());
+
+        cache.validate_reading(7, "wallet", 2_000_000_000, || async { Ok(0) }).await.unwrap();
+        cache.validate_reading(7, "wallet", 1_000_000_000, || async { Ok(0) }).await.unwrap();
+
+        let pending = queue.drain(7).await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].alert_type, "balance");
+        assert_eq!(pending[0].priority, "high");
+    }
+
+    #[tokio::test]
+    async fn test_trade_correlated_drop_is_silent() {
+        let queue = NotificationQueue::new();
+        let cache = BalanceCache::new(queue.clone());
+
+        cache.validate_reading(7, "wallet", 2_000_000_000, || async { Ok(0) }).await.unwrap();
+        cache.note_activity("wallet").await;
+        cache.validate_reading(7, "wallet", 1_000_000_000, || async { Ok(0) }).await.unwrap();
+
+        assert!(queue.drain(7).await.is_empty());
+    }
+
+    #[test]
+    fn test_batch_response_matched_by_id() {
+        let json = serde_json::json!([
+            { "jsonrpc": "2.0", "id": 2, "result": "0x12" },
+            { "jsonrpc": "2.0", "id": 0, "result": "0x01" },
+            { "jsonrpc": "2.0", "id": 1, "error": { "code": -32000, "message": "execution reverted" } },
+            { "jsonrpc": "2.0", "id": 9, "result": "0xff" },
+        ]);
+        let results = parse_batch_response(&json, 4).unwrap();
+        assert_eq!(results[0], Ok(serde_json::json!("0x01")));
+        assert_eq!(results[1], Err("RPC error: execution reverted".to_string()));
+        assert_eq!(results[2], Ok(serde_json::json!("0x12")));
+        assert!(results[3].is_err());
+
+        // A node without batch support answers with a single error object
+        assert!(parse_batch_response(&serde_json::json!({ "error": { "message": "batch not supported" } }), 1).is_err());
+    }
+}
@@ -4,7 +4,11 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::Utc;
+use sqlx::PgPool;
 use uuid::Uuid;
+use std::sync::Arc;
+use solana_client::rpc_client::RpcClient;
+use crate::execution::{self, BundleSwap, PriorityTier, WSOL_MINT};
 
 // ==================== DATA STRUCTURES ====================
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +22,10 @@ pub struct BundledTransaction {
     pub executed_at: Option<i64>,
     pub gas_saved: f64,
     pub total_gas_cost: f64,
+    pub max_wait_seconds: i64, // Send once the oldest transaction has waited this long
+    pub min_transactions: usize, // ...or as soon as this many are queued
+    #[serde(default)]
+    pub wallet_label: Option<String>, // Signing wallet, None = the user's default
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +36,29 @@ pub struct PendingTransaction {
     pub amount: String,
     pub slippage: f64,
     pub priority: i32, // 1-10, higher = more urgent
+    #[serde(default)]
+    pub position: Option<BundledPosition>, // Buys: the position to open once the swap lands
+    #[serde(default)]
+    pub sell: Option<BundledSell>, // Sells: the slice of a position they close
+}
+
+/// The position a bundled sell comes out of. `amount` on the transaction is the raw
+/// token amount of that slice.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundledSell {
+    pub position_id: String,
+    pub percent: f64,
+}
+
+/// Exit settings of the buy request a bundled buy came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundledPosition {
+    pub take_profit: f64,
+    pub stop_loss: f64,
+    pub exit_slippage_bps: i32,
+    pub trailing_stop: Option<f64>,
+    pub tp_ladder: Option<Vec<crate::positions::LadderRung>>,
+    pub panic_sell_on_rug: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +68,32 @@ pub enum BundleStatus {
     Executing,
     Completed,
     Failed,
+    Cancelled,
+}
+
+impl BundleStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BundleStatus::Pending => "Pending",
+            BundleStatus::Bundling => "Bundling",
+            BundleStatus::Executing => "Executing",
+            BundleStatus::Completed => "Completed",
+            BundleStatus::Failed => "Failed",
+            BundleStatus::Cancelled => "Cancelled",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Pending" => Some(BundleStatus::Pending),
+            "Bundling" => Some(BundleStatus::Bundling),
+            "Executing" => Some(BundleStatus::Executing),
+            "Completed" => Some(BundleStatus::Completed),
+            "Failed" => Some(BundleStatus::Failed),
+            "Cancelled" => Some(BundleStatus::Cancelled),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -48,6 +105,8 @@ pub struct AddToBundleRequest {
     pub amount: String,
     pub slippage: f64,
     pub priority: Option<i32>,
+    pub position: Option<BundledPosition>,
+    pub sell: Option<BundledSell>,
 }
 
 #[derive(Debug, Serialize)]
@@ -69,7 +128,51 @@ pub struct BundleStatusResponse {
 }
 
 // ==================== BUNDLE MANAGEMENT ====================
-pub fn create_bundle(user_id: i64, chain: String) -> BundledTransaction {
+
+/// `BUNDLE_MAX_WAIT_SECS`: default wait before a new bundle is sent (30).
+fn default_max_wait_seconds() -> i64 {
+    std::env::var("BUNDLE_MAX_WAIT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(30)
+}
+
+/// `BUNDLE_MIN_TRANSACTIONS`: default queue size that sends a new bundle early (3).
+fn default_min_transactions() -> usize {
+    std::env::var("BUNDLE_MIN_TRANSACTIONS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(3)
+}
+
+/// Upper bounds for per-bundle settings.
+const MAX_BUNDLE_WAIT_SECS: i64 = 3600;
+const MAX_BUNDLE_MIN_TRANSACTIONS: usize = 50;
+
+/// Apply user-chosen send triggers to an open bundle. `None` keeps the current value.
+pub fn apply_bundle_settings(
+    bundle: &mut BundledTransaction,
+    max_wait_seconds: Option<i64>,
+    min_transactions: Option<usize>,
+) -> Result<(), String> {
+    if let Some(wait) = max_wait_seconds {
+        if !(1..=MAX_BUNDLE_WAIT_SECS).contains(&wait) {
+            return Err(format!("Bundle wait must be between 1 and {} seconds", MAX_BUNDLE_WAIT_SECS));
+        }
+        bundle.max_wait_seconds = wait;
+    }
+    if let Some(min) = min_transactions {
+        if !(1..=MAX_BUNDLE_MIN_TRANSACTIONS).contains(&min) {
+            return Err(format!("Bundle minimum must be between 1 and {} transactions", MAX_BUNDLE_MIN_TRANSACTIONS));
+        }
+        bundle.min_transactions = min;
+    }
+    Ok(())
+}
+
+pub fn create_bundle(user_id: i64, chain: String, wallet_label: Option<String>) -> BundledTransaction {
     BundledTransaction {
         bundle_id: format!("bundle_{}_{}", user_id, Uuid::new_v4()),
         user_id,
@@ -80,6 +183,9 @@ pub fn create_bundle(user_id: i64, chain: String) -> BundledTransaction {
         executed_at: None,
         gas_saved: 0.0,
         total_gas_cost: 0.0,
+        max_wait_seconds: default_max_wait_seconds(),
+        min_transactions: default_min_transactions(),
+        wallet_label,
     }
 }
 
@@ -102,6 +208,11 @@ pub fn add_transaction_to_bundle(
         _ => return Err("Bundle is no longer accepting transactions".to_string()),
     }
     
+    // A sell is booked against its position once it lands
+    if request.tx_type.eq_ignore_ascii_case("sell") && request.sell.is_none() {
+        return Err("Bundled sells must name the position they sell".to_string());
+    }
+
     let tx_id = format!("tx_{}", Uuid::new_v4());
     let transaction = PendingTransaction {
         tx_id: tx_id.clone(),
@@ -110,6 +221,8 @@ pub fn add_transaction_to_bundle(
         amount: request.amount,
         slippage: request.slippage,
         priority: request.priority.unwrap_or(5),
+        position: request.position,
+        sell: request.sell,
     };
     
     bundle.transactions.push(transaction);
@@ -128,36 +241,48 @@ pub fn calculate_gas_savings(
     savings.max(0.0)
 }
 
-pub fn estimate_bundle_gas_cost(
-    chain: &str,
-    transaction_count: usize,
-) -> f64 {
-    // Base gas cost per chain
-    let base_gas = match chain {
+/// Fallback cost of one standalone transaction when no live gas price is available.
+fn default_tx_cost(chain: &str) -> f64 {
+    match chain {
         "solana" => 0.000005, // ~5000 lamports base
         "eth" | "ethereum" => 0.001, // ~100k gas base
         "bsc" | "binance" => 0.0001, // ~50k gas base
         _ => 0.001,
+    }
+}
+
+/// Cost of one standalone transaction at the current gas price, in the chain's native token.
+pub async fn single_tx_cost(chain: &str) -> f64 {
+    let gas_limit = match chain {
+        "bsc" | "binance" => 50_000,
+        _ => 100_000,
     };
-    
+    match crate::gas::get_gas_price(chain).await {
+        Ok(price) => crate::gas::estimate_transaction_cost(&price, gas_limit, chain),
+        Err(e) => {
+            tracing::warn!("Gas price unavailable for {} ({}), using defaults", chain, e);
+            default_tx_cost(chain)
+        }
+    }
+}
+
+pub fn bundle_gas_cost(base_gas: f64, transaction_count: usize) -> f64 {
     // Each additional transaction adds less gas (bundling benefit)
     let per_tx_gas = base_gas * 0.3; // 70% savings per additional tx
-    let total_gas = base_gas + (per_tx_gas * (transaction_count - 1) as f64);
-    
-    total_gas
+    base_gas + (per_tx_gas * transaction_count.saturating_sub(1) as f64)
 }
 
-pub fn get_bundle_status(bundle: &BundledTransaction) -> BundleStatusResponse {
-    let individual_gas = match bundle.chain.as_str() {
-        "solana" => 0.000005,
-        "eth" | "ethereum" => 0.001,
-        "bsc" | "binance" => 0.0001,
-        _ => 0.001,
+/// Status of a bundle. Open bundles report the savings estimated at current gas prices,
+/// executed ones what they actually saved.
+pub async fn get_bundle_status(bundle: &BundledTransaction) -> BundleStatusResponse {
+    let individual_gas = single_tx_cost(&bundle.chain).await;
+    let gas_saved = if bundle.executed_at.is_some() {
+        bundle.gas_saved
+    } else {
+        let bundled_gas = bundle_gas_cost(individual_gas, bundle.transactions.len());
+        calculate_gas_savings(individual_gas, bundled_gas, bundle.transactions.len())
     };
-    
-    let bundled_gas = estimate_bundle_gas_cost(&bundle.chain, bundle.transactions.len());
-    let gas_saved = calculate_gas_savings(individual_gas, bundled_gas, bundle.transactions.len());
-    let savings_percent = if bundle.transactions.len() > 0 {
+    let savings_percent = if !bundle.transactions.is_empty() {
         (gas_saved / (individual_gas * bundle.transactions.len() as f64)) * 100.0
     } else {
         0.0
@@ -174,47 +299,90 @@ pub fn get_bundle_status(bundle: &BundledTransaction) -> BundleStatusResponse {
 }
 
 // ==================== BUNDLE EXECUTION ====================
+
+/// Jupiter swap for a queued transaction. Buys spend `amount` SOL; sells spend `amount`
+/// raw token units. Slippage is in percent.
+pub fn to_bundle_swap(tx: &PendingTransaction) -> Result<BundleSwap, String> {
+    let slippage_bps = (tx.slippage * 100.0).round() as u64;
+    match tx.tx_type.to_lowercase().as_str() {
+        "buy" => Ok(BundleSwap {
+            input_mint: WSOL_MINT.to_string(),
+            output_mint: tx.token.clone(),
+            amount: u64::try_from(crate::units::parse_token_amount(&tx.amount, crate::units::SOL_DECIMALS)?)
+                .map_err(|_| format!("Amount too large: {}", tx.amount))?,
+            slippage_bps,
+        }),
+        "sell" => Ok(BundleSwap {
+            input_mint: tx.token.clone(),
+            output_mint: WSOL_MINT.to_string(),
+            amount: tx.amount.parse::<u64>().map_err(|_| format!("Invalid raw token amount: {}", tx.amount))?,
+            slippage_bps,
+        }),
+        other => Err(format!("Unsupported bundled transaction type: {}", other)),
+    }
+}
+
+/// Queued transactions in send order: highest priority first, otherwise as queued.
+pub fn send_order(bundle: &BundledTransaction) -> Vec<&PendingTransaction> {
+    let mut queued: Vec<&PendingTransaction> = bundle.transactions.iter().collect();
+    queued.sort_by_key(|tx| std::cmp::Reverse(tx.priority));
+    queued
+}
+
+/// Swaps in send order.
+pub fn bundle_swaps(bundle: &BundledTransaction) -> Result<Vec<BundleSwap>, String> {
+    send_order(bundle).into_iter().map(to_bundle_swap).collect()
+}
+
+/// Execute the bundle on-chain. Returns each queued transaction (in send order) with the
+/// signature of the transaction that carried it, since a bundle too large for one
+/// transaction is split across several.
 pub async fn execute_bundle(
     bundle: &mut BundledTransaction,
-) -> Result<String, String> {
+    client: &RpcClient,
+    signer: &solana_sdk::signature::Keypair,
+) -> Result<Vec<(PendingTransaction, String)>, String> {
     if bundle.transactions.is_empty() {
         return Err("Bundle has no transactions".to_string());
     }
-    
+    if bundle.chain != "solana" {
+        return Err(format!("Bundled execution is not supported on {}", bundle.chain));
+    }
+    let swaps = bundle_swaps(bundle)?;
+
     bundle.status = BundleStatus::Executing;
-    
-    // In production, this would:
-    // 1. Build a single transaction containing all operations
-    // 2. Sign with user's wallet
-    // 3. Submit to blockchain
-    // 4. Wait for confirmation
-    
-    // For now, simulate execution
-    let bundle_tx_hash = format!("bundle_tx_{}", Uuid::new_v4());
-    
-    // Calculate actual gas savings
-    let individual_gas = match bundle.chain.as_str() {
-        "solana" => 0.000005,
-        "eth" | "ethereum" => 0.001,
-        "bsc" | "binance" => 0.0001,
-        _ => 0.001,
+    let signatures = match execution::execute_solana_bundle(client, signer, &swaps, PriorityTier::Auto).await {
+        Ok(signatures) => signatures,
+        Err(e) => {
+            bundle.status = BundleStatus::Failed;
+            return Err(e.to_string());
+        }
     };
-    
-    let bundled_gas = estimate_bundle_gas_cost(&bundle.chain, bundle.transactions.len());
+
+    // Savings against sending every swap on its own
+    let mut sent = signatures.clone();
+    sent.dedup();
+    let individual_gas = single_tx_cost(&bundle.chain).await;
+    let bundled_gas = individual_gas * sent.len() as f64;
     bundle.gas_saved = calculate_gas_savings(individual_gas, bundled_gas, bundle.transactions.len());
     bundle.total_gas_cost = bundled_gas;
     bundle.executed_at = Some(Utc::now().timestamp());
     bundle.status = BundleStatus::Completed;
-    
-    Ok(bundle_tx_hash)
+
+    Ok(send_order(bundle).into_iter().cloned().zip(signatures).collect())
 }
 
 pub fn should_execute_bundle(
     bundle: &BundledTransaction,
     max_wait_seconds: i64,
     min_transactions: usize,
+    now: i64,
 ) -> bool {
-    let age = Utc::now().timestamp() - bundle.created_at;
+    // Nothing to send: no trigger applies
+    if bundle.transactions.is_empty() {
+        return false;
+    }
+    let age = now - bundle.created_at;
     
     // Execute if:
     // 1. Has minimum transactions AND waited long enough
@@ -227,3 +395,618 @@ pub fn should_execute_bundle(
     
     has_min_txs || has_high_priority || timeout_reached
 }
+
+// ==================== PERSISTENCE ====================
+
+#[derive(sqlx::FromRow)]
+struct BundleRow {
+    bundle_id: String,
+    user_id: i64,
+    chain: String,
+    status: String,
+    transactions: String, // JSON-encoded Vec<PendingTransaction>
+    created_at: i64,
+    executed_at: Option<i64>,
+    gas_saved: f64,
+    total_gas_cost: f64,
+    max_wait_seconds: i64,
+    min_transactions: i32,
+    wallet_label: Option<String>,
+}
+
+impl BundleRow {
+    fn into_bundle(self) -> Result<BundledTransaction, String> {
+        let status = BundleStatus::parse(&self.status)
+            .ok_or_else(|| format!("Unknown bundle status: {}", self.status))?;
+        let transactions = serde_json::from_str(&self.transactions)
+            .map_err(|e| format!("Corrupt bundle transactions for {}: {}", self.bundle_id, e))?;
+
+        Ok(BundledTransaction {
+            bundle_id: self.bundle_id,
+            user_id: self.user_id,
+            chain: self.chain,
+            transactions,
+            status,
+            created_at: self.created_at,
+            executed_at: self.executed_at,
+            gas_saved: self.gas_saved,
+            total_gas_cost: self.total_gas_cost,
+            max_wait_seconds: self.max_wait_seconds,
+            min_transactions: self.min_transactions.max(1) as usize,
+            wallet_label: self.wallet_label,
+        })
+    }
+}
+
+/// Upsert the bundle. Writing an open status over a bundle the scheduler has already
+/// claimed is refused, so a late append can't resurrect it.
+pub async fn save_bundle(bundle: &BundledTransaction, pool: &PgPool) -> Result<(), String> {
+    let transactions = serde_json::to_string(&bundle.transactions)
+        .map_err(|e| format!("Failed to encode bundle: {}", e))?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO bundles (bundle_id, user_id, chain, status, transactions, created_at, executed_at, gas_saved, total_gas_cost, max_wait_seconds, min_transactions, wallet_label)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        ON CONFLICT (bundle_id) DO UPDATE SET
+            status = EXCLUDED.status,
+            transactions = EXCLUDED.transactions,
+            executed_at = EXCLUDED.executed_at,
+            gas_saved = EXCLUDED.gas_saved,
+            total_gas_cost = EXCLUDED.total_gas_cost,
+            max_wait_seconds = EXCLUDED.max_wait_seconds,
+            min_transactions = EXCLUDED.min_transactions
+        WHERE bundles.status IN ('Pending', 'Bundling') OR EXCLUDED.status NOT IN ('Pending', 'Bundling')
+        "#
+    )
+    .bind(&bundle.bundle_id)
+    .bind(bundle.user_id)
+    .bind(&bundle.chain)
+    .bind(bundle.status.as_str())
+    .bind(transactions)
+    .bind(bundle.created_at)
+    .bind(bundle.executed_at)
+    .bind(bundle.gas_saved)
+    .bind(bundle.total_gas_cost)
+    .bind(bundle.max_wait_seconds)
+    .bind(bundle.min_transactions as i32)
+    .bind(&bundle.wallet_label)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save bundle: {}", e))?;
+
+    if result.rows_affected() == 0 {
+        return Err(format!("Bundle {} is already executing, please retry", bundle.bundle_id));
+    }
+    Ok(())
+}
+
+/// Move an open bundle to Executing. False if someone else got there first.
+pub async fn claim_bundle(bundle_id: &str, pool: &PgPool) -> Result<bool, String> {
+    let result = sqlx::query("UPDATE bundles SET status = 'Executing' WHERE bundle_id = $1 AND status IN ('Pending', 'Bundling')")
+        .bind(bundle_id)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to claim bundle: {}", e))?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Load every bundle that hasn't reached a terminal status.
+pub async fn load_open_bundles(pool: &PgPool) -> Result<Vec<BundledTransaction>, String> {
+    let rows = sqlx::query_as::<_, BundleRow>(
+        "SELECT * FROM bundles WHERE status NOT IN ('Completed', 'Failed', 'Cancelled') ORDER BY created_at"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load bundles: {}", e))?;
+
+    rows.into_iter().map(BundleRow::into_bundle).collect()
+}
+
+pub async fn load_bundle(bundle_id: &str, pool: &PgPool) -> Result<Option<BundledTransaction>, String> {
+    let row = sqlx::query_as::<_, BundleRow>("SELECT * FROM bundles WHERE bundle_id = $1")
+        .bind(bundle_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to load bundle: {}", e))?;
+    row.map(BundleRow::into_bundle).transpose()
+}
+
+/// Return the user's open bundle on `chain` for `wallet_label`, or start a new one.
+/// Each wallet gets its own bundle since a bundle is signed by one wallet.
+pub async fn get_or_create_open_bundle(user_id: i64, chain: &str, wallet_label: Option<&str>, pool: &PgPool) -> Result<BundledTransaction, String> {
+    let row = sqlx::query_as::<_, BundleRow>(
+        r#"
+        SELECT * FROM bundles
+        WHERE user_id = $1 AND chain = $2 AND wallet_label IS NOT DISTINCT FROM $3 AND status IN ('Pending', 'Bundling')
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#
+    )
+    .bind(user_id)
+    .bind(chain)
+    .bind(wallet_label)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to load bundle: {}", e))?;
+
+    match row {
+        Some(row) => row.into_bundle(),
+        None => Ok(create_bundle(user_id, chain.to_string(), wallet_label.map(String::from))),
+    }
+}
+
+// ==================== SCHEDULER ====================
+
+/// Claim, sign with the bundle's wallet, execute, book the fills and record the outcome.
+async fn run_bundle(
+    bundle: &mut BundledTransaction,
+    pool: &PgPool,
+    solana_client: &Arc<RpcClient>,
+    risk_state: &crate::risk_engine::RiskState,
+) -> Result<(), String> {
+    if !claim_bundle(&bundle.bundle_id, pool).await? {
+        return Ok(());
+    }
+    bundle.status = BundleStatus::Executing;
+
+    let signer = crate::wallet::get_wallet_keypair(
+        bundle.user_id,
+        &bundle.chain,
+        &crate::wallet::WalletSelector::from_label(bundle.wallet_label.as_deref()),
+        crate::wallet::KeyPurpose::Trade,
+        pool,
+    ).await;
+    let result = match signer {
+        Ok(signer) => execute_bundle(bundle, solana_client, &signer).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(fills) => {
+            tracing::info!("📦 Bundle {} landed", bundle.bundle_id);
+            for (tx, signature) in &fills {
+                if let Err(e) = record_bundled_fill(bundle, tx, signature, pool, risk_state).await {
+                    tracing::error!("Bundle {}: failed to record {} ({}): {}", bundle.bundle_id, tx.tx_id, signature, e);
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("Failed to execute bundle {}: {}", bundle.bundle_id, e);
+            bundle.status = BundleStatus::Failed;
+        }
+    }
+    save_bundle(bundle, pool).await
+}
+
+/// Record a landed bundled swap like a standalone trade. Buys log a transaction and open the
+/// position their request asked for; sells are settled against their position like any other
+/// sell. Bundles have no per-swap output readout, so a buy's token amount is estimated from
+/// cost at the entry price and a sell exits at the market price.
+async fn record_bundled_fill(
+    bundle: &BundledTransaction,
+    tx: &PendingTransaction,
+    signature: &str,
+    pool: &PgPool,
+    risk_state: &crate::risk_engine::RiskState,
+) -> Result<(), String> {
+    use crate::positions;
+
+    if let Some(sell) = &tx.sell {
+        let market_price = crate::price::fetch_token_price(&bundle.chain, &tx.token).await.ok().map(|p| p.price_usd);
+        let sol_price_usd = crate::price::fetch_sol_price().await.ok();
+        return record_bundled_sell(bundle, tx, sell, signature, market_price, sol_price_usd, pool, risk_state).await;
+    }
+
+    let (entry_price, price_unknown) = positions::resolve_entry_price(|| async {
+        crate::price::fetch_token_price(&bundle.chain, &tx.token).await.map(|p| p.price_usd)
+    }).await;
+    let sol_price_usd = crate::price::fetch_sol_price().await.ok();
+    let is_buy = tx.tx_type.eq_ignore_ascii_case("buy");
+
+    sqlx::query(
+        "INSERT INTO transactions (transaction_id, user_id, chain, type, token_address, amount, price, tx_hash, sol_price_usd) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(bundle.user_id)
+    .bind(&bundle.chain)
+    .bind(if is_buy { "BUY" } else { "SELL" })
+    .bind(&tx.token)
+    .bind(&tx.amount)
+    .bind(entry_price)
+    .bind(signature)
+    .bind(sol_price_usd)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to record transaction: {}", e))?;
+
+    let (true, Some(settings)) = (is_buy, &tx.position) else {
+        return Ok(());
+    };
+    let amount = tx.amount.parse::<f64>().map_err(|_| format!("Invalid amount: {}", tx.amount))?;
+    let cost_basis = positions::buy_cost_basis(&bundle.chain, amount, sol_price_usd);
+    let position_id = format!("{}_{}", bundle.user_id, Uuid::new_v4());
+    sqlx::query(
+        "INSERT INTO positions (position_id, user_id, chain, token_address, amount, entry_price, current_price, take_profit_percent, stop_loss_percent, exit_slippage_bps, price_unknown, trailing_stop_percent, high_water_mark, cost_basis_usd, tp_ladder, wallet_label, panic_sell_on_rug, token_amount) VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8, $9, $10, $11, $6, $12, $13, $14, $15, $16)"
+    )
+    .bind(&position_id)
+    .bind(bundle.user_id)
+    .bind(&bundle.chain)
+    .bind(&tx.token)
+    .bind(&tx.amount)
+    .bind(entry_price)
+    .bind(settings.take_profit)
+    .bind(settings.stop_loss)
+    .bind(settings.exit_slippage_bps)
+    .bind(price_unknown)
+    .bind(settings.trailing_stop)
+    .bind(cost_basis)
+    .bind(settings.tp_ladder.clone().map(sqlx::types::Json))
+    .bind(&bundle.wallet_label)
+    .bind(settings.panic_sell_on_rug)
+    .bind(positions::lot_token_amount(None, cost_basis, entry_price))
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to open position: {}", e))?;
+
+    tracing::info!("📦 Bundled buy {} opened position {}", tx.tx_id, position_id);
+    Ok(())
+}
+
+/// Settle a landed bundled sell through the same bookkeeping as perform_sell: realized PnL on
+/// the transaction, the daily loss, and the position shrunk or closed.
+#[allow(clippy::too_many_arguments)]
+async fn record_bundled_sell(
+    bundle: &BundledTransaction,
+    tx: &PendingTransaction,
+    sell: &BundledSell,
+    signature: &str,
+    market_price: Option<f64>,
+    sol_price_usd: Option<f64>,
+    pool: &PgPool,
+    risk_state: &crate::risk_engine::RiskState,
+) -> Result<(), String> {
+    let mut position = sqlx::query_as::<_, crate::Position>(
+        "SELECT * FROM positions WHERE position_id = $1 AND user_id = $2 AND status = 'OPEN'"
+    )
+    .bind(&sell.position_id)
+    .bind(bundle.user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("Failed to load position: {}", e))?
+    .ok_or_else(|| format!("Open position {} not found", sell.position_id))?;
+    if position.token_address != tx.token {
+        return Err(format!("Position {} holds {}, not {}", sell.position_id, position.token_address, tx.token));
+    }
+    if let Some(price) = market_price.filter(|p| *p > 0.0) {
+        position.current_price = price;
+    }
+
+    let fill = crate::SellFill { tx_hash: signature.to_string(), proceeds: None, platform_fee: None };
+    let outcome = crate::settle_sell(pool, risk_state, &position, sell.percent, execution::SellOutput::Sol, fill, sol_price_usd).await;
+    tracing::info!("📦 Bundled sell {} closed {}% of position {} ({:+.2}%)", tx.tx_id, sell.percent, sell.position_id, outcome.profit_loss);
+    Ok(())
+}
+
+/// Sends open bundles once their own triggers fire. Checks every `BUNDLE_CHECK_SECS` (default 5).
+pub fn spawn_bundle_scheduler(pool: PgPool, solana_client: Arc<RpcClient>, risk_state: crate::risk_engine::RiskState) {
+    let interval_secs = std::env::var("BUNDLE_CHECK_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(5);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+
+            let bundles = match load_open_bundles(&pool).await {
+                Ok(b) => b,
+                Err(e) => {
+                    tracing::error!("Bundle scheduler failed to load bundles: {}", e);
+                    continue;
+                }
+            };
+
+            let now = Utc::now().timestamp();
+            for mut bundle in bundles {
+                if !should_execute_bundle(&bundle, bundle.max_wait_seconds, bundle.min_transactions, now) {
+                    continue;
+                }
+                tracing::info!("📦 Executing bundle {} ({} txs)", bundle.bundle_id, bundle.transactions.len());
+                if let Err(e) = run_bundle(&mut bundle, &pool, &solana_client, &risk_state).await {
+                    tracing::error!("Bundle {} not recorded: {}", bundle.bundle_id, e);
+                }
+            }
+        }
+    });
+}
+
+// ==================== RESUME ON STARTUP ====================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResumeAction {
+    /// Still fresh, keep accepting transactions
+    KeepOpen,
+    /// Past max age with transactions queued, send it now
+    Execute,
+    /// Past max age with nothing queued
+    Cancel,
+    /// Was mid-execution when we stopped, can't tell if it landed
+    MarkFailed,
+}
+
+/// Decide what to do with a non-terminal bundle found at startup.
+pub fn resume_decision(bundle: &BundledTransaction, max_age_secs: i64, now: i64) -> ResumeAction {
+    match bundle.status {
+        BundleStatus::Executing => ResumeAction::MarkFailed,
+        _ if now - bundle.created_at < max_age_secs => ResumeAction::KeepOpen,
+        _ if bundle.transactions.is_empty() => ResumeAction::Cancel,
+        _ => ResumeAction::Execute,
+    }
+}
+
+/// Reconcile bundles left open by a previous run. Max age comes from
+/// `BUNDLE_MAX_AGE_SECS` (default 300).
+pub async fn resume_bundles(pool: &PgPool, solana_client: &Arc<RpcClient>, risk_state: &crate::risk_engine::RiskState) -> Result<(), String> {
+    let max_age_secs = std::env::var("BUNDLE_MAX_AGE_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(300);
+    let now = Utc::now().timestamp();
+
+    for mut bundle in load_open_bundles(pool).await? {
+        match resume_decision(&bundle, max_age_secs, now) {
+            ResumeAction::KeepOpen => {
+                tracing::info!("📦 Resuming bundle {} ({} txs)", bundle.bundle_id, bundle.transactions.len());
+                continue;
+            }
+            ResumeAction::Execute => {
+                tracing::info!("📦 Executing stale bundle {} ({} txs)", bundle.bundle_id, bundle.transactions.len());
+                run_bundle(&mut bundle, pool, solana_client, risk_state).await?;
+                continue;
+            }
+            ResumeAction::Cancel => {
+                tracing::info!("📦 Cancelling empty stale bundle {}", bundle.bundle_id);
+                bundle.status = BundleStatus::Cancelled;
+            }
+            ResumeAction::MarkFailed => {
+                tracing::warn!("⚠️  Bundle {} was executing during shutdown, marking failed for review", bundle.bundle_id);
+                bundle.status = BundleStatus::Failed;
+            }
+        }
+        save_bundle(&bundle, pool).await?;
+    }
+
+    Ok(())
+}
+
+// ==================== API HANDLERS ====================
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
+
+pub async fn get_bundle_status_handler(
+    State(state): State<AppState>,
+    Path(bundle_id): Path<String>,
+) -> impl IntoResponse {
+    match load_bundle(&bundle_id, &state.db).await {
+        Ok(Some(bundle)) => (StatusCode::OK, Json(serde_json::json!(get_bundle_status(&bundle).await))),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({"success": false, "error": "Bundle not found"}))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"success": false, "error": e}))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle_with(status: BundleStatus, created_at: i64, tx_count: usize) -> BundledTransaction {
+        let mut bundle = create_bundle(1, "solana".to_string(), None);
+        bundle.created_at = created_at;
+        for _ in 0..tx_count {
+            add_transaction_to_bundle(&mut bundle, AddToBundleRequest {
+                user_id: 1,
+                chain: "solana".to_string(),
+                tx_type: "BUY".to_string(),
+                token: "Mint".to_string(),
+                amount: "0.1".to_string(),
+                slippage: 10.0,
+                priority: None,
+                position: None,
+                sell: None,
+            }).unwrap();
+        }
+        bundle.status = status;
+        bundle
+    }
+
+    #[test]
+    fn test_bundle_swaps_follow_priority_and_direction() {
+        let mut bundle = bundle_with(BundleStatus::Bundling, 0, 1);
+        bundle.transactions.push(PendingTransaction {
+            tx_id: "tx_sell".to_string(),
+            tx_type: "SELL".to_string(),
+            token: "Mint".to_string(),
+            amount: "2500000".to_string(),
+            slippage: 1.5,
+            priority: 9,
+            position: None,
+            sell: Some(BundledSell { position_id: "p1".to_string(), percent: 50.0 }),
+        });
+
+        let swaps = bundle_swaps(&bundle).unwrap();
+        assert_eq!(swaps[0], BundleSwap { input_mint: "Mint".to_string(), output_mint: WSOL_MINT.to_string(), amount: 2_500_000, slippage_bps: 150 });
+        assert_eq!(swaps[1], BundleSwap { input_mint: WSOL_MINT.to_string(), output_mint: "Mint".to_string(), amount: 100_000_000, slippage_bps: 1000 });
+
+        bundle.transactions[0].tx_type = "swap".to_string();
+        assert!(bundle_swaps(&bundle).is_err());
+    }
+
+    #[test]
+    fn test_stale_bundling_bundle_is_executed_or_cancelled() {
+        let now = 10_000;
+        let stale_with_txs = bundle_with(BundleStatus::Bundling, now - 600, 2);
+        let stale_empty = bundle_with(BundleStatus::Bundling, now - 600, 0);
+
+        assert_eq!(resume_decision(&stale_with_txs, 300, now), ResumeAction::Execute);
+        assert_eq!(resume_decision(&stale_empty, 300, now), ResumeAction::Cancel);
+    }
+
+    #[test]
+    fn test_empty_bundle_never_fires() {
+        let now = 10_000;
+        let empty = bundle_with(BundleStatus::Pending, now - 600, 0);
+        assert!(!should_execute_bundle(&empty, 30, 0, now));
+
+        let waiting = bundle_with(BundleStatus::Bundling, now - 10, 1);
+        assert!(!should_execute_bundle(&waiting, 30, 3, now));
+        assert!(should_execute_bundle(&waiting, 5, 3, now)); // Waited long enough
+        assert!(should_execute_bundle(&waiting, 30, 1, now)); // Enough queued
+    }
+
+    #[test]
+    fn test_bundle_settings_are_validated() {
+        let mut bundle = bundle_with(BundleStatus::Pending, 0, 0);
+        apply_bundle_settings(&mut bundle, Some(120), Some(5)).unwrap();
+        assert_eq!((bundle.max_wait_seconds, bundle.min_transactions), (120, 5));
+
+        assert!(apply_bundle_settings(&mut bundle, Some(0), None).is_err());
+        assert!(apply_bundle_settings(&mut bundle, None, Some(0)).is_err());
+        apply_bundle_settings(&mut bundle, None, None).unwrap();
+        assert_eq!((bundle.max_wait_seconds, bundle.min_transactions), (120, 5));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_claimed_bundle_cannot_be_reopened() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query("CREATE TEMP TABLE bundles (LIKE public.bundles INCLUDING DEFAULTS INCLUDING CONSTRAINTS INCLUDING INDEXES)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut bundle = bundle_with(BundleStatus::Bundling, 0, 1);
+        save_bundle(&bundle, &pool).await.unwrap();
+        assert!(claim_bundle(&bundle.bundle_id, &pool).await.unwrap());
+        assert!(!claim_bundle(&bundle.bundle_id, &pool).await.unwrap());
+
+        // A late append is refused, the scheduler's final status is not
+        assert!(save_bundle(&bundle, &pool).await.is_err());
+        bundle.status = BundleStatus::Completed;
+        save_bundle(&bundle, &pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_each_wallet_gets_its_own_bundle() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query("CREATE TEMP TABLE bundles (LIKE public.bundles INCLUDING DEFAULTS INCLUDING CONSTRAINTS INCLUDING INDEXES)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let default_bundle = bundle_with(BundleStatus::Bundling, 0, 1);
+        save_bundle(&default_bundle, &pool).await.unwrap();
+
+        // A buy from another wallet can't join a bundle the default wallet will sign
+        let sniper = get_or_create_open_bundle(1, "solana", Some("sniper"), &pool).await.unwrap();
+        assert_ne!(sniper.bundle_id, default_bundle.bundle_id);
+        assert_eq!(sniper.wallet_label.as_deref(), Some("sniper"));
+        let reopened = get_or_create_open_bundle(1, "solana", None, &pool).await.unwrap();
+        assert_eq!(reopened.bundle_id, default_bundle.bundle_id);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_bundled_sell_closes_position_and_records_pnl() {
+        let pool = crate::test_db::pool().await;
+        for table in ["positions", "transactions", "daily_stats"] {
+            sqlx::query(&format!("CREATE TEMP TABLE {table} (LIKE public.{table} INCLUDING DEFAULTS INCLUDING CONSTRAINTS INCLUDING INDEXES)"))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        // 1000 tokens bought at $0.001 for $1
+        sqlx::query(
+            "INSERT INTO positions (position_id, user_id, chain, token_address, amount, entry_price, current_price, \
+             take_profit_percent, stop_loss_percent, status, cost_basis_usd, token_amount) \
+             VALUES ('p1', 1, 'solana', 'Mint', '0.01', 0.001, 0.001, 50, 20, 'OPEN', 1.0, 1000)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let risk_state = crate::risk_engine::RiskState {
+            daily_stats: Default::default(),
+            global_blacklist: Default::default(),
+            dev_blacklist: Default::default(),
+            trade_times: Default::default(),
+        };
+
+        let mut bundle = bundle_with(BundleStatus::Bundling, 0, 0);
+        let sell = BundledSell { position_id: "p1".to_string(), percent: 100.0 };
+        add_transaction_to_bundle(&mut bundle, AddToBundleRequest {
+            user_id: 1,
+            chain: "solana".to_string(),
+            tx_type: "SELL".to_string(),
+            token: "Mint".to_string(),
+            amount: "1000".to_string(),
+            slippage: 10.0,
+            priority: None,
+            position: None,
+            sell: Some(sell.clone()),
+        }).unwrap();
+        let tx = bundle.transactions[0].clone();
+
+        // Exits at the market price: 1000 tokens at $0.002 is a $1 profit
+        record_bundled_sell(&bundle, &tx, &sell, "sig", Some(0.002), None, &pool, &risk_state).await.unwrap();
+
+        let status: String = sqlx::query_scalar("SELECT status FROM positions WHERE position_id = 'p1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "CLOSED");
+        let (amount, profit_loss): (String, Option<f64>) =
+            sqlx::query_as("SELECT amount, profit_loss FROM transactions WHERE tx_hash = 'sig' AND type = 'SELL'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(amount, "1000");
+        assert!((profit_loss.unwrap() - 1.0).abs() < 1e-9);
+
+        // The position is gone, so selling it again is refused rather than logged twice
+        assert!(record_bundled_sell(&bundle, &tx, &sell, "sig2", Some(0.002), None, &pool, &risk_state).await.is_err());
+    }
+
+    #[test]
+    fn test_bundled_sell_must_name_its_position() {
+        let mut bundle = bundle_with(BundleStatus::Bundling, 0, 0);
+        let result = add_transaction_to_bundle(&mut bundle, AddToBundleRequest {
+            user_id: 1,
+            chain: "solana".to_string(),
+            tx_type: "SELL".to_string(),
+            token: "Mint".to_string(),
+            amount: "1000".to_string(),
+            slippage: 10.0,
+            priority: None,
+            position: None,
+            sell: None,
+        });
+        assert!(result.is_err());
+        assert!(bundle.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_fresh_bundle_is_kept_open() {
+        let now = 10_000;
+        let fresh = bundle_with(BundleStatus::Bundling, now - 30, 1);
+        assert_eq!(resume_decision(&fresh, 300, now), ResumeAction::KeepOpen);
+
+        let interrupted = bundle_with(BundleStatus::Executing, now - 30, 1);
+        assert_eq!(resume_decision(&interrupted, 300, now), ResumeAction::MarkFailed);
+    }
+}
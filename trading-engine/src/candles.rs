@@ -0,0 +1,344 @@
+// OHLC Candles Module - chart data from Birdeye (when BIRDEYE_API_KEY is set) or GeckoTerminal
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+/// One USD-denominated candle. `t` is the open time (unix seconds).
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Candle {
+    pub t: i64,
+    pub o: f64,
+    pub h: f64,
+    pub l: f64,
+    pub c: f64,
+    pub v: f64,
+}
+
+/// Candles as served, with the interval actually used (may differ from the one asked for).
+#[derive(Debug, Serialize, Clone)]
+pub struct CandleSeries {
+    pub interval: String,
+    pub source: &'static str,
+    pub candles: Vec<Candle>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CandlesQuery {
+    pub interval: Option<String>, // e.g. 1m, 15m, 1h, 1d (default 15m)
+    pub limit: Option<usize>,     // Most recent candles to return (default 100, max 1000)
+}
+
+#[derive(Debug, Serialize)]
+pub struct CandlesResponse {
+    pub success: bool,
+    #[serde(flatten)]
+    pub series: Option<CandleSeries>,
+    pub error: Option<String>,
+}
+
+const DEFAULT_INTERVAL: &str = "15m";
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+// ==================== PROVIDERS ====================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CandleProvider {
+    GeckoTerminal,
+    Birdeye { api_key: String },
+}
+
+impl CandleProvider {
+    /// Birdeye when `BIRDEYE_API_KEY` is set, GeckoTerminal's keyless API otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var("BIRDEYE_API_KEY") {
+            Ok(key) if !key.trim().is_empty() => CandleProvider::Birdeye { api_key: key.trim().to_string() },
+            _ => CandleProvider::GeckoTerminal,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CandleProvider::GeckoTerminal => "geckoterminal",
+            CandleProvider::Birdeye { .. } => "birdeye",
+        }
+    }
+
+    pub fn intervals(&self) -> &'static [&'static str] {
+        match self {
+            CandleProvider::GeckoTerminal => &["1m", "5m", "15m", "1h", "4h", "12h", "1d"],
+            CandleProvider::Birdeye { .. } => &["1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d", "3d", "1w"],
+        }
+    }
+
+    /// The interval to request, falling back to the default (with a warning) when the
+    /// provider doesn't offer the one asked for.
+    pub fn resolve_interval(&self, requested: Option<&str>) -> (String, Option<String>) {
+        let Some(requested) = requested.map(|i| i.trim().to_lowercase()).filter(|i| !i.is_empty()) else {
+            return (DEFAULT_INTERVAL.to_string(), None);
+        };
+        if self.intervals().contains(&requested.as_str()) {
+            return (requested, None);
+        }
+        let warning = format!(
+            "Interval {} is not offered by {} ({}), returning {} candles",
+            requested, self.name(), self.intervals().join(", "), DEFAULT_INTERVAL,
+        );
+        (DEFAULT_INTERVAL.to_string(), Some(warning))
+    }
+}
+
+/// Length of an interval like "15m" or "1w" in seconds.
+pub fn interval_secs(interval: &str) -> Option<i64> {
+    let (count, unit) = interval.split_at(interval.len().checked_sub(1)?);
+    let unit_secs = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    Some(count.parse::<i64>().ok().filter(|n| *n > 0)? * unit_secs)
+}
+
+/// GeckoTerminal OHLCV rows (`[t, o, h, l, c, v]`), oldest first.
+pub fn parse_geckoterminal_candles(json: &serde_json::Value) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = json["data"]["attributes"]["ohlcv_list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|row| Some(Candle {
+            t: row.get(0)?.as_i64()?,
+            o: row.get(1)?.as_f64()?,
+            h: row.get(2)?.as_f64()?,
+            l: row.get(3)?.as_f64()?,
+            c: row.get(4)?.as_f64()?,
+            v: row.get(5).and_then(|v| v.as_f64()).unwrap_or(0.0),
+        }))
+        .collect();
+    candles.sort_by_key(|c| c.t);
+    candles
+}
+
+/// Birdeye `/defi/ohlcv` items, oldest first.
+pub fn parse_birdeye_candles(json: &serde_json::Value) -> Vec<Candle> {
+    let mut candles: Vec<Candle> = json["data"]["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|item| Some(Candle {
+            t: item["unixTime"].as_i64()?,
+            o: item["o"].as_f64()?,
+            h: item["h"].as_f64()?,
+            l: item["l"].as_f64()?,
+            c: item["c"].as_f64()?,
+            v: item["v"].as_f64().unwrap_or(0.0),
+        }))
+        .collect();
+    candles.sort_by_key(|c| c.t);
+    candles
+}
+
+fn birdeye_chain(chain: &str) -> Result<&'static str, String> {
+    match chain {
+        "solana" => Ok("solana"),
+        "eth" | "ethereum" => Ok("ethereum"),
+        "bsc" | "binance" => Ok("bsc"),
+        _ => Err(format!("Unsupported chain: {}", chain)),
+    }
+}
+
+/// Birdeye spells hour/day/week intervals in upper case ("1H", "1D").
+fn birdeye_type(interval: &str) -> String {
+    match interval.strip_suffix('m') {
+        Some(minutes) => format!("{}m", minutes),
+        None => interval.to_uppercase(),
+    }
+}
+
+async fn fetch_birdeye(api_key: &str, chain: &str, token: &str, interval: &str, limit: usize) -> Result<Vec<Candle>, String> {
+    let chain = birdeye_chain(chain)?;
+    let span = interval_secs(interval).ok_or_else(|| format!("Unsupported interval {}", interval))?;
+    let time_to = chrono::Utc::now().timestamp();
+    let time_from = time_to - span * limit as i64;
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let url = format!(
+        "https://public-api.birdeye.so/defi/ohlcv?address={}&type={}&time_from={}&time_to={}",
+        token, birdeye_type(interval), time_from, time_to,
+    );
+    let response = client.get(&url)
+        .header("X-API-KEY", api_key)
+        .header("x-chain", chain)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch candles: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Birdeye API error: {}", response.status()));
+    }
+    let json: serde_json::Value = response.json().await.map_err(|e| format!("Failed to parse candles: {}", e))?;
+    Ok(parse_birdeye_candles(&json))
+}
+
+/// Up to `limit` most recent candles from the configured provider, oldest first.
+pub async fn fetch_candles(chain: &str, token: &str, interval: Option<&str>, limit: usize) -> Result<CandleSeries, String> {
+    let provider = CandleProvider::from_env();
+    let (interval, warning) = provider.resolve_interval(interval);
+    let limit = limit.clamp(1, MAX_LIMIT);
+
+    let mut candles = match &provider {
+        CandleProvider::GeckoTerminal => parse_geckoterminal_candles(&crate::price::fetch_ohlcv(chain, token, &interval, limit).await?),
+        CandleProvider::Birdeye { api_key } => fetch_birdeye(api_key, chain, token, &interval, limit).await?,
+    };
+    if candles.is_empty() {
+        return Err(format!("No candles returned for token {}", token));
+    }
+    if candles.len() > limit {
+        candles.drain(..candles.len() - limit);
+    }
+
+    Ok(CandleSeries { interval, source: provider.name(), candles, warnings: warning.into_iter().collect() })
+}
+
+// ==================== CACHE ====================
+
+/// How long fetched candles are reused, from `CANDLE_CACHE_SECS` (default 30).
+fn candle_cache_ttl() -> Duration {
+    let secs = std::env::var("CANDLE_CACHE_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+#[derive(Default)]
+struct CandleCache {
+    entries: HashMap<String, (CandleSeries, Instant)>,
+}
+
+impl CandleCache {
+    fn fresh(&self, key: &str, now: Instant, ttl: Duration) -> Option<CandleSeries> {
+        match self.entries.get(key) {
+            Some((series, fetched_at)) if now.duration_since(*fetched_at) < ttl => Some(series.clone()),
+            _ => None,
+        }
+    }
+
+    /// Store a fetch and drop anything that has gone stale, so the map only holds live charts.
+    fn store(&mut self, key: &str, series: CandleSeries, now: Instant, ttl: Duration) {
+        self.entries.retain(|_, (_, fetched_at)| now.duration_since(*fetched_at) < ttl);
+        self.entries.insert(key.to_string(), (series, now));
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CANDLE_CACHE: Mutex<CandleCache> = Mutex::new(CandleCache::default());
+}
+
+/// `fetch_candles` behind a short in-memory cache keyed by chain, token, interval and limit.
+pub async fn get_candles(chain: &str, token: &str, interval: Option<&str>, limit: usize) -> Result<CandleSeries, String> {
+    let key = format!("{}:{}:{}:{}", chain, token, interval.unwrap_or(DEFAULT_INTERVAL), limit);
+    let ttl = candle_cache_ttl();
+    if let Some(series) = CANDLE_CACHE.lock().await.fresh(&key, Instant::now(), ttl) {
+        return Ok(series);
+    }
+    // Fetched without holding the lock so one slow chart doesn't stall the others
+    let series = fetch_candles(chain, token, interval, limit).await?;
+    CANDLE_CACHE.lock().await.store(&key, series.clone(), Instant::now(), ttl);
+    Ok(series)
+}
+
+pub async fn get_candles_handler(
+    Path((chain, token)): Path<(String, String)>,
+    Query(query): Query<CandlesQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    match get_candles(&chain, &token, query.interval.as_deref(), limit).await {
+        Ok(series) => (StatusCode::OK, Json(CandlesResponse { success: true, series: Some(series), error: None })),
+        Err(e) => (StatusCode::BAD_GATEWAY, Json(CandlesResponse { success: false, series: None, error: Some(e) })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_provider_candles_oldest_first() {
+        let gecko = serde_json::json!({ "data": { "attributes": { "ohlcv_list": [
+            [1700003600, 1.1, 1.3, 1.0, 1.2, 5000.0],
+            [1700000000, 1.0, 1.2, 0.9, 1.1, 4000.0],
+            ["bad"]
+        ]}}});
+        let candles = parse_geckoterminal_candles(&gecko);
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0], Candle { t: 1700000000, o: 1.0, h: 1.2, l: 0.9, c: 1.1, v: 4000.0 });
+        assert_eq!(candles[1].c, 1.2);
+
+        let birdeye = serde_json::json!({ "success": true, "data": { "items": [
+            { "unixTime": 1700000900, "o": 2.0, "h": 2.5, "l": 1.9, "c": 2.4, "v": 10.0, "type": "15m" },
+            { "unixTime": 1700000000, "o": 1.8, "h": 2.1, "l": 1.7, "c": 2.0, "v": 12.0, "type": "15m" }
+        ]}});
+        let candles = parse_birdeye_candles(&birdeye);
+        assert_eq!(candles.iter().map(|c| c.t).collect::<Vec<_>>(), vec![1700000000, 1700000900]);
+        assert_eq!(candles[1], Candle { t: 1700000900, o: 2.0, h: 2.5, l: 1.9, c: 2.4, v: 10.0 });
+    }
+
+    #[test]
+    fn test_unsupported_interval_falls_back_to_default() {
+        let gecko = CandleProvider::GeckoTerminal;
+        assert_eq!(gecko.resolve_interval(None), ("15m".to_string(), None));
+        assert_eq!(gecko.resolve_interval(Some("4H")), ("4h".to_string(), None));
+
+        let (interval, warning) = gecko.resolve_interval(Some("30m"));
+        assert_eq!(interval, "15m");
+        assert!(warning.unwrap().contains("30m"));
+
+        // Birdeye offers more granularities
+        let birdeye = CandleProvider::Birdeye { api_key: "key".to_string() };
+        assert_eq!(birdeye.resolve_interval(Some("30m")), ("30m".to_string(), None));
+        assert_eq!(birdeye_type("30m"), "30m");
+        assert_eq!(birdeye_type("4h"), "4H");
+    }
+
+    #[test]
+    fn test_interval_secs() {
+        assert_eq!(interval_secs("15m"), Some(900));
+        assert_eq!(interval_secs("4h"), Some(14_400));
+        assert_eq!(interval_secs("1w"), Some(604_800));
+        assert_eq!(interval_secs("0m"), None);
+        assert_eq!(interval_secs("h"), None);
+        assert_eq!(interval_secs(""), None);
+    }
+
+    #[test]
+    fn test_candle_cache_expires() {
+        let ttl = Duration::from_secs(30);
+        let start = Instant::now();
+        let series = CandleSeries { interval: "1m".to_string(), source: "geckoterminal", candles: vec![], warnings: vec![] };
+        let mut cache = CandleCache::default();
+        cache.store("solana:Mint:1m:100", series, start, ttl);
+
+        assert!(cache.fresh("solana:Mint:1m:100", start + Duration::from_secs(29), ttl).is_some());
+        assert!(cache.fresh("solana:Mint:1m:100", start + ttl, ttl).is_none());
+        assert!(cache.fresh("solana:Mint:5m:100", start, ttl).is_none());
+
+        // Stale entries are swept on the next store
+        let later = start + Duration::from_secs(60);
+        let other = CandleSeries { interval: "5m".to_string(), source: "geckoterminal", candles: vec![], warnings: vec![] };
+        cache.store("solana:Mint:5m:100", other, later, ttl);
+        assert_eq!(cache.entries.len(), 1);
+    }
+}
@@ -0,0 +1,352 @@
+// DCA Module
+// Buys a fixed amount of a token every interval until the planned number of buys has run.
+// Driven by the DCA worker in main.rs.
+
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::limit_orders::{CancelOutcome, CancelResponse};
+
+// ==================== DATA STRUCTURES ====================
+
+pub const STATUS_ACTIVE: &str = "ACTIVE";
+pub const STATUS_COMPLETED: &str = "COMPLETED";
+
+const MIN_INTERVAL_SECS: i64 = 60;
+const MAX_TOTAL_BUYS: i32 = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DcaSchedule {
+    pub schedule_id: String,
+    pub user_id: i64,
+    pub chain: String,
+    pub token_address: String,
+    pub amount: String, // Native amount spent per buy
+    pub interval_secs: i64,
+    pub total_buys: i32,
+    pub executions: i32,  // Buys that filled
+    pub failed_runs: i32, // Buys that failed; they still use up a run
+    pub wallet_label: Option<String>,
+    pub status: String, // ACTIVE, EXECUTING (buy in flight), COMPLETED, CANCELLED
+    pub next_run: i64,
+    pub last_tx_hash: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDcaRequest {
+    pub user_id: i64,
+    pub chain: String,
+    pub token: String,
+    pub amount: String,
+    pub interval_secs: i64,
+    pub total_buys: i32,
+    pub wallet_label: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DcaResponse {
+    pub success: bool,
+    pub schedule: Option<DcaSchedule>,
+    pub error: Option<String>,
+}
+
+// ==================== SCHEDULING ====================
+
+/// Validate a request and build the schedule to store. The first buy runs on the next poll.
+pub fn new_schedule(request: CreateDcaRequest, now: i64) -> Result<DcaSchedule, String> {
+    // Stored under the name the buy path executes, so the `sol` alias is accepted too
+    let chain = match request.chain.as_str() {
+        "sol" => "solana".to_string(),
+        _ => request.chain,
+    };
+    crate::validation::validate_token_address(&chain, &request.token)?;
+    let amount = request.amount.parse::<f64>().map_err(|_| "Invalid amount format".to_string())?;
+    if !(amount.is_finite() && amount > 0.0) {
+        return Err("Amount must be greater than 0".to_string());
+    }
+    if request.interval_secs < MIN_INTERVAL_SECS {
+        return Err(format!("interval_secs must be at least {}", MIN_INTERVAL_SECS));
+    }
+    if !(1..=MAX_TOTAL_BUYS).contains(&request.total_buys) {
+        return Err(format!("total_buys must be between 1 and {}", MAX_TOTAL_BUYS));
+    }
+
+    Ok(DcaSchedule {
+        schedule_id: format!("dca_{}", Uuid::new_v4()),
+        user_id: request.user_id,
+        chain,
+        token_address: request.token,
+        amount: request.amount,
+        interval_secs: request.interval_secs,
+        total_buys: request.total_buys,
+        executions: 0,
+        failed_runs: 0,
+        wallet_label: request.wallet_label,
+        status: STATUS_ACTIVE.to_string(),
+        next_run: now,
+        last_tx_hash: None,
+        last_error: None,
+        created_at: now,
+    })
+}
+
+/// Status once a run finishes: completed when it used up the last planned buy.
+pub fn status_after_run(executions: i32, failed_runs: i32, total_buys: i32) -> &'static str {
+    if executions + failed_runs >= total_buys {
+        STATUS_COMPLETED
+    } else {
+        STATUS_ACTIVE
+    }
+}
+
+// ==================== PERSISTENCE ====================
+
+pub async fn save_schedule(schedule: &DcaSchedule, pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        r#"
+        INSERT INTO dca_schedules (schedule_id, user_id, chain, token_address, amount, interval_secs, total_buys, wallet_label, status, next_run, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#
+    )
+    .bind(&schedule.schedule_id)
+    .bind(schedule.user_id)
+    .bind(&schedule.chain)
+    .bind(&schedule.token_address)
+    .bind(&schedule.amount)
+    .bind(schedule.interval_secs)
+    .bind(schedule.total_buys)
+    .bind(&schedule.wallet_label)
+    .bind(&schedule.status)
+    .bind(schedule.next_run)
+    .bind(schedule.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save DCA schedule: {}", e))?;
+
+    Ok(())
+}
+
+pub async fn load_due_schedules(now: i64, pool: &PgPool) -> Result<Vec<DcaSchedule>, String> {
+    sqlx::query_as::<_, DcaSchedule>(
+        "SELECT * FROM dca_schedules WHERE status = 'ACTIVE' AND next_run <= $1 ORDER BY next_run"
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load DCA schedules: {}", e))
+}
+
+/// Mark a due schedule EXECUTING before its buy. Only one caller wins the ACTIVE -> EXECUTING
+/// transition, so an overlapping poll never buys twice and a cancel can't land mid-buy.
+pub async fn claim_run(schedule_id: &str, now: i64, pool: &PgPool) -> Result<bool, String> {
+    let result = sqlx::query("UPDATE dca_schedules SET status = 'EXECUTING' WHERE schedule_id = $1 AND status = 'ACTIVE' AND next_run <= $2")
+        .bind(schedule_id)
+        .bind(now)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to claim DCA run: {}", e))?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Record a claimed run's outcome and hand the schedule back (ACTIVE, due one interval
+/// after `now`) or finish it once every planned buy has run.
+pub async fn record_run(schedule: &DcaSchedule, result: &Result<String, String>, now: i64, pool: &PgPool) -> Result<(), String> {
+    let (executions, failed_runs) = match result {
+        Ok(_) => (schedule.executions + 1, schedule.failed_runs),
+        Err(_) => (schedule.executions, schedule.failed_runs + 1),
+    };
+    sqlx::query(
+        r#"
+        UPDATE dca_schedules SET
+            executions = $2,
+            failed_runs = $3,
+            last_tx_hash = COALESCE($4, last_tx_hash),
+            last_error = $5,
+            status = $6,
+            next_run = $7
+        WHERE schedule_id = $1 AND status = 'EXECUTING'
+        "#
+    )
+    .bind(&schedule.schedule_id)
+    .bind(executions)
+    .bind(failed_runs)
+    .bind(result.as_ref().ok())
+    .bind(result.as_ref().err())
+    .bind(status_after_run(executions, failed_runs, schedule.total_buys))
+    .bind(now + schedule.interval_secs)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to update DCA schedule: {}", e))?;
+
+    Ok(())
+}
+
+/// Cancel a schedule between runs. A schedule whose buy is in flight (EXECUTING) or that has
+/// finished isn't touched; its current status is reported instead. None if there is no such schedule.
+pub async fn cancel_schedule(schedule_id: &str, pool: &PgPool) -> Result<Option<CancelOutcome>, String> {
+    let cancelled = sqlx::query("UPDATE dca_schedules SET status = 'CANCELLED' WHERE schedule_id = $1 AND status = 'ACTIVE'")
+        .bind(schedule_id)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to cancel DCA schedule: {}", e))?
+        .rows_affected() == 1;
+
+    let row: Option<(String, i32)> = sqlx::query_as("SELECT status, executions FROM dca_schedules WHERE schedule_id = $1")
+        .bind(schedule_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to load DCA schedule: {}", e))?;
+    Ok(row.map(|(status, executions)| CancelOutcome { cancelled, status, executions: i64::from(executions) }))
+}
+
+// ==================== API HANDLERS ====================
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
+
+pub async fn create_dca_handler(
+    State(state): State<AppState>,
+    Json(request): Json<CreateDcaRequest>,
+) -> impl IntoResponse {
+    let schedule = match new_schedule(request, Utc::now().timestamp()) {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(DcaResponse { success: false, schedule: None, error: Some(e) })),
+    };
+
+    if let Err(e) = crate::verification::check_trading_allowed(&state.db, schedule.user_id).await {
+        return (StatusCode::FORBIDDEN, Json(DcaResponse { success: false, schedule: None, error: Some(e) }));
+    }
+
+    match save_schedule(&schedule, &state.db).await {
+        Ok(_) => (StatusCode::OK, Json(DcaResponse { success: true, schedule: Some(schedule), error: None })),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(DcaResponse { success: false, schedule: None, error: Some(e) })),
+    }
+}
+
+pub async fn get_dca_schedules_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let schedules = sqlx::query_as::<_, DcaSchedule>(
+        "SELECT * FROM dca_schedules WHERE user_id = $1 ORDER BY created_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match schedules {
+        Ok(s) => (StatusCode::OK, Json(s)),
+        Err(e) => {
+            tracing::error!("Failed to fetch DCA schedules for user {}: {}", user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(vec![]))
+        }
+    }
+}
+
+pub async fn cancel_dca_handler(
+    State(state): State<AppState>,
+    Path(schedule_id): Path<String>,
+) -> impl IntoResponse {
+    let (status, response) = CancelResponse::from_outcome(cancel_schedule(&schedule_id, &state.db).await, "DCA schedule not found");
+    (status, Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(amount: &str, interval_secs: i64, total_buys: i32) -> CreateDcaRequest {
+        CreateDcaRequest {
+            user_id: 7,
+            chain: "solana".to_string(),
+            token: "So11111111111111111111111111111111111111112".to_string(),
+            amount: amount.to_string(),
+            interval_secs,
+            total_buys,
+            wallet_label: None,
+        }
+    }
+
+    #[test]
+    fn test_new_schedule_runs_first_buy_now() {
+        let schedule = new_schedule(request("0.1", 3600, 5), 1_000).unwrap();
+        assert_eq!(schedule.status, STATUS_ACTIVE);
+        assert_eq!(schedule.next_run, 1_000);
+        assert_eq!(schedule.executions, 0);
+
+        assert!(new_schedule(request("0", 3600, 5), 0).is_err());
+        assert!(new_schedule(request("abc", 3600, 5), 0).is_err());
+        assert!(new_schedule(request("0.1", 10, 5), 0).is_err());
+        assert!(new_schedule(request("0.1", 3600, 0), 0).is_err());
+    }
+
+    #[test]
+    fn test_new_schedule_validates_token_address() {
+        let alias = new_schedule(CreateDcaRequest { chain: "sol".to_string(), ..request("0.1", 3600, 5) }, 0).unwrap();
+        assert_eq!(alias.chain, "solana");
+
+        let bad = CreateDcaRequest { token: "Mint".to_string(), ..request("0.1", 3600, 5) };
+        assert!(new_schedule(bad, 0).unwrap_err().contains("Invalid Solana address"));
+        let wrong_chain = CreateDcaRequest { chain: "eth".to_string(), ..request("0.1", 3600, 5) };
+        assert!(new_schedule(wrong_chain, 0).unwrap_err().contains("Invalid EVM address"));
+    }
+
+    #[test]
+    fn test_schedule_completes_after_last_run() {
+        assert_eq!(status_after_run(2, 0, 3), STATUS_ACTIVE);
+        // A failed buy still uses up its run
+        assert_eq!(status_after_run(2, 1, 3), STATUS_COMPLETED);
+        assert_eq!(status_after_run(3, 0, 3), STATUS_COMPLETED);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_cancel_partially_executed_schedule() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query(
+            "CREATE TEMP TABLE dca_schedules (schedule_id VARCHAR(100) PRIMARY KEY, user_id BIGINT, chain VARCHAR(20), \
+             token_address VARCHAR(100), amount VARCHAR(50), interval_secs BIGINT, total_buys INT, executions INT DEFAULT 0, \
+             failed_runs INT DEFAULT 0, wallet_label VARCHAR(50), status VARCHAR(20), next_run BIGINT, last_tx_hash VARCHAR(255), \
+             last_error TEXT, created_at BIGINT)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let schedule = new_schedule(request("0.1", 3600, 5), 1_000).unwrap();
+        save_schedule(&schedule, &pool).await.unwrap();
+        for run in 0..2 {
+            let now = 1_000 + run * 3600;
+            assert!(claim_run(&schedule.schedule_id, now, &pool).await.unwrap());
+            let current = sqlx::query_as::<_, DcaSchedule>("SELECT * FROM dca_schedules WHERE schedule_id = $1")
+                .bind(&schedule.schedule_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            record_run(&current, &Ok(format!("tx{}", run)), now, &pool).await.unwrap();
+        }
+
+        // The third buy is in flight: the cancel waits for it rather than racing it
+        assert!(claim_run(&schedule.schedule_id, 1_000 + 2 * 3600, &pool).await.unwrap());
+        let busy = cancel_schedule(&schedule.schedule_id, &pool).await.unwrap().unwrap();
+        assert_eq!(busy, CancelOutcome { cancelled: false, status: "EXECUTING".to_string(), executions: 2 });
+        sqlx::query("UPDATE dca_schedules SET status = 'ACTIVE' WHERE schedule_id = $1")
+            .bind(&schedule.schedule_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let outcome = cancel_schedule(&schedule.schedule_id, &pool).await.unwrap().unwrap();
+        assert_eq!(outcome, CancelOutcome { cancelled: true, status: "CANCELLED".to_string(), executions: 2 });
+        // Cancelled schedules are never claimed again
+        assert!(!claim_run(&schedule.schedule_id, i64::MAX, &pool).await.unwrap());
+        assert!(cancel_schedule("dca_missing", &pool).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,141 @@
+// Error Module
+// Typed failures for the trade paths, so handlers pick an HTTP status from the kind of
+// error instead of searching the message for keywords
+
+use crate::execution::EvmSwapError;
+use crate::risk_engine::RiskError;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    /// Malformed or unsupported request parameters.
+    Validation(String),
+    /// The wallet can't cover the trade plus fees.
+    InsufficientBalance(String),
+    /// The token failed the security check.
+    TokenRisk(String),
+    /// The user's risk limits reject the trade.
+    RiskControl(String),
+    /// The user may not trade (e.g. unverified).
+    Forbidden(String),
+    NotFound(String),
+    /// The RPC node failed or couldn't be reached.
+    RpcError(String),
+    /// The swap was sent to (or quoted by) the DEX and failed there.
+    SwapFailed(String),
+    /// A dependency needed to price or check the trade is down.
+    Unavailable(String),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_)
+            | AppError::InsufficientBalance(_)
+            | AppError::TokenRisk(_)
+            | AppError::RiskControl(_) => StatusCode::BAD_REQUEST,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::RpcError(_) | AppError::SwapFailed(_) => StatusCode::BAD_GATEWAY,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The JSON body sent with the status, in the `success`/`error` shape every endpoint uses.
+    pub fn body(&self) -> serde_json::Value {
+        serde_json::json!({ "success": false, "error": self.to_string() })
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::TokenRisk(e) => write!(f, "Token Risk: {}", e),
+            AppError::RiskControl(e) => write!(f, "Risk Control: {}", e),
+            AppError::Validation(e)
+            | AppError::InsufficientBalance(e)
+            | AppError::Forbidden(e)
+            | AppError::NotFound(e)
+            | AppError::RpcError(e)
+            | AppError::SwapFailed(e)
+            | AppError::Unavailable(e)
+            | AppError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self.body())).into_response()
+    }
+}
+
+impl From<RiskError> for AppError {
+    fn from(e: RiskError) -> Self {
+        match e {
+            RiskError::DatabaseError(_) => AppError::Internal(e.to_string()),
+            _ => AppError::RiskControl(e.to_string()),
+        }
+    }
+}
+
+impl From<EvmSwapError> for AppError {
+    fn from(e: EvmSwapError) -> Self {
+        match e {
+            EvmSwapError::InsufficientFunds(_) => AppError::InsufficientBalance(e.to_string()),
+            EvmSwapError::NoBalance => AppError::Validation(e.to_string()),
+            EvmSwapError::Failed(_) => AppError::SwapFailed(e.to_string()),
+        }
+    }
+}
+
+/// Lets the String-returning automation paths (price worker, limit orders) use `?` on trades.
+impl From<AppError> for String {
+    fn from(e: AppError) -> Self {
+        e.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_by_kind() {
+        assert_eq!(AppError::InsufficientBalance("Insufficient balance: need 1.2 SOL".to_string()).status(), StatusCode::BAD_REQUEST);
+        assert_eq!(AppError::NotFound("Position not found".to_string()).status(), StatusCode::NOT_FOUND);
+        assert_eq!(AppError::RpcError("Failed to get balance: timeout".to_string()).status(), StatusCode::BAD_GATEWAY);
+        // The status no longer depends on what the message happens to say
+        assert_eq!(AppError::Internal("Invalid vault seed".to_string()).status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_risk_errors() {
+        let limit = AppError::from(RiskError::MaxOpenPositionsExceeded(5, 5));
+        assert_eq!(limit.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(limit.to_string(), "Risk Control: Max open positions reached (5/5)");
+        assert_eq!(AppError::from(RiskError::DatabaseError("pool closed".to_string())).status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test_evm_swap_errors() {
+        let gas = AppError::from(EvmSwapError::InsufficientFunds("Insufficient funds for gas".to_string()));
+        assert_eq!(gas, AppError::InsufficientBalance("Insufficient funds for gas".to_string()));
+        assert_eq!(AppError::from(EvmSwapError::NoBalance).status(), StatusCode::BAD_REQUEST);
+        assert_eq!(AppError::from(EvmSwapError::Failed("Swap failed: nonce too low".to_string())).status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_body() {
+        let body = AppError::TokenRisk("Score 20/100".to_string()).body();
+        assert_eq!(body, serde_json::json!({ "success": false, "error": "Token Risk: Score 20/100" }));
+    }
+}
@@ -0,0 +1,346 @@
+// EVM Transaction Module
+// Minimal RLP / ABI encoding and EIP-155 signing for Uniswap V2-style router swaps
+
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use sha3::{Digest, Keccak256};
+
+pub type Address = [u8; 20];
+
+// ==================== CHAIN CONFIG ====================
+
+/// V2 router deployment for a chain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RouterConfig {
+    pub chain_id: u64,
+    pub router: &'static str,
+    pub wrapped_native: &'static str,
+}
+
+pub fn router_config(chain: &str) -> Result<RouterConfig, String> {
+    match chain {
+        // Uniswap V2
+        "eth" | "ethereum" => Ok(RouterConfig {
+            chain_id: 1,
+            router: "0x7a250d5630B4cF539739dF2C5dAcCb4c659F2488",
+            wrapped_native: "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+        }),
+        // PancakeSwap V2
+        "bsc" | "binance" => Ok(RouterConfig {
+            chain_id: 56,
+            router: "0x10ED43C718714eb63d5aA57B78B54704E256024E",
+            wrapped_native: "0xbb4CdB9CBd36B01bD8cBaEBF2De08d9173bc095c",
+        }),
+        _ => Err("Unsupported chain".to_string()),
+    }
+}
+
+// ==================== PRIMITIVES ====================
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+pub fn parse_address(address: &str) -> Result<Address, String> {
+    let hex_part = address.strip_prefix("0x").ok_or_else(|| format!("Invalid EVM address: {}", address))?;
+    let bytes = hex::decode(hex_part).map_err(|_| format!("Invalid EVM address: {}", address))?;
+    bytes.try_into().map_err(|_| format!("Invalid EVM address: {}", address))
+}
+
+pub fn format_address(address: &Address) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+pub fn address_from_key(key: &SecretKey) -> Address {
+    let public_key = PublicKey::from_secret_key(&Secp256k1::new(), key);
+    let hash = keccak256(&public_key.serialize_uncompressed()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// Parse a JSON-RPC hex quantity ("0x1a").
+pub fn parse_quantity(value: &str) -> Result<u128, String> {
+    let hex_part = value.strip_prefix("0x").unwrap_or(value);
+    if hex_part.is_empty() {
+        return Ok(0);
+    }
+    u128::from_str_radix(hex_part, 16).map_err(|e| format!("Invalid quantity {}: {}", value, e))
+}
+
+// ==================== RLP ====================
+
+fn rlp_length_prefix(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        vec![offset + len as u8]
+    } else {
+        let len_bytes = trim_leading_zeros(&(len as u64).to_be_bytes());
+        let mut out = vec![offset + 55 + len_bytes.len() as u8];
+        out.extend_from_slice(&len_bytes);
+        out
+    }
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    bytes[start..].to_vec()
+}
+
+pub fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut out = rlp_length_prefix(bytes.len(), 0x80);
+    out.extend_from_slice(bytes);
+    out
+}
+
+/// Integers are encoded big-endian with no leading zeros (zero is the empty string).
+pub fn rlp_uint(value: u128) -> Vec<u8> {
+    rlp_bytes(&trim_leading_zeros(&value.to_be_bytes()))
+}
+
+pub fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut out = rlp_length_prefix(payload.len(), 0xc0);
+    out.extend_from_slice(&payload);
+    out
+}
+
+// ==================== TRANSACTIONS ====================
+
+/// Legacy (type 0) transaction, signed with EIP-155 replay protection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegacyTransaction {
+    pub nonce: u64,
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    pub to: Address,
+    pub value: u128,
+    pub data: Vec<u8>,
+    pub chain_id: u64,
+}
+
+impl LegacyTransaction {
+    fn base_fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(self.nonce as u128),
+            rlp_uint(self.gas_price),
+            rlp_uint(self.gas_limit as u128),
+            rlp_bytes(&self.to),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+        ]
+    }
+
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut fields = self.base_fields();
+        fields.extend([rlp_uint(self.chain_id as u128), rlp_uint(0), rlp_uint(0)]);
+        keccak256(&rlp_list(&fields))
+    }
+
+    /// Sign and return the raw transaction bytes for `eth_sendRawTransaction`.
+    pub fn sign(&self, key: &SecretKey) -> Result<Vec<u8>, String> {
+        let message = Message::from_slice(&self.signing_hash())
+            .map_err(|e| format!("Invalid signing hash: {}", e))?;
+        let (recovery_id, signature) = Secp256k1::new()
+            .sign_ecdsa_recoverable(&message, key)
+            .serialize_compact();
+
+        let v = self.chain_id as u128 * 2 + 35 + recovery_id.to_i32() as u128;
+        let mut fields = self.base_fields();
+        fields.extend([
+            rlp_uint(v),
+            rlp_bytes(&trim_leading_zeros(&signature[..32])),
+            rlp_bytes(&trim_leading_zeros(&signature[32..])),
+        ]);
+        Ok(rlp_list(&fields))
+    }
+}
+
+// ==================== ABI ====================
+
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn word_uint(value: u128) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[16..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn word_address(address: &Address) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[12..].copy_from_slice(address);
+    word
+}
+
+fn address_array(path: &[Address]) -> Vec<u8> {
+    let mut out = word_uint(path.len() as u128).to_vec();
+    for address in path {
+        out.extend_from_slice(&word_address(address));
+    }
+    out
+}
+
+/// `swapExactETHForTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline)`
+pub fn encode_swap_exact_eth_for_tokens(amount_out_min: u128, path: &[Address], to: &Address, deadline: u64) -> Vec<u8> {
+    let mut data = selector("swapExactETHForTokens(uint256,address[],address,uint256)").to_vec();
+    data.extend_from_slice(&word_uint(amount_out_min));
+    data.extend_from_slice(&word_uint(4 * 32)); // offset of path
+    data.extend_from_slice(&word_address(to));
+    data.extend_from_slice(&word_uint(deadline as u128));
+    data.extend(address_array(path));
+    data
+}
+
+/// `swapExactTokensForETH(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline)`
+pub fn encode_swap_exact_tokens_for_eth(amount_in: u128, amount_out_min: u128, path: &[Address], to: &Address, deadline: u64) -> Vec<u8> {
+    let mut data = selector("swapExactTokensForETH(uint256,uint256,address[],address,uint256)").to_vec();
+    data.extend_from_slice(&word_uint(amount_in));
+    data.extend_from_slice(&word_uint(amount_out_min));
+    data.extend_from_slice(&word_uint(5 * 32)); // offset of path
+    data.extend_from_slice(&word_address(to));
+    data.extend_from_slice(&word_uint(deadline as u128));
+    data.extend(address_array(path));
+    data
+}
+
+/// `getAmountsOut(uint256 amountIn, address[] path)`
+pub fn encode_get_amounts_out(amount_in: u128, path: &[Address]) -> Vec<u8> {
+    let mut data = selector("getAmountsOut(uint256,address[])").to_vec();
+    data.extend_from_slice(&word_uint(amount_in));
+    data.extend_from_slice(&word_uint(2 * 32));
+    data.extend(address_array(path));
+    data
+}
+
+pub fn encode_approve(spender: &Address, amount: u128) -> Vec<u8> {
+    let mut data = selector("approve(address,uint256)").to_vec();
+    data.extend_from_slice(&word_address(spender));
+    data.extend_from_slice(&word_uint(amount));
+    data
+}
+
+pub fn encode_allowance(owner: &Address, spender: &Address) -> Vec<u8> {
+    let mut data = selector("allowance(address,address)").to_vec();
+    data.extend_from_slice(&word_address(owner));
+    data.extend_from_slice(&word_address(spender));
+    data
+}
+
+pub fn encode_balance_of(owner: &Address) -> Vec<u8> {
+    let mut data = selector("balanceOf(address)").to_vec();
+    data.extend_from_slice(&word_address(owner));
+    data
+}
+
+/// ERC-20 `decimals()`
+pub fn encode_decimals() -> Vec<u8> {
+    selector("decimals()").to_vec()
+}
+
+/// Read the `index`-th 32-byte word of `eth_call` output as a uint. Values beyond
+/// u128 (e.g. unlimited allowances) saturate.
+pub fn decode_uint_word(output: &[u8], index: usize) -> Result<u128, String> {
+    let word = output
+        .get(index * 32..(index + 1) * 32)
+        .ok_or_else(|| "Call returned too little data".to_string())?;
+    if word[..16].iter().any(|b| *b != 0) {
+        return Ok(u128::MAX);
+    }
+    Ok(u128::from_be_bytes(word[16..].try_into().expect("16 byte slice")))
+}
+
+/// Last element of a `uint256[]` return value (the output amount of `getAmountsOut`).
+pub fn decode_last_amount(output: &[u8]) -> Result<u128, String> {
+    let len = decode_uint_word(output, 1)? as usize;
+    if len == 0 {
+        return Err("Router returned no amounts".to_string());
+    }
+    decode_uint_word(output, 1 + len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rlp_encoding() {
+        assert_eq!(rlp_uint(0), vec![0x80]);
+        assert_eq!(rlp_uint(15), vec![0x0f]);
+        assert_eq!(rlp_uint(1024), vec![0x82, 0x04, 0x00]);
+        assert_eq!(rlp_bytes(b"dog"), vec![0x83, b'd', b'o', b'g']);
+        assert_eq!(rlp_list(&[rlp_bytes(b"cat"), rlp_bytes(b"dog")]), hex::decode("c88363617483646f67").unwrap());
+        // Long strings get a length-of-length prefix
+        let long = vec![0xaa; 60];
+        assert_eq!(&rlp_bytes(&long)[..2], &[0xb8, 60]);
+    }
+
+    #[test]
+    fn test_eip155_example_transaction() {
+        // Example from EIP-155
+        let tx = LegacyTransaction {
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: [0x35; 20],
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+            chain_id: 1,
+        };
+        assert_eq!(
+            hex::encode(tx.signing_hash()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+
+        let key = SecretKey::from_slice(&[0x46; 32]).unwrap();
+        assert_eq!(
+            hex::encode(tx.sign(&key).unwrap()),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+    }
+
+    #[test]
+    fn test_router_selectors() {
+        assert_eq!(hex::encode(selector("swapExactETHForTokens(uint256,address[],address,uint256)")), "7ff36ab5");
+        assert_eq!(hex::encode(selector("swapExactTokensForETH(uint256,uint256,address[],address,uint256)")), "18cbafe5");
+        assert_eq!(hex::encode(selector("approve(address,uint256)")), "095ea7b3");
+    }
+
+    #[test]
+    fn test_swap_calldata_layout() {
+        let weth = parse_address(router_config("eth").unwrap().wrapped_native).unwrap();
+        let token = [0x11; 20];
+        let to = [0x22; 20];
+        let data = encode_swap_exact_eth_for_tokens(500, &[weth, token], &to, 1_700_000_000);
+
+        // selector + 4 head words + length + 2 path entries
+        assert_eq!(data.len(), 4 + 32 * 7);
+        assert_eq!(decode_uint_word(&data[4..], 0).unwrap(), 500);
+        assert_eq!(decode_uint_word(&data[4..], 1).unwrap(), 128);
+        assert_eq!(&data[4 + 32 * 2 + 12..4 + 32 * 3], &to);
+        assert_eq!(decode_uint_word(&data[4..], 4).unwrap(), 2);
+        assert_eq!(&data[4 + 32 * 5 + 12..4 + 32 * 6], &weth);
+    }
+
+    #[test]
+    fn test_decode_amounts_out() {
+        // uint256[] = [1000, 2500]
+        let mut output = word_uint(32).to_vec();
+        output.extend_from_slice(&word_uint(2));
+        output.extend_from_slice(&word_uint(1000));
+        output.extend_from_slice(&word_uint(2500));
+        assert_eq!(decode_last_amount(&output).unwrap(), 2500);
+        assert!(decode_last_amount(&output[..64]).is_err());
+    }
+
+    #[test]
+    fn test_address_from_key() {
+        let key = SecretKey::from_slice(&[0x46; 32]).unwrap();
+        assert_eq!(format_address(&address_from_key(&key)), "0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+        assert!(parse_address("0x1234").is_err());
+        assert!(parse_address("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f").is_err());
+    }
+}
@@ -3,20 +3,33 @@
 
 use serde::{Deserialize, Serialize};
 use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
 use solana_sdk::{
-    transaction::Transaction,
+    address_lookup_table::{state::AddressLookupTable, AddressLookupTableAccount},
+    compute_budget::{self, ComputeBudgetInstruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
+    message::{v0, VersionedMessage},
+    packet::PACKET_DATA_SIZE,
+    transaction::{TransactionError, VersionedTransaction},
     signer::Signer,
     pubkey::Pubkey,
 };
 use std::str::FromStr;
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
 
 pub const JUPITER_API_URL: &str = "https://quote-api.jup.ag/v6";
 
+pub const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+pub const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+pub const USDT_MINT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
+
 // ==================== JUPITER TYPES ====================
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QuoteResponse {
     pub inputMint: String,
     pub inAmount: String,
@@ -32,19 +45,19 @@ pub struct QuoteResponse {
     pub timeTaken: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlatformFee {
     pub amount: String,
     pub feeBps: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoutePlan {
     pub swapInfo: SwapInfo,
     pub percent: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwapInfo {
     pub ammKey: String,
     pub label: String,
@@ -61,8 +74,10 @@ pub struct SwapRequest {
     pub quoteResponse: QuoteResponse,
     pub userPublicKey: String,
     pub wrapAndUnwrapSol: bool,
-    pub prioritizationFeeLamports: String,
+    pub prioritizationFeeLamports: serde_json::Value, // Lamports, or "auto"/a priority level if estimation failed
     pub dynamicComputeUnitLimit: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feeAccount: Option<String>, // Receives the platform fee, when one is configured
 }
 
 #[derive(Debug, Deserialize)]
@@ -71,35 +86,889 @@ pub struct SwapResponse {
     pub lastValidBlockHeight: Option<u64>,
 }
 
-// ==================== CORE FUNCTIONS ====================
+// ==================== PLATFORM FEE ====================
 
-pub async fn execute_solana_swap(
+/// Operator fee Jupiter takes on-chain from a swap's output and pays into `fee_wallet`'s
+/// token account for that mint. Off unless both `PLATFORM_FEE_BPS` and `PLATFORM_FEE_ACCOUNT`
+/// are set. Only swaps into SOL or a stablecoin are charged: the fee wallet can't hold an
+/// account for every memecoin, so buys go through fee-free.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlatformFeeConfig {
+    pub fee_bps: u64,
+    pub fee_wallet: Pubkey,
+}
+
+impl PlatformFeeConfig {
+    pub fn resolve(lookup: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let fee_bps = lookup("PLATFORM_FEE_BPS")
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|bps| *bps > 0 && *bps < 10_000)?;
+        let fee_wallet = lookup("PLATFORM_FEE_ACCOUNT").and_then(|a| Pubkey::from_str(a.trim()).ok())?;
+        Some(Self { fee_bps, fee_wallet })
+    }
+
+    pub fn from_env() -> Option<Self> {
+        Self::resolve(|key| std::env::var(key).ok())
+    }
+
+    /// The fee wallet's associated token account for `output_mint`, or None when swaps into
+    /// that mint aren't charged.
+    pub fn fee_account_for(&self, output_mint: &str) -> Option<Pubkey> {
+        SellOutput::from_mint(Some(output_mint)).ok()?;
+        let mint = Pubkey::from_str(output_mint).ok()?;
+        Some(crate::wallet::associated_token_address(&self.fee_wallet, &mint, &spl_token::id()))
+    }
+}
+
+/// Jupiter `/quote` URL, asking for the platform fee when one is configured.
+pub fn quote_url(input_mint: &str, output_mint: &str, amount: u64, slippage_bps: u64, fee: Option<&PlatformFeeConfig>) -> String {
+    let mut url = format!(
+        "{}/quote?inputMint={}&outputMint={}&amount={}&slippageBps={}",
+        JUPITER_API_URL, input_mint, output_mint, amount, slippage_bps
+    );
+    if let Some(fee) = fee.filter(|fee| fee.fee_account_for(output_mint).is_some()) {
+        url.push_str(&format!("&platformFeeBps={}", fee.fee_bps));
+    }
+    url
+}
+
+/// Fee account for a swap request built from `quote`. Only sent when the quote carries the
+/// fee, so a quote taken before the config changed still swaps cleanly.
+fn swap_fee_account(quote: &QuoteResponse) -> Option<String> {
+    quote.platformFee.as_ref()
+        .filter(|f| f.feeBps > 0)
+        .and(PlatformFeeConfig::from_env())
+        .and_then(|fee| fee.fee_account_for(&quote.outputMint))
+        .map(|account| account.to_string())
+}
+
+// ==================== SWAP GUARDS ====================
+
+/// A swap refused before sending because the route is too costly.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SwapGuardError {
+    #[error("Price impact {observed:.2}% exceeds limit of {threshold:.2}%")]
+    PriceImpactTooHigh { observed: f64, threshold: f64 },
+    #[error("Slippage {observed:.2}% exceeds limit of {threshold:.2}%")]
+    SlippageTooHigh { observed: f64, threshold: f64 },
+    #[error("Guaranteed output {guaranteed} is below the requested minimum of {floor}")]
+    MinimumOutputTooLow { guaranteed: u64, floor: u64 },
+    #[error("Invalid quote: {0}")]
+    InvalidQuote(String),
+}
+
+/// Swap limits, in percent. `None` disables the check.
+#[derive(Debug, Clone, Default)]
+pub struct SwapLimits {
+    pub max_price_impact_pct: Option<f64>,
+    pub max_slippage_pct: Option<f64>,
+    pub min_out_floor: Option<u64>, // Raw output units the quote must guarantee
+}
+
+impl SwapLimits {
+    /// Read `MAX_PRICE_IMPACT_PCT` / `MAX_SLIPPAGE_PCT` from the environment.
+    pub fn from_env() -> Self {
+        let read = |key: &str| std::env::var(key).ok().and_then(|v| v.parse::<f64>().ok());
+        Self {
+            max_price_impact_pct: read("MAX_PRICE_IMPACT_PCT"),
+            max_slippage_pct: read("MAX_SLIPPAGE_PCT"),
+            min_out_floor: None,
+        }
+    }
+
+    /// Apply a caller's own limits on top of the configured ones. The stricter impact limit wins.
+    pub fn with_request(mut self, max_price_impact_pct: Option<f64>, min_out_floor: Option<u64>) -> Self {
+        self.max_price_impact_pct = match (self.max_price_impact_pct, max_price_impact_pct) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.min_out_floor = min_out_floor.or(self.min_out_floor);
+        self
+    }
+
+    pub fn check_slippage(&self, slippage_bps: u64) -> Result<(), SwapGuardError> {
+        let observed = slippage_bps as f64 / 100.0;
+        match self.max_slippage_pct {
+            Some(threshold) if observed > threshold => Err(SwapGuardError::SlippageTooHigh { observed, threshold }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_price_impact(&self, observed: f64) -> Result<(), SwapGuardError> {
+        match self.max_price_impact_pct {
+            Some(threshold) if observed > threshold => Err(SwapGuardError::PriceImpactTooHigh { observed, threshold }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_min_out(&self, guaranteed: u64) -> Result<(), SwapGuardError> {
+        match self.min_out_floor {
+            Some(floor) if guaranteed < floor => Err(SwapGuardError::MinimumOutputTooLow { guaranteed, floor }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a Jupiter quote's price impact and guaranteed minimum (`otherAmountThreshold`).
+    pub fn check_quote(&self, quote: &QuoteResponse) -> Result<(), SwapGuardError> {
+        // Jupiter reports impact as a fraction ("0.0123" = 1.23%)
+        let price_impact_pct = quote.priceImpactPct.parse::<f64>()
+            .map_err(|_| SwapGuardError::InvalidQuote(format!("bad priceImpactPct {}", quote.priceImpactPct)))? * 100.0;
+        self.check_price_impact(price_impact_pct)?;
+
+        let parse = |v: &str| v.parse::<u64>().map_err(|_| SwapGuardError::InvalidQuote(format!("bad amount {}", v)));
+        let out = parse(&quote.outAmount)?;
+        let guaranteed = parse(&quote.otherAmountThreshold)?;
+        if guaranteed > out {
+            return Err(SwapGuardError::InvalidQuote(format!("minimum {} exceeds expected output {}", guaranteed, out)));
+        }
+        self.check_min_out(guaranteed)
+    }
+}
+
+// ==================== EXECUTION PROFILES ====================
+
+/// What kind of flow is executing a swap. Each has its own slippage / priority defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AutomationKind {
+    Manual,
+    Grid,
+    Dca,
+    LimitOrder,
+    AutoExit, // TP/SL, trailing stop and scheduled exits
+}
+
+impl AutomationKind {
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            AutomationKind::Manual => "EXEC_MANUAL",
+            AutomationKind::Grid => "EXEC_GRID",
+            AutomationKind::Dca => "EXEC_DCA",
+            AutomationKind::LimitOrder => "EXEC_LIMIT",
+            AutomationKind::AutoExit => "EXEC_EXIT",
+        }
+    }
+
+    fn default_profile(&self) -> ExecutionProfile {
+        let (slippage_bps, priority) = match self {
+            AutomationKind::Manual => (500, PriorityTier::Auto),
+            AutomationKind::Grid => (100, PriorityTier::Medium),
+            AutomationKind::Dca => (200, PriorityTier::Low),
+            AutomationKind::LimitOrder => (50, PriorityTier::Medium),
+            AutomationKind::AutoExit => (500, PriorityTier::VeryHigh),
+        };
+        ExecutionProfile { slippage_bps, priority, private_tx: false, max_slippage_bps: None }
+    }
+}
+
+/// Jupiter priority fee levels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriorityTier {
+    Auto,
+    Low,
+    Medium,
+    High,
+    VeryHigh,
+}
+
+impl PriorityTier {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "auto" => Some(PriorityTier::Auto),
+            "low" => Some(PriorityTier::Low),
+            "medium" => Some(PriorityTier::Medium),
+            "high" => Some(PriorityTier::High),
+            "veryhigh" | "very_high" => Some(PriorityTier::VeryHigh),
+            _ => None,
+        }
+    }
+
+    /// Percentile of recent fees to pay. `auto` uses the configured percentile.
+    pub fn percentile(self, auto_percentile: u8) -> u8 {
+        match self {
+            PriorityTier::Auto => auto_percentile,
+            PriorityTier::Low => 25,
+            PriorityTier::Medium => 50,
+            PriorityTier::High => 75,
+            PriorityTier::VeryHigh => 95,
+        }
+    }
+
+    /// Value for the swap request's `prioritizationFeeLamports` when no estimate is available.
+    pub fn to_jupiter(self, max_lamports: u64) -> serde_json::Value {
+        let level = match self {
+            PriorityTier::Auto => return serde_json::json!("auto"),
+            PriorityTier::Low => "low",
+            PriorityTier::Medium => "medium",
+            PriorityTier::High => "high",
+            PriorityTier::VeryHigh => "veryHigh",
+        };
+        serde_json::json!({ "priorityLevelWithMaxLamports": { "priorityLevel": level, "maxLamports": max_lamports } })
+    }
+}
+
+/// Cap on the priority fee Jupiter may pick, from `PRIORITY_FEE_MAX_LAMPORTS` (default 0.005 SOL).
+pub fn priority_fee_max_lamports() -> u64 {
+    std::env::var("PRIORITY_FEE_MAX_LAMPORTS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5_000_000)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecutionProfile {
+    pub slippage_bps: u64,
+    pub priority: PriorityTier,
+    pub private_tx: bool, // Submit through Jito when configured
+    pub max_slippage_bps: Option<u64>, // Ceiling for retries after a slippage revert (None = fail outright)
+}
+
+impl ExecutionProfile {
+    /// Defaults for `kind`, overridden by `<PREFIX>_SLIPPAGE_BPS` / `<PREFIX>_PRIORITY`
+    /// (e.g. `EXEC_GRID_SLIPPAGE_BPS=75`, `EXEC_DCA_PRIORITY=low`).
+    pub fn resolve(kind: AutomationKind, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = kind.default_profile();
+        let prefix = kind.env_prefix();
+        Self {
+            slippage_bps: lookup(&format!("{}_SLIPPAGE_BPS", prefix))
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|bps| *bps > 0 && *bps <= 10_000)
+                .unwrap_or(defaults.slippage_bps),
+            priority: lookup(&format!("{}_PRIORITY", prefix))
+                .and_then(|v| PriorityTier::parse(&v))
+                .unwrap_or(defaults.priority),
+            private_tx: defaults.private_tx,
+            max_slippage_bps: defaults.max_slippage_bps,
+        }
+    }
+
+    pub fn for_kind(kind: AutomationKind) -> Self {
+        Self::resolve(kind, |key| std::env::var(key).ok())
+    }
+
+    /// Use an explicit slippage (e.g. the user's own setting) instead of the profile's.
+    pub fn with_slippage(mut self, slippage_bps: u64) -> Self {
+        self.slippage_bps = slippage_bps;
+        self
+    }
+
+    /// Opt in to private (Jito) submission for this swap.
+    pub fn with_private_tx(mut self, private_tx: bool) -> Self {
+        self.private_tx = private_tx;
+        self
+    }
+
+    /// Opt in to slippage escalation: a swap reverted for slippage is re-quoted at double
+    /// the slippage, up to `max_slippage_bps`.
+    pub fn with_max_slippage(mut self, max_slippage_bps: Option<u64>) -> Self {
+        self.max_slippage_bps = max_slippage_bps;
+        self
+    }
+}
+
+// ==================== FAIR EXECUTION QUEUE ====================
+
+/// Per-token FIFO queue for shared sniping events. Buys for the same token
+/// are dispatched in the order they were submitted, at most `concurrency`
+/// at a time, instead of all racing. Tokio semaphores hand out permits in
+/// acquire order, which is what gives us the FIFO guarantee.
+#[derive(Debug, Clone)]
+pub struct FairExecutionQueue {
+    lanes: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+    concurrency: usize,
+}
+
+impl FairExecutionQueue {
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            lanes: Arc::new(Mutex::new(HashMap::new())),
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    /// Enabled with `FAIR_EXECUTION_QUEUE=true`, concurrency from
+    /// `FAIR_EXECUTION_CONCURRENCY` (default 1 = strictly sequential).
+    pub fn from_env() -> Option<Self> {
+        let enabled = std::env::var("FAIR_EXECUTION_QUEUE")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+
+        let concurrency = std::env::var("FAIR_EXECUTION_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(1);
+        tracing::info!("⚖️  Fair execution queue enabled (concurrency {} per token)", concurrency);
+        Some(Self::new(concurrency))
+    }
+
+    /// Wait for this submission's turn on `token`. Hold the permit until the swap is sent.
+    pub async fn acquire(&self, token: &str) -> OwnedSemaphorePermit {
+        let lane = {
+            let mut lanes = self.lanes.lock().await;
+            // Drop lanes nobody is using so finished launches don't accumulate
+            lanes.retain(|_, sem| Arc::strong_count(sem) > 1 || sem.available_permits() < self.concurrency);
+            lanes.entry(token.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.concurrency)))
+                .clone()
+        };
+
+        lane.acquire_owned().await.expect("fair queue semaphore is never closed")
+    }
+}
+
+// ==================== MINT DECIMALS CACHE ====================
+
+/// Mint decimals never change, so look them up once per mint.
+/// Size bounded by `DECIMALS_CACHE_MAX_ENTRIES` (default 10000).
+#[derive(Debug, Clone)]
+pub struct DecimalsCache {
+    entries: Arc<tokio::sync::RwLock<HashMap<String, u8>>>,
+    max_entries: usize,
+}
+
+impl DecimalsCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let max_entries = std::env::var("DECIMALS_CACHE_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(10_000);
+        Self::new(max_entries)
+    }
+
+    /// Return cached decimals for `mint`, calling `fetch` only on a miss.
+    pub async fn get_or_fetch<F, Fut>(&self, mint: &str, fetch: F) -> Result<u8, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<u8, String>>,
+    {
+        if let Some(decimals) = self.entries.read().await.get(mint) {
+            return Ok(*decimals);
+        }
+
+        let decimals = fetch().await?;
+
+        let mut entries = self.entries.write().await;
+        if entries.len() >= self.max_entries {
+            entries.clear();
+        }
+        entries.insert(mint.to_string(), decimals);
+        Ok(decimals)
+    }
+}
+
+// ==================== SELL OUTPUTS ====================
+
+/// What a sell swaps into. Only mints we can price and settle PnL in are allowed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SellOutput {
+    Sol,
+    Usdc,
+    Usdt,
+}
+
+impl SellOutput {
+    /// Parse an optional `output_mint`, defaulting to WSOL.
+    pub fn from_mint(mint: Option<&str>) -> Result<Self, String> {
+        match mint.map(str::trim) {
+            None | Some("") | Some(WSOL_MINT) => Ok(SellOutput::Sol),
+            Some(USDC_MINT) => Ok(SellOutput::Usdc),
+            Some(USDT_MINT) => Ok(SellOutput::Usdt),
+            Some(other) => Err(format!("Unsupported output mint: {} (expected WSOL, USDC or USDT)", other)),
+        }
+    }
+
+    pub fn mint(&self) -> &'static str {
+        match self {
+            SellOutput::Sol => WSOL_MINT,
+            SellOutput::Usdc => USDC_MINT,
+            SellOutput::Usdt => USDT_MINT,
+        }
+    }
+
+    pub fn decimals(&self) -> u8 {
+        match self {
+            SellOutput::Sol => 9,
+            SellOutput::Usdc | SellOutput::Usdt => 6,
+        }
+    }
+
+    pub fn denomination(&self) -> &'static str {
+        match self {
+            SellOutput::Sol => "SOL",
+            SellOutput::Usdc => "USDC",
+            SellOutput::Usdt => "USDT",
+        }
+    }
+
+    /// Express a USD PnL in this output's units (stables are treated as $1).
+    /// `amount` of this output in USD; None for SOL when its price is unknown.
+    pub fn to_usd(self, amount: f64, sol_price_usd: Option<f64>) -> Option<f64> {
+        match self {
+            SellOutput::Sol => sol_price_usd.filter(|p| *p > 0.0).map(|p| amount * p),
+            SellOutput::Usdc | SellOutput::Usdt => Some(amount),
+        }
+    }
+
+    pub fn pnl_from_usd(&self, pnl_usd: f64, sol_price_usd: f64) -> f64 {
+        match self {
+            SellOutput::Sol if sol_price_usd > 0.0 => pnl_usd / sol_price_usd,
+            SellOutput::Sol => 0.0,
+            SellOutput::Usdc | SellOutput::Usdt => pnl_usd,
+        }
+    }
+}
+
+// ==================== SELL PREVIEW ====================
+
+/// What a sell is expected to return, in the output token and USD.
+#[derive(Debug, Clone, Serialize)]
+pub struct SellPreview {
+    pub denomination: &'static str,  // Output token (SOL, USDC or USDT)
+    pub out_amount_native: f64,     // Expected output after fees
+    pub min_out_amount_native: f64, // Worst case at the quoted slippage, after fees
+    pub out_amount_usd: f64,
+    pub min_out_amount_usd: f64,
+    pub estimated_fee_native: f64,  // Network/priority fee plus any platform fee, in SOL
+    pub price_impact_pct: f64,
+    pub sol_price_usd: f64,
+}
+
+/// Network + priority fee assumed for a swap, from `SWAP_FEE_ESTIMATE_LAMPORTS` (default 0.0001 SOL).
+pub fn swap_fee_estimate_lamports() -> u64 {
+    std::env::var("SWAP_FEE_ESTIMATE_LAMPORTS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(100_000)
+}
+
+/// Turn a token -> output quote into a preview. `outAmount` is already net of the platform fee,
+/// which is reported in the fee figure for transparency only. The network fee is paid in SOL,
+/// so it only comes out of the proceeds for SOL sells.
+pub fn build_sell_preview(quote: &QuoteResponse, output: SellOutput, network_fee_lamports: u64, sol_price_usd: f64) -> std::result::Result<SellPreview, String> {
+    let parse = |v: &str| v.parse::<u64>().map_err(|_| format!("Invalid quote amount: {}", v));
+    let out = parse(&quote.outAmount)?;
+    let min_out = parse(&quote.otherAmountThreshold)?;
+    let platform_fee = match &quote.platformFee {
+        Some(fee) => parse(&fee.amount)?,
+        None => 0,
+    };
+
+    let to_sol = |lamports: u64| lamports as f64 / 1_000_000_000.0;
+    let (out_amount_native, min_out_amount_native, usd_per_unit, platform_fee_sol) = match output {
+        SellOutput::Sol => (
+            to_sol(out.saturating_sub(network_fee_lamports)),
+            to_sol(min_out.saturating_sub(network_fee_lamports)),
+            sol_price_usd,
+            to_sol(platform_fee),
+        ),
+        SellOutput::Usdc | SellOutput::Usdt => {
+            let to_stable = |raw: u64| raw as f64 / 1_000_000.0;
+            let fee_sol = if sol_price_usd > 0.0 { to_stable(platform_fee) / sol_price_usd } else { 0.0 };
+            (to_stable(out), to_stable(min_out), 1.0, fee_sol)
+        }
+    };
+
+    Ok(SellPreview {
+        denomination: output.denomination(),
+        out_amount_native,
+        min_out_amount_native,
+        out_amount_usd: out_amount_native * usd_per_unit,
+        min_out_amount_usd: min_out_amount_native * usd_per_unit,
+        estimated_fee_native: to_sol(network_fee_lamports) + platform_fee_sol,
+        price_impact_pct: quote.priceImpactPct.parse::<f64>().unwrap_or(0.0) * 100.0,
+        sol_price_usd,
+    })
+}
+
+/// What a SOL -> token buy is expected to return, in token units.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuyPreview {
+    pub amount_in_sol: f64,
+    pub out_amount: f64,         // Expected tokens after any platform fee
+    pub min_out_amount: f64,     // Worst case at the quoted slippage
+    pub platform_fee_amount: f64, // Taken in the output token, already excluded from out_amount
+    pub platform_fee_bps: u64,
+    pub network_fee_sol: f64,
+    pub price_impact_pct: f64,
+}
+
+/// Turn a SOL -> token quote into a preview. `decimals` are the output mint's.
+pub fn build_buy_preview(quote: &QuoteResponse, decimals: u8, network_fee_lamports: u64) -> std::result::Result<BuyPreview, String> {
+    let parse = |v: &str| v.parse::<u64>().map_err(|_| format!("Invalid quote amount: {}", v));
+    let scale = 10f64.powi(decimals as i32);
+    let (platform_fee, platform_fee_bps) = match &quote.platformFee {
+        Some(fee) => (parse(&fee.amount)?, fee.feeBps),
+        None => (0, 0),
+    };
+
+    Ok(BuyPreview {
+        amount_in_sol: parse(&quote.inAmount)? as f64 / 1_000_000_000.0,
+        out_amount: parse(&quote.outAmount)? as f64 / scale,
+        min_out_amount: parse(&quote.otherAmountThreshold)? as f64 / scale,
+        platform_fee_amount: platform_fee as f64 / scale,
+        platform_fee_bps,
+        network_fee_sol: network_fee_lamports as f64 / 1_000_000_000.0,
+        price_impact_pct: quote.priceImpactPct.parse::<f64>().unwrap_or(0.0) * 100.0,
+    })
+}
+
+// ==================== ROUTE SUMMARY ====================
+
+/// One leg of a Jupiter route, with the fee the AMM takes.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RouteHop {
+    pub label: String,
+    pub input_mint: String,
+    pub output_mint: String,
+    pub percent: u32, // Share of the input sent through this leg
+    pub fee_amount: String,
+    pub fee_mint: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RouteSummary {
+    pub route: String, // e.g. "Raydium -> Whirlpool"
+    pub hops: Vec<RouteHop>,
+    pub platform_fee_amount: Option<String>,
+    pub platform_fee_bps: Option<u64>,
+}
+
+pub fn summarize_route(quote: &QuoteResponse) -> RouteSummary {
+    let hops: Vec<RouteHop> = quote.routePlan.iter().map(|step| RouteHop {
+        label: step.swapInfo.label.clone(),
+        input_mint: step.swapInfo.inputMint.clone(),
+        output_mint: step.swapInfo.outputMint.clone(),
+        percent: step.percent,
+        fee_amount: step.swapInfo.feeAmount.clone(),
+        fee_mint: step.swapInfo.feeMint.clone(),
+    }).collect();
+
+    let mut labels: Vec<&str> = Vec::new();
+    for hop in &hops {
+        if labels.last() != Some(&hop.label.as_str()) {
+            labels.push(&hop.label);
+        }
+    }
+
+    RouteSummary {
+        route: labels.join(" -> "),
+        platform_fee_amount: quote.platformFee.as_ref().map(|f| f.amount.clone()),
+        platform_fee_bps: quote.platformFee.as_ref().map(|f| f.feeBps),
+        hops,
+    }
+}
+
+// ==================== COMPUTE BUDGET ====================
+
+/// Runtime maximum compute units per transaction.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// How far a swap's compute unit limit may be raised after a compute-exceeded simulation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComputeBumpPolicy {
+    pub max_retries: u32,
+    pub cap: u32,
+}
+
+impl ComputeBumpPolicy {
+    /// `COMPUTE_BUMP_RETRIES` (default 2, 0 disables simulation) and `COMPUTE_UNIT_LIMIT_CAP`
+    /// (default and hard maximum 1,400,000).
+    pub fn from_env() -> Self {
+        Self {
+            max_retries: std::env::var("COMPUTE_BUMP_RETRIES").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
+            cap: std::env::var("COMPUTE_UNIT_LIMIT_CAP")
+                .ok()
+                .and_then(|v| v.parse::<u32>().ok())
+                .unwrap_or(MAX_COMPUTE_UNIT_LIMIT)
+                .min(MAX_COMPUTE_UNIT_LIMIT),
+        }
+    }
+
+    /// Double the limit, clamped to the cap. `None` once we're already at the cap.
+    pub fn next_limit(&self, current: u32) -> Option<u32> {
+        if current >= self.cap {
+            return None;
+        }
+        Some(current.saturating_mul(2).min(self.cap))
+    }
+}
+
+/// True when a transaction ran out of compute units rather than failing on its own logic.
+pub fn is_compute_exceeded(err: &TransactionError, logs: &[String]) -> bool {
+    if matches!(err, TransactionError::InstructionError(_, InstructionError::ComputationalBudgetExceeded)) {
+        return true;
+    }
+    // Programs that hit the meter mid-CPI surface as a generic failure - the logs say why
+    logs.iter().any(|line| line.contains("exceeded CUs meter") || line.contains("Computational budget exceeded"))
+}
+
+const SET_COMPUTE_UNIT_LIMIT_TAG: u8 = 2;
+
+/// Index of the SetComputeUnitLimit instruction in a message, if it has one.
+fn compute_limit_ix_index(message: &VersionedMessage) -> Option<usize> {
+    let compute_budget_index = message
+        .static_account_keys()
+        .iter()
+        .position(|key| *key == compute_budget::id())?;
+
+    message.instructions().iter().position(|ix| {
+        ix.program_id_index as usize == compute_budget_index
+            && ix.data.len() == 5
+            && ix.data[0] == SET_COMPUTE_UNIT_LIMIT_TAG
+    })
+}
+
+pub fn compute_unit_limit(message: &VersionedMessage) -> Option<u32> {
+    let data = &message.instructions()[compute_limit_ix_index(message)?].data;
+    Some(u32::from_le_bytes([data[1], data[2], data[3], data[4]]))
+}
+
+/// Rewrite the SetComputeUnitLimit instruction in a swap message.
+/// Returns the previous limit, or `None` if the message has no such instruction.
+pub fn set_compute_unit_limit(message: &mut VersionedMessage, units: u32) -> Option<u32> {
+    let index = compute_limit_ix_index(message)?;
+    let previous = compute_unit_limit(message);
+
+    let instructions = match message {
+        VersionedMessage::Legacy(m) => &mut m.instructions,
+        VersionedMessage::V0(m) => &mut m.instructions,
+    };
+    instructions[index].data = ComputeBudgetInstruction::set_compute_unit_limit(units).data;
+    previous
+}
+
+/// Decode Jupiter's base64 `swapTransaction`. Both legacy and v0 wire formats decode
+/// into a `VersionedTransaction`, the message variant telling them apart.
+fn decode_swap_transaction(encoded: &str) -> Result<VersionedTransaction> {
+    let tx_bytes = STANDARD.decode(encoded)?;
+    let tx: VersionedTransaction = bincode::deserialize(&tx_bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to deserialize swap transaction: {}", e))?;
+    tracing::debug!("Swap transaction is {:?} with {} required signer(s)", tx.version(), tx.message.header().num_required_signatures);
+    Ok(tx)
+}
+
+/// Sign as `signer` in the slot matching its key among the message's required signers,
+/// keeping any signatures already present for the other signers.
+fn sign_versioned(tx: &mut VersionedTransaction, signer: &solana_sdk::signature::Keypair) -> Result<()> {
+    let pubkey = signer.pubkey();
+    let required = tx.message.header().num_required_signatures as usize;
+    let index = tx.message.static_account_keys()
+        .iter()
+        .take(required)
+        .position(|key| *key == pubkey)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a signer of the swap transaction", pubkey))?;
+
+    // `serialize` emits the legacy or v0 encoding to match the message variant
+    let signature = signer.sign_message(&tx.message.serialize());
+    tx.signatures.resize(required, solana_sdk::signature::Signature::default());
+    tx.signatures[index] = signature;
+    Ok(())
+}
+
+/// Simulate the signed swap and, while it fails for lack of compute, raise the
+/// SetComputeUnitLimit (bounded by the policy) and re-sign. Other simulation errors
+/// are logged and the transaction is sent as-is, like before.
+fn bump_compute_until_fits(
     client: &RpcClient,
     signer: &solana_sdk::signature::Keypair,
-    input_mint: &str,
+    tx: &mut VersionedTransaction,
+    policy: ComputeBumpPolicy,
+) -> Result<()> {
+    let config = RpcSimulateTransactionConfig { sig_verify: false, ..Default::default() };
+
+    for attempt in 0..=policy.max_retries {
+        let sim = match client.simulate_transaction_with_config(&*tx, config.clone()) {
+            Ok(sim) => sim.value,
+            Err(e) => {
+                tracing::warn!("Swap simulation unavailable, sending without it: {}", e);
+                return Ok(());
+            }
+        };
+
+        let Some(err) = sim.err else { return Ok(()) };
+        let logs = sim.logs.unwrap_or_default();
+        if !is_compute_exceeded(&err, &logs) {
+            tracing::warn!("Swap simulation failed: {:?}", err);
+            return Ok(());
+        }
+        if attempt == policy.max_retries {
+            break;
+        }
+
+        let Some(current) = compute_unit_limit(&tx.message) else {
+            anyhow::bail!("Swap exceeded its compute budget and has no compute limit to raise");
+        };
+        let Some(next) = policy.next_limit(current) else { break };
+        set_compute_unit_limit(&mut tx.message, next);
+        sign_versioned(tx, signer)?;
+        tracing::info!("⛽ Compute exceeded at {} CU, retrying with {} CU", current, next);
+    }
+
+    anyhow::bail!("Swap exceeded its compute budget (cap {} CU)", policy.cap)
+}
+
+// ==================== CORE FUNCTIONS ====================
+
+/// A confirmed Jupiter swap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapExecution {
+    pub signature: String,
+    pub slot: u64,
+    pub in_amount: u64,
+    /// Output actually received (raw units), read from the confirmed transaction.
+    /// Falls back to the quoted amount when the transaction can't be read back.
+    pub out_amount: u64,
+    /// Slippage the landed swap was quoted at, and how many slippage reverts it took to get there.
+    pub slippage_bps: u64,
+    pub slippage_retries: u32,
+    /// Platform fee taken from the output (raw units), per the quote that landed.
+    pub platform_fee: Option<u64>,
+}
+
+/// Swap attempts (fresh transaction each time) when the blockhash expires or the node lags.
+const MAX_SWAP_ATTEMPTS: u32 = 3;
+
+/// How long to wait for a sent swap to confirm, from `SWAP_CONFIRM_TIMEOUT_SECS` (default 60).
+fn swap_confirm_timeout() -> std::time::Duration {
+    let secs = std::env::var("SWAP_CONFIRM_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(60);
+    std::time::Duration::from_secs(secs)
+}
+
+/// Send/confirm failures worth retrying with a freshly built transaction.
+pub fn is_retryable_send_error(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    ["blockhash not found", "blockhashnotfound", "block height exceeded", "node is behind", "node is unhealthy"]
+        .iter()
+        .any(|pattern| lower.contains(pattern))
+}
+
+/// Jupiter's SlippageToleranceExceeded, as it shows up in a reverted transaction's error.
+pub fn is_slippage_error(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    ["custom(6001)", "0x1771", "slippage"].iter().any(|pattern| lower.contains(pattern))
+}
+
+/// Next slippage to try after a slippage revert: double the last one, capped at `ceiling`.
+/// None once the ceiling is reached (or escalation is off).
+pub fn escalate_slippage(current_bps: u64, ceiling_bps: Option<u64>) -> Option<u64> {
+    let ceiling = ceiling_bps?.min(10_000);
+    let next = current_bps.max(1).saturating_mul(2).min(ceiling);
+    (next > current_bps).then_some(next)
+}
+
+enum SendFailure {
+    Retryable(String),
+    Fatal(String),
+    Reverted(String), // Landed but failed on-chain, so the RPC itself is fine
+}
+
+/// A wallet's balance of one mint in a transaction's pre/post token balances.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBalanceEntry {
+    pub owner: String,
+    pub mint: String,
+    pub amount: u64,
+}
+
+/// How much of `output_mint` the owner gained. SOL outputs are unwrapped, so they show up
+/// in the fee payer's lamports (with the fee added back).
+pub fn output_received(
     output_mint: &str,
-    amount_lamports: u64,
-    slippage_bps: u64, // 100 = 1%
-) -> Result<String> { // Returns TX Signature
-    
-    tracing::info!("🔄 Fetching Jupiter Quote: {} -> {} (Amt: {})", input_mint, output_mint, amount_lamports);
+    owner: &str,
+    native: (u64, u64),
+    fee: u64,
+    pre_tokens: &[TokenBalanceEntry],
+    post_tokens: &[TokenBalanceEntry],
+) -> Option<u64> {
+    if output_mint == WSOL_MINT {
+        let (pre, post) = native;
+        return (post + fee).checked_sub(pre);
+    }
+    let total = |entries: &[TokenBalanceEntry]| -> u64 {
+        entries.iter().filter(|e| e.owner == owner && e.mint == output_mint).map(|e| e.amount).sum()
+    };
+    total(post_tokens).checked_sub(total(pre_tokens))
+}
 
-    // 0. Setup Client with API Key
-    let client_http = get_jupiter_client()?;
+fn read_output_received(client: &RpcClient, signature: &solana_sdk::signature::Signature, owner: &Pubkey, output_mint: &str) -> Option<u64> {
+    use solana_client::rpc_config::RpcTransactionConfig;
+    use solana_sdk::commitment_config::CommitmentConfig;
+    use solana_transaction_status::{option_serializer::OptionSerializer, UiTransactionEncoding, UiTransactionTokenBalance};
 
-    // 1. Get Quote
-    let quote = get_jupiter_quote(&client_http, input_mint, output_mint, amount_lamports, slippage_bps).await?;
+    let tx = client.get_transaction_with_config(signature, RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    }).ok()?;
+    let meta = tx.transaction.meta?;
+
+    let entries = |balances: OptionSerializer<Vec<UiTransactionTokenBalance>>| -> Vec<TokenBalanceEntry> {
+        Option::<Vec<UiTransactionTokenBalance>>::from(balances)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|b| Some(TokenBalanceEntry {
+                owner: Option::<String>::from(b.owner)?,
+                amount: b.ui_token_amount.amount.parse().ok()?,
+                mint: b.mint,
+            }))
+            .collect()
+    };
 
+    output_received(
+        output_mint,
+        &owner.to_string(),
+        (*meta.pre_balances.first()?, *meta.post_balances.first()?),
+        meta.fee,
+        &entries(meta.pre_token_balances),
+        &entries(meta.post_token_balances),
+    )
+}
 
-    tracing::info!("   Quote received. Out Amount: {} (Impact: {}%)", quote.outAmount, quote.priceImpactPct);
+/// Concrete priority fee from recent fees on the route's pools, falling back to
+/// Jupiter's own estimate if the lookup fails.
+async fn estimate_priority_fee(quote: &QuoteResponse, priority: PriorityTier) -> serde_json::Value {
+    let cap = priority_fee_max_lamports();
+    let pools: Vec<String> = quote.routePlan.iter().map(|step| step.swapInfo.ammKey.clone()).collect();
+    let percentile = priority.percentile(crate::gas::priority_fee_percentile());
+    match crate::gas::get_solana_priority_fee(&pools, percentile, cap).await {
+        Ok(lamports) => {
+            tracing::info!("⛽ Priority fee: {} lamports (p{} over {} pools)", lamports, percentile, pools.len());
+            serde_json::json!(lamports)
+        }
+        Err(e) => {
+            tracing::warn!("⚠️ Priority fee estimate failed, letting Jupiter choose: {}", e);
+            priority.to_jupiter(cap)
+        }
+    }
+}
 
-    // 2. Get Swap Transaction
+/// Fetch a swap transaction for the quote from Jupiter, sign it and size its compute budget.
+async fn build_swap_transaction(
+    client: &RpcClient,
+    client_http: &reqwest::Client,
+    signer: &solana_sdk::signature::Keypair,
+    quote: &QuoteResponse,
+    priority: PriorityTier,
+) -> Result<VersionedTransaction> {
     let swap_req = SwapRequest {
-        quoteResponse: quote,
+        quoteResponse: quote.clone(),
         userPublicKey: signer.pubkey().to_string(),
         wrapAndUnwrapSol: true,
-        prioritizationFeeLamports: "auto".to_string(), // Dynamic fees for speed
+        prioritizationFeeLamports: estimate_priority_fee(quote, priority).await,
         dynamicComputeUnitLimit: true, // Essential for high-compute routes
+        feeAccount: swap_fee_account(quote),
     };
 
     let swap_res: SwapResponse = client_http.post(format!("{}/swap", JUPITER_API_URL))
@@ -109,86 +978,821 @@ pub async fn execute_solana_swap(
         .json()
         .await?;
 
-    // 3. Deserialize Transaction
-    let tx_bytes = STANDARD.decode(&swap_res.swapTransaction)?;
-    let mut transaction: Transaction = bincode::deserialize(&tx_bytes)?;
+    // Jupiter v6 returns a base64 transaction with a recent blockhash already set
+    let mut versioned_tx = decode_swap_transaction(&swap_res.swapTransaction)?;
+    sign_versioned(&mut versioned_tx, signer)?;
 
-    // 4. Sign Transaction
-    // Needs recent blockhash - Jupiter usually provides one, but best to refresh if stale logic isn't perfect
-    // BUT Jupiter's serialized TX *already* has a blockhash. We just need to sign.
-    // However, the transaction from Jupiter is partially signed if using shared accounts sometimes? 
-    // Usually for simple swaps it's just us.
-    
-    // We must sign it.
-    // Note: Transaction might be VersionedTransaction in newer Jupiter API versions.
-    // The v6 API returns a "serialized transaction".
-    // "swapTransaction" is base64 encoded.
-    
-    // IMPORTANT: Checking if it's a legacy or versioned transaction.
-    // bincode::deserialize::<Transaction> works for legacy. 
-    // For Versioned, we might need solana_sdk::transaction::VersionedTransaction.
-    // Let's try VersionedTransaction as Jupiter v6 defaults to it.
-    
-    // Re-attempt deserialization as VersionedTransaction if possible, but our dependencies might be old?
-    // Checking Cargo.toml from memory (solana-sdk 1.18 is recent).
-    // Let's use `VersionedTransaction` if available, or try unsafe generic deserialization if we are unsure.
-    // For now, let's assume `VersionedTransaction` is the way.
-    
-    use solana_sdk::transaction::VersionedTransaction;
-    let mut versioned_tx: VersionedTransaction = bincode::deserialize(&tx_bytes)
-        .map_err(|e| anyhow::anyhow!("Failed to deserialize versioned tx: {}", e))?;
+    // Simulate, raising the compute limit if the route runs out of CUs
+    let policy = ComputeBumpPolicy::from_env();
+    if policy.max_retries > 0 {
+        bump_compute_until_fits(client, signer, &mut versioned_tx, policy)?;
+    }
 
-    // Sign
-    let message_data = versioned_tx.message.serialize();
-    let signature = signer.sign_message(&message_data);
-    versioned_tx.signatures = vec![signature];
+    Ok(versioned_tx)
+}
 
-    // 5. Send Transaction
-    tracing::info!("🚀 Sending Transaction...");
+/// Send and poll until the swap confirms, fails, or its blockhash expires.
+async fn send_and_confirm_swap(
+    client: &RpcClient,
+    tx: &VersionedTransaction,
+    timeout: std::time::Duration,
+) -> std::result::Result<(solana_sdk::signature::Signature, u64), SendFailure> {
     let config = solana_client::rpc_config::RpcSendTransactionConfig {
         skip_preflight: true,
         ..Default::default()
     };
-    
-    let signature = client.send_transaction_with_config(
-        &versioned_tx,
-        config,
-    )?;
-
+    let signature = client.send_transaction_with_config(tx, config).map_err(|e| {
+        let message = e.to_string();
+        if is_retryable_send_error(&message) { SendFailure::Retryable(message) } else { SendFailure::Fatal(message) }
+    })?;
     tracing::info!("✅ Transaction Sent: {}", signature);
-    
-    // 6. Confirm (Optional blocking wait, or return signature immediately)
-    // For a trading bot, we often want to return immediately and track status async.
-    // But for this flow, let's wait a standard confirmation or at least return the Sig.
-    
-    Ok(signature.to_string())
+
+    confirm_swap(client, tx, signature, timeout).await
 }
 
-// ==================== HELPERS ====================
+/// Poll a sent transaction until it confirms, fails, or its blockhash expires.
+async fn confirm_swap(
+    client: &RpcClient,
+    tx: &VersionedTransaction,
+    signature: solana_sdk::signature::Signature,
+    timeout: std::time::Duration,
+) -> std::result::Result<(solana_sdk::signature::Signature, u64), SendFailure> {
+    use solana_sdk::commitment_config::CommitmentConfig;
 
+    let started = std::time::Instant::now();
+    while started.elapsed() < timeout {
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
+        let status = match client.get_signature_statuses(&[signature]) {
+            Ok(response) => response.value.into_iter().next().flatten(),
+            Err(e) => {
+                tracing::debug!("Signature status lookup failed: {}", e);
+                continue;
+            }
+        };
 
-pub async fn get_jupiter_quote(
-    client: &reqwest::Client,
-    input_mint: &str,
+        if let Some(status) = status {
+            if let Some(err) = status.err {
+                return Err(SendFailure::Reverted(format!("Transaction {} failed: {:?}", signature, err)));
+            }
+            if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                return Ok((signature, status.slot));
+            }
+            continue;
+        }
+
+        // Not seen yet: once the blockhash is gone the transaction can never land
+        if let Ok(false) = client.is_blockhash_valid(tx.message.recent_blockhash(), CommitmentConfig::processed()) {
+            return Err(SendFailure::Retryable(format!("Transaction {} expired: block height exceeded", signature)));
+        }
+    }
+
+    Err(SendFailure::Fatal(format!("Transaction {} not confirmed after {}s", signature, timeout.as_secs())))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_solana_swap(
+    client: &RpcClient,
+    signer: &solana_sdk::signature::Keypair,
+    input_mint: &str,
     output_mint: &str,
     amount_lamports: u64,
-    slippage_bps: u64,
-) -> Result<QuoteResponse> {
-    let quote_url = format!(
-        "{}/quote?inputMint={}&outputMint={}&amount={}&slippageBps={}",
-        JUPITER_API_URL, input_mint, output_mint, amount_lamports, slippage_bps
-    );
+    profile: &ExecutionProfile,
+    limits: &SwapLimits,
+    metrics: &crate::metrics::Metrics,
+    breaker: &crate::health::CircuitBreaker,
+) -> Result<SwapExecution> {
+    
+    tracing::info!("🔄 Fetching Jupiter Quote: {} -> {} (Amt: {}, {:?})", input_mint, output_mint, amount_lamports, profile);
+
+    let mut slippage_bps = profile.slippage_bps;
+    limits.check_slippage(slippage_bps)?;
+
+    // 0. Setup Client with API Key
+    let client_http = get_jupiter_client()?;
+
+    // 1. Get Quote
+    let quote_started = std::time::Instant::now();
+    let quote = get_jupiter_quote(&client_http, input_mint, output_mint, amount_lamports, slippage_bps).await;
+    metrics.observe_jupiter_latency(quote_started.elapsed());
+    let mut quote = quote?;
+
+    tracing::info!("   Quote received. Out Amount: {} (Min: {}, Impact: {}%)", quote.outAmount, quote.otherAmountThreshold, quote.priceImpactPct);
+    limits.check_quote(&quote)?;
+    let mut quoted_out = quote.outAmount.parse::<u64>().unwrap_or(0);
+    let mut slippage_retries = 0;
+
+    // Private submission when asked for and configured, public RPC otherwise
+    let mut jito = if profile.private_tx { JitoConfig::from_env() } else { None };
+    if profile.private_tx && jito.is_none() {
+        tracing::warn!("⚠️ Private submission requested but Jito is not configured, sending publicly");
+    }
+
+    // 2. Build, send and confirm - with a fresh transaction if the last one expired
+    let timeout = swap_confirm_timeout();
+    let mut attempt = 1;
+    loop {
+        let tx = build_swap_transaction(client, &client_http, signer, &quote, profile.priority).await?;
+
+        tracing::info!("🚀 Sending Transaction (attempt {}/{})...", attempt, MAX_SWAP_ATTEMPTS);
+        let sent = match &jito {
+            Some(config) => match send_jito_bundle(config, signer, &tx).await {
+                Ok(bundle_id) => {
+                    tracing::info!("🛡️ Swap submitted privately in Jito bundle {}", bundle_id);
+                    confirm_swap(client, &tx, tx.signatures[0], timeout).await
+                }
+                Err(e) => {
+                    tracing::warn!("⚠️ Jito bundle rejected ({}), sending publicly", e);
+                    jito = None;
+                    send_and_confirm_swap(client, &tx, timeout).await
+                }
+            },
+            None => send_and_confirm_swap(client, &tx, timeout).await,
+        };
+        if sent.is_err() {
+            metrics.record_rpc_error();
+        }
+        match &sent {
+            Ok(_) | Err(SendFailure::Reverted(_)) => breaker.record_success(),
+            Err(_) => breaker.record_failure(),
+        }
+        if matches!(sent, Err(SendFailure::Retryable(_))) && jito.take().is_some() {
+            tracing::warn!("⚠️ Jito bundle did not land, retrying publicly");
+        }
+
+        match sent {
+            Ok((signature, slot)) => {
+                let out_amount = read_output_received(client, &signature, &signer.pubkey(), output_mint)
+                    .unwrap_or(quoted_out);
+                tracing::info!("✅ Swap confirmed in slot {}: {} (out {})", slot, signature, out_amount);
+                return Ok(SwapExecution {
+                    signature: signature.to_string(),
+                    slot,
+                    in_amount: amount_lamports,
+                    out_amount,
+                    slippage_bps,
+                    slippage_retries,
+                    platform_fee: quote.platformFee.as_ref().and_then(|f| f.amount.parse::<u64>().ok()),
+                });
+            }
+            Err(SendFailure::Retryable(e)) if attempt < MAX_SWAP_ATTEMPTS => {
+                tracing::warn!("⚠️ Swap attempt {} failed ({}), rebuilding transaction", attempt, e);
+                attempt += 1;
+            }
+            Err(SendFailure::Reverted(e)) if is_slippage_error(&e) => {
+                // Escalation stays inside the engine-wide slippage guard
+                let Some(next) = escalate_slippage(slippage_bps, profile.max_slippage_bps).filter(|bps| limits.check_slippage(*bps).is_ok()) else {
+                    anyhow::bail!(e);
+                };
+                tracing::warn!("⚠️ Swap reverted on slippage at {} bps, re-quoting at {} bps", slippage_bps, next);
+                slippage_bps = next;
+                slippage_retries += 1;
+                quote = get_jupiter_quote(&client_http, input_mint, output_mint, amount_lamports, slippage_bps).await?;
+                limits.check_quote(&quote)?;
+                quoted_out = quote.outAmount.parse::<u64>().unwrap_or(0);
+            }
+            Err(SendFailure::Retryable(e)) | Err(SendFailure::Fatal(e)) | Err(SendFailure::Reverted(e)) => anyhow::bail!(e),
+        }
+    }
+}
+
+// ==================== PRIVATE SUBMISSION (JITO) ====================
+
+/// Jito block engine settings. Both the URL and tip account must be set to enable it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JitoConfig {
+    pub block_engine_url: String,
+    pub tip_account: Pubkey,
+    pub tip_lamports: u64,
+}
+
+impl JitoConfig {
+    /// `JITO_BLOCK_ENGINE_URL`, `JITO_TIP_ACCOUNT` and `JITO_TIP_LAMPORTS` (default 10000).
+    pub fn resolve(lookup: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let block_engine_url = lookup("JITO_BLOCK_ENGINE_URL").filter(|u| !u.trim().is_empty())?;
+        let tip_account = lookup("JITO_TIP_ACCOUNT").and_then(|a| Pubkey::from_str(a.trim()).ok())?;
+        let tip_lamports = lookup("JITO_TIP_LAMPORTS")
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|l| *l > 0)
+            .unwrap_or(10_000);
+        Some(Self { block_engine_url: block_engine_url.trim_end_matches('/').to_string(), tip_account, tip_lamports })
+    }
+
+    pub fn from_env() -> Option<Self> {
+        Self::resolve(|key| std::env::var(key).ok())
+    }
+}
+
+/// Tip transfer sent after the swap in the same bundle, on the swap's blockhash so both expire together.
+fn jito_tip_transaction(signer: &solana_sdk::signature::Keypair, config: &JitoConfig, swap: &VersionedTransaction) -> VersionedTransaction {
+    let payer = signer.pubkey();
+    let tip = solana_sdk::system_instruction::transfer(&payer, &config.tip_account, config.tip_lamports);
+    let tx = solana_sdk::transaction::Transaction::new_signed_with_payer(&[tip], Some(&payer), &[signer], *swap.message.recent_blockhash());
+    VersionedTransaction::from(tx)
+}
+
+/// `sendBundle` JSON-RPC body for base64-encoded transactions.
+pub fn jito_bundle_request(txs: &[VersionedTransaction]) -> Result<serde_json::Value> {
+    let encoded = txs.iter()
+        .map(|tx| bincode::serialize(tx).map(|bytes| STANDARD.encode(bytes)))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "sendBundle",
+        "params": [encoded, { "encoding": "base64" }],
+    }))
+}
+
+/// Bundle id on acceptance, the block engine's error otherwise.
+pub fn parse_jito_response(body: &serde_json::Value) -> std::result::Result<String, String> {
+    if let Some(id) = body["result"].as_str() {
+        return Ok(id.to_string());
+    }
+    Err(body["error"]["message"].as_str().unwrap_or("no bundle id returned").to_string())
+}
+
+async fn send_jito_bundle(config: &JitoConfig, signer: &solana_sdk::signature::Keypair, swap: &VersionedTransaction) -> Result<String> {
+    let body = jito_bundle_request(&[swap.clone(), jito_tip_transaction(signer, config, swap)])?;
+    let http = reqwest::Client::builder().timeout(std::time::Duration::from_secs(10)).build()?;
+    let response: serde_json::Value = http.post(format!("{}/api/v1/bundles", config.block_engine_url))
+        .json(&body)
+        .send()
+        .await?
+        .json()
+        .await?;
+    parse_jito_response(&response).map_err(|e| anyhow::anyhow!(e))
+}
+
+// ==================== BUNDLED SWAPS ====================
+
+/// Compute units one transaction may request.
+pub const MAX_TX_COMPUTE_UNITS: u32 = 1_400_000;
+
+/// Used when Jupiter doesn't size the swap's compute budget.
+const DEFAULT_SWAP_COMPUTE_UNITS: u32 = 300_000;
+
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterAccountMeta {
+    pubkey: String,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JupiterInstruction {
+    program_id: String,
+    accounts: Vec<JupiterAccountMeta>,
+    data: String, // base64
+}
+
+impl JupiterInstruction {
+    fn into_instruction(self) -> Result<Instruction> {
+        let accounts = self.accounts.into_iter()
+            .map(|a| Ok(AccountMeta { pubkey: Pubkey::from_str(&a.pubkey)?, is_signer: a.is_signer, is_writable: a.is_writable }))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Instruction { program_id: Pubkey::from_str(&self.program_id)?, accounts, data: STANDARD.decode(&self.data)? })
+    }
+}
+
+/// Jupiter `/swap-instructions`: the pieces of a swap transaction, unassembled.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SwapInstructionsResponse {
+    #[serde(default)]
+    compute_budget_instructions: Vec<JupiterInstruction>,
+    #[serde(default)]
+    setup_instructions: Vec<JupiterInstruction>,
+    swap_instruction: JupiterInstruction,
+    cleanup_instruction: Option<JupiterInstruction>,
+    #[serde(default)]
+    address_lookup_table_addresses: Vec<String>,
+}
+
+/// One swap of a bundle, to be sent alongside others in a shared transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleSwap {
+    pub input_mint: String,
+    pub output_mint: String,
+    pub amount: u64, // Raw units of the input mint
+    pub slippage_bps: u64,
+}
+
+/// A swap's instructions with its compute budget pulled out, so several can share one.
+#[derive(Debug, Clone)]
+pub struct SwapLeg {
+    pub instructions: Vec<Instruction>,
+    pub compute_units: u32,
+    pub compute_unit_price: u64, // Micro-lamports
+    pub lookup_tables: Vec<Pubkey>,
+}
+
+impl SwapLeg {
+    fn from_response(res: SwapInstructionsResponse) -> Result<Self> {
+        let mut compute_units = DEFAULT_SWAP_COMPUTE_UNITS;
+        let mut compute_unit_price = 0;
+        for ix in res.compute_budget_instructions {
+            let data = STANDARD.decode(&ix.data)?;
+            match data.split_first() {
+                Some((&SET_COMPUTE_UNIT_LIMIT_TAG, rest)) if rest.len() == 4 => {
+                    compute_units = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]);
+                }
+                Some((&SET_COMPUTE_UNIT_PRICE_TAG, rest)) if rest.len() == 8 => {
+                    compute_unit_price = u64::from_le_bytes(rest.try_into()?);
+                }
+                _ => {}
+            }
+        }
+
+        let mut instructions = res.setup_instructions.into_iter()
+            .map(JupiterInstruction::into_instruction)
+            .collect::<Result<Vec<_>>>()?;
+        instructions.push(res.swap_instruction.into_instruction()?);
+        if let Some(cleanup) = res.cleanup_instruction {
+            instructions.push(cleanup.into_instruction()?);
+        }
+        let lookup_tables = res.address_lookup_table_addresses.iter()
+            .map(|a| Pubkey::from_str(a))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Self { instructions, compute_units, compute_unit_price, lookup_tables })
+    }
+}
+
+/// One compute budget for the whole group (summed limit, highest price), then each leg in order.
+fn bundle_instructions(legs: &[&SwapLeg]) -> Vec<Instruction> {
+    let units: u64 = legs.iter().map(|l| l.compute_units as u64).sum();
+    let price = legs.iter().map(|l| l.compute_unit_price).max().unwrap_or(0);
+
+    let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(units.min(MAX_TX_COMPUTE_UNITS as u64) as u32)];
+    if price > 0 {
+        instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+    }
+    instructions.extend(legs.iter().flat_map(|l| l.instructions.iter().cloned()));
+    instructions
+}
+
+fn compile_bundle(
+    payer: &Pubkey,
+    legs: &[&SwapLeg],
+    tables: &[AddressLookupTableAccount],
+    blockhash: solana_sdk::hash::Hash,
+) -> Result<VersionedMessage> {
+    let used: Vec<AddressLookupTableAccount> = tables.iter()
+        .filter(|t| legs.iter().any(|l| l.lookup_tables.contains(&t.key)))
+        .cloned()
+        .collect();
+    let message = v0::Message::try_compile(payer, &bundle_instructions(legs), &used, blockhash)?;
+    Ok(VersionedMessage::V0(message))
+}
+
+/// Whether the legs fit one transaction: compute within the per-transaction cap and the
+/// signed transaction within the packet size.
+pub fn fits_in_transaction(payer: &Pubkey, legs: &[&SwapLeg], tables: &[AddressLookupTableAccount]) -> bool {
+    let units: u64 = legs.iter().map(|l| l.compute_units as u64).sum();
+    if units > MAX_TX_COMPUTE_UNITS as u64 {
+        return false;
+    }
+    let Ok(message) = compile_bundle(payer, legs, tables, solana_sdk::hash::Hash::default()) else {
+        return false;
+    };
+    let signatures = vec![solana_sdk::signature::Signature::default(); message.header().num_required_signatures as usize];
+    let tx = VersionedTransaction { signatures, message };
+    bincode::serialized_size(&tx).is_ok_and(|size| size as usize <= PACKET_DATA_SIZE)
+}
 
-    let quote: QuoteResponse = client.get(&quote_url)
+/// Split legs, in order, into the fewest consecutive groups that each fit one transaction.
+pub fn pack_swap_legs(payer: &Pubkey, legs: &[SwapLeg], tables: &[AddressLookupTableAccount]) -> std::result::Result<Vec<Vec<usize>>, String> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+
+    for index in 0..legs.len() {
+        let candidate: Vec<&SwapLeg> = current.iter().chain([&index]).map(|&i| &legs[i]).collect();
+        if fits_in_transaction(payer, &candidate, tables) {
+            current.push(index);
+            continue;
+        }
+        if current.is_empty() || !fits_in_transaction(payer, &[&legs[index]], tables) {
+            return Err(format!("Swap {} is too large for a single transaction", index + 1));
+        }
+        groups.push(std::mem::replace(&mut current, vec![index]));
+    }
+    if !current.is_empty() {
+        groups.push(current);
+    }
+    Ok(groups)
+}
+
+async fn fetch_swap_leg(
+    client_http: &reqwest::Client,
+    signer: &Pubkey,
+    swap: &BundleSwap,
+    priority: PriorityTier,
+) -> Result<SwapLeg> {
+    let quote = get_jupiter_quote(client_http, &swap.input_mint, &swap.output_mint, swap.amount, swap.slippage_bps).await?;
+    let swap_req = SwapRequest {
+        prioritizationFeeLamports: estimate_priority_fee(&quote, priority).await,
+        feeAccount: swap_fee_account(&quote),
+        quoteResponse: quote,
+        userPublicKey: signer.to_string(),
+        wrapAndUnwrapSol: true,
+        dynamicComputeUnitLimit: true,
+    };
+
+    let res: SwapInstructionsResponse = client_http.post(format!("{}/swap-instructions", JUPITER_API_URL))
+        .json(&swap_req)
         .send()
         .await?
+        .error_for_status()?
         .json()
         .await?;
+    SwapLeg::from_response(res)
+}
+
+fn fetch_lookup_tables(client: &RpcClient, keys: &[Pubkey]) -> Result<Vec<AddressLookupTableAccount>> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+    client.get_multiple_accounts(keys)?
+        .into_iter()
+        .zip(keys)
+        .map(|(account, key)| {
+            let account = account.ok_or_else(|| anyhow::anyhow!("Lookup table {} not found", key))?;
+            let table = AddressLookupTable::deserialize(&account.data)
+                .map_err(|e| anyhow::anyhow!("Invalid lookup table {}: {}", key, e))?;
+            Ok(AddressLookupTableAccount { key: *key, addresses: table.addresses.to_vec() })
+        })
+        .collect()
+}
+
+/// Execute several Jupiter swaps as few transactions as possible, each signed once by
+/// `signer`. Returns, for each swap, the signature of the transaction that carried it.
+/// A failure part-way through leaves the earlier transactions landed, and the error lists them.
+pub async fn execute_solana_bundle(
+    client: &RpcClient,
+    signer: &solana_sdk::signature::Keypair,
+    swaps: &[BundleSwap],
+    priority: PriorityTier,
+) -> Result<Vec<String>> {
+    let client_http = get_jupiter_client()?;
+    let payer = signer.pubkey();
+
+    let mut legs = Vec::with_capacity(swaps.len());
+    for swap in swaps {
+        legs.push(fetch_swap_leg(&client_http, &payer, swap, priority).await?);
+    }
+
+    let mut table_keys: Vec<Pubkey> = legs.iter().flat_map(|l| l.lookup_tables.iter().copied()).collect();
+    table_keys.sort();
+    table_keys.dedup();
+    let mut tables = fetch_lookup_tables(client, &table_keys)?;
+
+    let groups = pack_swap_legs(&payer, &legs, &tables).map_err(|e| anyhow::anyhow!(e))?;
+    tracing::info!("📦 Bundling {} swaps into {} transaction(s)", legs.len(), groups.len());
+
+    let timeout = swap_confirm_timeout();
+    let mut signatures = Vec::with_capacity(groups.len());
+    for group in &groups {
+        let mut group_legs: Vec<SwapLeg> = group.iter().map(|&i| legs[i].clone()).collect();
+        let mut attempt = 1;
+        loop {
+            let blockhash = client.get_latest_blockhash()?;
+            let leg_refs: Vec<&SwapLeg> = group_legs.iter().collect();
+            let message = compile_bundle(&payer, &leg_refs, &tables, blockhash)?;
+            let tx = VersionedTransaction::try_new(message, &[signer])?;
+
+            let error = match send_and_confirm_swap(client, &tx, timeout).await {
+                Ok((signature, slot)) => {
+                    tracing::info!("✅ Bundle transaction confirmed in slot {}: {} ({} swaps)", slot, signature, group.len());
+                    signatures.push((group, signature.to_string()));
+                    break;
+                }
+                Err(SendFailure::Retryable(e)) if attempt < MAX_SWAP_ATTEMPTS => {
+                    tracing::warn!("⚠️ Bundle attempt {} failed ({}), re-quoting its swaps", attempt, e);
+                    attempt += 1;
+                    // The old quotes (and their routes) may be stale by now
+                    match requote_group(client, &client_http, &payer, group, swaps, priority, &mut tables).await {
+                        Ok(fresh) => {
+                            group_legs = fresh;
+                            continue;
+                        }
+                        Err(e) => e.to_string(),
+                    }
+                }
+                Err(SendFailure::Retryable(e)) | Err(SendFailure::Fatal(e)) | Err(SendFailure::Reverted(e)) => e,
+            };
+            if signatures.is_empty() {
+                anyhow::bail!(error);
+            }
+            let landed: Vec<&str> = signatures.iter().map(|(_, sig)| sig.as_str()).collect();
+            anyhow::bail!("{} (already landed: {})", error, landed.join(", "));
+        }
+    }
+
+    let mut per_swap = vec![String::new(); swaps.len()];
+    for (group, signature) in signatures {
+        for &i in group {
+            per_swap[i] = signature.clone();
+        }
+    }
+    Ok(per_swap)
+}
+
+/// Fresh legs for one packed group, loading any lookup tables the new routes use.
+/// Fails if the new routes no longer fit one transaction.
+async fn requote_group(
+    client: &RpcClient,
+    client_http: &reqwest::Client,
+    payer: &Pubkey,
+    group: &[usize],
+    swaps: &[BundleSwap],
+    priority: PriorityTier,
+    tables: &mut Vec<AddressLookupTableAccount>,
+) -> Result<Vec<SwapLeg>> {
+    let mut fresh = Vec::with_capacity(group.len());
+    for &i in group {
+        fresh.push(fetch_swap_leg(client_http, payer, &swaps[i], priority).await?);
+    }
+
+    let mut missing: Vec<Pubkey> = fresh.iter()
+        .flat_map(|l| l.lookup_tables.iter().copied())
+        .filter(|key| !tables.iter().any(|t| t.key == *key))
+        .collect();
+    missing.sort();
+    missing.dedup();
+    tables.extend(fetch_lookup_tables(client, &missing)?);
+
+    let refs: Vec<&SwapLeg> = fresh.iter().collect();
+    if !fits_in_transaction(payer, &refs, tables) {
+        anyhow::bail!("Re-quoted swaps no longer fit one transaction");
+    }
+    Ok(fresh)
+}
+
+// ==================== EVM SWAPS ====================
+
+/// Gas limit used when estimation isn't possible (e.g. a sell whose approval isn't mined yet).
+const DEFAULT_EVM_SWAP_GAS: u64 = 350_000;
+const EVM_SWAP_DEADLINE_SECS: u64 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvmSwapSide {
+    /// Spend native currency (wei) on the token.
+    Buy { value_wei: u128 },
+    /// Sell this percentage of the wallet's token balance for native currency.
+    Sell { percent: f64 },
+}
+
+/// A sent EVM swap. `token_amount` is the quoted tokens out of a buy, in whole units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvmSwapExecution {
+    pub tx_hash: String,
+    pub token_amount: Option<f64>,
+}
+
+/// Why an EVM swap didn't go out, split by who can fix it.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EvmSwapError {
+    /// The wallet can't pay for the swap value plus gas.
+    #[error("{0}")]
+    InsufficientFunds(String),
+    /// Nothing to sell.
+    #[error("No token balance to sell")]
+    NoBalance,
+    /// The node, router or chain config failed.
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<String> for EvmSwapError {
+    fn from(e: String) -> Self {
+        EvmSwapError::Failed(e)
+    }
+}
+
+/// Classify a raw node error, with the message from `evm_error_message`.
+pub fn evm_swap_error(raw: &str) -> EvmSwapError {
+    let message = evm_error_message(raw);
+    if raw.to_lowercase().contains("insufficient funds") {
+        EvmSwapError::InsufficientFunds(message)
+    } else {
+        EvmSwapError::Failed(message)
+    }
+}
+
+/// Turn raw node errors into something a user can act on.
+pub fn evm_error_message(raw: &str) -> String {
+    let lower = raw.to_lowercase();
+    if lower.contains("insufficient funds") {
+        "Insufficient funds for gas: wallet balance can't cover the swap value plus gas fees".to_string()
+    } else if lower.contains("insufficient_output_amount") {
+        "Swap failed: price moved beyond slippage tolerance".to_string()
+    } else {
+        format!("Swap failed: {}", raw)
+    }
+}
+
+/// Minimum acceptable output for a quoted amount.
+pub fn min_amount_out(quoted: u128, slippage_bps: u64) -> u128 {
+    let keep = 10_000u128.saturating_sub(slippage_bps as u128);
+    quoted / 10_000 * keep + quoted % 10_000 * keep / 10_000
+}
+
+/// Portion of a raw token balance to sell.
+pub fn evm_sell_amount(balance: u128, percent: f64) -> u128 {
+    if percent >= 100.0 {
+        return balance;
+    }
+    let bps = (percent.max(0.0) * 100.0).round() as u128;
+    balance / 10_000 * bps + balance % 10_000 * bps / 10_000
+}
+
+struct EvmClient {
+    rpc_url: String,
+}
+
+impl EvmClient {
+    async fn call(&self, method: &str, params: serde_json::Value) -> std::result::Result<serde_json::Value, String> {
+        crate::balance::evm_rpc_call(&self.rpc_url, method, params).await
+    }
+
+    async fn quantity(&self, method: &str, params: serde_json::Value) -> std::result::Result<u128, String> {
+        let value = self.call(method, params).await?;
+        crate::evm::parse_quantity(value.as_str().ok_or_else(|| format!("{} returned a non-string", method))?)
+    }
+
+    async fn eth_call(&self, to: &crate::evm::Address, data: &[u8]) -> std::result::Result<Vec<u8>, String> {
+        let params = serde_json::json!([
+            { "to": crate::evm::format_address(to), "data": format!("0x{}", hex::encode(data)) },
+            "latest"
+        ]);
+        let output = self.call("eth_call", params).await?;
+        let hex_out = output.as_str().unwrap_or("0x");
+        hex::decode(hex_out.strip_prefix("0x").unwrap_or(hex_out)).map_err(|e| format!("Invalid eth_call output: {}", e))
+    }
+
+    async fn estimate_gas(&self, from: &crate::evm::Address, to: &crate::evm::Address, value: u128, data: &[u8]) -> std::result::Result<u64, String> {
+        let params = serde_json::json!([{
+            "from": crate::evm::format_address(from),
+            "to": crate::evm::format_address(to),
+            "value": format!("0x{:x}", value),
+            "data": format!("0x{}", hex::encode(data)),
+        }]);
+        Ok(self.quantity("eth_estimateGas", params).await? as u64)
+    }
+
+    /// Sign and broadcast. Returns the transaction hash reported by the node.
+    async fn send(&self, key: &secp256k1::SecretKey, tx: &crate::evm::LegacyTransaction) -> std::result::Result<String, EvmSwapError> {
+        let raw = tx.sign(key)?;
+        let hash = self
+            .call("eth_sendRawTransaction", serde_json::json!([format!("0x{}", hex::encode(raw))]))
+            .await
+            .map_err(|e| evm_swap_error(&e))?;
+        hash.as_str().map(str::to_string).ok_or_else(|| EvmSwapError::Failed("eth_sendRawTransaction returned no hash".to_string()))
+    }
+}
+
+/// Swap through the chain's V2 router (Uniswap on Ethereum, PancakeSwap on BSC).
+/// Sells approve the router first when the allowance is short.
+pub async fn execute_evm_swap(
+    chain: &str,
+    key: &secp256k1::SecretKey,
+    token: &str,
+    side: EvmSwapSide,
+    slippage_bps: u64,
+) -> std::result::Result<EvmSwapExecution, EvmSwapError> {
+    use crate::evm;
+
+    let config = evm::router_config(chain)?;
+    let router = evm::parse_address(config.router)?;
+    let wrapped_native = evm::parse_address(config.wrapped_native)?;
+    let token = evm::parse_address(token)?;
+    let (rpc_url, _) = crate::balance::evm_rpc_urls(chain)?;
+    let client = EvmClient { rpc_url };
+
+    let from = evm::address_from_key(key);
+    let from_hex = evm::format_address(&from);
+    let mut nonce = client.quantity("eth_getTransactionCount", serde_json::json!([from_hex, "pending"])).await? as u64;
+    let gas_price = client.quantity("eth_gasPrice", serde_json::json!([])).await?;
+    let deadline = chrono::Utc::now().timestamp() as u64 + EVM_SWAP_DEADLINE_SECS;
+
+    let (value, data, approved, token_amount) = match side {
+        EvmSwapSide::Buy { value_wei } => {
+            let path = [wrapped_native, token];
+            let quoted = evm::decode_last_amount(&client.eth_call(&router, &evm::encode_get_amounts_out(value_wei, &path)).await?)?;
+            let data = evm::encode_swap_exact_eth_for_tokens(min_amount_out(quoted, slippage_bps), &path, &from, deadline);
+            // Only used to size the position, so a token without decimals() doesn't block the buy
+            let token_amount = match client.eth_call(&token, &evm::encode_decimals()).await.and_then(|out| evm::decode_uint_word(&out, 0)) {
+                Ok(decimals) if decimals <= 36 => Some(quoted as f64 / 10f64.powi(decimals as i32)),
+                _ => None,
+            };
+            (value_wei, data, false, token_amount)
+        }
+        EvmSwapSide::Sell { percent } => {
+            let balance = evm::decode_uint_word(&client.eth_call(&token, &evm::encode_balance_of(&from)).await?, 0)?;
+            let amount_in = evm_sell_amount(balance, percent);
+            if amount_in == 0 {
+                return Err(EvmSwapError::NoBalance);
+            }
+
+            let allowance = evm::decode_uint_word(&client.eth_call(&token, &evm::encode_allowance(&from, &router)).await?, 0)?;
+            let approved = allowance < amount_in;
+            if approved {
+                let approve_data = evm::encode_approve(&router, amount_in);
+                let gas_limit = client
+                    .estimate_gas(&from, &token, 0, &approve_data)
+                    .await
+                    .map_err(|e| evm_swap_error(&e))?;
+                let approve = evm::LegacyTransaction { nonce, gas_price, gas_limit: gas_limit * 12 / 10, to: token, value: 0, data: approve_data, chain_id: config.chain_id };
+                let approve_hash = client.send(key, &approve).await?;
+                tracing::info!("   Router approval sent: {}", approve_hash);
+                nonce += 1;
+            }
+
+            let path = [token, wrapped_native];
+            let quoted = evm::decode_last_amount(&client.eth_call(&router, &evm::encode_get_amounts_out(amount_in, &path)).await?)?;
+            let data = evm::encode_swap_exact_tokens_for_eth(amount_in, min_amount_out(quoted, slippage_bps), &path, &from, deadline);
+            (0, data, approved, None)
+        }
+    };
+
+    // Estimation fails until a fresh approval is mined, so fall back to a fixed limit then
+    let gas_limit = match client.estimate_gas(&from, &router, value, &data).await {
+        Ok(gas) => gas * 12 / 10,
+        Err(e) if e.to_lowercase().contains("insufficient funds") => return Err(evm_swap_error(&e)),
+        Err(_) if approved => DEFAULT_EVM_SWAP_GAS,
+        Err(e) => return Err(evm_swap_error(&e)),
+    };
+
+    let tx = evm::LegacyTransaction { nonce, gas_price, gas_limit, to: router, value, data, chain_id: config.chain_id };
+    tracing::info!("🚀 Sending {} swap from {} (gas {} @ {} wei)", chain, from_hex, gas_limit, gas_price);
+    let tx_hash = client.send(key, &tx).await?;
+    Ok(EvmSwapExecution { tx_hash, token_amount })
+}
+
+// ==================== HELPERS ====================
+
+
+
+pub async fn get_jupiter_quote(
+    client: &reqwest::Client,
+    input_mint: &str,
+    output_mint: &str,
+    amount_lamports: u64,
+    slippage_bps: u64,
+) -> Result<QuoteResponse> {
+    let quote_url = quote_url(input_mint, output_mint, amount_lamports, slippage_bps, PlatformFeeConfig::from_env().as_ref());
+
+    let response = client.get(&quote_url).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(QuoteRejected::from_body(status.as_u16(), &body).into());
+    }
+    let quote: QuoteResponse = response.json().await?;
     
     Ok(quote)
 }
 
+/// errorCodes Jupiter answers with when no route exists between two mints.
+const NO_ROUTE_ERROR_CODES: [&str; 2] = ["COULD_NOT_FIND_ANY_ROUTE", "NO_ROUTES_FOUND"];
+
+/// A quote Jupiter answered with an error status.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Jupiter returned {status}: {message}")]
+pub struct QuoteRejected {
+    pub status: u16,
+    pub code: Option<String>, // Jupiter's errorCode, when the body had one
+    pub message: String,
+}
+
+impl QuoteRejected {
+    /// Read Jupiter's `{"error": ..., "errorCode": ...}` body, keeping the raw text otherwise.
+    pub fn from_body(status: u16, body: &str) -> Self {
+        let json: Option<serde_json::Value> = serde_json::from_str(body).ok();
+        let field = |key: &str| json.as_ref().and_then(|j| j.get(key)).and_then(|v| v.as_str()).map(str::to_string);
+        Self {
+            status,
+            code: field("errorCode"),
+            message: field("error").unwrap_or_else(|| body.trim().to_string()),
+        }
+    }
+
+    pub fn is_missing_route(&self) -> bool {
+        self.code.as_deref().is_some_and(|code| NO_ROUTE_ERROR_CODES.contains(&code))
+    }
+}
+
+/// Only Jupiter's own no-route answer counts. Transport errors, rate limits and bodies
+/// that fail to decode say nothing about the token.
+pub fn is_missing_route(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<QuoteRejected>().is_some_and(QuoteRejected::is_missing_route)
+}
+
 pub fn get_jupiter_client() -> Result<reqwest::Client> {
     let mut headers = reqwest::header::HeaderMap::new();
     // Read API Key from Environment
@@ -211,6 +1815,580 @@ pub fn get_jupiter_client() -> Result<reqwest::Client> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_sell_preview_usd_matches_injected_sol_price() {
+        let quote = QuoteResponse {
+            inputMint: "Token".to_string(),
+            inAmount: "1000000".to_string(),
+            outputMint: WSOL_MINT.to_string(),
+            outAmount: "1200100000".to_string(), // 1.2001 SOL
+            otherAmountThreshold: "1140100000".to_string(),
+            swapMode: "ExactIn".to_string(),
+            slippageBps: 500,
+            platformFee: Some(PlatformFee { amount: "50000".to_string(), feeBps: 5 }),
+            priceImpactPct: "0.012".to_string(),
+            routePlan: vec![],
+            contextSlot: None,
+            timeTaken: None,
+        };
+
+        let preview = build_sell_preview(&quote, SellOutput::Sol, 100_000, 150.0).unwrap();
+        assert_eq!(preview.denomination, "SOL");
+        assert!((preview.out_amount_native - 1.2).abs() < 1e-12);
+        assert!((preview.min_out_amount_native - 1.14).abs() < 1e-12);
+        assert_eq!(preview.out_amount_usd, preview.out_amount_native * 150.0);
+        assert_eq!(preview.min_out_amount_usd, preview.min_out_amount_native * 150.0);
+        assert!((preview.estimated_fee_native - 0.00015).abs() < 1e-12);
+        assert!((preview.price_impact_pct - 1.2).abs() < 1e-9);
+    }
+
+    fn swap_step(label: &str, input: &str, output: &str, percent: u32, fee: &str) -> RoutePlan {
+        RoutePlan {
+            swapInfo: SwapInfo {
+                ammKey: format!("{}Amm", label),
+                label: label.to_string(),
+                inputMint: input.to_string(),
+                outputMint: output.to_string(),
+                inAmount: "0".to_string(),
+                outAmount: "0".to_string(),
+                feeAmount: fee.to_string(),
+                feeMint: input.to_string(),
+            },
+            percent,
+        }
+    }
+
+    #[test]
+    fn test_sell_route_summary() {
+        // Token -> SOL via Raydium, then SOL -> USDC split across two pools
+        let quote = QuoteResponse {
+            inputMint: "Token".to_string(),
+            inAmount: "1000000".to_string(),
+            outputMint: USDC_MINT.to_string(),
+            outAmount: "180000000".to_string(), // 180 USDC
+            otherAmountThreshold: "171000000".to_string(),
+            swapMode: "ExactIn".to_string(),
+            slippageBps: 500,
+            platformFee: Some(PlatformFee { amount: "90000".to_string(), feeBps: 5 }),
+            priceImpactPct: "0.004".to_string(),
+            routePlan: vec![
+                swap_step("Raydium", "Token", WSOL_MINT, 100, "2500"),
+                swap_step("Whirlpool", WSOL_MINT, USDC_MINT, 70, "3000"),
+                swap_step("Meteora DLMM", WSOL_MINT, USDC_MINT, 30, "1200"),
+            ],
+            contextSlot: None,
+            timeTaken: None,
+        };
+
+        let summary = summarize_route(&quote);
+        assert_eq!(summary.route, "Raydium -> Whirlpool -> Meteora DLMM");
+        assert_eq!(summary.hops.len(), 3);
+        assert_eq!(summary.hops[0].output_mint, WSOL_MINT);
+        assert_eq!(summary.hops[1].percent, 70);
+        assert_eq!(summary.hops[2].fee_amount, "1200");
+        assert_eq!(summary.platform_fee_amount.as_deref(), Some("90000"));
+        assert_eq!(summary.platform_fee_bps, Some(5));
+
+        // Stable outputs are priced at $1 and keep the SOL network fee separate
+        let preview = build_sell_preview(&quote, SellOutput::Usdc, 100_000, 150.0).unwrap();
+        assert_eq!(preview.denomination, "USDC");
+        assert!((preview.out_amount_native - 180.0).abs() < 1e-9);
+        assert!((preview.min_out_amount_usd - 171.0).abs() < 1e-9);
+        assert!((preview.estimated_fee_native - (0.0001 + 0.09 / 150.0)).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_fair_queue_dispatches_in_arrival_order() {
+        let queue = FairExecutionQueue::new(1);
+        let dispatched = Arc::new(Mutex::new(Vec::new()));
+
+        // Block the lane so every submission has to queue up
+        let gate = queue.acquire("launch").await;
+
+        let mut handles = Vec::new();
+        for id in 0..5 {
+            let queue = queue.clone();
+            let dispatched = dispatched.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = queue.acquire("launch").await;
+                dispatched.lock().await.push(id);
+                tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            }));
+            // Let this submission reach the queue before the next one arrives
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        drop(gate);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(*dispatched.lock().await, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_decimals_cache_skips_second_lookup() {
+        let cache = DecimalsCache::new(10);
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let fetch = || async {
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(6)
+        };
+
+        assert_eq!(cache.get_or_fetch("Mint", fetch).await, Ok(6));
+        assert_eq!(cache.get_or_fetch("Mint", fetch).await, Ok(6));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_usdc_sell_output() {
+        let output = SellOutput::from_mint(Some(USDC_MINT)).unwrap();
+        assert_eq!(output, SellOutput::Usdc);
+        assert_eq!(output.mint(), USDC_MINT);
+        assert_eq!(output.denomination(), "USDC");
+        // $30 profit settles as 30 USDC regardless of SOL price
+        assert_eq!(output.pnl_from_usd(30.0, 150.0), 30.0);
+
+        let default = SellOutput::from_mint(None).unwrap();
+        assert_eq!(default.mint(), WSOL_MINT);
+        assert!((default.pnl_from_usd(30.0, 150.0) - 0.2).abs() < 1e-12);
+
+        assert!(SellOutput::from_mint(Some("NotAStableMint111")).is_err());
+    }
+
+    #[test]
+    fn test_swap_limits() {
+        let limits = SwapLimits { max_price_impact_pct: Some(5.0), max_slippage_pct: Some(10.0), min_out_floor: None };
+
+        assert!(limits.check_price_impact(4.9).is_ok());
+        assert_eq!(
+            limits.check_price_impact(7.5),
+            Err(SwapGuardError::PriceImpactTooHigh { observed: 7.5, threshold: 5.0 })
+        );
+        assert!(limits.check_slippage(1000).is_ok());
+        assert!(limits.check_slippage(1500).is_err());
+
+        // No limits configured means nothing is rejected
+        assert!(SwapLimits::default().check_price_impact(99.0).is_ok());
+    }
+
+    #[test]
+    fn test_buy_preview_scales_by_mint_decimals() {
+        let mut quote = quote_with("1234500000", "1200000000", "0.025");
+        quote.platformFee = Some(PlatformFee { amount: "5000000".to_string(), feeBps: 40 });
+
+        let preview = build_buy_preview(&quote, 6, 100_000).unwrap();
+        assert_eq!(preview.amount_in_sol, 1.0);
+        assert!((preview.out_amount - 1234.5).abs() < 1e-9);
+        assert!((preview.min_out_amount - 1200.0).abs() < 1e-9);
+        assert!((preview.platform_fee_amount - 5.0).abs() < 1e-9);
+        assert_eq!(preview.platform_fee_bps, 40);
+        assert!((preview.network_fee_sol - 0.0001).abs() < 1e-12);
+        assert!((preview.price_impact_pct - 2.5).abs() < 1e-9);
+    }
+
+    fn quote_with(out: &str, min_out: &str, impact: &str) -> QuoteResponse {
+        QuoteResponse {
+            inputMint: WSOL_MINT.to_string(),
+            inAmount: "1000000000".to_string(),
+            outputMint: "MemeToken".to_string(),
+            outAmount: out.to_string(),
+            otherAmountThreshold: min_out.to_string(),
+            swapMode: "ExactIn".to_string(),
+            slippageBps: 100,
+            platformFee: None,
+            priceImpactPct: impact.to_string(),
+            routePlan: vec![],
+            contextSlot: None,
+            timeTaken: None,
+        }
+    }
+
+    #[test]
+    fn test_rejects_catastrophic_price_impact() {
+        let limits = SwapLimits::default().with_request(Some(15.0), None);
+        let quote = quote_with("5000000", "4950000", "0.4"); // 40% impact
+
+        assert_eq!(
+            limits.check_quote(&quote),
+            Err(SwapGuardError::PriceImpactTooHigh { observed: 40.0, threshold: 15.0 })
+        );
+        assert!(limits.check_quote(&quote_with("5000000", "4950000", "0.02")).is_ok());
+    }
+
+    #[test]
+    fn test_request_limits_tighten_config() {
+        let config = SwapLimits { max_price_impact_pct: Some(10.0), max_slippage_pct: None, min_out_floor: None };
+        assert_eq!(config.clone().with_request(Some(25.0), None).max_price_impact_pct, Some(10.0));
+        assert_eq!(config.clone().with_request(Some(3.0), None).max_price_impact_pct, Some(3.0));
+        assert_eq!(SwapLimits::default().with_request(None, None).max_price_impact_pct, None);
+    }
+
+    #[test]
+    fn test_min_out_floor() {
+        let limits = SwapLimits::default().with_request(None, Some(4_960_000));
+        assert_eq!(
+            limits.check_quote(&quote_with("5000000", "4950000", "0.01")),
+            Err(SwapGuardError::MinimumOutputTooLow { guaranteed: 4_950_000, floor: 4_960_000 })
+        );
+        assert!(limits.check_quote(&quote_with("5000000", "4970000", "0.01")).is_ok());
+        // A threshold above the quoted output is malformed
+        assert!(matches!(
+            SwapLimits::default().check_quote(&quote_with("100", "200", "0.01")),
+            Err(SwapGuardError::InvalidQuote(_))
+        ));
+    }
+
+    #[test]
+    fn test_retryable_send_errors() {
+        assert!(is_retryable_send_error("RPC response error -32002: Transaction simulation failed: Blockhash not found"));
+        assert!(is_retryable_send_error("RPC response error -32005: Node is behind by 42 slots"));
+        assert!(is_retryable_send_error("Transaction 5xy expired: block height exceeded"));
+        assert!(!is_retryable_send_error("Transaction 5xy failed: InstructionError(2, Custom(6001))"));
+    }
+
+    #[test]
+    fn test_slippage_escalation() {
+        assert!(is_slippage_error("Transaction 5xy failed: InstructionError(2, Custom(6001))"));
+        assert!(is_slippage_error("custom program error: 0x1771"));
+        assert!(!is_slippage_error("Transaction 5xy failed: InstructionError(2, Custom(1))"));
+
+        // Opt-in only
+        assert_eq!(escalate_slippage(100, None), None);
+        // Doubles, then clamps to the ceiling, then stops
+        assert_eq!(escalate_slippage(100, Some(500)), Some(200));
+        assert_eq!(escalate_slippage(400, Some(500)), Some(500));
+        assert_eq!(escalate_slippage(500, Some(500)), None);
+        // A ceiling below the starting slippage never loosens anything
+        assert_eq!(escalate_slippage(500, Some(300)), None);
+        assert_eq!(escalate_slippage(8000, Some(20_000)), Some(10_000));
+    }
+
+    #[test]
+    fn test_output_received_from_balances() {
+        let owner = "Owner111";
+        let entry = |owner: &str, mint: &str, amount: u64| TokenBalanceEntry { owner: owner.to_string(), mint: mint.to_string(), amount };
+
+        // Sell to SOL: 1.2 SOL arrives, 5000 lamports fee
+        assert_eq!(output_received(WSOL_MINT, owner, (1_000_000_000, 2_199_995_000), 5_000, &[], &[]), Some(1_200_000_000));
+
+        // Sell to USDC: only the owner's USDC accounts count
+        let pre = vec![entry(owner, USDC_MINT, 10_000_000), entry("Pool", USDC_MINT, 900_000_000)];
+        let post = vec![entry(owner, USDC_MINT, 190_000_000), entry("Pool", USDC_MINT, 720_000_000)];
+        assert_eq!(output_received(USDC_MINT, owner, (0, 0), 5_000, &pre, &post), Some(180_000_000));
+
+        // A first-time buy has no pre-balance entry
+        assert_eq!(output_received("Meme", owner, (0, 0), 0, &[], &[entry(owner, "Meme", 42)]), Some(42));
+    }
+
+    #[test]
+    fn test_execution_profiles_per_automation() {
+        let config: HashMap<&str, &str> = [
+            ("EXEC_GRID_SLIPPAGE_BPS", "75"),
+            ("EXEC_GRID_PRIORITY", "high"),
+            ("EXEC_DCA_PRIORITY", "low"),
+            ("EXEC_LIMIT_SLIPPAGE_BPS", "not-a-number"),
+        ].into_iter().collect();
+        let lookup = |key: &str| config.get(key).map(|v| v.to_string());
+
+        let grid = ExecutionProfile::resolve(AutomationKind::Grid, lookup);
+        assert_eq!(grid, ExecutionProfile { slippage_bps: 75, priority: PriorityTier::High, private_tx: false, max_slippage_bps: None });
+
+        let dca = ExecutionProfile::resolve(AutomationKind::Dca, lookup);
+        assert_eq!(dca, ExecutionProfile { slippage_bps: 200, priority: PriorityTier::Low, private_tx: false, max_slippage_bps: None });
+
+        // Bad values fall back to the type's defaults; limit fills stay tighter than manual snipes
+        let limit = ExecutionProfile::resolve(AutomationKind::LimitOrder, lookup);
+        let manual = ExecutionProfile::resolve(AutomationKind::Manual, lookup);
+        assert_eq!(limit.slippage_bps, 50);
+        assert!(limit.slippage_bps < manual.slippage_bps);
+        assert_eq!(manual.with_slippage(1000).slippage_bps, 1000);
+    }
+
+    #[test]
+    fn test_priority_tier_swap_field() {
+        assert_eq!(PriorityTier::Auto.to_jupiter(5_000_000), serde_json::json!("auto"));
+        assert_eq!(
+            PriorityTier::VeryHigh.to_jupiter(5_000_000),
+            serde_json::json!({ "priorityLevelWithMaxLamports": { "priorityLevel": "veryHigh", "maxLamports": 5_000_000 } })
+        );
+        assert_eq!(PriorityTier::parse("VeryHigh"), Some(PriorityTier::VeryHigh));
+        assert_eq!(PriorityTier::parse("urgent"), None);
+    }
+
+    #[test]
+    fn test_detects_compute_exceeded() {
+        let budget = TransactionError::InstructionError(2, InstructionError::ComputationalBudgetExceeded);
+        assert!(is_compute_exceeded(&budget, &[]));
+
+        let generic = TransactionError::InstructionError(3, InstructionError::ProgramFailedToComplete);
+        let logs = vec![
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 consumed 199850 of 200000 compute units".to_string(),
+            "Program JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4 failed: exceeded CUs meter at BPF instruction".to_string(),
+        ];
+        assert!(is_compute_exceeded(&generic, &logs));
+
+        // Slippage and other program errors are not retried
+        assert!(!is_compute_exceeded(&TransactionError::InstructionError(3, InstructionError::Custom(6001)), &[]));
+        assert!(!is_compute_exceeded(&TransactionError::InsufficientFundsForFee, &[]));
+    }
+
+    #[test]
+    fn test_compute_bump_stays_within_cap() {
+        let policy = ComputeBumpPolicy { max_retries: 2, cap: 1_000_000 };
+        assert_eq!(policy.next_limit(200_000), Some(400_000));
+        assert_eq!(policy.next_limit(600_000), Some(1_000_000));
+        assert_eq!(policy.next_limit(1_000_000), None);
+        assert_eq!(policy.next_limit(1_200_000), None);
+    }
+
+    fn leg(accounts: usize, compute_units: u32) -> SwapLeg {
+        let program_id = Pubkey::new_unique();
+        let metas = (0..accounts).map(|_| AccountMeta::new(Pubkey::new_unique(), false)).collect();
+        SwapLeg {
+            instructions: vec![Instruction { program_id, accounts: metas, data: vec![1; 16] }],
+            compute_units,
+            compute_unit_price: 1_000,
+            lookup_tables: vec![],
+        }
+    }
+
+    #[test]
+    fn test_pack_swap_legs_splits_on_size_and_compute() {
+        let payer = Pubkey::new_unique();
+
+        let small = vec![leg(3, 200_000), leg(3, 200_000), leg(3, 200_000)];
+        assert_eq!(pack_swap_legs(&payer, &small, &[]).unwrap(), vec![vec![0, 1, 2]]);
+
+        // ~20 accounts each: two legs overflow the packet, so every leg rides alone
+        let wide = vec![leg(20, 200_000), leg(20, 200_000), leg(20, 200_000)];
+        assert_eq!(pack_swap_legs(&payer, &wide, &[]).unwrap(), vec![vec![0], vec![1], vec![2]]);
+
+        let heavy = vec![leg(2, 600_000), leg(2, 600_000), leg(2, 600_000)];
+        assert_eq!(pack_swap_legs(&payer, &heavy, &[]).unwrap(), vec![vec![0, 1], vec![2]]);
+
+        assert!(pack_swap_legs(&payer, &[leg(50, 200_000)], &[]).is_err());
+    }
+
+    #[test]
+    fn test_lookup_tables_shrink_bundles() {
+        let payer = Pubkey::new_unique();
+        let legs = vec![leg(20, 200_000), leg(20, 200_000)];
+        let mut table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses: vec![] };
+        for l in &legs {
+            table.addresses.extend(l.instructions[0].accounts.iter().map(|a| a.pubkey));
+        }
+        let legs: Vec<SwapLeg> = legs.into_iter().map(|mut l| { l.lookup_tables = vec![table.key]; l }).collect();
+        assert_eq!(pack_swap_legs(&payer, &legs, &[table]).unwrap(), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_swap_leg_takes_compute_budget_from_jupiter() {
+        let limit = STANDARD.encode(ComputeBudgetInstruction::set_compute_unit_limit(350_000).data);
+        let price = STANDARD.encode(ComputeBudgetInstruction::set_compute_unit_price(25_000).data);
+        let program = compute_budget::id().to_string();
+        let swap_program = Pubkey::new_unique().to_string();
+        let res: SwapInstructionsResponse = serde_json::from_value(serde_json::json!({
+            "computeBudgetInstructions": [
+                { "programId": program, "accounts": [], "data": limit },
+                { "programId": program, "accounts": [], "data": price },
+            ],
+            "setupInstructions": [],
+            "swapInstruction": { "programId": swap_program, "accounts": [{ "pubkey": swap_program, "isSigner": false, "isWritable": true }], "data": "AQI=" },
+            "cleanupInstruction": null,
+            "addressLookupTableAddresses": [],
+        })).unwrap();
+
+        let leg = SwapLeg::from_response(res).unwrap();
+        assert_eq!((leg.compute_units, leg.compute_unit_price), (350_000, 25_000));
+        assert_eq!(leg.instructions.len(), 1);
+        assert_eq!(leg.instructions[0].data, vec![1, 2]);
+
+        // One budget for the group: summed limit, highest price
+        let ixs = bundle_instructions(&[&leg, &leg]);
+        assert_eq!(ixs[0], ComputeBudgetInstruction::set_compute_unit_limit(700_000));
+        assert_eq!(ixs[1], ComputeBudgetInstruction::set_compute_unit_price(25_000));
+        assert_eq!(ixs.len(), 4);
+    }
+
+    /// A Jupiter-style base64 swap transaction: unsigned, `signers` as its required signers.
+    fn jupiter_swap_base64(signers: &[Pubkey], legacy: bool) -> String {
+        let blockhash = solana_sdk::hash::Hash::new_unique();
+        let accounts = signers.iter().map(|key| AccountMeta::new(*key, true)).collect();
+        let ix = Instruction::new_with_bytes(Pubkey::new_unique(), &[1, 2, 3], accounts);
+        let message = if legacy {
+            VersionedMessage::Legacy(solana_sdk::message::Message::new_with_blockhash(&[ix], Some(&signers[0]), &blockhash))
+        } else {
+            VersionedMessage::V0(v0::Message::try_compile(&signers[0], &[ix], &[], blockhash).unwrap())
+        };
+        let tx = VersionedTransaction {
+            signatures: vec![solana_sdk::signature::Signature::default(); signers.len()],
+            message,
+        };
+        STANDARD.encode(bincode::serialize(&tx).unwrap())
+    }
+
+    #[test]
+    fn test_sign_fills_our_slot_only() {
+        let fee_payer = solana_sdk::signature::Keypair::new();
+        let user = solana_sdk::signature::Keypair::new();
+
+        for legacy in [false, true] {
+            let encoded = jupiter_swap_base64(&[fee_payer.pubkey(), user.pubkey()], legacy);
+            let mut tx = decode_swap_transaction(&encoded).unwrap();
+            assert_eq!(matches!(tx.message, VersionedMessage::Legacy(_)), legacy);
+
+            // The co-signer signs first; our signature must not clobber theirs
+            sign_versioned(&mut tx, &fee_payer).unwrap();
+            sign_versioned(&mut tx, &user).unwrap();
+            assert_eq!(tx.signatures.len(), 2);
+            assert!(tx.verify_with_results().iter().all(|ok| *ok));
+            assert_eq!(tx.signatures[1], user.sign_message(&tx.message.serialize()));
+        }
+    }
+
+    #[test]
+    fn test_sign_rejects_non_signer() {
+        let encoded = jupiter_swap_base64(&[Pubkey::new_unique()], false);
+        let mut tx = decode_swap_transaction(&encoded).unwrap();
+        assert!(sign_versioned(&mut tx, &solana_sdk::signature::Keypair::new()).is_err());
+        assert!(decode_swap_transaction("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_jito_needs_url_and_tip_account() {
+        let tip = Pubkey::new_unique().to_string();
+        let env = |pairs: Vec<(&'static str, String)>| move |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone());
+
+        assert_eq!(JitoConfig::resolve(env(vec![("JITO_TIP_ACCOUNT", tip.clone())])), None);
+        assert_eq!(JitoConfig::resolve(env(vec![("JITO_BLOCK_ENGINE_URL", "https://jito.example".to_string())])), None);
+
+        let config = JitoConfig::resolve(env(vec![
+            ("JITO_BLOCK_ENGINE_URL", "https://jito.example/".to_string()),
+            ("JITO_TIP_ACCOUNT", tip.clone()),
+        ])).unwrap();
+        assert_eq!(config.block_engine_url, "https://jito.example");
+        assert_eq!(config.tip_lamports, 10_000);
+    }
+
+    #[test]
+    fn test_platform_fee_is_opt_in() {
+        let account = Pubkey::new_unique();
+        let env = |pairs: Vec<(&'static str, String)>| move |key: &str| pairs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone());
+
+        // Unset (the default) or half-configured: no fee, and the quote URL is unchanged
+        assert_eq!(PlatformFeeConfig::resolve(env(vec![])), None);
+        assert_eq!(PlatformFeeConfig::resolve(env(vec![("PLATFORM_FEE_BPS", "50".to_string())])), None);
+        assert_eq!(PlatformFeeConfig::resolve(env(vec![("PLATFORM_FEE_BPS", "0".to_string()), ("PLATFORM_FEE_ACCOUNT", account.to_string())])), None);
+        assert!(!quote_url(WSOL_MINT, USDC_MINT, 1_000, 50, None).contains("platformFeeBps"));
+
+        let fee = PlatformFeeConfig::resolve(env(vec![
+            ("PLATFORM_FEE_BPS", "25".to_string()),
+            ("PLATFORM_FEE_ACCOUNT", account.to_string()),
+        ])).unwrap();
+        assert_eq!(fee, PlatformFeeConfig { fee_bps: 25, fee_wallet: account });
+        assert!(quote_url(WSOL_MINT, USDC_MINT, 1_000, 50, Some(&fee)).ends_with("&slippageBps=50&platformFeeBps=25"));
+    }
+
+    #[test]
+    fn test_platform_fee_only_on_sol_and_stable_outputs() {
+        let wallet = Pubkey::new_unique();
+        let fee = PlatformFeeConfig { fee_bps: 25, fee_wallet: wallet };
+        let memecoin = Pubkey::new_unique().to_string();
+
+        // Paid into the fee wallet's account for the output mint
+        let usdc = Pubkey::from_str(USDC_MINT).unwrap();
+        assert_eq!(fee.fee_account_for(USDC_MINT), Some(crate::wallet::associated_token_address(&wallet, &usdc, &spl_token::id())));
+        assert_ne!(fee.fee_account_for(WSOL_MINT), fee.fee_account_for(USDC_MINT));
+
+        // Buys output a memecoin: no fee requested
+        assert_eq!(fee.fee_account_for(&memecoin), None);
+        assert!(!quote_url(WSOL_MINT, &memecoin, 1_000, 50, Some(&fee)).contains("platformFeeBps"));
+        assert!(quote_url(&memecoin, WSOL_MINT, 1_000, 50, Some(&fee)).contains("platformFeeBps=25"));
+    }
+
+    #[test]
+    fn test_jito_bundle_carries_swap_then_tip() {
+        let signer = solana_sdk::signature::Keypair::new();
+        let config = JitoConfig { block_engine_url: String::new(), tip_account: Pubkey::new_unique(), tip_lamports: 5_000 };
+        let blockhash = solana_sdk::hash::Hash::new_unique();
+        let swap = VersionedTransaction::from(solana_sdk::transaction::Transaction::new_signed_with_payer(
+            &[ComputeBudgetInstruction::set_compute_unit_limit(200_000)], Some(&signer.pubkey()), &[&signer], blockhash,
+        ));
+
+        let tip = jito_tip_transaction(&signer, &config, &swap);
+        assert_eq!(tip.message.recent_blockhash(), &blockhash);
+        assert!(tip.message.static_account_keys().contains(&config.tip_account));
+
+        let body = jito_bundle_request(&[swap, tip]).unwrap();
+        assert_eq!(body["method"], "sendBundle");
+        assert_eq!(body["params"][0].as_array().unwrap().len(), 2);
+
+        assert_eq!(parse_jito_response(&serde_json::json!({ "result": "abc" })), Ok("abc".to_string()));
+        assert_eq!(parse_jito_response(&serde_json::json!({ "error": { "message": "bundle dropped" } })), Err("bundle dropped".to_string()));
+    }
+
+    #[test]
+    fn test_set_compute_unit_limit_rewrites_instruction() {
+        use solana_sdk::message::Message;
+
+        let payer = Pubkey::new_unique();
+        let ixs = vec![
+            ComputeBudgetInstruction::set_compute_unit_price(1_000),
+            ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+        ];
+        let mut message = VersionedMessage::Legacy(Message::new(&ixs, Some(&payer)));
+
+        assert_eq!(compute_unit_limit(&message), Some(200_000));
+        assert_eq!(set_compute_unit_limit(&mut message, 400_000), Some(200_000));
+        assert_eq!(compute_unit_limit(&message), Some(400_000));
+        // The price instruction is untouched
+        assert_eq!(message.instructions()[0].data, ComputeBudgetInstruction::set_compute_unit_price(1_000).data);
+
+        let mut no_budget = VersionedMessage::Legacy(Message::new(&[], Some(&payer)));
+        assert_eq!(set_compute_unit_limit(&mut no_budget, 400_000), None);
+    }
+
+    #[test]
+    fn test_evm_insufficient_funds_error() {
+        let raw = "RPC error: insufficient funds for gas * price + value: balance 100, tx cost 2000";
+        assert_eq!(
+            evm_error_message(raw),
+            "Insufficient funds for gas: wallet balance can't cover the swap value plus gas fees"
+        );
+        assert!(evm_error_message("RPC error: execution reverted: UniswapV2Router: INSUFFICIENT_OUTPUT_AMOUNT").contains("slippage"));
+        assert_eq!(evm_error_message("RPC error: nonce too low"), "Swap failed: RPC error: nonce too low");
+
+        assert!(matches!(evm_swap_error(raw), EvmSwapError::InsufficientFunds(_)));
+        assert_eq!(evm_swap_error("RPC error: nonce too low"), EvmSwapError::Failed("Swap failed: RPC error: nonce too low".to_string()));
+    }
+
+    #[test]
+    fn test_evm_amounts() {
+        assert_eq!(min_amount_out(1_000_000, 100), 990_000);
+        assert_eq!(min_amount_out(u128::MAX, 0), u128::MAX);
+        assert_eq!(evm_sell_amount(1_000, 25.0), 250);
+        assert_eq!(evm_sell_amount(1_000, 100.0), 1_000);
+        assert_eq!(evm_sell_amount(u128::MAX, 100.0), u128::MAX);
+    }
+
+    #[test]
+    fn test_only_no_route_code_counts_as_missing_route() {
+        let no_route = QuoteRejected::from_body(400, r#"{"error":"Could not find any route","errorCode":"COULD_NOT_FIND_ANY_ROUTE"}"#);
+        assert_eq!(no_route.code.as_deref(), Some("COULD_NOT_FIND_ANY_ROUTE"));
+        assert_eq!(no_route.message, "Could not find any route");
+        assert!(is_missing_route(&anyhow::Error::new(no_route)));
+
+        // Rate limits and gateway pages are not a verdict on the token
+        let rate_limited = QuoteRejected::from_body(429, r#"{"error":"Too many requests","errorCode":"RATE_LIMITED"}"#);
+        assert!(!is_missing_route(&anyhow::Error::new(rate_limited)));
+        let gateway = QuoteRejected::from_body(502, "<html>Bad Gateway</html>");
+        assert_eq!(gateway.code, None);
+        assert_eq!(gateway.message, "<html>Bad Gateway</html>");
+        assert!(!is_missing_route(&anyhow::Error::new(gateway)));
+        assert!(!is_missing_route(&anyhow::anyhow!("connection refused")));
+    }
+
     #[tokio::test]
     async fn test_jupiter_quote() {
         // SOL (So11111111111111111111111111111111111111112) -> USDC (EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v)
@@ -1,5 +1,16 @@
 // Gas Price Monitoring Module - Production Ready
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
 
 #[derive(Debug, Serialize, Clone)]
 pub struct GasPrice {
@@ -18,7 +29,178 @@ pub struct GasPriceResponse {
     pub error: Option<String>,
 }
 
+// ==================== CACHE ====================
+
+/// Gas prices are cached per chain for this long to stay under API rate limits.
+const GAS_CACHE_TTL: Duration = Duration::from_secs(15);
+
+#[derive(Default)]
+struct GasCache {
+    entries: HashMap<String, (GasPrice, Instant)>,
+}
+
+impl GasCache {
+    fn fresh(&self, chain: &str, now: Instant, ttl: Duration) -> Option<GasPrice> {
+        match self.entries.get(chain) {
+            Some((price, fetched_at)) if now.duration_since(*fetched_at) < ttl => Some(price.clone()),
+            _ => None,
+        }
+    }
+
+    fn store(&mut self, chain: &str, price: GasPrice, now: Instant) {
+        self.entries.insert(chain.to_string(), (price, now));
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref GAS_CACHE: Mutex<GasCache> = Mutex::new(GasCache::default());
+}
+
+/// Current gas price tiers for `chain`, cached for 15 seconds.
+/// EVM tiers are gas prices in gwei. Solana tiers are SOL per transaction
+/// (base fee plus priority fee for a typical swap).
 pub async fn get_gas_price(chain: &str) -> Result<GasPrice, String> {
+    let mut cache = GAS_CACHE.lock().await;
+    if let Some(price) = cache.fresh(chain, Instant::now(), GAS_CACHE_TTL) {
+        return Ok(price);
+    }
+    let price = fetch_gas_price_live(chain).await?;
+    cache.store(chain, price.clone(), Instant::now());
+    Ok(price)
+}
+
+// ==================== SOLANA PRIORITY FEES ====================
+
+const SOLANA_BASE_FEE_LAMPORTS: u64 = 5_000;
+/// Compute units a Jupiter swap typically uses, for turning per-CU priority fees into a per-tx cost.
+const SOLANA_SWAP_COMPUTE_UNITS: u64 = 200_000;
+
+/// Slow/standard/fast/fastest as the 25th/50th/75th/95th percentile of recent
+/// priority fees (micro-lamports per CU), converted to SOL per transaction.
+pub fn solana_fee_tiers(recent_fees: &[u64]) -> [f64; 4] {
+    let mut fees = recent_fees.to_vec();
+    fees.sort_unstable();
+    [25, 50, 75, 95].map(|p| {
+        let priority_lamports = percentile(&fees, p).unwrap_or(0) * SOLANA_SWAP_COMPUTE_UNITS / 1_000_000;
+        (SOLANA_BASE_FEE_LAMPORTS + priority_lamports) as f64 / 1e9
+    })
+}
+
+/// `p`th percentile of already-sorted samples.
+fn percentile(sorted: &[u64], p: u8) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    Some(sorted[((sorted.len() - 1) * p.min(100) as usize) / 100])
+}
+
+/// Recent priority fees (micro-lamports per CU). `accounts` narrows the sample to
+/// transactions that wrote to them. Empty = network-wide.
+async fn fetch_solana_priority_fees_for(accounts: &[Pubkey]) -> Result<Vec<u64>, String> {
+    use solana_client::nonblocking::rpc_client::RpcClient;
+
+    let rpc_url = std::env::var("SOLANA_RPC")
+        .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
+    let fees = RpcClient::new(rpc_url)
+        .get_recent_prioritization_fees(accounts)
+        .await
+        .map_err(|e| format!("getRecentPrioritizationFees failed: {}", e))?;
+    Ok(fees.into_iter().map(|f| f.prioritization_fee).collect())
+}
+
+async fn fetch_solana_priority_fees() -> Result<Vec<u64>, String> {
+    fetch_solana_priority_fees_for(&[]).await
+}
+
+// ==================== SWAP PRIORITY FEE ====================
+
+/// RPC accepts at most this many accounts for getRecentPrioritizationFees.
+const MAX_FEE_ACCOUNTS: usize = 128;
+const PRIORITY_FEE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// `PRIORITY_FEE_PERCENTILE`: percentile of recent fees paid by swaps with the `auto` tier.
+pub fn priority_fee_percentile() -> u8 {
+    std::env::var("PRIORITY_FEE_PERCENTILE")
+        .ok()
+        .and_then(|v| v.parse::<u8>().ok())
+        .filter(|p| *p <= 100)
+        .unwrap_or(75)
+}
+
+/// Total priority fee in lamports for a swap: the `percentile` of per-CU samples
+/// times the typical swap's compute units, capped at `cap_lamports`.
+pub fn priority_fee_lamports(samples: &[u64], percentile_rank: u8, cap_lamports: u64) -> Option<u64> {
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let per_cu = percentile(&sorted, percentile_rank)?;
+    let total = (per_cu as u128 * SOLANA_SWAP_COMPUTE_UNITS as u128 / 1_000_000) as u64;
+    Some(total.min(cap_lamports))
+}
+
+/// Helius reports fixed levels (25th/50th/75th/95th percentile). Picks the nearest.
+pub fn helius_level(percentile_rank: u8) -> &'static str {
+    match percentile_rank {
+        0..=37 => "low",
+        38..=62 => "medium",
+        63..=85 => "high",
+        _ => "veryHigh",
+    }
+}
+
+/// Per-CU fee for `level` from a getPriorityFeeEstimate response.
+pub fn parse_helius_estimate(response: &serde_json::Value, level: &str) -> Option<u64> {
+    let fee = response["result"]["priorityFeeLevels"][level].as_f64()?;
+    (fee.is_finite() && fee >= 0.0).then(|| fee.ceil() as u64)
+}
+
+async fn fetch_helius_fee(api_key: &str, accounts: &[String], percentile_rank: u8) -> Result<u64, String> {
+    let body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": "priority-fee",
+        "method": "getPriorityFeeEstimate",
+        "params": [{ "accountKeys": accounts, "options": { "includeAllPriorityFeeLevels": true } }],
+    });
+    let base_url = std::env::var("HELIUS_PRIORITY_FEE_URL")
+        .unwrap_or_else(|_| "https://mainnet.helius-rpc.com".to_string());
+    // Errors carry the request URL, and with it the API key: strip it before they get logged
+    let response: serde_json::Value = reqwest::Client::new()
+        .post(format!("{}/?api-key={}", base_url.trim_end_matches('/'), api_key))
+        .json(&body)
+        .timeout(PRIORITY_FEE_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Helius request failed: {}", e.without_url()))?
+        .json()
+        .await
+        .map_err(|e| format!("Helius response unreadable: {}", e.without_url()))?;
+    parse_helius_estimate(&response, helius_level(percentile_rank))
+        .ok_or_else(|| format!("Helius returned no estimate: {}", response))
+}
+
+/// Priority fee in lamports for a swap writing to `writable_accounts`. Uses Helius when
+/// `HELIUS_API_KEY` is set, otherwise the RPC's recent fees for those accounts.
+pub async fn get_solana_priority_fee(writable_accounts: &[String], percentile_rank: u8, cap_lamports: u64) -> Result<u64, String> {
+    if let Ok(api_key) = std::env::var("HELIUS_API_KEY") {
+        if !api_key.is_empty() {
+            let per_cu = fetch_helius_fee(&api_key, writable_accounts, percentile_rank).await?;
+            return priority_fee_lamports(&[per_cu], 100, cap_lamports).ok_or_else(|| "No fee estimate".to_string());
+        }
+    }
+
+    let accounts: Vec<Pubkey> = writable_accounts.iter()
+        .filter_map(|a| Pubkey::from_str(a).ok())
+        .take(MAX_FEE_ACCOUNTS)
+        .collect();
+    let samples = tokio::time::timeout(PRIORITY_FEE_TIMEOUT, fetch_solana_priority_fees_for(&accounts))
+        .await
+        .map_err(|_| "Priority fee lookup timed out".to_string())??;
+    priority_fee_lamports(&samples, percentile_rank, cap_lamports)
+        .ok_or_else(|| "No recent priority fees for the route's accounts".to_string())
+}
+
+// ==================== LIVE PRICES ====================
+
+async fn fetch_gas_price_live(chain: &str) -> Result<GasPrice, String> {
     use std::time::{SystemTime, UNIX_EPOCH};
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -27,16 +209,31 @@ pub async fn get_gas_price(chain: &str) -> Result<GasPrice, String> {
     
     match chain {
         "solana" => {
-            // Solana uses priority fees - fetch from RPC
-            // For now, return standard values (would query getRecentPrioritizationFees in production)
-            Ok(GasPrice {
-                chain: "solana".to_string(),
-                slow: "0.000005".to_string(),
-                standard: "0.00001".to_string(),
-                fast: "0.00005".to_string(),
-                fastest: "0.0001".to_string(),
-                timestamp,
-            })
+            match fetch_solana_priority_fees().await {
+                Ok(fees) => {
+                    let [slow, standard, fast, fastest] = solana_fee_tiers(&fees);
+                    Ok(GasPrice {
+                        chain: "solana".to_string(),
+                        slow: format!("{:.9}", slow),
+                        standard: format!("{:.9}", standard),
+                        fast: format!("{:.9}", fast),
+                        fastest: format!("{:.9}", fastest),
+                        timestamp,
+                    })
+                }
+                Err(e) => {
+                    // Fallback to typical values
+                    tracing::warn!("⚠️ {}, using default Solana fees", e);
+                    Ok(GasPrice {
+                        chain: "solana".to_string(),
+                        slow: "0.000005".to_string(),
+                        standard: "0.00001".to_string(),
+                        fast: "0.00005".to_string(),
+                        fastest: "0.0001".to_string(),
+                        timestamp,
+                    })
+                }
+            }
         }
         "eth" | "ethereum" => {
             // Query Ethereum gas prices from public API
@@ -102,8 +299,8 @@ pub fn estimate_transaction_cost(
     
     match chain {
         "solana" => {
-            // Solana uses lamports (1 SOL = 1e9 lamports)
-            price_gwei * gas_limit as f64 / 1e9
+            // Solana tiers are already SOL per transaction
+            price_gwei
         }
         "eth" | "ethereum" | "bsc" | "binance" => {
             // EVM: gas_price (gwei) * gas_limit / 1e9 = ETH/BNB cost
@@ -112,3 +309,79 @@ pub fn estimate_transaction_cost(
         _ => 0.0,
     }
 }
+
+// ==================== API HANDLERS ====================
+
+pub async fn get_gas_price_handler(Path(chain): Path<String>) -> impl IntoResponse {
+    match get_gas_price(&chain).await {
+        Ok(gas_price) => (StatusCode::OK, Json(GasPriceResponse { success: true, gas_price: Some(gas_price), error: None })),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(GasPriceResponse { success: false, gas_price: None, error: Some(e) })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_fee_percentile_and_cap() {
+        let samples = [0, 10_000, 20_000, 50_000, 1_000_000];
+        // 50th percentile = 20k micro-lamports/CU * 200k CU = 4000 lamports
+        assert_eq!(priority_fee_lamports(&samples, 50, 5_000_000), Some(4_000));
+        assert_eq!(priority_fee_lamports(&samples, 100, 5_000_000), Some(200_000));
+        assert_eq!(priority_fee_lamports(&samples, 100, 150_000), Some(150_000));
+        assert_eq!(priority_fee_lamports(&[], 75, 5_000_000), None);
+    }
+
+    #[test]
+    fn test_helius_estimate_parsing() {
+        let response = serde_json::json!({"result": {"priorityFeeLevels": {
+            "min": 0.0, "low": 1000.0, "medium": 5000.5, "high": 20000.0, "veryHigh": 90000.0, "unsafeMax": 5000000.0
+        }}});
+        assert_eq!(helius_level(75), "high");
+        assert_eq!(helius_level(95), "veryHigh");
+        assert_eq!(parse_helius_estimate(&response, helius_level(50)), Some(5001));
+        assert_eq!(parse_helius_estimate(&response, helius_level(75)), Some(20_000));
+        assert_eq!(parse_helius_estimate(&serde_json::json!({"error": "bad"}), "high"), None);
+    }
+
+    fn gas_price(standard: &str) -> GasPrice {
+        GasPrice {
+            chain: "eth".to_string(),
+            slow: standard.to_string(),
+            standard: standard.to_string(),
+            fast: standard.to_string(),
+            fastest: standard.to_string(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_gas_cache_expires_per_chain() {
+        let start = Instant::now();
+        let mut cache = GasCache::default();
+        cache.store("eth", gas_price("30"), start);
+
+        assert_eq!(cache.fresh("eth", start + Duration::from_secs(14), GAS_CACHE_TTL).unwrap().standard, "30");
+        assert!(cache.fresh("eth", start + Duration::from_secs(15), GAS_CACHE_TTL).is_none());
+        assert!(cache.fresh("bsc", start, GAS_CACHE_TTL).is_none());
+    }
+
+    #[test]
+    fn test_solana_fee_tiers_from_recent_fees() {
+        // No priority fees paid recently => base fee only
+        assert_eq!(solana_fee_tiers(&[]), [0.000005; 4]);
+
+        // Median 10,000 micro-lamports/CU x 200k CU = 2,000 lamports on top of the base fee
+        let fees: Vec<u64> = (0..=100).map(|i| i * 200).collect();
+        let [slow, standard, fast, fastest] = solana_fee_tiers(&fees);
+        assert!((standard - 0.000007).abs() < 1e-12);
+        assert!(slow < standard && standard < fast && fast < fastest);
+    }
+
+    #[test]
+    fn test_evm_transaction_cost() {
+        // 30 gwei x 100k gas = 0.003 ETH
+        assert!((estimate_transaction_cost(&gas_price("30"), 100_000, "eth") - 0.003).abs() < 1e-12);
+    }
+}
@@ -5,6 +5,13 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use chrono::Utc;
 use uuid::Uuid;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
 
 // ==================== DATA STRUCTURES ====================
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,8 +24,12 @@ pub struct GridStrategy {
     pub lower_price: f64,
     pub upper_price: f64,
     pub grid_count: usize,
-    pub grid_spacing: f64,
-    pub investment_amount: f64,
+    pub grid_spacing: f64, // Arithmetic: price step between levels
+    #[serde(default)]
+    pub spacing_mode: SpacingMode,
+    #[serde(default)]
+    pub grid_ratio: f64, // Geometric: price ratio between levels
+    pub investment_amount: f64, // USD, like every price on the grid
     pub status: GridStatus,
     pub created_at: i64,
     pub last_price: f64,
@@ -26,6 +37,13 @@ pub struct GridStrategy {
     pub total_trades: usize,
     pub active_orders: Vec<GridOrder>,
     pub completed_orders: Vec<GridOrder>,
+    // Inventory tracking for vs-HODL comparison
+    #[serde(default)]
+    pub initial_price: f64,
+    #[serde(default)]
+    pub token_inventory: f64,
+    #[serde(default)]
+    pub cash_balance: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,11 +51,15 @@ pub struct GridOrder {
     pub order_id: String,
     pub order_type: OrderType,
     pub price: f64,
-    pub amount: f64,
+    pub amount: f64, // USD committed at this level
+    #[serde(default)]
+    pub quantity: f64, // Tokens bought on fill / to sell
     pub status: OrderStatus,
     pub filled_at: Option<i64>,
     pub filled_price: Option<f64>,
     pub profit: Option<f64>,
+    #[serde(default)]
+    pub opened_by: Option<String>, // Sells: order_id of that buy
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -62,6 +84,21 @@ pub enum GridStatus {
     Completed,
 }
 
+/// How level prices are spread over the range: a constant price step, or a constant
+/// ratio (even in percentage terms, which suits ranges spanning an order of magnitude).
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SpacingMode {
+    #[default]
+    Arithmetic,
+    Geometric,
+}
+
+impl GridStatus {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, GridStatus::Stopped | GridStatus::Completed)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateGridRequest {
     pub user_id: i64,
@@ -71,7 +108,11 @@ pub struct CreateGridRequest {
     pub lower_price: f64,
     pub upper_price: f64,
     pub grid_count: usize,
-    pub investment_amount: f64,
+    pub investment_amount: f64, // USD
+    #[serde(default)]
+    pub current_price: Option<f64>, // Entry price for the HODL baseline (defaults to mid-range)
+    #[serde(default)]
+    pub spacing_mode: SpacingMode,
 }
 
 #[derive(Debug, Serialize)]
@@ -94,6 +135,50 @@ pub struct GridStats {
     pub current_price: f64,
     pub price_range: (f64, f64),
     pub grid_levels: Vec<GridLevel>,
+    pub vs_hodl: HodlComparison,
+}
+
+/// Grid performance versus simply holding the initial investment.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HodlComparison {
+    pub hodl_value: f64,
+    pub grid_value: f64,
+    pub cash_balance: f64,
+    pub token_inventory: f64,
+    pub inventory_value: f64,
+    pub difference: f64,
+    pub difference_percent: f64,
+}
+
+/// Grid config plus where its prices come from: an explicit series, or candles
+/// for the request's token at `timeframe` (e.g. "15m", "1h", "1d").
+#[derive(Debug, Deserialize)]
+pub struct BacktestRequest {
+    #[serde(flatten)]
+    pub grid: CreateGridRequest,
+    #[serde(default)]
+    pub prices: Option<Vec<(i64, f64)>>, // (unix timestamp, price)
+    #[serde(default)]
+    pub timeframe: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>, // Candles to fetch (default 500)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BacktestResult {
+    pub total_profit: f64,
+    pub total_trades: usize,
+    pub max_drawdown_percent: f64, // Largest peak-to-trough fall in cash + inventory value
+    pub final_value: f64,
+    pub vs_hodl: HodlComparison,
+    pub price_points: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BacktestResponse {
+    pub success: bool,
+    pub result: Option<BacktestResult>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -120,29 +205,44 @@ pub fn create_grid_strategy(request: CreateGridRequest) -> Result<GridStrategy,
         return Err("Investment amount must be positive".to_string());
     }
     
+    if request.spacing_mode == SpacingMode::Geometric && request.lower_price <= 0.0 {
+        return Err("Geometric spacing needs a positive lower price".to_string());
+    }
+    
     // Calculate grid spacing
     let price_range = request.upper_price - request.lower_price;
-    let grid_spacing = price_range / (request.grid_count - 1) as f64;
+    let steps = (request.grid_count - 1) as f64;
+    let grid_spacing = price_range / steps;
+    let grid_ratio = match request.spacing_mode {
+        SpacingMode::Arithmetic => 0.0,
+        SpacingMode::Geometric => (request.upper_price / request.lower_price).powf(1.0 / steps),
+    };
     
     // Create initial buy orders at each grid level
     let amount_per_level = request.investment_amount / request.grid_count as f64;
     let mut active_orders = Vec::new();
     
     for i in 0..request.grid_count {
-        let price = request.lower_price + (grid_spacing * i as f64);
+        let price = level_price(request.spacing_mode, request.lower_price, grid_spacing, grid_ratio, i);
         let order = GridOrder {
             order_id: format!("grid_{}_{}", Uuid::new_v4(), i),
             order_type: OrderType::Buy,
             price,
             amount: amount_per_level,
+            quantity: 0.0,
             status: OrderStatus::Pending,
             filled_at: None,
             filled_price: None,
             profit: None,
+            opened_by: None,
         };
         active_orders.push(order);
     }
     
+    let initial_price = request.current_price
+        .filter(|p| *p > 0.0)
+        .unwrap_or((request.lower_price + request.upper_price) / 2.0);
+    
     Ok(GridStrategy {
         strategy_id: format!("grid_{}_{}", request.user_id, Uuid::new_v4()),
         user_id: request.user_id,
@@ -153,17 +253,57 @@ pub fn create_grid_strategy(request: CreateGridRequest) -> Result<GridStrategy,
         upper_price: request.upper_price,
         grid_count: request.grid_count,
         grid_spacing,
+        spacing_mode: request.spacing_mode,
+        grid_ratio,
         investment_amount: request.investment_amount,
         status: GridStatus::Active,
         created_at: Utc::now().timestamp(),
-        last_price: (request.lower_price + request.upper_price) / 2.0,
+        last_price: initial_price,
         total_profit: 0.0,
         total_trades: 0,
         active_orders,
         completed_orders: Vec::new(),
+        initial_price,
+        token_inventory: 0.0,
+        cash_balance: request.investment_amount,
     })
 }
 
+/// Price of level `i` (0 = the lower bound).
+fn level_price(mode: SpacingMode, lower_price: f64, grid_spacing: f64, grid_ratio: f64, i: usize) -> f64 {
+    match mode {
+        SpacingMode::Arithmetic => lower_price + grid_spacing * i as f64,
+        SpacingMode::Geometric => lower_price * grid_ratio.powi(i as i32),
+    }
+}
+
+/// Whether an order price sits on a level, allowing for float drift from repeated steps.
+fn on_level(order_price: f64, level_price: f64) -> bool {
+    (order_price - level_price).abs() <= level_price.abs() * 1e-9
+}
+
+impl GridStrategy {
+    pub fn level_price(&self, i: usize) -> f64 {
+        level_price(self.spacing_mode, self.lower_price, self.grid_spacing, self.grid_ratio, i)
+    }
+
+    /// Where the sell for a buy filled at level `price` goes.
+    pub fn level_above(&self, price: f64) -> f64 {
+        match self.spacing_mode {
+            SpacingMode::Arithmetic => price + self.grid_spacing,
+            SpacingMode::Geometric => price * self.grid_ratio,
+        }
+    }
+
+    /// Where the buy replacing a sold level `price` goes.
+    pub fn level_below(&self, price: f64) -> f64 {
+        match self.spacing_mode {
+            SpacingMode::Arithmetic => price - self.grid_spacing,
+            SpacingMode::Geometric => price / self.grid_ratio,
+        }
+    }
+}
+
 // ==================== GRID EXECUTION ====================
 pub fn update_grid_with_price(
     strategy: &mut GridStrategy,
@@ -203,19 +343,24 @@ pub fn update_grid_with_price(
         order.status = OrderStatus::Filled;
         order.filled_at = Some(Utc::now().timestamp());
         order.filled_price = Some(current_price);
+        order.quantity = order.amount / current_price;
+        strategy.cash_balance -= order.amount;
+        strategy.token_inventory += order.quantity;
         
         // Create corresponding sell order at next grid level
-        let sell_price = order.price + strategy.grid_spacing;
-        if sell_price <= strategy.upper_price {
+        let sell_price = strategy.level_above(order.price);
+        if sell_price <= strategy.upper_price || on_level(sell_price, strategy.upper_price) {
             let sell_order = GridOrder {
                 order_id: format!("grid_sell_{}", Uuid::new_v4()),
                 order_type: OrderType::Sell,
                 price: sell_price,
                 amount: order.amount,
+                quantity: order.quantity,
                 status: OrderStatus::Pending,
                 filled_at: None,
                 filled_price: None,
                 profit: None,
+                opened_by: Some(order.order_id.clone()),
             };
             strategy.active_orders.push(sell_order.clone());
             new_orders.push(sell_order);
@@ -242,28 +387,31 @@ pub fn update_grid_with_price(
         order.status = OrderStatus::Filled;
         order.filled_at = Some(Utc::now().timestamp());
         order.filled_price = Some(current_price);
+        strategy.cash_balance += order.quantity * current_price;
+        strategy.token_inventory -= order.quantity;
         
-        // Calculate profit
-        if let Some(buy_order) = strategy.completed_orders.iter()
-            .find(|o| matches!(o.order_type, OrderType::Buy) && o.price < order.price) {
-            let profit = (order.price - buy_order.price) / buy_order.price * 100.0;
-            let profit_usd = order.amount * (order.price - buy_order.price);
-            order.profit = Some(profit);
-            strategy.total_profit += profit_usd;
+        // Profit against the buy this sell was opened for, so each buy is counted once.
+        // A sell carries that buy's spend and tokens, so its buy price is amount / quantity
+        if order.quantity > 0.0 && order.amount > 0.0 {
+            let buy_price = order.amount / order.quantity;
+            order.profit = Some((current_price - buy_price) / buy_price * 100.0);
+            strategy.total_profit += order.quantity * current_price - order.amount;
         }
         
         // Create new buy order at lower grid level
-        let buy_price = order.price - strategy.grid_spacing;
-        if buy_price >= strategy.lower_price {
+        let buy_price = strategy.level_below(order.price);
+        if buy_price >= strategy.lower_price || on_level(buy_price, strategy.lower_price) {
             let buy_order = GridOrder {
                 order_id: format!("grid_buy_{}", Uuid::new_v4()),
                 order_type: OrderType::Buy,
                 price: buy_price,
                 amount: order.amount,
+                quantity: 0.0,
                 status: OrderStatus::Pending,
                 filled_at: None,
                 filled_price: None,
                 profit: None,
+                opened_by: None,
             };
             strategy.active_orders.push(buy_order.clone());
             new_orders.push(buy_order);
@@ -276,19 +424,109 @@ pub fn update_grid_with_price(
     new_orders
 }
 
+/// Book what a buy actually received on-chain in place of the quantity estimated at fill
+/// time, so the sell it opened sells exactly that.
+pub fn record_bought_quantity(strategy: &mut GridStrategy, buy_order_id: &str, quantity: f64) {
+    let Some(buy) = strategy.completed_orders.iter_mut().find(|o| o.order_id == buy_order_id) else {
+        return;
+    };
+    strategy.token_inventory += quantity - buy.quantity;
+    buy.quantity = quantity;
+    if let Some(sell) = strategy.active_orders.iter_mut().find(|o| o.opened_by.as_deref() == Some(buy_order_id)) {
+        sell.quantity = quantity;
+    }
+}
+
+/// Base units a fill swaps on-chain. Buys spend the order's USD as SOL at `sol_price_usd`;
+/// sells spend the tokens the buy received.
+pub fn order_swap_amount(order: &GridOrder, sol_price_usd: Option<f64>, decimals: u8) -> Result<u64, String> {
+    match order.order_type {
+        OrderType::Buy => {
+            let sol_price_usd = sol_price_usd
+                .filter(|p| *p > 0.0)
+                .ok_or_else(|| "SOL price unavailable to size the buy".to_string())?;
+            Ok(crate::balance::usd_to_lamports(order.amount, sol_price_usd))
+        }
+        OrderType::Sell => Ok((order.quantity * 10f64.powi(decimals as i32)) as u64),
+    }
+}
+
+// ==================== BACKTESTING ====================
+
+/// Replay a price series through a fresh grid. Pure: nothing touches the chain or DB,
+/// and the same config and series always give the same result. Points are replayed in
+/// timestamp order, and unusable prices are skipped. The HODL baseline starts at the
+/// first price unless the request sets one.
+pub fn backtest_grid(mut request: CreateGridRequest, price_series: &[(i64, f64)]) -> Result<BacktestResult, String> {
+    let mut series: Vec<(i64, f64)> = price_series.iter()
+        .copied()
+        .filter(|(_, price)| price.is_finite() && *price > 0.0)
+        .collect();
+    series.sort_by_key(|(ts, _)| *ts);
+    let (Some(&(_, first_price)), Some(&(_, last_price))) = (series.first(), series.last()) else {
+        return Err("Price series has no usable prices".to_string());
+    };
+    request.current_price = request.current_price.or(Some(first_price));
+
+    let mut strategy = create_grid_strategy(request)?;
+    let mut peak_value = strategy.investment_amount;
+    let mut max_drawdown_percent: f64 = 0.0;
+    for &(_, price) in &series {
+        update_grid_with_price(&mut strategy, price);
+        let value = strategy.cash_balance + strategy.token_inventory * price;
+        peak_value = peak_value.max(value);
+        if peak_value > 0.0 {
+            max_drawdown_percent = max_drawdown_percent.max((peak_value - value) / peak_value * 100.0);
+        }
+    }
+
+    let vs_hodl = compute_vs_hodl(&strategy, last_price);
+    Ok(BacktestResult {
+        total_profit: strategy.total_profit,
+        total_trades: strategy.total_trades,
+        max_drawdown_percent,
+        final_value: vs_hodl.grid_value,
+        vs_hodl,
+        price_points: series.len(),
+    })
+}
+
 // ==================== GRID STATS ====================
+
+/// Compare the grid's cash + token inventory against holding the whole
+/// investment from the initial price.
+pub fn compute_vs_hodl(strategy: &GridStrategy, current_price: f64) -> HodlComparison {
+    let hodl_value = if strategy.initial_price > 0.0 {
+        strategy.investment_amount / strategy.initial_price * current_price
+    } else {
+        strategy.investment_amount
+    };
+    let inventory_value = strategy.token_inventory * current_price;
+    let grid_value = strategy.cash_balance + inventory_value;
+    let difference = grid_value - hodl_value;
+
+    HodlComparison {
+        hodl_value,
+        grid_value,
+        cash_balance: strategy.cash_balance,
+        token_inventory: strategy.token_inventory,
+        inventory_value,
+        difference,
+        difference_percent: if hodl_value > 0.0 { difference / hodl_value * 100.0 } else { 0.0 },
+    }
+}
 pub fn get_grid_stats(strategy: &GridStrategy, current_price: f64) -> GridStats {
     let mut grid_levels = Vec::new();
     
     for i in 0..strategy.grid_count {
-        let price = strategy.lower_price + (strategy.grid_spacing * i as f64);
+        let price = strategy.level_price(i);
         
         let buy_order = strategy.active_orders.iter()
-            .find(|o| matches!(o.order_type, OrderType::Buy) && (o.price - price).abs() < 0.0001)
+            .find(|o| matches!(o.order_type, OrderType::Buy) && on_level(o.price, price))
             .cloned();
         
         let sell_order = strategy.active_orders.iter()
-            .find(|o| matches!(o.order_type, OrderType::Sell) && (o.price - price).abs() < 0.0001)
+            .find(|o| matches!(o.order_type, OrderType::Sell) && on_level(o.price, price))
             .cloned();
         
         // Calculate profit at this level
@@ -325,6 +563,7 @@ pub fn get_grid_stats(strategy: &GridStrategy, current_price: f64) -> GridStats
         current_price,
         price_range: (strategy.lower_price, strategy.upper_price),
         grid_levels,
+        vs_hodl: compute_vs_hodl(strategy, current_price),
     }
 }
 
@@ -349,6 +588,110 @@ pub fn stop_grid(strategy: &mut GridStrategy) {
     }
 }
 
+/// Reject a new grid if the user already has `max_open_grids` non-terminal grids.
+pub fn check_grid_limit<'a>(
+    grids: impl Iterator<Item = &'a GridStrategy>,
+    user_id: i64,
+    max_open_grids: i32,
+) -> Result<(), String> {
+    let open = grids
+        .filter(|g| g.user_id == user_id && !g.status.is_terminal())
+        .count();
+
+    if open as i32 >= max_open_grids {
+        return Err(format!("Max open grids reached ({}/{})", open, max_open_grids));
+    }
+    Ok(())
+}
+
+// ==================== API HANDLERS ====================
+
+pub async fn create_grid_handler(
+    State(state): State<AppState>,
+    Json(request): Json<CreateGridRequest>,
+) -> impl IntoResponse {
+    let profile = match crate::risk_engine::get_risk_profile(request.user_id, &state.db).await {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(GridResponse { success: false, strategy_id: None, message: None, error: Some(e) })),
+    };
+
+    let strategy = match create_grid_strategy(request) {
+        Ok(s) => s,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(GridResponse { success: false, strategy_id: None, message: None, error: Some(e) })),
+    };
+
+    // Hold the write lock across the check and insert so concurrent creates can't both slip in
+    let mut grids = state.grids.write().await;
+    if let Err(e) = check_grid_limit(grids.values(), strategy.user_id, profile.max_open_grids) {
+        return (StatusCode::BAD_REQUEST, Json(GridResponse { success: false, strategy_id: None, message: None, error: Some(e) }));
+    }
+
+    let strategy_id = strategy.strategy_id.clone();
+    let message = format!("Grid created with {} levels", strategy.grid_count);
+    grids.insert(strategy_id.clone(), strategy);
+
+    (StatusCode::OK, Json(GridResponse { success: true, strategy_id: Some(strategy_id), message: Some(message), error: None }))
+}
+
+pub async fn backtest_grid_handler(Json(request): Json<BacktestRequest>) -> impl IntoResponse {
+    let failure = |status: StatusCode, e: String| (status, Json(BacktestResponse { success: false, result: None, error: Some(e) }));
+
+    let prices = match (request.prices, request.timeframe.as_deref()) {
+        (Some(prices), _) => prices,
+        (None, Some(timeframe)) => {
+            let limit = request.limit.unwrap_or(500);
+            match crate::price::fetch_price_candles(&request.grid.chain, &request.grid.token, timeframe, limit).await {
+                Ok(prices) => prices,
+                Err(e) => return failure(StatusCode::BAD_GATEWAY, e),
+            }
+        }
+        (None, None) => return failure(StatusCode::BAD_REQUEST, "Provide either prices or a timeframe".to_string()),
+    };
+
+    match backtest_grid(request.grid, &prices) {
+        Ok(result) => (StatusCode::OK, Json(BacktestResponse { success: true, result: Some(result), error: None })),
+        Err(e) => failure(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+pub async fn get_user_grids_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let grids = state.grids.read().await;
+    let user_grids: Vec<GridStrategy> = grids.values()
+        .filter(|g| g.user_id == user_id)
+        .cloned()
+        .collect();
+
+    (StatusCode::OK, Json(user_grids))
+}
+
+pub async fn get_grid_stats_handler(
+    State(state): State<AppState>,
+    Path(strategy_id): Path<String>,
+) -> impl IntoResponse {
+    let grids = state.grids.read().await;
+    match grids.get(&strategy_id) {
+        Some(strategy) => (StatusCode::OK, Json(get_grid_stats(strategy, strategy.last_price))).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Grid not found"}))).into_response(),
+    }
+}
+
+pub async fn stop_grid_handler(
+    State(state): State<AppState>,
+    Path(strategy_id): Path<String>,
+) -> impl IntoResponse {
+    let mut grids = state.grids.write().await;
+    match grids.get_mut(&strategy_id) {
+        Some(strategy) => {
+            stop_grid(strategy);
+            (StatusCode::OK, Json(GridResponse { success: true, strategy_id: Some(strategy_id), message: Some("Grid stopped".to_string()), error: None }))
+        }
+        None => (StatusCode::NOT_FOUND, Json(GridResponse { success: false, strategy_id: None, message: None, error: Some("Grid not found".to_string()) })),
+    }
+}
+
 // ==================== WHALE INTEGRATION ====================
 /// Adjust grid strategy based on whale activity
 pub fn adjust_grid_for_whale_activity(
@@ -435,3 +778,211 @@ pub fn optimize_grid_for_volatility(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Levels at 1.0 / 1.5 / 2.0, $30 each. Tests override what they need.
+    fn grid_request() -> CreateGridRequest {
+        CreateGridRequest {
+            user_id: 1,
+            chain: "solana".to_string(),
+            token: "Mint".to_string(),
+            token_symbol: "MEME".to_string(),
+            lower_price: 1.0,
+            upper_price: 2.0,
+            grid_count: 3,
+            investment_amount: 90.0,
+            current_price: None,
+            spacing_mode: SpacingMode::Arithmetic,
+        }
+    }
+
+    #[test]
+    fn test_backtest_is_deterministic() {
+        // Shuffled timestamps and a junk point: replayed as 1.5 -> 1.0 -> 1.5 -> 2.0
+        let series = [(3, 2.0), (0, 1.5), (1, 1.0), (2, 1.5), (4, f64::NAN)];
+
+        let first = backtest_grid(grid_request(), &series).unwrap();
+        let second = backtest_grid(grid_request(), &series).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(first.price_points, 4);
+        assert!(first.total_trades > 0);
+        assert!(first.max_drawdown_percent > 0.0);
+        // The HODL baseline starts at the first replayed price
+        assert!((first.vs_hodl.hodl_value - 90.0 / 1.5 * 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_backtest_needs_prices() {
+        assert!(backtest_grid(grid_request(), &[]).is_err());
+        assert!(backtest_grid(grid_request(), &[(0, 0.0), (1, -1.0)]).is_err());
+    }
+
+    fn grid_for(user_id: i64) -> GridStrategy {
+        create_grid_strategy(CreateGridRequest { user_id, ..grid_request() }).unwrap()
+    }
+
+    #[test]
+    fn test_grid_limit_rejects_beyond_max() {
+        let grids = vec![grid_for(1), grid_for(1), grid_for(2)];
+
+        assert!(check_grid_limit(grids.iter(), 1, 2).is_err());
+        assert!(check_grid_limit(grids.iter(), 1, 3).is_ok());
+        // Other users' grids don't count
+        assert!(check_grid_limit(grids.iter(), 2, 2).is_ok());
+    }
+
+    #[test]
+    fn test_stopping_grid_frees_slot() {
+        let mut grids = vec![grid_for(1), grid_for(1)];
+        assert!(check_grid_limit(grids.iter(), 1, 2).is_err());
+
+        stop_grid(&mut grids[0]);
+        assert!(check_grid_limit(grids.iter(), 1, 2).is_ok());
+    }
+
+    #[test]
+    fn test_vs_hodl_on_price_path() {
+        // Levels at 1.0 / 1.5 / 2.0, $30 each, entered at 1.5
+        let mut grid = create_grid_strategy(CreateGridRequest { current_price: Some(1.5), ..grid_request() }).unwrap();
+
+        update_grid_with_price(&mut grid, 1.9); // buys the 2.0 level
+        update_grid_with_price(&mut grid, 1.4); // buys the 1.5 level, sell placed at 2.0
+        update_grid_with_price(&mut grid, 2.0); // sells the 1.4 fill
+
+        let inventory = 30.0 / 1.9;
+        let cash = 90.0 - 30.0 - 30.0 + (30.0 / 1.4) * 2.0;
+        assert!((grid.token_inventory - inventory).abs() < 1e-9);
+        assert!((grid.cash_balance - cash).abs() < 1e-9);
+
+        let cmp = compute_vs_hodl(&grid, 2.0);
+        assert!((cmp.hodl_value - 120.0).abs() < 1e-9); // 60 tokens @ 2.0
+        assert!((cmp.grid_value - (cash + inventory * 2.0)).abs() < 1e-9);
+        assert!(cmp.difference < 0.0); // Holding beat the grid on a straight run up
+    }
+
+    fn wide_range(spacing_mode: SpacingMode) -> GridStrategy {
+        // $1 to $100, three levels
+        create_grid_strategy(CreateGridRequest { upper_price: 100.0, spacing_mode, ..grid_request() }).unwrap()
+    }
+
+    #[test]
+    fn test_spacing_modes_over_same_range() {
+        let levels = |grid: &GridStrategy| get_grid_stats(grid, 50.0).grid_levels.iter().map(|l| l.price).collect::<Vec<_>>();
+        let close = |a: &[f64], b: &[f64]| a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-9);
+
+        let arithmetic = wide_range(SpacingMode::Arithmetic);
+        assert!(close(&levels(&arithmetic), &[1.0, 50.5, 100.0]));
+
+        // Same endpoints, but each level is 10x the one below
+        let geometric = wide_range(SpacingMode::Geometric);
+        assert!(close(&levels(&geometric), &[1.0, 10.0, 100.0]));
+        assert!(close(&geometric.active_orders.iter().map(|o| o.price).collect::<Vec<_>>(), &[1.0, 10.0, 100.0]));
+        // Every level shows its opening buy
+        assert!(get_grid_stats(&geometric, 50.0).grid_levels.iter().all(|l| l.buy_order.is_some()));
+    }
+
+    #[test]
+    fn test_geometric_grid_trades_between_levels() {
+        let mut grid = wide_range(SpacingMode::Geometric);
+        update_grid_with_price(&mut grid, 9.0); // Buys the 10 and 100 levels
+        let sells: Vec<f64> = grid.active_orders.iter().filter(|o| matches!(o.order_type, OrderType::Sell)).map(|o| o.price).collect();
+        assert_eq!(sells.len(), 1);
+        assert!((sells[0] - 100.0).abs() < 1e-9); // One level up from 10, not 10 + 49.5
+
+        update_grid_with_price(&mut grid, 100.0);
+        let buys: Vec<f64> = grid.active_orders.iter().filter(|o| matches!(o.order_type, OrderType::Buy)).map(|o| o.price).collect();
+        assert!(buys.iter().any(|p| (p - 10.0).abs() < 1e-9)); // The sold level's buy is back at 10
+        let level_10 = &get_grid_stats(&grid, 100.0).grid_levels[1];
+        assert!(level_10.buy_order.is_some());
+    }
+
+    #[test]
+    fn test_geometric_needs_positive_lower_price() {
+        let request = CreateGridRequest { lower_price: 0.0, spacing_mode: SpacingMode::Geometric, ..grid_request() };
+        assert!(create_grid_strategy(request).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_profit_is_exact() {
+        // Levels at 1.0 / 1.5 / 2.0, $30 each
+        let mut grid = create_grid_strategy(grid_request()).unwrap();
+        update_grid_with_price(&mut grid, 1.5); // Buys the 1.5 and 2.0 levels, sell placed at 2.0
+        update_grid_with_price(&mut grid, 1.0); // Buys the 1.0 level, sell placed at 1.5
+        update_grid_with_price(&mut grid, 1.5); // Sells the 1.0 buy: 30 tokens for $45
+        update_grid_with_price(&mut grid, 2.0); // Sells the 1.5 buy: 20 tokens for $40
+
+        // $15 + $10, each buy paired with its own sell
+        assert!((grid.total_profit - 25.0).abs() < 1e-9);
+        let sells: Vec<f64> = grid.completed_orders.iter()
+            .filter(|o| matches!(o.order_type, OrderType::Sell))
+            .filter_map(|o| o.profit)
+            .collect();
+        assert_eq!(sells.len(), 2);
+        assert!((sells[0] - 50.0).abs() < 1e-9);
+        assert!((sells[1] - 100.0 / 3.0).abs() < 1e-9);
+        // Realized profit matches the cash: $90 in, $85 back, 20 tokens from the 2.0 level still held
+        assert!((grid.cash_balance - 85.0).abs() < 1e-9);
+        assert!((grid.token_inventory - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sell_uses_quantity_bought_on_chain() {
+        let mut grid = create_grid_strategy(grid_request()).unwrap();
+        update_grid_with_price(&mut grid, 1.0); // Buys every level, sells placed at 1.5 and 2.0
+        let buy_id = grid.completed_orders.iter().find(|o| o.price == 1.0).unwrap().order_id.clone();
+
+        // The swap delivered 28 tokens rather than the 30 estimated
+        record_bought_quantity(&mut grid, &buy_id, 28.0);
+        let sell = grid.active_orders.iter().find(|o| o.opened_by.as_deref() == Some(buy_id.as_str())).unwrap();
+        assert_eq!(sell.quantity, 28.0);
+        assert!((grid.token_inventory - 88.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_restored_sell_still_records_profit() {
+        let mut grid = create_grid_strategy(grid_request()).unwrap();
+        update_grid_with_price(&mut grid, 1.0);
+
+        // Sells saved before pairing was tracked carry only their amount and quantity
+        for order in grid.active_orders.iter_mut().filter(|o| matches!(o.order_type, OrderType::Sell)) {
+            let mut saved = serde_json::to_value(&*order).unwrap();
+            saved.as_object_mut().unwrap().remove("opened_by");
+            *order = serde_json::from_value(saved).unwrap();
+        }
+
+        update_grid_with_price(&mut grid, 1.5);
+        let sell = grid.completed_orders.iter().find(|o| matches!(o.order_type, OrderType::Sell)).unwrap();
+        assert!((sell.profit.unwrap() - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fill_swaps_match_the_grid_books() {
+        // Levels at 1.0 / 1.5 / 2.0, $30 each; SOL at $150, token with 6 decimals
+        let mut grid = create_grid_strategy(grid_request()).unwrap();
+        let sol_price = Some(150.0);
+        let mut grids = HashMap::new();
+
+        update_grid_with_price(&mut grid, 1.5); // Buys the 1.5 and 2.0 levels
+        let buy = grid.completed_orders.iter().find(|o| o.price == 1.5).unwrap().clone();
+        // $30 of SOL goes out, not 30 SOL
+        assert_eq!(order_swap_amount(&buy, sol_price, 6), Ok(200_000_000));
+        assert!(order_swap_amount(&buy, None, 6).is_err());
+        // The 1.0 level is still held back for the grid: $30 = 0.2 SOL
+        grids.insert(grid.strategy_id.clone(), grid.clone());
+        let committed = crate::balance::committed_usd(&grids, 1, "solana");
+        assert_eq!(crate::balance::usd_to_lamports(committed, 150.0), 200_000_000);
+
+        // The swap delivered 19.5 tokens; the sell at 2.0 sells exactly those
+        record_bought_quantity(&mut grid, &buy.order_id, 19.5);
+        update_grid_with_price(&mut grid, 2.0);
+        let sell = grid.completed_orders.iter().find(|o| o.opened_by.as_deref() == Some(buy.order_id.as_str())).unwrap();
+        assert_eq!(order_swap_amount(sell, None, 6), Ok(19_500_000));
+
+        // Profit and cash stay in USD: 19.5 tokens at $2 against the $30 spent
+        assert!((grid.total_profit - 9.0).abs() < 1e-9);
+        assert!((grid.cash_balance - (90.0 - 60.0 + 39.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,369 @@
+// RPC Health Gate Module
+// Keeps trade endpoints off while the Solana RPC is unhealthy (when REQUIRE_HEALTHY_RPC is set),
+// and trips a circuit breaker when trades keep hitting RPC errors
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use solana_client::rpc_client::RpcClient;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// ==================== HEALTH GATE ====================
+
+#[derive(Debug, Clone)]
+pub struct RpcHealthGate {
+    healthy: Arc<AtomicBool>,
+    required: bool,
+}
+
+impl RpcHealthGate {
+    pub fn new(required: bool, initially_healthy: bool) -> Self {
+        Self {
+            healthy: Arc::new(AtomicBool::new(initially_healthy)),
+            required,
+        }
+    }
+
+    /// `REQUIRE_HEALTHY_RPC=true` turns the gate on. Off by default (trading always allowed).
+    pub fn from_env(initially_healthy: bool) -> Self {
+        let required = std::env::var("REQUIRE_HEALTHY_RPC")
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        Self::new(required, initially_healthy)
+    }
+
+    pub fn set_healthy(&self, healthy: bool) {
+        let was = self.healthy.swap(healthy, Ordering::SeqCst);
+        if was != healthy {
+            if healthy {
+                tracing::info!("✅ Solana RPC recovered - trading enabled");
+            } else {
+                tracing::error!("❌ Solana RPC unhealthy - trading disabled");
+            }
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+    }
+
+    pub fn trading_allowed(&self) -> bool {
+        !self.required || self.is_healthy()
+    }
+}
+
+/// Middleware for trade routes: 503 while the gate is closed.
+pub async fn require_healthy_rpc(
+    State(gate): State<RpcHealthGate>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !gate.trading_allowed() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "success": false,
+                "error": "Trading temporarily disabled: Solana RPC is unhealthy",
+            })),
+        ).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Re-probe the RPC every `HEALTH_REPROBE_SECS` (default 30) and update the gate.
+pub fn spawn_health_monitor(gate: RpcHealthGate, client: Arc<RpcClient>) {
+    let interval_secs = std::env::var("HEALTH_REPROBE_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            let client = client.clone();
+            let healthy = tokio::task::spawn_blocking(move || client.get_health().is_ok())
+                .await
+                .unwrap_or(false);
+            gate.set_healthy(healthy);
+        }
+    });
+}
+
+// ==================== CIRCUIT BREAKER ====================
+// Closed: trades run normally. After `failure_threshold` RPC errors in a row, all inside
+// `window`, the breaker opens and trade endpoints answer 503 for `cooldown`. It then
+// half-opens: one trade is let through as a probe, and its outcome closes or re-opens it.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BreakerConfig {
+    pub failure_threshold: usize,
+    pub window: Duration,
+    pub cooldown: Duration,
+}
+
+impl BreakerConfig {
+    /// `RPC_BREAKER_FAILURES` (default 5, 0 disables), `RPC_BREAKER_WINDOW_SECS` (60)
+    /// and `RPC_BREAKER_COOLDOWN_SECS` (30).
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: u64| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default);
+        Self {
+            failure_threshold: var("RPC_BREAKER_FAILURES", 5) as usize,
+            window: Duration::from_secs(var("RPC_BREAKER_WINDOW_SECS", 60)),
+            cooldown: Duration::from_secs(var("RPC_BREAKER_COOLDOWN_SECS", 30).max(1)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerInner {
+    failures: VecDeque<Instant>, // Consecutive failures, cleared by any success
+    opened_at: Option<Instant>,
+    probe_started: Option<Instant>,
+}
+
+/// Snapshot for /health.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BreakerStatus {
+    pub state: BreakerState,
+    pub consecutive_failures: usize,
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Cheap to clone. All clones share one breaker.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Mutex<BreakerInner>>,
+    config: BreakerConfig,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self { inner: Arc::default(), config }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(BreakerConfig::from_env())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    pub fn record_failure_at(&self, now: Instant) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+        let mut inner = self.lock();
+        if inner.opened_at.is_some() {
+            // A failed probe (or a straggler from before the trip) restarts the cooldown
+            inner.opened_at = Some(now);
+            inner.probe_started = None;
+            return;
+        }
+        inner.failures.push_back(now);
+        while inner.failures.front().is_some_and(|t| now.duration_since(*t) > self.config.window) {
+            inner.failures.pop_front();
+        }
+        if inner.failures.len() >= self.config.failure_threshold {
+            tracing::error!("🔌 RPC circuit breaker open after {} consecutive failures - trading paused for {}s", inner.failures.len(), self.config.cooldown.as_secs());
+            inner.opened_at = Some(now);
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.lock();
+        if inner.opened_at.is_some() {
+            tracing::info!("🔌 RPC circuit breaker closed - trading resumed");
+        }
+        *inner = BreakerInner::default();
+    }
+
+    pub fn state_at(&self, now: Instant) -> BreakerState {
+        match self.lock().opened_at {
+            None => BreakerState::Closed,
+            Some(opened) if now.duration_since(opened) < self.config.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether a new trade may run. While half-open a single probe is admitted per
+    /// cooldown, so a probe that never reaches the RPC can't wedge the breaker.
+    /// Err carries how long to wait.
+    pub fn try_acquire_at(&self, now: Instant) -> Result<(), Duration> {
+        let mut inner = self.lock();
+        let Some(opened) = inner.opened_at else { return Ok(()) };
+        let reopen = opened + self.config.cooldown;
+        if now < reopen {
+            return Err(reopen - now);
+        }
+        match inner.probe_started {
+            Some(probe) if now.duration_since(probe) < self.config.cooldown => Err(probe + self.config.cooldown - now),
+            _ => {
+                inner.probe_started = Some(now);
+                Ok(())
+            }
+        }
+    }
+
+    /// `try_acquire_at` now, with the error worded for the client.
+    pub fn try_acquire(&self) -> Result<(), String> {
+        self.try_acquire_at(Instant::now()).map_err(|wait| {
+            format!("Trading paused: Solana RPC is failing repeatedly. Retry in {}s", wait.as_secs().max(1))
+        })
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        let now = Instant::now();
+        let state = self.state_at(now);
+        let inner = self.lock();
+        let retry_after_secs = match (state, inner.opened_at) {
+            (BreakerState::Open, Some(opened)) => Some((opened + self.config.cooldown).saturating_duration_since(now).as_secs().max(1)),
+            _ => None,
+        };
+        BreakerStatus { state, consecutive_failures: inner.failures.len(), retry_after_secs }
+    }
+}
+
+/// Middleware for Solana-only routes: 503 while the breaker is open. Routes shared with
+/// EVM chains check the breaker in their Solana branch instead.
+pub async fn require_closed_breaker(
+    State(breaker): State<CircuitBreaker>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(e) = breaker.try_acquire() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "success": false, "error": e })),
+        ).into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn gated_router(gate: RpcHealthGate) -> Router {
+        Router::new()
+            .route("/api/buy", post(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn_with_state(gate, require_healthy_rpc))
+    }
+
+    async fn buy_status(router: Router) -> StatusCode {
+        let request = Request::builder().method("POST").uri("/api/buy").body(Body::empty()).unwrap();
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_trading_gated_until_recovery() {
+        let gate = RpcHealthGate::new(true, false);
+        assert_eq!(buy_status(gated_router(gate.clone())).await, StatusCode::SERVICE_UNAVAILABLE);
+
+        gate.set_healthy(true);
+        assert_eq!(buy_status(gated_router(gate.clone())).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_gate_disabled_allows_trading() {
+        let gate = RpcHealthGate::new(false, false);
+        assert!(gate.trading_allowed());
+        assert_eq!(buy_status(gated_router(gate)).await, StatusCode::OK);
+    }
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig { failure_threshold: 3, window: Duration::from_secs(60), cooldown: Duration::from_secs(30) })
+    }
+
+    #[test]
+    fn test_breaker_opens_after_consecutive_failures() {
+        let b = breaker();
+        let t0 = Instant::now();
+        b.record_failure_at(t0);
+        b.record_failure_at(t0 + Duration::from_secs(1));
+        b.record_success(); // Breaks the streak
+        b.record_failure_at(t0 + Duration::from_secs(2));
+        b.record_failure_at(t0 + Duration::from_secs(3));
+        assert_eq!(b.state_at(t0 + Duration::from_secs(3)), BreakerState::Closed);
+
+        // Spread wider than the window: the oldest no longer counts
+        b.record_failure_at(t0 + Duration::from_secs(70));
+        assert_eq!(b.state_at(t0 + Duration::from_secs(70)), BreakerState::Closed);
+
+        b.record_failure_at(t0 + Duration::from_secs(71));
+        b.record_failure_at(t0 + Duration::from_secs(72));
+        assert_eq!(b.state_at(t0 + Duration::from_secs(72)), BreakerState::Open);
+        assert_eq!(b.try_acquire_at(t0 + Duration::from_secs(82)), Err(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn test_breaker_half_open_probe() {
+        let b = breaker();
+        let t0 = Instant::now();
+        for _ in 0..3 {
+            b.record_failure_at(t0);
+        }
+        let after_cooldown = t0 + Duration::from_secs(30);
+        assert_eq!(b.state_at(after_cooldown), BreakerState::HalfOpen);
+        // One probe at a time
+        assert_eq!(b.try_acquire_at(after_cooldown), Ok(()));
+        assert!(b.try_acquire_at(after_cooldown + Duration::from_secs(1)).is_err());
+
+        // Failed probe re-opens for a full cooldown
+        b.record_failure_at(after_cooldown + Duration::from_secs(2));
+        assert_eq!(b.state_at(after_cooldown + Duration::from_secs(31)), BreakerState::Open);
+
+        // Successful probe closes it
+        let t1 = after_cooldown + Duration::from_secs(32);
+        assert_eq!(b.try_acquire_at(t1), Ok(()));
+        b.record_success();
+        assert_eq!(b.state_at(t1), BreakerState::Closed);
+        assert_eq!(b.status().consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_breaker_rejection_says_when_to_retry() {
+        let b = breaker();
+        for _ in 0..3 {
+            b.record_failure();
+        }
+        let err = b.try_acquire().unwrap_err();
+        assert!(err.starts_with("Trading paused: Solana RPC"), "{}", err);
+        assert!(err.ends_with("Retry in 30s") || err.ends_with("Retry in 29s"), "{}", err);
+    }
+
+    #[test]
+    fn test_breaker_disabled() {
+        let b = CircuitBreaker::new(BreakerConfig { failure_threshold: 0, window: Duration::from_secs(60), cooldown: Duration::from_secs(30) });
+        let t0 = Instant::now();
+        for _ in 0..100 {
+            b.record_failure_at(t0);
+        }
+        assert_eq!(b.try_acquire_at(t0), Ok(()));
+    }
+}
@@ -0,0 +1,320 @@
+// Honeypot Detection Module
+// Simulates a round trip through Jupiter: if a buy route exists but the bought
+// tokens can't be quoted back to SOL, the token is treated as a honeypot.
+// Token-2022 extensions are parsed from the mint and scored by how much control
+// they leave the issuer over holders' tokens.
+
+use std::time::Duration;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::{
+    default_account_state::DefaultAccountState, permanent_delegate::PermanentDelegate,
+    transfer_fee::TransferFeeConfig, transfer_hook::TransferHook, BaseStateWithExtensions,
+    ExtensionType, StateWithExtensions,
+};
+use spl_token_2022::state::AccountState;
+use crate::execution;
+
+/// Size of the probe buy quoted when simulating a sell (0.01 SOL).
+pub const PROBE_LAMPORTS: u64 = 10_000_000;
+const PROBE_SLIPPAGE_BPS: u64 = 500;
+
+// ==================== CONFIG ====================
+
+/// `SECURITY_CHECK_TIMEOUT_SECS` bounds every network call made by the security check.
+pub fn security_check_timeout() -> Duration {
+    let secs = std::env::var("SECURITY_CHECK_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(5);
+    Duration::from_secs(secs)
+}
+
+/// `MAX_TRANSFER_FEE_BPS`: Token-2022 mints charging more than this per transfer are unsafe.
+pub fn max_transfer_fee_bps() -> u16 {
+    std::env::var("MAX_TRANSFER_FEE_BPS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(500)
+}
+
+/// `MIN_LIQUIDITY_USD`: pools shallower than this are penalized by the security check.
+pub fn min_liquidity_usd() -> f64 {
+    std::env::var("MIN_LIQUIDITY_USD")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(5_000.0)
+}
+
+// ==================== LIQUIDITY ====================
+
+/// Score penalty and warning for a pool under the liquidity floor.
+pub fn liquidity_finding(liquidity_usd: f64, floor_usd: f64) -> Option<(i32, String)> {
+    if liquidity_usd >= floor_usd {
+        return None;
+    }
+    Some((25, format!("Low liquidity: ${:.0} (floor ${:.0})", liquidity_usd, floor_usd)))
+}
+
+// ==================== TRANSFER FEES ====================
+
+/// Transfer fee in bps from a Token-2022 mint's TransferFeeConfig, or None without the extension.
+/// Takes the higher of the older/newer fee so a scheduled increase is caught before it lands.
+pub fn transfer_fee_bps(mint_data: &[u8]) -> Option<u16> {
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data).ok()?;
+    let config = state.get_extension::<TransferFeeConfig>().ok()?;
+    let older = u16::from(config.older_transfer_fee.transfer_fee_basis_points);
+    let newer = u16::from(config.newer_transfer_fee.transfer_fee_basis_points);
+    Some(older.max(newer))
+}
+
+// ==================== TOKEN-2022 EXTENSIONS ====================
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtensionFinding {
+    pub warning: String,
+    pub penalty: i32,
+    /// Marks the token unsafe regardless of score.
+    pub hard_fail: bool,
+}
+
+impl ExtensionFinding {
+    fn new(warning: String, penalty: i32, hard_fail: bool) -> Self {
+        Self { warning, penalty, hard_fail }
+    }
+}
+
+/// Risky extensions on a Token-2022 mint. Extensions not listed here (MetadataPointer,
+/// TokenMetadata, MintCloseAuthority, ...) are benign and produce no finding.
+pub fn assess_token2022_extensions(mint_data: &[u8], max_fee_bps: u16) -> Result<Vec<ExtensionFinding>, String> {
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data)
+        .map_err(|e| format!("Failed to parse Token-2022 extensions: {}", e))?;
+    let extension_types = state.get_extension_types()
+        .map_err(|e| format!("Failed to parse Token-2022 extensions: {}", e))?;
+
+    let mut findings = Vec::new();
+    for extension in extension_types {
+        match extension {
+            ExtensionType::TransferFeeConfig => {
+                let Some(fee_bps) = transfer_fee_bps(mint_data) else { continue };
+                let pct = fee_bps as f64 / 100.0;
+                if fee_bps > max_fee_bps {
+                    findings.push(ExtensionFinding::new(
+                        format!("Transfer fee of {:.2}% exceeds the {:.2}% limit", pct, max_fee_bps as f64 / 100.0), 30, true));
+                } else if fee_bps > 0 {
+                    findings.push(ExtensionFinding::new(format!("Transfer fee of {:.2}% on every transfer", pct), 10, false));
+                }
+            }
+            ExtensionType::PermanentDelegate => {
+                let delegate = state.get_extension::<PermanentDelegate>().ok().and_then(|d| Option::<Pubkey>::from(d.delegate));
+                if let Some(delegate) = delegate {
+                    findings.push(ExtensionFinding::new(
+                        format!("Permanent delegate {} can transfer or burn any holder's tokens", delegate), 50, true));
+                }
+            }
+            ExtensionType::DefaultAccountState => {
+                let frozen = state.get_extension::<DefaultAccountState>()
+                    .map(|d| d.state == AccountState::Frozen as u8)
+                    .unwrap_or(false);
+                if frozen {
+                    findings.push(ExtensionFinding::new(
+                        "New token accounts start frozen (issuer must thaw holders before they can sell)".to_string(), 50, true));
+                }
+            }
+            ExtensionType::TransferHook => {
+                let hook = state.get_extension::<TransferHook>().ok();
+                if let Some(program) = hook.and_then(|h| Option::<Pubkey>::from(h.program_id)) {
+                    findings.push(ExtensionFinding::new(
+                        format!("Transfer hook program {} runs on every transfer and can block sells", program), 25, false));
+                } else if hook.and_then(|h| Option::<Pubkey>::from(h.authority)).is_some() {
+                    findings.push(ExtensionFinding::new(
+                        "Transfer hook authority can attach a hook program later".to_string(), 10, false));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(findings)
+}
+
+// ==================== SELL SIMULATION ====================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SellSimulation {
+    Sellable,
+    /// A buy route exists but selling back fails.
+    Honeypot(String),
+    /// No buy route either, a quote couldn't be fetched, or the check timed out.
+    /// Not evidence of a honeypot.
+    Inconclusive(String),
+}
+
+/// Why a probe quote failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeError {
+    /// Jupiter answered that no route exists.
+    NoRoute(String),
+    /// Transport error, rate limit or unreadable response: says nothing about the token.
+    Unavailable(String),
+}
+
+/// `buy` is the buy quote's out amount, `sell` the sell quote's (None if it wasn't attempted).
+/// Only a confirmed missing sell route counts against the token.
+pub fn classify_sell_simulation(buy: &Result<u64, ProbeError>, sell: Option<&Result<u64, ProbeError>>) -> SellSimulation {
+    match (buy, sell) {
+        (Err(ProbeError::NoRoute(e)), _) => SellSimulation::Inconclusive(format!("No buy route: {}", e)),
+        (Err(ProbeError::Unavailable(e)), _) => SellSimulation::Inconclusive(format!("Buy quote failed: {}", e)),
+        (Ok(_), None) => SellSimulation::Inconclusive("Buy quote returned no tokens".to_string()),
+        (Ok(_), Some(Err(ProbeError::NoRoute(e)))) => SellSimulation::Honeypot(format!("No sell route: {}", e)),
+        (Ok(_), Some(Err(ProbeError::Unavailable(e)))) => SellSimulation::Inconclusive(format!("Sell quote failed: {}", e)),
+        (Ok(_), Some(Ok(0))) => SellSimulation::Honeypot("Sell quote returns nothing".to_string()),
+        (Ok(_), Some(Ok(_))) => SellSimulation::Sellable,
+    }
+}
+
+async fn quote_out_amount(client: &reqwest::Client, input: &str, output: &str, amount: u64) -> Result<u64, ProbeError> {
+    let quote = execution::get_jupiter_quote(client, input, output, amount, PROBE_SLIPPAGE_BPS)
+        .await
+        .map_err(|e| if execution::is_missing_route(&e) {
+            ProbeError::NoRoute(e.to_string())
+        } else {
+            ProbeError::Unavailable(e.to_string())
+        })?;
+    quote.outAmount.parse::<u64>().map_err(|e| ProbeError::Unavailable(format!("Bad outAmount: {}", e)))
+}
+
+async fn probe_round_trip(token: &str) -> SellSimulation {
+    let client = match execution::get_jupiter_client() {
+        Ok(c) => c,
+        Err(e) => return SellSimulation::Inconclusive(format!("Jupiter client error: {}", e)),
+    };
+    let buy = quote_out_amount(&client, execution::WSOL_MINT, token, PROBE_LAMPORTS).await;
+    let sell = match buy {
+        Ok(out) if out > 0 => Some(quote_out_amount(&client, token, execution::WSOL_MINT, out).await),
+        _ => None,
+    };
+    classify_sell_simulation(&buy, sell.as_ref())
+}
+
+/// Quotes a small buy and sells the result back, giving up after `timeout`.
+pub async fn simulate_sell(token: &str, timeout: Duration) -> SellSimulation {
+    match tokio::time::timeout(timeout, probe_round_trip(token)).await {
+        Ok(result) => result,
+        Err(_) => SellSimulation::Inconclusive(format!("Sell simulation timed out after {}s", timeout.as_secs())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+    use spl_token_2022::extension::{metadata_pointer::MetadataPointer, StateWithExtensionsMut};
+
+    type MintState<'a> = StateWithExtensionsMut<'a, spl_token_2022::state::Mint>;
+
+    fn mint_with_extensions(extensions: &[ExtensionType], init: impl FnOnce(&mut MintState)) -> Vec<u8> {
+        if extensions.is_empty() {
+            let mut data = vec![0u8; spl_token_2022::state::Mint::LEN];
+            let mint = spl_token_2022::state::Mint { is_initialized: true, ..Default::default() };
+            spl_token_2022::state::Mint::pack(mint, &mut data).unwrap();
+            return data;
+        }
+        let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state = MintState::unpack_uninitialized(&mut data).unwrap();
+        init(&mut state);
+        state.base.is_initialized = true;
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    fn mint_with_fee(fee_bps: Option<(u16, u16)>) -> Vec<u8> {
+        match fee_bps {
+            None => mint_with_extensions(&[], |_| {}),
+            Some((older, newer)) => mint_with_extensions(&[ExtensionType::TransferFeeConfig], |state| {
+                let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+                config.older_transfer_fee.transfer_fee_basis_points = older.into();
+                config.newer_transfer_fee.transfer_fee_basis_points = newer.into();
+            }),
+        }
+    }
+
+    #[test]
+    fn test_transfer_fee_parsed_from_extension() {
+        assert_eq!(transfer_fee_bps(&mint_with_fee(Some((100, 100)))), Some(100));
+        // Scheduled increase is reported
+        assert_eq!(transfer_fee_bps(&mint_with_fee(Some((100, 2500)))), Some(2500));
+        assert_eq!(transfer_fee_bps(&mint_with_fee(None)), None);
+        assert_eq!(transfer_fee_bps(&[0u8; 10]), None);
+    }
+
+    #[test]
+    fn test_sell_failure_with_buy_route_is_honeypot() {
+        let buy: Result<u64, ProbeError> = Ok(1_000);
+        let no_route = Err(ProbeError::NoRoute("No routes found".to_string()));
+        assert!(matches!(classify_sell_simulation(&buy, Some(&no_route)), SellSimulation::Honeypot(_)));
+        assert!(matches!(classify_sell_simulation(&buy, Some(&Ok(0))), SellSimulation::Honeypot(_)));
+        assert_eq!(classify_sell_simulation(&buy, Some(&Ok(9_000_000))), SellSimulation::Sellable);
+    }
+
+    #[test]
+    fn test_sell_quote_outage_is_inconclusive() {
+        let buy: Result<u64, ProbeError> = Ok(1_000);
+        let outage = Err(ProbeError::Unavailable("error sending request: connection reset".to_string()));
+        assert!(matches!(classify_sell_simulation(&buy, Some(&outage)), SellSimulation::Inconclusive(_)));
+    }
+
+    #[test]
+    fn test_missing_buy_route_is_inconclusive() {
+        let buy: Result<u64, ProbeError> = Err(ProbeError::NoRoute("No routes found".to_string()));
+        assert!(matches!(classify_sell_simulation(&buy, None), SellSimulation::Inconclusive(_)));
+        assert!(matches!(classify_sell_simulation(&Ok(0), None), SellSimulation::Inconclusive(_)));
+    }
+
+    #[test]
+    fn test_extension_findings_by_severity() {
+        let fee = mint_with_fee(Some((100, 100)));
+        let findings = assess_token2022_extensions(&fee, 500).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert!(!findings[0].hard_fail);
+        assert!(findings[0].warning.contains("1.00%"));
+        assert!(assess_token2022_extensions(&fee, 50).unwrap()[0].hard_fail);
+
+        let delegate = mint_with_extensions(&[ExtensionType::PermanentDelegate], |state| {
+            state.init_extension::<PermanentDelegate>(true).unwrap().delegate =
+                Some(Pubkey::new_unique()).try_into().unwrap();
+        });
+        assert!(assess_token2022_extensions(&delegate, 500).unwrap()[0].hard_fail);
+
+        let frozen = mint_with_extensions(&[ExtensionType::DefaultAccountState], |state| {
+            state.init_extension::<DefaultAccountState>(true).unwrap().state = AccountState::Frozen as u8;
+        });
+        assert!(assess_token2022_extensions(&frozen, 500).unwrap()[0].hard_fail);
+
+        let hook = mint_with_extensions(&[ExtensionType::TransferHook], |state| {
+            state.init_extension::<TransferHook>(true).unwrap().program_id =
+                Some(Pubkey::new_unique()).try_into().unwrap();
+        });
+        let findings = assess_token2022_extensions(&hook, 500).unwrap();
+        assert_eq!(findings[0].penalty, 25);
+        assert!(!findings[0].hard_fail);
+    }
+
+    #[test]
+    fn test_metadata_pointer_is_benign() {
+        let data = mint_with_extensions(&[ExtensionType::MetadataPointer], |state| {
+            state.init_extension::<MetadataPointer>(true).unwrap();
+        });
+        assert_eq!(assess_token2022_extensions(&data, 500).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_liquidity_floor() {
+        assert_eq!(liquidity_finding(10_000.0, 5_000.0), None);
+        assert_eq!(liquidity_finding(5_000.0, 5_000.0), None);
+        let (penalty, warning) = liquidity_finding(1_200.0, 5_000.0).unwrap();
+        assert_eq!(penalty, 25);
+        assert!(warning.contains("$1200"));
+    }
+}
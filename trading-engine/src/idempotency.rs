@@ -0,0 +1,231 @@
+// Idempotency Keys Module
+// Buy/sell requests may carry an idempotency_key. The first request with a key runs and its
+// response is stored; repeats within IDEMPOTENCY_KEY_TTL_SECS get that response back instead
+// of executing again.
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use crate::error::AppError;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::future::Future;
+
+const MAX_KEY_LEN: usize = 100;
+
+/// How long a key blocks repeats, from `IDEMPOTENCY_KEY_TTL_SECS` (default 24h).
+pub fn key_ttl_secs() -> i64 {
+    std::env::var("IDEMPOTENCY_KEY_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(86_400)
+}
+
+/// Outcome of trying to take a key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Claim {
+    /// First use (or the previous use expired): run the request.
+    New,
+    /// Already completed: send this back.
+    Replay(u16, serde_json::Value),
+    /// Another request with this key hasn't finished yet.
+    InProgress,
+    /// The key was used for a different endpoint.
+    Mismatch(String),
+}
+
+pub fn validate_key(key: &str) -> Result<(), String> {
+    if key.trim().is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(format!("idempotency_key must be 1-{} characters", MAX_KEY_LEN));
+    }
+    Ok(())
+}
+
+/// What to do with a key that's already stored and still live.
+pub fn existing_claim(endpoint: &str, stored_endpoint: &str, status_code: Option<i32>, response: Option<serde_json::Value>) -> Claim {
+    if stored_endpoint != endpoint {
+        return Claim::Mismatch(stored_endpoint.to_string());
+    }
+    match (status_code, response) {
+        (Some(status), Some(body)) => Claim::Replay(status as u16, body),
+        _ => Claim::InProgress,
+    }
+}
+
+/// Take `key` for `endpoint`. The insert (or takeover of an expired row) is a single
+/// statement, so of two concurrent requests with the same key exactly one gets `New`.
+pub async fn claim(pool: &PgPool, user_id: i64, key: &str, endpoint: &str) -> Result<Claim, String> {
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, idempotency_key, endpoint) VALUES ($1, $2, $3) \
+         ON CONFLICT (user_id, idempotency_key) DO UPDATE \
+         SET endpoint = EXCLUDED.endpoint, status_code = NULL, response = NULL, created_at = NOW() \
+         WHERE idempotency_keys.created_at < NOW() - make_interval(secs => $4) \
+         RETURNING idempotency_key"
+    )
+    .bind(user_id)
+    .bind(key)
+    .bind(endpoint)
+    .bind(key_ttl_secs() as f64)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if claimed.is_some() {
+        return Ok(Claim::New);
+    }
+
+    let stored: Option<(String, Option<i32>, Option<serde_json::Value>)> = sqlx::query_as(
+        "SELECT endpoint, status_code, response FROM idempotency_keys WHERE user_id = $1 AND idempotency_key = $2"
+    )
+    .bind(user_id)
+    .bind(key)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    match stored {
+        Some((stored_endpoint, status_code, response)) => Ok(existing_claim(endpoint, &stored_endpoint, status_code, response)),
+        // Swept between the two statements: treat as in flight and let the client retry
+        None => Ok(Claim::InProgress),
+    }
+}
+
+/// Store the response for a claimed key.
+pub async fn complete(pool: &PgPool, user_id: i64, key: &str, status: StatusCode, response: &serde_json::Value) -> Result<(), String> {
+    sqlx::query("UPDATE idempotency_keys SET status_code = $3, response = $4 WHERE user_id = $1 AND idempotency_key = $2")
+        .bind(user_id)
+        .bind(key)
+        .bind(status.as_u16() as i32)
+        .bind(response)
+        .execute(pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Run `handler` at most once per (user, key). Without a key it just runs.
+/// Every completed response is stored, failures included: a failed swap may still land,
+/// so retrying under the same key must not send a second one.
+pub async fn run_once<T, F>(pool: &PgPool, user_id: i64, key: Option<&str>, endpoint: &str, handler: F) -> Response
+where
+    T: Serialize,
+    F: Future<Output = Result<T, AppError>>,
+{
+    run_once_with_status(pool, user_id, key, endpoint, async { handler.await.map(|response| (StatusCode::OK, response)) }).await
+}
+
+/// `run_once` for handlers that pick their own success status (e.g. 207 for a partial run).
+pub async fn run_once_with_status<T, F>(pool: &PgPool, user_id: i64, key: Option<&str>, endpoint: &str, handler: F) -> Response
+where
+    T: Serialize,
+    F: Future<Output = Result<(StatusCode, T), AppError>>,
+{
+    let Some(key) = key else {
+        return match handler.await {
+            Ok((status, response)) => (status, Json(response)).into_response(),
+            Err(e) => e.into_response(),
+        };
+    };
+    let reject = |status: StatusCode, error: String| {
+        (status, Json(serde_json::json!({ "success": false, "error": error }))).into_response()
+    };
+    if let Err(e) = validate_key(key) {
+        return reject(StatusCode::BAD_REQUEST, e);
+    }
+
+    match claim(pool, user_id, key, endpoint).await {
+        Ok(Claim::New) => {}
+        Ok(Claim::Replay(status, body)) => {
+            tracing::info!("🔁 Replaying stored response for idempotency key {} (user {})", key, user_id);
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::OK);
+            return (status, Json(body)).into_response();
+        }
+        Ok(Claim::InProgress) => return reject(StatusCode::CONFLICT, "A request with this idempotency_key is still in progress".to_string()),
+        Ok(Claim::Mismatch(other)) => return reject(StatusCode::UNPROCESSABLE_ENTITY, format!("idempotency_key was already used for {}", other)),
+        Err(e) => return reject(StatusCode::INTERNAL_SERVER_ERROR, format!("Idempotency check failed: {}", e)),
+    }
+
+    let (status, body) = match handler.await {
+        Ok((status, response)) => match serde_json::to_value(&response) {
+            Ok(body) => (status, body),
+            Err(e) => {
+                tracing::error!("Failed to serialize response for idempotency key {}: {}", key, e);
+                return (status, Json(response)).into_response();
+            }
+        },
+        Err(e) => (e.status(), e.body()),
+    };
+    if let Err(e) = complete(pool, user_id, key, status, &body).await {
+        tracing::error!("Failed to store response for idempotency key {}: {}", key, e);
+    }
+    (status, Json(body)).into_response()
+}
+
+/// Delete expired keys hourly so the table doesn't grow without bound.
+pub fn spawn_key_sweeper(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let swept = sqlx::query("DELETE FROM idempotency_keys WHERE created_at < NOW() - make_interval(secs => $1)")
+                .bind(key_ttl_secs() as f64)
+                .execute(&pool)
+                .await;
+            match swept {
+                Ok(r) if r.rows_affected() > 0 => tracing::debug!("Swept {} expired idempotency keys", r.rows_affected()),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("⚠️ Idempotency key sweep failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_validation() {
+        assert!(validate_key("buy-7f3a").is_ok());
+        assert!(validate_key("  ").is_err());
+        assert!(validate_key(&"k".repeat(101)).is_err());
+    }
+
+    #[test]
+    fn test_existing_claim() {
+        let body = serde_json::json!({ "success": true, "tx_hash": "5xy" });
+        assert_eq!(existing_claim("buy", "buy", Some(200), Some(body.clone())), Claim::Replay(200, body));
+        // Claimed but no response yet
+        assert_eq!(existing_claim("buy", "buy", None, None), Claim::InProgress);
+        // Same key reused for a sell
+        assert_eq!(existing_claim("sell", "buy", Some(200), None), Claim::Mismatch("buy".to_string()));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_claim_is_exclusive_and_replays() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query(
+            "CREATE TEMP TABLE idempotency_keys (user_id BIGINT NOT NULL, idempotency_key VARCHAR(100) NOT NULL, \
+             endpoint VARCHAR(50) NOT NULL, status_code INTEGER, response JSONB, created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(), \
+             PRIMARY KEY (user_id, idempotency_key))"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let key = "retry-1";
+
+        // Two concurrent claims: exactly one may run
+        let (a, b) = tokio::join!(claim(&pool, 42, key, "buy"), claim(&pool, 42, key, "buy"));
+        let mut claims = vec![a.unwrap(), b.unwrap()];
+        claims.sort_by_key(|c| c != &Claim::New);
+        assert_eq!(claims, vec![Claim::New, Claim::InProgress]);
+
+        let body = serde_json::json!({ "success": true });
+        complete(&pool, 42, key, StatusCode::OK, &body).await.unwrap();
+        assert_eq!(claim(&pool, 42, key, "buy").await.unwrap(), Claim::Replay(200, body));
+        // Keys are per user
+        assert_eq!(claim(&pool, 43, key, "buy").await.unwrap(), Claim::New);
+    }
+}
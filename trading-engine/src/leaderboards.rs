@@ -19,6 +19,8 @@ pub struct LeaderboardEntry {
     pub username: Option<String>,
     pub rank: usize,
     pub total_pnl_usd: f64,
+    pub total_pnl: f64, // In the requested denomination
+    pub denom: Denomination,
     pub total_pnl_percent: f64,
     pub win_rate: f64,
     pub total_trades: usize,
@@ -48,6 +50,21 @@ pub enum LeaderboardPeriod {
     AllTime,
 }
 
+/// Quote currency leaderboard PnL is ranked in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Denomination {
+    #[default]
+    Usd,
+    Sol,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DenomQuery {
+    #[serde(default)]
+    pub denom: Denomination,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GetLeaderboardRequest {
     pub period: Option<String>, // "daily", "weekly", "monthly", "alltime"
@@ -60,6 +77,7 @@ pub fn calculate_user_stats(
     user_id: i64,
     trades: &[TradeRecord],
     period: &LeaderboardPeriod,
+    denom: Denomination,
 ) -> LeaderboardEntry {
     let now = Utc::now().timestamp();
     let cutoff_time = match period {
@@ -75,6 +93,7 @@ pub fn calculate_user_stats(
         .collect();
     
     let mut total_pnl_usd = 0.0;
+    let mut total_pnl = 0.0;
     let mut total_pnl_percent = 0.0;
     let mut winning_trades = 0;
     let mut losing_trades = 0;
@@ -103,6 +122,7 @@ pub fn calculate_user_stats(
     
     for trade in &filtered_trades {
         total_pnl_usd += trade.pnl_usd;
+        total_pnl += trade.pnl_in(denom);
         total_pnl_percent += trade.pnl_percent;
         total_volume += trade.volume_usd;
         
@@ -137,6 +157,8 @@ pub fn calculate_user_stats(
         username: None,
         rank: 0, // Will be set when building leaderboard
         total_pnl_usd,
+        total_pnl,
+        denom,
         total_pnl_percent: if total_trades > 0 { total_pnl_percent / total_trades as f64 } else { 0.0 },
         win_rate,
         total_trades,
@@ -156,6 +178,7 @@ pub fn build_leaderboard(
     period: LeaderboardPeriod,
     metric: &str,
     limit: usize,
+    denom: Denomination,
 ) -> Leaderboard {
     let now = Utc::now().timestamp();
     
@@ -168,13 +191,13 @@ pub fn build_leaderboard(
     
     // Calculate stats for each user
     let mut entries: Vec<LeaderboardEntry> = user_ids.iter()
-        .map(|&user_id| calculate_user_stats(user_id, all_trades, &period))
+        .map(|&user_id| calculate_user_stats(user_id, all_trades, &period, denom))
         .collect();
     
     // Sort by metric
     match metric {
         "pnl" => {
-            entries.sort_by(|a, b| b.total_pnl_usd.partial_cmp(&a.total_pnl_usd).unwrap_or(std::cmp::Ordering::Equal));
+            entries.sort_by(|a, b| b.total_pnl.partial_cmp(&a.total_pnl).unwrap_or(std::cmp::Ordering::Equal));
         }
         "volume" => {
             entries.sort_by(|a, b| b.total_volume_usd.partial_cmp(&a.total_volume_usd).unwrap_or(std::cmp::Ordering::Equal));
@@ -183,7 +206,7 @@ pub fn build_leaderboard(
             entries.sort_by(|a, b| b.win_rate.partial_cmp(&a.win_rate).unwrap_or(std::cmp::Ordering::Equal));
         }
         _ => {
-            entries.sort_by(|a, b| b.total_pnl_usd.partial_cmp(&a.total_pnl_usd).unwrap_or(std::cmp::Ordering::Equal));
+            entries.sort_by(|a, b| b.total_pnl.partial_cmp(&a.total_pnl).unwrap_or(std::cmp::Ordering::Equal));
         }
     }
     
@@ -214,9 +237,49 @@ pub struct TradeRecord {
     pub pnl_usd: f64,
     pub pnl_percent: f64,
     pub timestamp: i64,
+    #[sqlx(default)]
+    pub sol_price_usd: Option<f64>, // SOL price when the trade executed
+}
+
+// Maps the transactions table onto TradeRecord. Only sells carry realized PnL, so only
+// SELL rows with a recorded profit_loss count. Volume is what the sell returned in USD,
+// and the percent is PnL over cost (proceeds - PnL).
+const TRADE_RECORDS_QUERY: &str = r#"
+    SELECT
+        user_id,
+        transaction_id AS trade_id,
+        chain,
+        token_address AS token,
+        LOWER(type) AS trade_type,
+        COALESCE(proceeds_usd, 0) AS volume_usd,
+        profit_loss AS pnl_usd,
+        COALESCE(profit_loss / NULLIF(proceeds_usd - profit_loss, 0) * 100, 0)::float8 AS pnl_percent,
+        EXTRACT(EPOCH FROM timestamp)::BIGINT AS timestamp,
+        sol_price_usd
+    FROM transactions
+    WHERE type = 'SELL' AND profit_loss IS NOT NULL
+    ORDER BY timestamp
+"#;
+
+pub async fn load_trade_records<'e, E: sqlx::PgExecutor<'e>>(executor: E) -> Result<Vec<TradeRecord>, sqlx::Error> {
+    sqlx::query_as::<_, TradeRecord>(TRADE_RECORDS_QUERY)
+        .fetch_all(executor)
+        .await
 }
 
 impl TradeRecord {
+    /// PnL converted at the SOL price recorded with the trade.
+    /// Trades without a recorded price can't be converted and count as zero.
+    pub fn pnl_in(&self, denom: Denomination) -> f64 {
+        match denom {
+            Denomination::Usd => self.pnl_usd,
+            Denomination::Sol => match self.sol_price_usd {
+                Some(price) if price > 0.0 => self.pnl_usd / price,
+                _ => 0.0,
+            },
+        }
+    }
+
     pub fn from_position_close(
         user_id: i64,
         chain: String,
@@ -240,6 +303,7 @@ impl TradeRecord {
             pnl_usd,
             pnl_percent,
             timestamp,
+            sol_price_usd: None,
         }
     }
 }
@@ -264,22 +328,41 @@ pub fn get_user_position(
 
 // ==================== API HANDLERS ====================
 
+async fn fetch_trade_records(state: &AppState, denom: Denomination) -> Vec<TradeRecord> {
+    let mut trades = load_trade_records(&state.db)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to load trades for leaderboard: {}", e);
+            vec![]
+        });
+
+    // Older trades predate per-trade SOL prices, convert those at today's price
+    if denom == Denomination::Sol {
+        if let Ok(current) = crate::price::fetch_sol_price().await {
+            for trade in trades.iter_mut() {
+                trade.sol_price_usd.get_or_insert(current);
+            }
+        }
+    }
+
+    trades
+}
+
 pub async fn get_daily_leaderboard_handler(
     State(state): State<AppState>,
     Path(user_id): Path<i64>,
+    Query(query): Query<DenomQuery>,
 ) -> impl IntoResponse {
     // 1. Fetch all trades from DB
-    let trades = sqlx::query_as::<_, TradeRecord>("SELECT * FROM transactions")
-        .fetch_all(&state.db)
-        .await
-        .unwrap_or(vec![]);
+    let trades = fetch_trade_records(&state, query.denom).await;
 
     // 2. Build Daily Leaderboard
     let leaderboard = build_leaderboard(
         &trades,
         LeaderboardPeriod::Daily,
         "pnl",
-        100 // Top 100
+        100, // Top 100
+        query.denom,
     );
 
     // 3. Get User's Rank
@@ -288,6 +371,7 @@ pub async fn get_daily_leaderboard_handler(
 
     (StatusCode::OK, Json(serde_json::json!({
         "period": "Daily",
+        "denom": query.denom,
         "rank": user_rank,
         "entry": user_entry,
         "top_10": leaderboard.entries.iter().take(10).collect::<Vec<_>>(),
@@ -296,23 +380,105 @@ pub async fn get_daily_leaderboard_handler(
 
 pub async fn get_alltime_leaderboard_handler(
     State(state): State<AppState>,
+    Query(query): Query<DenomQuery>,
 ) -> impl IntoResponse {
-    let trades = sqlx::query_as::<_, TradeRecord>("SELECT * FROM transactions")
-        .fetch_all(&state.db)
-        .await
-        .unwrap_or(vec![]);
+    let trades = fetch_trade_records(&state, query.denom).await;
 
     let leaderboard = build_leaderboard(
         &trades,
         LeaderboardPeriod::AllTime,
         "pnl",
-        100
+        100,
+        query.denom,
     );
 
     (StatusCode::OK, Json(serde_json::json!({
         "period": "AllTime",
+        "denom": query.denom,
         "top_10": leaderboard.entries.iter().take(10).collect::<Vec<_>>(),
     })))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn closed_trade(user_id: i64, pnl_usd: f64, sol_price_usd: f64) -> TradeRecord {
+        TradeRecord {
+            user_id,
+            trade_id: format!("t{}", user_id),
+            chain: "solana".to_string(),
+            token: "Mint".to_string(),
+            trade_type: "sell".to_string(),
+            volume_usd: 0.0,
+            pnl_usd,
+            pnl_percent: 0.0,
+            timestamp: Utc::now().timestamp(),
+            sol_price_usd: Some(sol_price_usd),
+        }
+    }
+
+    #[test]
+    fn test_denomination_changes_ordering() {
+        // User 1 made $150 while SOL was $150 (1 SOL)
+        // User 2 made $120 while SOL was $60 (2 SOL)
+        let trades = vec![closed_trade(1, 150.0, 150.0), closed_trade(2, 120.0, 60.0)];
+
+        let usd = build_leaderboard(&trades, LeaderboardPeriod::AllTime, "pnl", 10, Denomination::Usd);
+        let sol = build_leaderboard(&trades, LeaderboardPeriod::AllTime, "pnl", 10, Denomination::Sol);
 
+        assert_eq!(usd.entries.iter().map(|e| e.user_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(sol.entries.iter().map(|e| e.user_id).collect::<Vec<_>>(), vec![2, 1]);
+        assert!((sol.entries[0].total_pnl - 2.0).abs() < 1e-9);
+    }
+
+    /// Uses a temp table that shadows `transactions` for this connection only.
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_leaderboard_from_transactions_table() {
+        let mut conn = crate::test_db::connection().await;
+        sqlx::query(
+            "CREATE TEMP TABLE transactions (transaction_id VARCHAR(100) PRIMARY KEY, user_id BIGINT, chain VARCHAR(20) NOT NULL, \
+             type VARCHAR(20) NOT NULL, token_address VARCHAR(255) NOT NULL, amount VARCHAR(100) NOT NULL, price DOUBLE PRECISION NOT NULL, \
+             tx_hash VARCHAR(255) NOT NULL, timestamp TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP, \
+             profit_loss DOUBLE PRECISION, fee DOUBLE PRECISION, sol_price_usd DOUBLE PRECISION, proceeds_usd DOUBLE PRECISION)"
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        // (id, user, type, tokens, price, profit_loss, proceeds_usd)
+        type Row<'a> = (&'a str, i64, &'a str, &'a str, f64, Option<f64>, Option<f64>);
+        let rows: [Row; 5] = [
+            ("t1", 1, "SELL", "100", 1.5, Some(50.0), Some(150.0)), // $150 out, $100 in
+            ("t2", 2, "SELL", "200", 1.0, Some(120.0), Some(200.0)),
+            ("t3", 2, "SELL", "50", 0.5, Some(-5.0), Some(25.0)),
+            ("t4", 2, "BUY", "1.0", 2.0, None, None),               // Buys never count
+            ("t5", 3, "SELL", "10", 1.0, None, Some(10.0)),         // No realized PnL recorded
+        ];
+        for (id, user_id, kind, amount, price, pnl, proceeds) in rows {
+            sqlx::query("INSERT INTO transactions (transaction_id, user_id, chain, type, token_address, amount, price, tx_hash, profit_loss, sol_price_usd, proceeds_usd) VALUES ($1, $2, 'solana', $3, 'Mint', $4, $5, 'sig', $6, 150.0, $7)")
+                .bind(id)
+                .bind(user_id)
+                .bind(kind)
+                .bind(amount)
+                .bind(price)
+                .bind(pnl)
+                .bind(proceeds)
+                .execute(&mut conn)
+                .await
+                .unwrap();
+        }
+
+        let trades = load_trade_records(&mut conn).await.unwrap();
+        assert_eq!(trades.len(), 3);
+        let first = trades.iter().find(|t| t.trade_id == "t1").unwrap();
+        assert_eq!(first.volume_usd, 150.0);
+        assert!((first.pnl_percent - 50.0).abs() < 1e-9);
+
+        let board = build_leaderboard(&trades, LeaderboardPeriod::Daily, "pnl", 10, Denomination::Usd);
+        assert_eq!(board.entries.iter().map(|e| e.user_id).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(board.entries[0].total_pnl_usd, 115.0);
+        assert_eq!(board.entries[0].total_trades, 2);
+    }
+}
@@ -0,0 +1,431 @@
+// Limit Orders Module
+// Buy or sell once the price crosses a target. Evaluated by the price worker on every poll.
+
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+impl OrderSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderSide::Buy => "BUY",
+            OrderSide::Sell => "SELL",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_uppercase().as_str() {
+            "BUY" => Some(OrderSide::Buy),
+            "SELL" => Some(OrderSide::Sell),
+            _ => None,
+        }
+    }
+
+    /// Buys wait for a dip, sells wait for a rise.
+    pub fn default_direction(&self) -> TriggerDirection {
+        match self {
+            OrderSide::Buy => TriggerDirection::Below,
+            OrderSide::Sell => TriggerDirection::Above,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerDirection {
+    Below, // Fire when price <= trigger
+    Above, // Fire when price >= trigger
+}
+
+impl TriggerDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TriggerDirection::Below => "BELOW",
+            TriggerDirection::Above => "ABOVE",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_uppercase().as_str() {
+            "BELOW" => Some(TriggerDirection::Below),
+            "ABOVE" => Some(TriggerDirection::Above),
+            _ => None,
+        }
+    }
+}
+
+pub const STATUS_OPEN: &str = "OPEN";
+pub const STATUS_FILLED: &str = "FILLED";
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LimitOrder {
+    pub order_id: String,
+    pub user_id: i64,
+    pub chain: String,
+    pub token_address: String,
+    pub side: String,      // BUY / SELL
+    pub trigger_price: f64,
+    pub direction: String, // BELOW / ABOVE
+    pub amount: String,    // Buys: native amount to spend. Sells: percent of the position
+    pub position_id: Option<String>, // Position a sell closes
+    pub status: String,
+    pub tx_hash: Option<String>,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub filled_at: Option<i64>,
+}
+
+impl LimitOrder {
+    pub fn side(&self) -> Option<OrderSide> {
+        OrderSide::parse(&self.side)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLimitOrderRequest {
+    pub user_id: i64,
+    pub chain: String,
+    pub token: String,
+    pub side: OrderSide,
+    pub trigger_price: f64,
+    pub direction: Option<TriggerDirection>, // Defaults to below for buys, above for sells
+    pub amount: String,
+    pub position_id: Option<String>, // Required for sells
+}
+
+#[derive(Debug, Serialize)]
+pub struct LimitOrderResponse {
+    pub success: bool,
+    pub order: Option<LimitOrder>,
+    pub error: Option<String>,
+}
+
+/// Where an order or DCA schedule ended up after a cancel request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancelOutcome {
+    pub cancelled: bool, // False when it had already filled, finished or was mid-execution
+    pub status: String,
+    pub executions: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CancelResponse {
+    pub success: bool,
+    pub status: Option<String>,
+    pub executions: i64,
+    pub error: Option<String>,
+}
+
+impl CancelResponse {
+    /// 200 when cancelled, 409 with the final state when it no longer could be, 404 when unknown.
+    pub fn from_outcome(outcome: Result<Option<CancelOutcome>, String>, not_found: &str) -> (StatusCode, Self) {
+        match outcome {
+            Ok(Some(o)) if o.cancelled => (StatusCode::OK, Self { success: true, status: Some(o.status), executions: o.executions, error: None }),
+            Ok(Some(o)) => {
+                let error = format!("Can't cancel while {}", o.status);
+                (StatusCode::CONFLICT, Self { success: false, status: Some(o.status), executions: o.executions, error: Some(error) })
+            }
+            Ok(None) => (StatusCode::NOT_FOUND, Self { success: false, status: None, executions: 0, error: Some(not_found.to_string()) }),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Self { success: false, status: None, executions: 0, error: Some(e) }),
+        }
+    }
+}
+
+// ==================== TRIGGERS ====================
+
+pub fn is_triggered(direction: TriggerDirection, trigger_price: f64, price: f64) -> bool {
+    match direction {
+        TriggerDirection::Below => price <= trigger_price,
+        TriggerDirection::Above => price >= trigger_price,
+    }
+}
+
+/// Open orders whose trigger has been crossed at `price`.
+pub fn triggered_orders(orders: &[LimitOrder], price: f64) -> Vec<LimitOrder> {
+    orders
+        .iter()
+        .filter(|o| o.status == STATUS_OPEN)
+        .filter(|o| TriggerDirection::parse(&o.direction).is_some_and(|d| is_triggered(d, o.trigger_price, price)))
+        .cloned()
+        .collect()
+}
+
+/// Validate a request and build the order to store.
+pub fn new_order(request: CreateLimitOrderRequest, now: i64) -> Result<LimitOrder, String> {
+    if !(request.trigger_price.is_finite() && request.trigger_price > 0.0) {
+        return Err("trigger_price must be greater than 0".to_string());
+    }
+    let amount = request.amount.parse::<f64>().map_err(|_| "Invalid amount format".to_string())?;
+    match request.side {
+        OrderSide::Buy if amount <= 0.0 => return Err("Amount must be greater than 0".to_string()),
+        OrderSide::Sell if !(amount > 0.0 && amount <= 100.0) => {
+            return Err("Sell amount is a percent and must be between 0 and 100".to_string());
+        }
+        OrderSide::Sell if request.position_id.is_none() => {
+            return Err("Sell orders need a position_id".to_string());
+        }
+        _ => {}
+    }
+
+    Ok(LimitOrder {
+        order_id: format!("order_{}", Uuid::new_v4()),
+        user_id: request.user_id,
+        chain: request.chain,
+        token_address: request.token,
+        side: request.side.as_str().to_string(),
+        trigger_price: request.trigger_price,
+        direction: request.direction.unwrap_or_else(|| request.side.default_direction()).as_str().to_string(),
+        amount: request.amount,
+        position_id: if request.side == OrderSide::Sell { request.position_id } else { None },
+        status: STATUS_OPEN.to_string(),
+        tx_hash: None,
+        error: None,
+        created_at: now,
+        filled_at: None,
+    })
+}
+
+// ==================== PERSISTENCE ====================
+
+pub async fn save_order(order: &LimitOrder, pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        r#"
+        INSERT INTO limit_orders (order_id, user_id, chain, token_address, side, trigger_price, direction, amount, position_id, status, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#
+    )
+    .bind(&order.order_id)
+    .bind(order.user_id)
+    .bind(&order.chain)
+    .bind(&order.token_address)
+    .bind(&order.side)
+    .bind(order.trigger_price)
+    .bind(&order.direction)
+    .bind(&order.amount)
+    .bind(&order.position_id)
+    .bind(&order.status)
+    .bind(order.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save limit order: {}", e))?;
+
+    Ok(())
+}
+
+pub async fn load_open_orders(chain: &str, token: &str, pool: &PgPool) -> Result<Vec<LimitOrder>, String> {
+    sqlx::query_as::<_, LimitOrder>(
+        "SELECT * FROM limit_orders WHERE chain = $1 AND token_address = $2 AND status = 'OPEN' ORDER BY created_at"
+    )
+    .bind(chain)
+    .bind(token)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load limit orders: {}", e))
+}
+
+/// Mark an order FILLED before executing it. Only one caller can win the OPEN -> FILLED
+/// transition, so an overlapping poll never executes the same order twice.
+pub async fn claim_order(order_id: &str, pool: &PgPool) -> Result<bool, String> {
+    let result = sqlx::query("UPDATE limit_orders SET status = 'FILLED', filled_at = $2 WHERE order_id = $1 AND status = 'OPEN'")
+        .bind(order_id)
+        .bind(Utc::now().timestamp())
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to claim limit order: {}", e))?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Record the outcome of a claimed order. Failed orders are not retried.
+pub async fn record_fill(order_id: &str, result: &Result<String, String>, pool: &PgPool) -> Result<(), String> {
+    let query = match result {
+        Ok(tx_hash) => sqlx::query("UPDATE limit_orders SET tx_hash = $2 WHERE order_id = $1").bind(order_id).bind(tx_hash),
+        Err(e) => sqlx::query("UPDATE limit_orders SET status = 'FAILED', error = $2 WHERE order_id = $1").bind(order_id).bind(e),
+    };
+    query
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to update limit order: {}", e))?;
+    Ok(())
+}
+
+/// Cancel an order that hasn't triggered yet. The price worker claims an order (OPEN -> FILLED)
+/// before executing it, so a cancel only wins while the order is still OPEN; otherwise the
+/// order's current status is reported instead. None if there is no such order.
+pub async fn cancel_order(order_id: &str, pool: &PgPool) -> Result<Option<CancelOutcome>, String> {
+    let cancelled = sqlx::query("UPDATE limit_orders SET status = 'CANCELLED' WHERE order_id = $1 AND status = 'OPEN'")
+        .bind(order_id)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to cancel limit order: {}", e))?
+        .rows_affected() == 1;
+
+    let status: Option<String> = sqlx::query_scalar("SELECT status FROM limit_orders WHERE order_id = $1")
+        .bind(order_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Failed to load limit order: {}", e))?;
+    Ok(status.map(|status| CancelOutcome { cancelled, executions: i64::from(status == STATUS_FILLED), status }))
+}
+
+// ==================== API HANDLERS ====================
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
+
+pub async fn create_limit_order_handler(
+    State(state): State<AppState>,
+    Json(request): Json<CreateLimitOrderRequest>,
+) -> impl IntoResponse {
+    let order = match new_order(request, Utc::now().timestamp()) {
+        Ok(o) => o,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(LimitOrderResponse { success: false, order: None, error: Some(e) })),
+    };
+
+    if let Err(e) = crate::verification::check_trading_allowed(&state.db, order.user_id).await {
+        return (StatusCode::FORBIDDEN, Json(LimitOrderResponse { success: false, order: None, error: Some(e) }));
+    }
+
+    if let Some(position_id) = &order.position_id {
+        let owned: Result<Option<String>, _> = sqlx::query_scalar(
+            "SELECT position_id FROM positions WHERE position_id = $1 AND user_id = $2 AND token_address = $3 AND status = 'OPEN'"
+        )
+        .bind(position_id)
+        .bind(order.user_id)
+        .bind(&order.token_address)
+        .fetch_optional(&state.db)
+        .await;
+        match owned {
+            Ok(Some(_)) => {}
+            Ok(None) => return (StatusCode::NOT_FOUND, Json(LimitOrderResponse { success: false, order: None, error: Some("Open position not found".to_string()) })),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(LimitOrderResponse { success: false, order: None, error: Some(e.to_string()) })),
+        }
+    }
+
+    match save_order(&order, &state.db).await {
+        Ok(_) => (StatusCode::OK, Json(LimitOrderResponse { success: true, order: Some(order), error: None })),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(LimitOrderResponse { success: false, order: None, error: Some(e) })),
+    }
+}
+
+pub async fn get_orders_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let orders = sqlx::query_as::<_, LimitOrder>(
+        "SELECT * FROM limit_orders WHERE user_id = $1 ORDER BY created_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match orders {
+        Ok(o) => (StatusCode::OK, Json(o)),
+        Err(e) => {
+            tracing::error!("Failed to fetch limit orders for user {}: {}", user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(vec![]))
+        }
+    }
+}
+
+pub async fn cancel_order_handler(
+    State(state): State<AppState>,
+    Path(order_id): Path<String>,
+) -> impl IntoResponse {
+    let (status, response) = CancelResponse::from_outcome(cancel_order(&order_id, &state.db).await, "Order not found");
+    (status, Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(side: OrderSide, trigger_price: f64, amount: &str, position_id: Option<&str>) -> CreateLimitOrderRequest {
+        CreateLimitOrderRequest {
+            user_id: 7,
+            chain: "solana".to_string(),
+            token: "Mint".to_string(),
+            side,
+            trigger_price,
+            direction: None,
+            amount: amount.to_string(),
+            position_id: position_id.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_buy_fills_at_or_below_trigger() {
+        let buy = new_order(request(OrderSide::Buy, 1.0, "0.5", None), 0).unwrap();
+        assert_eq!(buy.direction, "BELOW");
+
+        let orders = vec![buy];
+        assert!(triggered_orders(&orders, 1.01).is_empty());
+        assert_eq!(triggered_orders(&orders, 1.0).len(), 1);
+        assert_eq!(triggered_orders(&orders, 0.9).len(), 1);
+    }
+
+    #[test]
+    fn test_sell_fills_at_or_above_trigger() {
+        let sell = new_order(request(OrderSide::Sell, 2.0, "50", Some("pos1")), 0).unwrap();
+        assert_eq!(sell.direction, "ABOVE");
+
+        let mut orders = vec![sell];
+        assert!(triggered_orders(&orders, 1.99).is_empty());
+        assert_eq!(triggered_orders(&orders, 2.5).len(), 1);
+
+        // Once filled it no longer triggers
+        orders[0].status = "FILLED".to_string();
+        assert!(triggered_orders(&orders, 2.5).is_empty());
+    }
+
+    #[test]
+    fn test_rejects_invalid_orders() {
+        assert!(new_order(request(OrderSide::Buy, 0.0, "0.5", None), 0).is_err());
+        assert!(new_order(request(OrderSide::Buy, 1.0, "abc", None), 0).is_err());
+        assert!(new_order(request(OrderSide::Sell, 1.0, "150", Some("pos1")), 0).is_err());
+        assert!(new_order(request(OrderSide::Sell, 1.0, "50", None), 0).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_cancel_unfilled_order() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query(
+            "CREATE TEMP TABLE limit_orders (order_id VARCHAR(100) PRIMARY KEY, user_id BIGINT, chain VARCHAR(20), \
+             token_address VARCHAR(100), side VARCHAR(10), trigger_price FLOAT8, direction VARCHAR(10), amount VARCHAR(50), \
+             position_id VARCHAR(100), status VARCHAR(20), tx_hash VARCHAR(255), error TEXT, created_at BIGINT, filled_at BIGINT)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let open = new_order(request(OrderSide::Buy, 1.0, "0.5", None), 0).unwrap();
+        save_order(&open, &pool).await.unwrap();
+        let outcome = cancel_order(&open.order_id, &pool).await.unwrap().unwrap();
+        assert_eq!(outcome, CancelOutcome { cancelled: true, status: "CANCELLED".to_string(), executions: 0 });
+        // The price worker can no longer claim it, and a second cancel just reports the state
+        assert!(!claim_order(&open.order_id, &pool).await.unwrap());
+        assert!(!cancel_order(&open.order_id, &pool).await.unwrap().unwrap().cancelled);
+
+        // An order the worker already claimed has executed
+        let filled = new_order(request(OrderSide::Buy, 1.0, "0.5", None), 0).unwrap();
+        save_order(&filled, &pool).await.unwrap();
+        assert!(claim_order(&filled.order_id, &pool).await.unwrap());
+        let outcome = cancel_order(&filled.order_id, &pool).await.unwrap().unwrap();
+        assert_eq!(outcome, CancelOutcome { cancelled: false, status: STATUS_FILLED.to_string(), executions: 1 });
+        assert!(cancel_order("order_missing", &pool).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,201 @@
+// Metrics Module
+// In-process counters for GET /metrics, rendered in the Prometheus text format.
+// Trade paths and workers update them through the shared handle in AppState, and
+// point-in-time gauges (open positions, active grids) are read at scrape time.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use axum::{extract::State, http::{header, StatusCode}, response::IntoResponse};
+use crate::AppState;
+
+/// Upper bounds (seconds) of the Jupiter latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()], // Non-cumulative, summed when rendered
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|le| secs <= *le) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    buys: AtomicU64,
+    sells: AtomicU64,
+    failures: Mutex<BTreeMap<&'static str, u64>>, // By failure_reason
+    rpc_errors: AtomicU64,
+    jupiter_latency: Histogram,
+}
+
+/// Cheap to clone. All clones share one registry.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Registry>,
+}
+
+/// Gauges read from live state when scraped.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Gauges {
+    pub open_positions: i64,
+    pub active_grids: usize,
+}
+
+/// Bucket a trade error into a low-cardinality label.
+pub fn failure_reason(error: &str) -> &'static str {
+    let e = error.to_lowercase();
+    if e.contains("insufficient") {
+        "insufficient_funds"
+    } else if e.contains("token risk") || e.contains("honeypot") {
+        "token_risk"
+    } else if e.contains("risk control") || e.contains("kill switch") {
+        "risk_control"
+    } else if e.contains("slippage") || e.contains("price impact") {
+        "slippage"
+    } else if e.contains("invalid") {
+        "invalid_request"
+    } else if e.contains("unsupported chain") {
+        "unsupported_chain"
+    } else {
+        "other"
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_buy(&self) {
+        self.inner.buys.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_sell(&self) {
+        self.inner.sells.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, error: &str) {
+        let mut failures = self.inner.failures.lock().unwrap_or_else(|e| e.into_inner());
+        *failures.entry(failure_reason(error)).or_default() += 1;
+    }
+
+    pub fn record_rpc_error(&self) {
+        self.inner.rpc_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_jupiter_latency(&self, elapsed: Duration) {
+        self.inner.jupiter_latency.observe(elapsed);
+    }
+
+    /// Prometheus text exposition format (version 0.0.4).
+    pub fn render(&self, gauges: Gauges) -> String {
+        let r = &self.inner;
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP trading_engine_trades_total Trades executed successfully.");
+        let _ = writeln!(out, "# TYPE trading_engine_trades_total counter");
+        let _ = writeln!(out, "trading_engine_trades_total{{side=\"buy\"}} {}", r.buys.load(Ordering::Relaxed));
+        let _ = writeln!(out, "trading_engine_trades_total{{side=\"sell\"}} {}", r.sells.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP trading_engine_trade_failures_total Trades that failed, by reason.");
+        let _ = writeln!(out, "# TYPE trading_engine_trade_failures_total counter");
+        for (reason, count) in r.failures.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            let _ = writeln!(out, "trading_engine_trade_failures_total{{reason=\"{}\"}} {}", reason, count);
+        }
+
+        let _ = writeln!(out, "# HELP trading_engine_open_positions Positions currently open.");
+        let _ = writeln!(out, "# TYPE trading_engine_open_positions gauge");
+        let _ = writeln!(out, "trading_engine_open_positions {}", gauges.open_positions);
+
+        let _ = writeln!(out, "# HELP trading_engine_active_grids Grid strategies currently running.");
+        let _ = writeln!(out, "# TYPE trading_engine_active_grids gauge");
+        let _ = writeln!(out, "trading_engine_active_grids {}", gauges.active_grids);
+
+        let _ = writeln!(out, "# HELP trading_engine_rpc_errors_total Failed RPC sends and confirmations.");
+        let _ = writeln!(out, "# TYPE trading_engine_rpc_errors_total counter");
+        let _ = writeln!(out, "trading_engine_rpc_errors_total {}", r.rpc_errors.load(Ordering::Relaxed));
+
+        let h = &r.jupiter_latency;
+        let _ = writeln!(out, "# HELP trading_engine_jupiter_quote_seconds Jupiter quote request latency.");
+        let _ = writeln!(out, "# TYPE trading_engine_jupiter_quote_seconds histogram");
+        let mut cumulative = 0;
+        for (le, bucket) in LATENCY_BUCKETS.iter().zip(&h.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "trading_engine_jupiter_quote_seconds_bucket{{le=\"{}\"}} {}", le, cumulative);
+        }
+        let count = h.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "trading_engine_jupiter_quote_seconds_bucket{{le=\"+Inf\"}} {}", count);
+        let _ = writeln!(out, "trading_engine_jupiter_quote_seconds_sum {}", h.sum_micros.load(Ordering::Relaxed) as f64 / 1e6);
+        let _ = writeln!(out, "trading_engine_jupiter_quote_seconds_count {}", count);
+
+        out
+    }
+}
+
+pub async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let open_positions = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM positions WHERE status = 'OPEN'")
+        .fetch_one(&state.db)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("⚠️ Metrics: failed to count open positions: {}", e);
+            0
+        });
+    let active_grids = state.grids.read().await.values().filter(|g| matches!(g.status, crate::grid_trading::GridStatus::Active)).count();
+
+    let body = state.metrics.render(Gauges { open_positions, active_grids });
+    (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_reasons() {
+        assert_eq!(failure_reason("Insufficient balance: need 1.2 SOL"), "insufficient_funds");
+        assert_eq!(failure_reason("Risk Control: daily loss limit reached"), "risk_control");
+        assert_eq!(failure_reason("Token Risk: mint authority enabled"), "token_risk");
+        assert_eq!(failure_reason("Jupiter Swap Failed: price impact 14% above limit"), "slippage");
+        assert_eq!(failure_reason("Transaction expired"), "other");
+    }
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let metrics = Metrics::new();
+        let shared = metrics.clone();
+        shared.record_buy();
+        shared.record_buy();
+        shared.record_sell();
+        shared.record_failure("Insufficient funds");
+        shared.record_rpc_error();
+        shared.observe_jupiter_latency(Duration::from_millis(80));
+        shared.observe_jupiter_latency(Duration::from_secs(20));
+
+        let text = metrics.render(Gauges { open_positions: 4, active_grids: 1 });
+        assert!(text.contains("trading_engine_trades_total{side=\"buy\"} 2\n"));
+        assert!(text.contains("trading_engine_trades_total{side=\"sell\"} 1\n"));
+        assert!(text.contains("trading_engine_trade_failures_total{reason=\"insufficient_funds\"} 1\n"));
+        assert!(text.contains("trading_engine_open_positions 4\n"));
+        assert!(text.contains("trading_engine_active_grids 1\n"));
+        assert!(text.contains("trading_engine_rpc_errors_total 1\n"));
+        // Buckets are cumulative, and the 20s outlier only lands in +Inf
+        assert!(text.contains("trading_engine_jupiter_quote_seconds_bucket{le=\"0.05\"} 0\n"));
+        assert!(text.contains("trading_engine_jupiter_quote_seconds_bucket{le=\"0.1\"} 1\n"));
+        assert!(text.contains("trading_engine_jupiter_quote_seconds_bucket{le=\"10\"} 1\n"));
+        assert!(text.contains("trading_engine_jupiter_quote_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("trading_engine_jupiter_quote_seconds_count 2\n"));
+    }
+}
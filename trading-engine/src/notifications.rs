@@ -1,25 +1,34 @@
 // Notifications & Alerts Module - Production Ready
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Alert {
+    pub alert_id: String,
     pub user_id: i64,
-    pub alert_type: String, // "tp", "sl", "price", "balance"
+    pub alert_type: String, // "price" (token USD price) or "balance" (default wallet USD value)
     pub chain: Option<String>,
     pub token: Option<String>,
     pub threshold: f64,
     pub condition: String, // "above", "below", "equals"
     pub active: bool,
+    pub repeat: bool, // Re-arms once the condition clears instead of deactivating
+    pub armed: bool,  // Repeating alerts fire once per crossing
     pub created_at: i64,
+    pub last_triggered_at: Option<i64>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Notification {
     pub user_id: i64,
     pub message: String,
     pub alert_type: String,
     pub timestamp: i64,
     pub priority: String, // "low", "medium", "high", "critical"
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub delivery_failed: bool, // Telegram push gave up, left for polling
 }
 
 pub fn create_notification(
@@ -40,6 +49,7 @@ pub fn create_notification(
         alert_type,
         timestamp,
         priority,
+        delivery_failed: false,
     }
 }
 
@@ -55,6 +65,25 @@ pub fn check_alert_triggered(
     }
 }
 
+/// What a check of `alert` against a fresh value should do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertOutcome {
+    /// Notify. One-shot alerts deactivate, repeating ones disarm until the condition clears
+    Fire,
+    /// Condition cleared on a disarmed repeating alert
+    Rearm,
+    Unchanged,
+}
+
+pub fn evaluate_alert(alert: &Alert, current_value: f64) -> AlertOutcome {
+    let triggered = check_alert_triggered(alert, current_value);
+    match (triggered, alert.armed) {
+        (true, true) => AlertOutcome::Fire,
+        (false, false) if alert.repeat => AlertOutcome::Rearm,
+        _ => AlertOutcome::Unchanged,
+    }
+}
+
 pub fn format_notification_message(notification: &Notification) -> String {
     let emoji = match notification.priority.as_str() {
         "critical" => "🔴",
@@ -65,3 +94,566 @@ pub fn format_notification_message(notification: &Notification) -> String {
     
     format!("{} {}: {}", emoji, notification.alert_type.to_uppercase(), notification.message)
 }
+
+// ==================== DELIVERY QUEUE ====================
+
+/// Pending notifications per user, drained by the bot when it polls.
+#[derive(Debug, Clone, Default)]
+pub struct NotificationQueue {
+    pending: Arc<RwLock<HashMap<i64, Vec<Notification>>>>,
+}
+
+impl NotificationQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn push(&self, notification: Notification) {
+        tracing::info!("🔔 {}", format_notification_message(&notification));
+        self.pending.write().await
+            .entry(notification.user_id)
+            .or_default()
+            .push(notification);
+    }
+
+    /// Take all pending notifications for a user.
+    pub async fn drain(&self, user_id: i64) -> Vec<Notification> {
+        self.pending.write().await.remove(&user_id).unwrap_or_default()
+    }
+
+    /// Users with notifications not yet pushed.
+    pub async fn undelivered_users(&self) -> Vec<i64> {
+        self.pending.read().await
+            .iter()
+            .filter(|(_, queued)| queued.iter().any(|n| !n.delivery_failed))
+            .map(|(user_id, _)| *user_id)
+            .collect()
+    }
+
+    /// Take a user's notifications that haven't failed delivery yet. Failed ones stay for polling.
+    pub async fn take_undelivered(&self, user_id: i64) -> Vec<Notification> {
+        let mut pending = self.pending.write().await;
+        let Some(queued) = pending.get_mut(&user_id) else { return Vec::new() };
+        let (failed, undelivered): (Vec<_>, Vec<_>) = std::mem::take(queued).into_iter().partition(|n| n.delivery_failed);
+        *queued = failed;
+        if queued.is_empty() {
+            pending.remove(&user_id);
+        }
+        undelivered
+    }
+
+    /// Put notifications back, ahead of anything queued since they were taken.
+    pub async fn requeue(&self, user_id: i64, notifications: Vec<Notification>) {
+        if notifications.is_empty() {
+            return;
+        }
+        let mut pending = self.pending.write().await;
+        let queued = pending.entry(user_id).or_default();
+        let newer = std::mem::replace(queued, notifications);
+        queued.extend(newer);
+    }
+}
+
+// ==================== ALERTS ====================
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAlertRequest {
+    pub user_id: i64,
+    pub alert_type: String,
+    pub chain: String,
+    pub token: Option<String>, // Required for price alerts
+    pub threshold: f64,
+    pub condition: String,
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+/// Validate a request and build the alert to store.
+pub fn new_alert(request: CreateAlertRequest, now: i64) -> Result<Alert, String> {
+    let alert_type = request.alert_type.to_lowercase();
+    let condition = request.condition.to_lowercase();
+    let token = request.token.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    match alert_type.as_str() {
+        "price" if token.is_none() => return Err("Price alerts need a token".to_string()),
+        "price" | "balance" => {}
+        other => return Err(format!("Unsupported alert type: {}", other)),
+    }
+    if !matches!(condition.as_str(), "above" | "below" | "equals") {
+        return Err("Condition must be above, below or equals".to_string());
+    }
+    if !(request.threshold.is_finite() && request.threshold >= 0.0) {
+        return Err("Threshold must be a non-negative number".to_string());
+    }
+
+    Ok(Alert {
+        alert_id: format!("alert_{}", uuid::Uuid::new_v4()),
+        user_id: request.user_id,
+        token: if alert_type == "price" { token } else { None },
+        alert_type,
+        chain: Some(request.chain),
+        threshold: request.threshold,
+        condition,
+        active: true,
+        repeat: request.repeat,
+        armed: true,
+        created_at: now,
+        last_triggered_at: None,
+    })
+}
+
+pub fn alert_message(alert: &Alert, value: f64) -> String {
+    let subject = match alert.alert_type.as_str() {
+        "price" => format!("{} price", alert.token.as_deref().unwrap_or("Token")),
+        _ => format!("{} wallet balance", alert.chain.as_deref().unwrap_or("")),
+    };
+    format!("{} is {} ${}: now ${:.6}", subject, alert.condition, alert.threshold, value)
+}
+
+pub async fn save_alert(alert: &Alert, pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        r#"
+        INSERT INTO alerts (alert_id, user_id, alert_type, chain, token, threshold, condition, active, repeat, armed, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#
+    )
+    .bind(&alert.alert_id)
+    .bind(alert.user_id)
+    .bind(&alert.alert_type)
+    .bind(&alert.chain)
+    .bind(&alert.token)
+    .bind(alert.threshold)
+    .bind(&alert.condition)
+    .bind(alert.active)
+    .bind(alert.repeat)
+    .bind(alert.armed)
+    .bind(alert.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save alert: {}", e))?;
+    Ok(())
+}
+
+async fn apply_alert_outcome(alert: &Alert, outcome: AlertOutcome, now: i64, pool: &PgPool) -> Result<(), String> {
+    let query = match outcome {
+        AlertOutcome::Fire => sqlx::query("UPDATE alerts SET active = repeat, armed = FALSE, last_triggered_at = $2 WHERE alert_id = $1")
+            .bind(&alert.alert_id)
+            .bind(now),
+        AlertOutcome::Rearm => sqlx::query("UPDATE alerts SET armed = TRUE WHERE alert_id = $1").bind(&alert.alert_id),
+        AlertOutcome::Unchanged => return Ok(()),
+    };
+    query.execute(pool).await.map_err(|e| format!("Failed to update alert: {}", e))?;
+    Ok(())
+}
+
+/// Current value an alert watches: token USD price, or the default wallet's USD value.
+async fn alert_value(state: &AppState, alert: &Alert) -> Result<f64, String> {
+    let chain = alert.chain.as_deref().unwrap_or("solana");
+    match alert.alert_type.as_str() {
+        "price" => {
+            let token = alert.token.as_deref().ok_or("Price alert without a token")?;
+            crate::price::fetch_token_price(chain, token).await.map(|p| p.price_usd)
+        }
+        "balance" => {
+            let wallet = crate::wallet::WalletSelector::Default;
+            let address = crate::wallet::fetch_wallet_field(alert.user_id, chain, &wallet, "address", &state.db).await?;
+            let balance = match chain {
+                "solana" | "sol" => crate::balance::get_solana_balance(alert.user_id, &address, &state.solana_client, &state.balance_cache).await?,
+                _ => crate::balance::get_evm_balance(&address, chain, None).await?,
+            };
+            Ok(balance.total_usd)
+        }
+        other => Err(format!("Unsupported alert type: {}", other)),
+    }
+}
+
+/// Check active alerts every `ALERT_CHECK_SECS` (default 30). Each distinct price or
+/// balance is fetched once per pass.
+pub fn spawn_alert_worker(state: AppState) {
+    let interval_secs = std::env::var("ALERT_CHECK_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(30);
+    if interval_secs == 0 {
+        tracing::info!("⏸️  Alert worker disabled (ALERT_CHECK_SECS=0)");
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+
+            let alerts = match sqlx::query_as::<_, Alert>("SELECT * FROM alerts WHERE active ORDER BY created_at")
+                .fetch_all(&state.db)
+                .await
+            {
+                Ok(a) => a,
+                Err(e) => {
+                    tracing::error!("Alert worker failed to load alerts: {}", e);
+                    continue;
+                }
+            };
+
+            // Price alerts share a value per token, balance alerts per user wallet
+            let mut values: HashMap<(String, Option<String>, Option<i64>), Option<f64>> = HashMap::new();
+            let now = chrono::Utc::now().timestamp();
+            for alert in alerts {
+                let key = match alert.alert_type.as_str() {
+                    "balance" => (alert.alert_type.clone(), alert.chain.clone(), Some(alert.user_id)),
+                    _ => (alert.alert_type.clone(), alert.chain.clone().zip(alert.token.clone()).map(|(c, t)| format!("{}_{}", c, t)), None),
+                };
+                let value = match values.get(&key) {
+                    Some(v) => *v,
+                    None => {
+                        let v = alert_value(&state, &alert).await
+                            .map_err(|e| tracing::debug!("Alert {}: value unavailable: {}", alert.alert_id, e))
+                            .ok();
+                        values.insert(key, v);
+                        v
+                    }
+                };
+                let Some(value) = value else { continue };
+
+                let outcome = evaluate_alert(&alert, value);
+                if outcome == AlertOutcome::Fire {
+                    state.notifications.push(create_notification(
+                        alert.user_id,
+                        alert_message(&alert, value),
+                        alert.alert_type.clone(),
+                        "medium".to_string(),
+                    )).await;
+                }
+                if let Err(e) = apply_alert_outcome(&alert, outcome, now, &state.db).await {
+                    tracing::error!("Alert {}: {}", alert.alert_id, e);
+                }
+            }
+        }
+    });
+}
+
+// ==================== TELEGRAM DELIVERY ====================
+
+pub const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+/// Send attempts per notification before it's left for polling.
+const TELEGRAM_MAX_ATTEMPTS: u32 = 3;
+/// Longest rate-limit wait honoured before giving up on a message.
+const TELEGRAM_MAX_RETRY_AFTER_SECS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum TelegramError {
+    #[error("Telegram delivery is not configured")]
+    NotConfigured,
+    #[error("User {0} has no linked Telegram chat")]
+    NotLinked(i64),
+    #[error("Rate limited, retry after {0}s")]
+    RateLimited(u64),
+    #[error("Telegram delivery failed: {0}")]
+    Failed(String),
+}
+
+/// Wait before the next attempt: Telegram's `retry_after` on 429s, else exponential backoff.
+pub fn telegram_backoff(error: &TelegramError, attempt: u32) -> Option<std::time::Duration> {
+    match error {
+        TelegramError::RateLimited(secs) if *secs <= TELEGRAM_MAX_RETRY_AFTER_SECS => Some(std::time::Duration::from_secs(*secs)),
+        TelegramError::RateLimited(_) => None,
+        TelegramError::Failed(_) => Some(std::time::Duration::from_secs(1 << attempt.min(4))),
+        TelegramError::NotConfigured | TelegramError::NotLinked(_) => None,
+    }
+}
+
+/// Classify a Bot API reply. 429s carry `parameters.retry_after`.
+pub fn parse_telegram_response(status: u16, body: &serde_json::Value) -> Result<(), TelegramError> {
+    if status == 429 {
+        let retry_after = body["parameters"]["retry_after"].as_u64().unwrap_or(1);
+        return Err(TelegramError::RateLimited(retry_after));
+    }
+    if body["ok"].as_bool() == Some(true) {
+        return Ok(());
+    }
+    let description = body["description"].as_str().unwrap_or("unknown error");
+    Err(TelegramError::Failed(format!("HTTP {}: {}", status, description)))
+}
+
+fn telegram_bot_token() -> Option<String> {
+    std::env::var("TELEGRAM_BOT_TOKEN").ok().filter(|t| !t.is_empty())
+}
+
+async fn telegram_chat_id(pool: &PgPool, user_id: i64) -> Result<Option<i64>, TelegramError> {
+    sqlx::query_scalar::<_, Option<i64>>("SELECT telegram_chat_id FROM users WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map(Option::flatten)
+        .map_err(|e| TelegramError::Failed(format!("Database error: {}", e)))
+}
+
+async fn post_telegram(client: &reqwest::Client, bot_token: &str, chat_id: i64, text: &str) -> Result<(), TelegramError> {
+    let response = client
+        .post(format!("{}/bot{}/sendMessage", TELEGRAM_API_URL, bot_token))
+        .json(&serde_json::json!({ "chat_id": chat_id, "text": text }))
+        .send()
+        .await
+        .map_err(|e| TelegramError::Failed(e.without_url().to_string()))?;
+    let status = response.status().as_u16();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    parse_telegram_response(status, &body)
+}
+
+/// Push one notification to the user's linked Telegram chat, retrying rate limits and
+/// transient failures.
+pub async fn send_telegram(pool: &PgPool, user_id: i64, notification: &Notification) -> Result<(), TelegramError> {
+    let bot_token = telegram_bot_token().ok_or(TelegramError::NotConfigured)?;
+    let chat_id = telegram_chat_id(pool, user_id).await?.ok_or(TelegramError::NotLinked(user_id))?;
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| TelegramError::Failed(e.to_string()))?;
+    let text = format_notification_message(notification);
+
+    let mut attempt = 1;
+    loop {
+        let error = match post_telegram(&client, &bot_token, chat_id, &text).await {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+        let wait = telegram_backoff(&error, attempt).filter(|_| attempt < TELEGRAM_MAX_ATTEMPTS);
+        let Some(wait) = wait else { return Err(error) };
+        tracing::debug!("Telegram send to user {} failed ({}), retrying in {:?}", user_id, error, wait);
+        tokio::time::sleep(wait).await;
+        attempt += 1;
+    }
+}
+
+/// Push queued notifications to Telegram every `TELEGRAM_DELIVERY_SECS` (default 2).
+/// Users without a linked chat keep theirs for polling, as do notifications that fail.
+pub fn spawn_telegram_delivery_worker(queue: NotificationQueue, pool: PgPool) {
+    if telegram_bot_token().is_none() {
+        tracing::info!("⏸️  Telegram delivery disabled (TELEGRAM_BOT_TOKEN unset)");
+        return;
+    }
+    let interval_secs = std::env::var("TELEGRAM_DELIVERY_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(2);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        // Users seen without a chat, so they aren't looked up on every tick
+        let mut unlinked: HashMap<i64, std::time::Instant> = HashMap::new();
+        loop {
+            interval.tick().await;
+            unlinked.retain(|_, seen| seen.elapsed() < std::time::Duration::from_secs(300));
+
+            for user_id in queue.undelivered_users().await {
+                if unlinked.contains_key(&user_id) {
+                    continue;
+                }
+                let mut leftover = Vec::new();
+                for mut notification in queue.take_undelivered(user_id).await {
+                    if !leftover.is_empty() {
+                        leftover.push(notification); // Stop after the first failure, keep order
+                        continue;
+                    }
+                    match send_telegram(&pool, user_id, &notification).await {
+                        Ok(()) => {}
+                        Err(TelegramError::NotLinked(_)) => {
+                            unlinked.insert(user_id, std::time::Instant::now());
+                            leftover.push(notification);
+                        }
+                        Err(e) => {
+                            tracing::warn!("⚠️ Telegram delivery to user {} failed: {}", user_id, e);
+                            notification.delivery_failed = true;
+                            leftover.push(notification);
+                        }
+                    }
+                }
+                queue.requeue(user_id, leftover).await;
+            }
+        }
+    });
+}
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
+use sqlx::PgPool;
+
+#[derive(Debug, Deserialize)]
+pub struct LinkTelegramRequest {
+    pub chat_id: Option<i64>, // None unlinks
+}
+
+pub async fn get_notifications_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.notifications.drain(user_id).await))
+}
+
+pub async fn create_alert_handler(
+    State(state): State<AppState>,
+    Json(request): Json<CreateAlertRequest>,
+) -> impl IntoResponse {
+    let alert = match new_alert(request, chrono::Utc::now().timestamp()) {
+        Ok(a) => a,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "success": false, "error": e }))),
+    };
+    match save_alert(&alert, &state.db).await {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "alert": alert }))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "success": false, "error": e }))),
+    }
+}
+
+pub async fn get_alerts_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let alerts = sqlx::query_as::<_, Alert>("SELECT * FROM alerts WHERE user_id = $1 ORDER BY created_at DESC")
+        .bind(user_id)
+        .fetch_all(&state.db)
+        .await;
+
+    match alerts {
+        Ok(a) => (StatusCode::OK, Json(a)),
+        Err(e) => {
+            tracing::error!("Failed to fetch alerts for user {}: {}", user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(vec![]))
+        }
+    }
+}
+
+pub async fn delete_alert_handler(
+    State(state): State<AppState>,
+    Path(alert_id): Path<String>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM alerts WHERE alert_id = $1")
+        .bind(&alert_id)
+        .execute(&state.db)
+        .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (StatusCode::OK, Json(serde_json::json!({"success": true}))),
+        Ok(_) => (StatusCode::NOT_FOUND, Json(serde_json::json!({"success": false, "error": "Alert not found"}))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"success": false, "error": e.to_string()}))),
+    }
+}
+
+/// Link (or unlink) the Telegram chat that receives this user's notifications.
+pub async fn link_telegram_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+    Json(request): Json<LinkTelegramRequest>,
+) -> impl IntoResponse {
+    let result = sqlx::query(
+        "INSERT INTO users (user_id, telegram_chat_id) VALUES ($1, $2) \
+         ON CONFLICT (user_id) DO UPDATE SET telegram_chat_id = $2, updated_at = NOW()"
+    )
+    .bind(user_id)
+    .bind(request.chat_id)
+    .execute(&state.db)
+    .await;
+
+    match result {
+        Ok(_) => (StatusCode::OK, Json(serde_json::json!({ "success": true, "chat_id": request.chat_id }))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": format!("Database error: {}", e) }))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(user_id: i64, message: &str) -> Notification {
+        create_notification(user_id, message.to_string(), "trade".to_string(), "high".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_stays_queued_for_polling() {
+        let queue = NotificationQueue::new();
+        queue.push(note(1, "a")).await;
+        queue.push(note(1, "b")).await;
+
+        let mut taken = queue.take_undelivered(1).await;
+        assert_eq!(taken.len(), 2);
+        queue.push(note(1, "c")).await; // Arrives while sending
+
+        taken[1].delivery_failed = true;
+        queue.requeue(1, taken.split_off(1)).await;
+
+        // "b" stays ahead of "c" but is not retried over Telegram
+        assert_eq!(queue.undelivered_users().await, vec![1]);
+        assert_eq!(queue.take_undelivered(1).await.iter().map(|n| n.message.as_str()).collect::<Vec<_>>(), vec!["c"]);
+        assert!(queue.undelivered_users().await.is_empty());
+        assert_eq!(queue.drain(1).await.iter().map(|n| n.message.as_str()).collect::<Vec<_>>(), vec!["b"]);
+    }
+
+    fn price_alert(condition: &str, repeat: bool) -> Alert {
+        new_alert(CreateAlertRequest {
+            user_id: 1,
+            alert_type: "price".to_string(),
+            chain: "solana".to_string(),
+            token: Some("Mint".to_string()),
+            threshold: 2.0,
+            condition: condition.to_string(),
+            repeat,
+        }, 0).unwrap()
+    }
+
+    #[test]
+    fn test_one_shot_alert_fires_once() {
+        let mut alert = price_alert("above", false);
+        assert_eq!(evaluate_alert(&alert, 1.5), AlertOutcome::Unchanged);
+        assert_eq!(evaluate_alert(&alert, 2.5), AlertOutcome::Fire);
+        // After firing the row is inactive and disarmed, and never re-arms
+        alert.armed = false;
+        assert_eq!(evaluate_alert(&alert, 1.0), AlertOutcome::Unchanged);
+    }
+
+    #[test]
+    fn test_repeating_alert_rearms_after_condition_clears() {
+        let mut alert = price_alert("below", true);
+        assert_eq!(evaluate_alert(&alert, 1.0), AlertOutcome::Fire);
+        alert.armed = false;
+        assert_eq!(evaluate_alert(&alert, 1.5), AlertOutcome::Unchanged); // Still below
+        assert_eq!(evaluate_alert(&alert, 3.0), AlertOutcome::Rearm);
+        alert.armed = true;
+        assert_eq!(evaluate_alert(&alert, 1.9), AlertOutcome::Fire);
+    }
+
+    #[test]
+    fn test_new_alert_validation() {
+        let base = || CreateAlertRequest {
+            user_id: 1,
+            alert_type: "balance".to_string(),
+            chain: "solana".to_string(),
+            token: Some("ignored".to_string()),
+            threshold: 100.0,
+            condition: "below".to_string(),
+            repeat: false,
+        };
+        assert_eq!(new_alert(base(), 0).unwrap().token, None);
+        assert!(new_alert(CreateAlertRequest { alert_type: "price".to_string(), token: None, ..base() }, 0).is_err());
+        assert!(new_alert(CreateAlertRequest { condition: "sideways".to_string(), ..base() }, 0).is_err());
+        assert!(new_alert(CreateAlertRequest { threshold: f64::NAN, ..base() }, 0).is_err());
+        assert!(new_alert(CreateAlertRequest { alert_type: "tp".to_string(), ..base() }, 0).is_err());
+    }
+
+    #[test]
+    fn test_telegram_rate_limit_backoff() {
+        let limited = parse_telegram_response(429, &serde_json::json!({ "ok": false, "parameters": { "retry_after": 7 } }));
+        assert_eq!(limited, Err(TelegramError::RateLimited(7)));
+        assert_eq!(telegram_backoff(&TelegramError::RateLimited(7), 1), Some(std::time::Duration::from_secs(7)));
+        assert_eq!(telegram_backoff(&TelegramError::RateLimited(120), 1), None);
+
+        assert_eq!(parse_telegram_response(200, &serde_json::json!({ "ok": true })), Ok(()));
+        let blocked = parse_telegram_response(403, &serde_json::json!({ "ok": false, "description": "Forbidden: bot was blocked by the user" }));
+        assert!(matches!(blocked, Err(TelegramError::Failed(ref m)) if m.contains("blocked")));
+        assert_eq!(telegram_backoff(&TelegramError::NotLinked(1), 1), None);
+    }
+}
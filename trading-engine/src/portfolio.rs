@@ -1,13 +1,23 @@
 // Portfolio Analytics Module - Production Ready
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
 use crate::balance::WalletBalance;
+use crate::AppState;
 
 #[derive(Debug, Serialize)]
 pub struct PortfolioSummary {
     pub user_id: i64,
     pub total_value_usd: f64,
-    pub total_profit_loss_usd: f64,
+    pub total_profit_loss_usd: f64, // realized + unrealized
     pub total_profit_loss_percent: f64,
+    pub realized_pnl_usd: f64,   // Closed sells
+    pub unrealized_pnl_usd: f64, // Open positions at current price
     pub active_positions: usize,
     pub wallets: Vec<WalletBalance>,
     pub positions_pnl: f64,
@@ -27,14 +37,38 @@ pub struct PortfolioStats {
     pub total_volume: f64,
 }
 
+/// Open-position PnL in USD from (tokens held, entry_price, current_price). Positions with an
+/// unknown entry or token quantity are skipped.
+pub fn unrealized_pnl_usd(positions: impl IntoIterator<Item = (Option<f64>, f64, f64)>) -> f64 {
+    positions.into_iter()
+        .filter(|(_, entry, _)| *entry > 0.0)
+        .filter_map(|(tokens, entry, current)| tokens.map(|tokens| (current - entry) * tokens))
+        .sum()
+}
+
+/// Sum of recorded sell PnL (USD) for a user.
+pub async fn load_realized_pnl<'e, E: sqlx::PgExecutor<'e>>(executor: E, user_id: i64) -> Result<f64, String> {
+    sqlx::query_scalar::<_, f64>(
+        "SELECT COALESCE(SUM(profit_loss), 0)::float8 FROM transactions WHERE user_id = $1 AND type = 'SELL' AND profit_loss IS NOT NULL"
+    )
+    .bind(user_id)
+    .fetch_one(executor)
+    .await
+    .map_err(|e| format!("Database error: {}", e))
+}
+
+/// `positions_pnl` is unrealized. Wallet balances already value every holding at market,
+/// so the total value is the wallets alone.
 pub fn calculate_portfolio_summary(
     user_id: i64,
     wallets: Vec<WalletBalance>,
+    realized_pnl_usd: f64,
     positions_pnl: f64,
     active_positions: usize,
 ) -> PortfolioSummary {
     let total_wallet_value: f64 = wallets.iter().map(|w| w.total_usd).sum();
-    let total_value = total_wallet_value + positions_pnl;
+    let total_value = total_wallet_value;
+    let total_pnl = realized_pnl_usd + positions_pnl;
     
     use std::time::{SystemTime, UNIX_EPOCH};
     let timestamp = SystemTime::now()
@@ -45,12 +79,14 @@ pub fn calculate_portfolio_summary(
     PortfolioSummary {
         user_id,
         total_value_usd: total_value,
-        total_profit_loss_usd: positions_pnl,
+        total_profit_loss_usd: total_pnl,
         total_profit_loss_percent: if total_wallet_value > 0.0 {
-            (positions_pnl / total_wallet_value) * 100.0
+            (total_pnl / total_wallet_value) * 100.0
         } else {
             0.0
         },
+        realized_pnl_usd,
+        unrealized_pnl_usd: positions_pnl,
         active_positions,
         wallets,
         positions_pnl,
@@ -100,3 +136,184 @@ pub fn calculate_portfolio_stats(positions: Vec<f64>) -> PortfolioStats {
         total_volume,
     }
 }
+
+// ==================== HISTORY ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, sqlx::FromRow)]
+pub struct PortfolioSnapshot {
+    pub ts: i64,
+    pub total_value_usd: f64,
+}
+
+/// `PORTFOLIO_SNAPSHOT_SECS`: how often the worker records each user's value. 0 disables it.
+pub fn snapshot_interval_secs() -> u64 {
+    std::env::var("PORTFOLIO_SNAPSHOT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3600)
+}
+
+pub async fn save_snapshot(user_id: i64, snapshot: PortfolioSnapshot, pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO portfolio_snapshots (user_id, ts, total_value_usd) VALUES ($1, $2, $3) \
+         ON CONFLICT (user_id, ts) DO UPDATE SET total_value_usd = $3"
+    )
+    .bind(user_id)
+    .bind(snapshot.ts)
+    .bind(snapshot.total_value_usd)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    Ok(())
+}
+
+pub async fn load_snapshots(user_id: i64, from: i64, to: i64, pool: &PgPool) -> Result<Vec<PortfolioSnapshot>, String> {
+    sqlx::query_as::<_, PortfolioSnapshot>(
+        "SELECT ts, total_value_usd FROM portfolio_snapshots WHERE user_id = $1 AND ts >= $2 AND ts <= $3 ORDER BY ts"
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))
+}
+
+/// Last snapshot in each `interval_secs` bucket. Input must be sorted by ts.
+pub fn downsample(points: &[PortfolioSnapshot], interval_secs: i64) -> Vec<PortfolioSnapshot> {
+    if interval_secs <= 1 {
+        return points.to_vec();
+    }
+    let mut out: Vec<PortfolioSnapshot> = Vec::new();
+    for point in points {
+        match out.last_mut() {
+            Some(last) if last.ts.div_euclid(interval_secs) == point.ts.div_euclid(interval_secs) => *last = *point,
+            _ => out.push(*point),
+        }
+    }
+    out
+}
+
+/// Largest peak-to-trough fall, as a positive percent of the peak.
+pub fn max_drawdown_pct(points: &[PortfolioSnapshot]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut worst: f64 = 0.0;
+    for point in points {
+        peak = peak.max(point.total_value_usd);
+        if peak > 0.0 {
+            worst = worst.max((peak - point.total_value_usd) / peak * 100.0);
+        }
+    }
+    worst
+}
+
+/// Change from the first to the last point. None without a positive starting value.
+pub fn period_return_pct(points: &[PortfolioSnapshot]) -> Option<f64> {
+    let (first, last) = (points.first()?, points.last()?);
+    (first.total_value_usd > 0.0)
+        .then(|| (last.total_value_usd - first.total_value_usd) / first.total_value_usd * 100.0)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    pub from: Option<i64>,     // Unix seconds. Defaults to 7 days before `to`
+    pub to: Option<i64>,       // Unix seconds. Defaults to now
+    pub interval: Option<i64>, // Bucket size in seconds. Defaults to every snapshot
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortfolioHistoryResponse {
+    pub user_id: i64,
+    pub from: i64,
+    pub to: i64,
+    pub points: Vec<PortfolioSnapshot>,
+    pub max_drawdown_pct: f64,
+    pub period_return_pct: Option<f64>,
+}
+
+pub async fn get_portfolio_history_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+    Query(query): Query<HistoryQuery>,
+) -> impl IntoResponse {
+    let to = query.to.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let from = query.from.unwrap_or(to - 7 * 86_400);
+    if from > to {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": "from must be before to"}))).into_response();
+    }
+
+    match load_snapshots(user_id, from, to, &state.db).await {
+        Ok(snapshots) => {
+            let points = downsample(&snapshots, query.interval.unwrap_or(0));
+            (StatusCode::OK, Json(PortfolioHistoryResponse {
+                user_id,
+                from,
+                to,
+                max_drawdown_pct: max_drawdown_pct(&points),
+                period_return_pct: period_return_pct(&points),
+                points,
+            })).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e}))).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(values: &[(i64, f64)]) -> Vec<PortfolioSnapshot> {
+        values.iter().map(|&(ts, v)| PortfolioSnapshot { ts, total_value_usd: v }).collect()
+    }
+
+    #[test]
+    fn test_drawdown_and_return() {
+        let points = series(&[(0, 100.0), (1, 120.0), (2, 90.0), (3, 110.0), (4, 130.0)]);
+        assert!((max_drawdown_pct(&points) - 25.0).abs() < 1e-9);
+        assert!((period_return_pct(&points).unwrap() - 30.0).abs() < 1e-9);
+        assert_eq!(max_drawdown_pct(&[]), 0.0);
+        assert_eq!(period_return_pct(&series(&[(0, 0.0), (1, 50.0)])), None);
+    }
+
+    #[test]
+    fn test_realized_and_unrealized_pnl_are_separate() {
+        // Closed winner: +$40 recorded on the SELL. Open loser: 100 tokens bought at $2, now $1.5
+        let realized = 40.0;
+        let unrealized = unrealized_pnl_usd([(Some(100.0), 2.0, 1.5), (Some(10.0), 0.0, 5.0), (None, 1.0, 3.0)]);
+        assert_eq!(unrealized, -50.0);
+
+        let summary = calculate_portfolio_summary(1, vec![], realized, unrealized, 1);
+        assert_eq!(summary.realized_pnl_usd, 40.0);
+        assert_eq!(summary.unrealized_pnl_usd, -50.0);
+        assert_eq!(summary.total_profit_loss_usd, -10.0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_realized_pnl_from_transactions_table() {
+        let mut conn = crate::test_db::connection().await;
+        sqlx::query("CREATE TEMP TABLE transactions (user_id BIGINT, type VARCHAR(20) NOT NULL, profit_loss DOUBLE PRECISION)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        for (user_id, kind, pnl) in [(1, "SELL", Some(40.0)), (1, "BUY", None), (1, "SELL", None), (2, "SELL", Some(-7.0))] {
+            sqlx::query("INSERT INTO transactions (user_id, type, profit_loss) VALUES ($1, $2, $3)")
+                .bind(user_id as i64)
+                .bind(kind)
+                .bind(pnl)
+                .execute(&mut conn)
+                .await
+                .unwrap();
+        }
+        assert_eq!(load_realized_pnl(&mut conn, 1).await.unwrap(), 40.0);
+        assert_eq!(load_realized_pnl(&mut conn, 3).await.unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_downsample_keeps_last_per_bucket() {
+        let points = series(&[(0, 1.0), (1800, 2.0), (3600, 3.0), (5000, 4.0), (7300, 5.0)]);
+        let hourly = downsample(&points, 3600);
+        assert_eq!(hourly, series(&[(1800, 2.0), (5000, 4.0), (7300, 5.0)]));
+        assert_eq!(downsample(&points, 0), points);
+    }
+}
@@ -0,0 +1,179 @@
+// Position Stream Module
+// Live position updates over WebSocket. The price worker publishes to a broadcast
+// channel, and each socket forwards only its own user's updates.
+
+use serde::Serialize;
+use std::time::Duration;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    response::IntoResponse,
+};
+use tokio::sync::broadcast;
+use crate::AppState;
+
+/// Updates buffered per subscriber. A socket that falls further behind skips ahead.
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PositionUpdate {
+    #[serde(rename = "type")]
+    pub kind: &'static str, // Always "position"
+    pub user_id: i64,
+    pub position_id: String,
+    pub chain: String,
+    pub token_address: String,
+    pub token_amount: Option<f64>, // Tokens held (None = unknown)
+    pub entry_price: f64,
+    pub current_price: f64,
+    pub pnl_percent: f64,
+    pub pnl_usd: Option<f64>, // Only known with the token quantity
+    pub timestamp: i64,
+}
+
+impl PositionUpdate {
+    pub fn new(user_id: i64, position_id: &str, chain: &str, token_address: &str, token_amount: Option<f64>, entry_price: f64, current_price: f64) -> Self {
+        let pnl_percent = if entry_price > 0.0 { (current_price - entry_price) / entry_price * 100.0 } else { 0.0 };
+        Self {
+            kind: "position",
+            user_id,
+            position_id: position_id.to_string(),
+            chain: chain.to_string(),
+            token_address: token_address.to_string(),
+            token_amount,
+            entry_price,
+            current_price,
+            pnl_percent,
+            pnl_usd: token_amount.filter(|_| entry_price > 0.0).map(|tokens| crate::positions::realized_pnl(entry_price, current_price, tokens)),
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PositionStream {
+    sender: broadcast::Sender<PositionUpdate>,
+}
+
+impl Default for PositionStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PositionStream {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// No-op when nobody is listening.
+    pub fn publish(&self, update: PositionUpdate) {
+        let _ = self.sender.send(update);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PositionUpdate> {
+        self.sender.subscribe()
+    }
+}
+
+/// `WS_HEARTBEAT_SECS`: ping interval so proxies don't drop idle sockets.
+fn heartbeat_interval() -> Duration {
+    let secs = std::env::var("WS_HEARTBEAT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|&s| s > 0)
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+/// Next update for `user_id`, skipping other users. None once the channel is closed.
+pub async fn next_for_user(rx: &mut broadcast::Receiver<PositionUpdate>, user_id: i64) -> Option<PositionUpdate> {
+    loop {
+        match rx.recv().await {
+            Ok(update) if update.user_id == user_id => return Some(update),
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::debug!("Position stream for user {} lagged, skipped {} updates", user_id, skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+pub async fn ws_positions_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let rx = state.position_stream.subscribe();
+    ws.on_upgrade(move |socket| stream_positions(socket, rx, user_id))
+}
+
+async fn stream_positions(mut socket: WebSocket, mut rx: broadcast::Receiver<PositionUpdate>, user_id: i64) {
+    tracing::debug!("📡 Position stream opened for user {}", user_id);
+    let mut heartbeat = tokio::time::interval(heartbeat_interval());
+    heartbeat.tick().await; // First tick fires immediately
+
+    loop {
+        tokio::select! {
+            update = next_for_user(&mut rx, user_id) => {
+                let Some(update) = update else { break };
+                let Ok(json) = serde_json::to_string(&update) else { continue };
+                if socket.send(Message::Text(json)).await.is_err() {
+                    break;
+                }
+            }
+            _ = heartbeat.tick() => {
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                // Pongs and client chatter are ignored. axum answers pings itself
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    tracing::debug!("📡 Position stream closed for user {}", user_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(user_id: i64, position_id: &str) -> PositionUpdate {
+        PositionUpdate::new(user_id, position_id, "solana", "Mint", Some(100.0), 0.5, 0.75)
+    }
+
+    #[tokio::test]
+    async fn test_stream_filters_by_user() {
+        let stream = PositionStream::new();
+        let mut rx = stream.subscribe();
+        stream.publish(update(1, "a"));
+        stream.publish(update(2, "b"));
+        stream.publish(update(1, "c"));
+        drop(stream);
+
+        assert_eq!(next_for_user(&mut rx, 1).await.map(|u| u.position_id), Some("a".to_string()));
+        assert_eq!(next_for_user(&mut rx, 1).await.map(|u| u.position_id), Some("c".to_string()));
+        assert_eq!(next_for_user(&mut rx, 1).await, None);
+    }
+
+    #[test]
+    fn test_update_pnl() {
+        let u = update(1, "a");
+        assert!((u.pnl_percent - 50.0).abs() < 1e-9);
+        assert!((u.pnl_usd.unwrap() - 25.0).abs() < 1e-9);
+        assert_eq!(serde_json::to_value(&u).unwrap()["type"], "position");
+
+        // Without the token quantity there's no USD figure, only the percent
+        let unknown = PositionUpdate::new(1, "b", "solana", "Mint", None, 0.5, 0.75);
+        assert_eq!(unknown.pnl_usd, None);
+        assert!((unknown.pnl_percent - 50.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,816 @@
+// Position Management Module
+// Bookkeeping helpers shared by the trade handlers and background workers
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// ==================== POSITION MERGING ====================
+
+/// Merge an additional lot into an existing position. Amounts are token quantities, so the
+/// entry is the average price paid per token.
+/// Returns (new_amount, weighted_average_entry_price).
+pub fn merge_lot(
+    existing_amount: f64,
+    existing_entry_price: f64,
+    added_amount: f64,
+    added_price: f64,
+) -> (f64, f64) {
+    let total_amount = existing_amount + added_amount;
+    if total_amount <= 0.0 {
+        return (0.0, existing_entry_price);
+    }
+
+    let weighted_entry = (existing_amount * existing_entry_price + added_amount * added_price) / total_amount;
+    (total_amount, weighted_entry)
+}
+
+/// A lot's weight in a merge: its token quantity, or for a lot of unknown size the native
+/// amount spent over its entry price (proportional to tokens while the native price holds).
+pub fn merge_weight(token_amount: Option<f64>, spent: f64, entry_price: f64) -> f64 {
+    match token_amount {
+        Some(tokens) => tokens,
+        None if entry_price > 0.0 => spent / entry_price,
+        None => 0.0,
+    }
+}
+
+// ==================== ENTRY PRICE ====================
+
+/// Entry price stored when no market price was available. Always paired with `price_unknown = true`.
+pub const UNKNOWN_ENTRY_PRICE: f64 = 0.0;
+
+/// Look up the entry price for a new position. Returns (price, price_unknown).
+pub async fn resolve_entry_price<F, Fut>(lookup: F) -> (f64, bool)
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<f64, String>>,
+{
+    match lookup().await {
+        Ok(price) if price.is_finite() && price > 0.0 => (price, false),
+        Ok(price) => {
+            tracing::warn!("⚠️ Price source returned unusable price {}, entry price unknown", price);
+            (UNKNOWN_ENTRY_PRICE, true)
+        }
+        Err(e) => {
+            tracing::warn!("⚠️ Price lookup failed ({}), entry price unknown", e);
+            (UNKNOWN_ENTRY_PRICE, true)
+        }
+    }
+}
+
+// ==================== PARTIAL CLOSES ====================
+
+/// Remaining amounts below this are dust and close the position.
+pub const CLOSE_EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CloseResult {
+    pub sold: f64,
+    pub remaining: f64,
+    pub closed: bool,
+}
+
+/// Apply a sell of `percent` (clamped to 0-100) to a position holding `amount`.
+pub fn apply_partial_close(amount: f64, percent: f64) -> CloseResult {
+    let fraction = (percent / 100.0).clamp(0.0, 1.0);
+    let sold = amount * fraction;
+    let remaining = (amount - sold).max(0.0);
+
+    if remaining <= CLOSE_EPSILON {
+        CloseResult { sold: amount, remaining: 0.0, closed: true }
+    } else {
+        CloseResult { sold, remaining, closed: false }
+    }
+}
+
+/// How much of a lot a sell closes, in tokens (when the lot's token quantity is known) and
+/// in the native currency spent on them. Both shrink by the same share, and the token
+/// quantity decides when what's left is dust.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LotClose {
+    pub tokens_sold: Option<f64>,
+    pub tokens_remaining: Option<f64>,
+    pub spent_sold: f64,
+    pub spent_remaining: f64,
+    pub closed: bool,
+}
+
+/// Apply a sell of `percent` to a lot holding `token_amount` tokens bought for `spent`.
+pub fn close_lot(token_amount: Option<f64>, spent: f64, percent: f64) -> LotClose {
+    let by_spent = apply_partial_close(spent, percent);
+    let Some(held) = token_amount else {
+        return LotClose {
+            tokens_sold: None,
+            tokens_remaining: None,
+            spent_sold: by_spent.sold,
+            spent_remaining: by_spent.remaining,
+            closed: by_spent.closed,
+        };
+    };
+    let by_tokens = apply_partial_close(held, percent);
+    if by_tokens.closed {
+        return LotClose { tokens_sold: Some(held), tokens_remaining: Some(0.0), spent_sold: spent, spent_remaining: 0.0, closed: true };
+    }
+    LotClose {
+        tokens_sold: Some(by_tokens.sold),
+        tokens_remaining: Some(by_tokens.remaining),
+        spent_sold: by_spent.sold,
+        spent_remaining: by_spent.remaining,
+        closed: false,
+    }
+}
+
+/// Realized PnL in price units for selling `quantity` bought at `entry_price`.
+pub fn realized_pnl(entry_price: f64, exit_price: f64, quantity: f64) -> f64 {
+    (exit_price - entry_price) * quantity
+}
+
+// ==================== EXIT TRIGGERS ====================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitTrigger {
+    TakeProfit,
+    StopLoss,
+    TrailingStop,
+}
+
+impl ExitTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExitTrigger::TakeProfit => "take profit",
+            ExitTrigger::StopLoss => "stop loss",
+            ExitTrigger::TrailingStop => "trailing stop",
+        }
+    }
+}
+
+/// Check a position's TP/SL/trailing stop against the latest price. Percentages are
+/// positive distances (TP/SL from entry, trailing from the high-water mark); zero or
+/// negative disables that rule. With both stops set, the tighter (higher) one wins.
+pub fn evaluate_exit(
+    entry_price: f64,
+    current_price: f64,
+    take_profit_percent: f64,
+    stop_loss_percent: f64,
+    trailing_stop_percent: Option<f64>,
+    high_water_mark: f64,
+) -> Option<ExitTrigger> {
+    if entry_price <= 0.0 || current_price <= 0.0 {
+        return None;
+    }
+
+    let change_percent = (current_price - entry_price) / entry_price * 100.0;
+    if take_profit_percent > 0.0 && change_percent >= take_profit_percent {
+        return Some(ExitTrigger::TakeProfit);
+    }
+
+    let fixed_stop = (stop_loss_percent > 0.0)
+        .then(|| (entry_price * (1.0 - stop_loss_percent / 100.0), ExitTrigger::StopLoss));
+    let trailing_stop = trailing_stop_percent
+        .filter(|t| *t > 0.0)
+        .map(|t| {
+            let peak = update_high_water_mark(high_water_mark.max(entry_price), current_price);
+            (peak * (1.0 - t / 100.0), ExitTrigger::TrailingStop)
+        });
+
+    let stop = match (fixed_stop, trailing_stop) {
+        (Some(f), Some(t)) => Some(if t.0 > f.0 { t } else { f }),
+        (f, t) => f.or(t),
+    };
+
+    match stop {
+        Some((stop_price, trigger)) if current_price <= stop_price => Some(trigger),
+        _ => None,
+    }
+}
+
+/// Highest price seen since entry.
+pub fn update_high_water_mark(high_water_mark: f64, current_price: f64) -> f64 {
+    high_water_mark.max(current_price)
+}
+
+/// Per-token price a sell actually filled at. `None` when there's nothing to divide by.
+pub fn fill_price(proceeds_usd: f64, tokens_sold: f64) -> Option<f64> {
+    (proceeds_usd > 0.0 && tokens_sold > 0.0).then(|| proceeds_usd / tokens_sold)
+}
+
+// ==================== TAKE-PROFIT LADDER ====================
+
+/// One scale-out step: sell `close_pct` of the original position once the price
+/// is `trigger_pct` above entry. Each rung fires once.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LadderRung {
+    pub trigger_pct: f64,
+    pub close_pct: f64,
+    #[serde(default)]
+    pub fired: bool,
+}
+
+/// Validate (trigger_pct, close_pct) pairs and order them by trigger.
+pub fn build_ladder(rungs: &[(f64, f64)]) -> Result<Vec<LadderRung>, String> {
+    if rungs.is_empty() {
+        return Err("Take-profit ladder needs at least one rung".to_string());
+    }
+    let valid = |(trigger, close): &(f64, f64)| *trigger > 0.0 && *close > 0.0 && *close <= 100.0;
+    if !rungs.iter().all(valid) {
+        return Err("Ladder rungs need a trigger above 0% and a close between 0 and 100%".to_string());
+    }
+    let total: f64 = rungs.iter().map(|(_, close)| close).sum();
+    if total > 100.0 + CLOSE_EPSILON {
+        return Err(format!("Ladder closes {}% of the position, must be at most 100%", total));
+    }
+
+    let mut ladder: Vec<LadderRung> = rungs
+        .iter()
+        .map(|(trigger_pct, close_pct)| LadderRung { trigger_pct: *trigger_pct, close_pct: *close_pct, fired: false })
+        .collect();
+    ladder.sort_by(|a, b| a.trigger_pct.total_cmp(&b.trigger_pct));
+    Ok(ladder)
+}
+
+/// Unfired rungs the price has reached.
+pub fn crossed_rungs(ladder: &[LadderRung], entry_price: f64, current_price: f64) -> Vec<usize> {
+    if entry_price <= 0.0 || current_price <= 0.0 {
+        return Vec::new();
+    }
+    let change_percent = (current_price - entry_price) / entry_price * 100.0;
+    ladder
+        .iter()
+        .enumerate()
+        .filter(|(_, rung)| !rung.fired && change_percent >= rung.trigger_pct)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Percent of the *current* holding to sell for `rungs`. Rungs are sized against the
+/// original position, so earlier fills shrink the base (25/25/50 sells 25%, 33.3%, 100%).
+pub fn ladder_sell_percent(ladder: &[LadderRung], rungs: &[usize]) -> f64 {
+    let already_sold: f64 = ladder.iter().filter(|r| r.fired).map(|r| r.close_pct).sum();
+    let remaining = 100.0 - already_sold;
+    if remaining <= CLOSE_EPSILON {
+        return 0.0;
+    }
+    let closing: f64 = rungs.iter().filter_map(|i| ladder.get(*i)).map(|r| r.close_pct).sum();
+    (closing / remaining * 100.0).clamp(0.0, 100.0)
+}
+
+// ==================== EXTERNAL CLOSE RECONCILIATION ====================
+
+pub const EXTERNAL_CLOSE_REASON: &str = "closed externally";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reconciliation {
+    StillHeld,
+    ClosedExternally,
+}
+
+/// Compare an open position with the wallet's on-chain token balance (in token units).
+/// Anything at or below `dust` counts as sold outside the engine.
+pub fn reconcile_with_balance(onchain_amount: f64, dust: f64) -> Reconciliation {
+    if onchain_amount <= dust {
+        Reconciliation::ClosedExternally
+    } else {
+        Reconciliation::StillHeld
+    }
+}
+
+/// What a reconcile does to the open lots sharing one wallet balance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LotAdjustment {
+    Unchanged,
+    /// Keep this fraction of every lot (amount and cost basis).
+    Scaled(f64),
+    Closed,
+}
+
+/// Compare the wallet's balance with what the lots should hold (`expected`, in token units,
+/// when known). Zero/dust closes them. A balance more than `drift_pct` below expected shrinks
+/// every lot pro rata. Holding more than expected (tokens bought elsewhere) changes nothing.
+pub fn reconcile_lots(onchain_amount: f64, expected: Option<f64>, dust: f64, drift_pct: f64) -> LotAdjustment {
+    if reconcile_with_balance(onchain_amount, dust) == Reconciliation::ClosedExternally {
+        return LotAdjustment::Closed;
+    }
+    match expected {
+        Some(expected) if expected > 0.0 && onchain_amount < expected * (1.0 - drift_pct / 100.0) => {
+            LotAdjustment::Scaled(onchain_amount / expected)
+        }
+        _ => LotAdjustment::Unchanged,
+    }
+}
+
+/// Tokens a lot should hold, from what was paid for them. Only known with a cost basis.
+pub fn expected_tokens(cost_basis_usd: Option<f64>, entry_price: f64) -> Option<f64> {
+    cost_basis_usd.filter(|_| entry_price > 0.0).map(|basis| basis / entry_price)
+}
+
+/// Tokens a new lot holds: what the swap delivered, else estimated from its USD cost at the
+/// entry price (simulated buys). None when neither is known.
+pub fn lot_token_amount(received: Option<f64>, cost_basis_usd: Option<f64>, entry_price: f64) -> Option<f64> {
+    received
+        .filter(|tokens| tokens.is_finite() && *tokens > 0.0)
+        .or_else(|| expected_tokens(cost_basis_usd, entry_price))
+}
+
+// ==================== COST BASIS ====================
+
+/// USD spent opening (or adding to) a position. Only Solana buys are priced in USD, as
+/// SOL amount x SOL price; None means unknown and PnL falls back to entry/exit prices.
+pub fn buy_cost_basis(chain: &str, amount_native: f64, sol_price_usd: Option<f64>) -> Option<f64> {
+    match (chain, sol_price_usd) {
+        ("solana", Some(price)) if price > 0.0 && amount_native > 0.0 => Some(amount_native * price),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CostSplit {
+    pub sold: f64,
+    pub remaining: f64,
+}
+
+/// Split a position's cost basis pro rata between the `sold` tokens and what's left of `held`.
+pub fn split_cost_basis(cost_basis_usd: f64, held: f64, sold: f64) -> CostSplit {
+    if held <= 0.0 || sold >= held {
+        return CostSplit { sold: cost_basis_usd, remaining: 0.0 };
+    }
+    let sold_cost = cost_basis_usd * (sold.max(0.0) / held);
+    CostSplit { sold: sold_cost, remaining: cost_basis_usd - sold_cost }
+}
+
+/// Realized (USD, percent) PnL of a sell: what it returned against the cost of what was
+/// sold when both are known, else the price move on the tokens sold. USD is None when
+/// neither is known (e.g. an EVM lot of unknown size).
+pub fn sell_pnl(proceeds_usd: Option<f64>, cost_sold_usd: Option<f64>, tokens_sold: Option<f64>, entry_price: f64, exit_price: f64) -> (Option<f64>, f64) {
+    if let (Some(received), Some(cost)) = (proceeds_usd, cost_sold_usd) {
+        let pnl = received - cost;
+        return (Some(pnl), if cost > 0.0 { pnl / cost * 100.0 } else { 0.0 });
+    }
+    if entry_price <= 0.0 {
+        return (None, 0.0);
+    }
+    let percent = (exit_price - entry_price) / entry_price * 100.0;
+    (tokens_sold.map(|tokens| realized_pnl(entry_price, exit_price, tokens)), percent)
+}
+
+/// Expected (USD, percent) PnL of selling `sold` of `held` tokens for `proceeds_usd`, against
+/// the cost basis when known and the entry price otherwise. None when there's nothing to compare with.
+pub fn estimate_sell_pnl(proceeds_usd: f64, cost_basis_usd: Option<f64>, held: f64, sold: f64, entry_price: f64) -> Option<(f64, f64)> {
+    let cost = match cost_basis_usd {
+        Some(basis) => split_cost_basis(basis, held, sold).sold,
+        None => entry_price * sold,
+    };
+    (cost > 0.0).then(|| {
+        let pnl = proceeds_usd - cost;
+        (pnl, pnl / cost * 100.0)
+    })
+}
+
+// ==================== EXIT SLIPPAGE ====================
+
+/// Slippage used for sells when a position has none stored (5%).
+pub const DEFAULT_EXIT_SLIPPAGE_BPS: u64 = 500;
+
+/// Exit slippage to store on a new position: the explicit value if given,
+/// otherwise the buy slippage (percent) converted to bps.
+pub fn initial_exit_slippage_bps(requested_bps: Option<u64>, buy_slippage_pct: f64) -> i32 {
+    let bps = requested_bps.unwrap_or_else(|| (buy_slippage_pct.max(0.0) * 100.0).round() as u64);
+    bps.clamp(1, 10_000) as i32
+}
+
+/// Slippage (bps) to use when selling a position.
+pub fn exit_slippage_bps(stored: Option<i32>) -> u64 {
+    match stored {
+        Some(bps) if bps > 0 => bps as u64,
+        _ => DEFAULT_EXIT_SLIPPAGE_BPS,
+    }
+}
+
+// ==================== SELL BY TOKEN ====================
+
+/// Which lots a sell-by-token drains first when a token is held in several positions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LotOrder {
+    #[default]
+    Fifo, // Oldest position first
+    Lifo, // Newest position first
+}
+
+/// Split a sell of `percent` of the combined holding across `lots` (position_id, tokens held),
+/// given oldest first. Returns (position_id, percent of that position) in the order they
+/// should be sold. Lots the sell doesn't reach are left out.
+pub fn allocate_token_sell(lots: &[(String, f64)], percent: f64, order: LotOrder) -> Vec<(String, f64)> {
+    let held: Vec<&(String, f64)> = match order {
+        LotOrder::Fifo => lots.iter().filter(|(_, amount)| *amount > 0.0).collect(),
+        LotOrder::Lifo => lots.iter().rev().filter(|(_, amount)| *amount > 0.0).collect(),
+    };
+    let total: f64 = held.iter().map(|(_, amount)| amount).sum();
+    let mut to_sell = total * (percent / 100.0).clamp(0.0, 1.0);
+
+    let mut plan = Vec::new();
+    for (position_id, amount) in held {
+        if to_sell <= CLOSE_EPSILON {
+            break;
+        }
+        let lot_percent = if to_sell >= amount - CLOSE_EPSILON { 100.0 } else { to_sell / amount * 100.0 };
+        to_sell -= amount.min(to_sell);
+        plan.push((position_id.clone(), lot_percent));
+    }
+    plan
+}
+
+// ==================== AUTO-SELL BACKOFF ====================
+// A failed automated sell isn't retried on every price tick. Each position waits
+// AUTO_SELL_RETRY_SECS after its first failure, doubling per failure up to MAX_SELL_RETRY_SECS.
+
+const MAX_SELL_RETRY_SECS: i64 = 1800;
+
+/// Base wait after a failed automated sell, from `AUTO_SELL_RETRY_SECS` (default 30).
+pub fn sell_retry_base_secs() -> i64 {
+    std::env::var("AUTO_SELL_RETRY_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(30)
+}
+
+/// Wait before the next attempt after `failures` failed sells in a row.
+pub fn sell_retry_delay(base_secs: i64, failures: u32) -> i64 {
+    let doublings = failures.saturating_sub(1).min(20);
+    base_secs.max(1).saturating_mul(1 << doublings).min(MAX_SELL_RETRY_SECS)
+}
+
+/// Failed automated sells per position: (failures in a row, next attempt allowed at).
+#[derive(Debug, Clone, Default)]
+pub struct SellBackoff {
+    failures: Arc<Mutex<HashMap<String, (u32, i64)>>>,
+}
+
+impl SellBackoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// False while the position is waiting out a failed sell.
+    pub fn ready(&self, position_id: &str, now: i64) -> bool {
+        self.failures.lock().unwrap().get(position_id).is_none_or(|(_, retry_at)| now >= *retry_at)
+    }
+
+    /// Book a failed sell. Returns the seconds until the next attempt.
+    pub fn record_failure(&self, position_id: &str, now: i64, base_secs: i64) -> i64 {
+        let mut failures = self.failures.lock().unwrap();
+        let entry = failures.entry(position_id.to_string()).or_insert((0, now));
+        entry.0 += 1;
+        let delay = sell_retry_delay(base_secs, entry.0);
+        entry.1 = now + delay;
+        delay
+    }
+
+    pub fn clear(&self, position_id: &str) {
+        self.failures.lock().unwrap().remove(position_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sell_backoff_doubles_and_resets() {
+        assert_eq!(sell_retry_delay(30, 1), 30);
+        assert_eq!(sell_retry_delay(30, 3), 120);
+        assert_eq!(sell_retry_delay(30, 50), MAX_SELL_RETRY_SECS);
+
+        let backoff = SellBackoff::new();
+        assert!(backoff.ready("p1", 0));
+        assert_eq!(backoff.record_failure("p1", 0, 30), 30);
+        assert!(!backoff.ready("p1", 29));
+        assert!(backoff.ready("p1", 30));
+        assert!(backoff.ready("p2", 0)); // Other positions aren't held back
+
+        assert_eq!(backoff.record_failure("p1", 30, 30), 60);
+        assert!(!backoff.ready("p1", 89));
+        backoff.clear("p1");
+        assert!(backoff.ready("p1", 31));
+        assert_eq!(backoff.record_failure("p1", 31, 30), 30);
+    }
+
+    #[test]
+    fn test_merge_lot_averages_down() {
+        // 10 @ $2.00 + 30 @ $1.00 => 40 @ $1.25
+        let (amount, entry) = merge_lot(10.0, 2.0, 30.0, 1.0);
+        assert_eq!(amount, 40.0);
+        assert!((entry - 1.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_merge_weights_by_tokens_not_spend() {
+        // 1 SOL bought 1000 tokens @ $0.15, another 1 SOL bought 3000 @ $0.05: the average
+        // cost per token is $0.075, not the $0.10 a SOL-weighted mean would give
+        let held = merge_weight(Some(1000.0), 1.0, 0.15);
+        let added = merge_weight(Some(3000.0), 1.0, 0.05);
+        let (tokens, entry) = merge_lot(held, 0.15, added, 0.05);
+        assert_eq!(tokens, 4000.0);
+        assert!((entry - 0.075).abs() < 1e-12);
+
+        // Unknown sizes fall back to spend over price, which lands on the same average
+        let (_, entry) = merge_lot(merge_weight(None, 1.0, 0.15), 0.15, merge_weight(None, 1.0, 0.05), 0.05);
+        assert!((entry - 0.075).abs() < 1e-12);
+        assert_eq!(merge_weight(None, 1.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_merge_lot_averages_up() {
+        // 5 @ $1.00 + 5 @ $3.00 => 10 @ $2.00
+        let (amount, entry) = merge_lot(5.0, 1.0, 5.0, 3.0);
+        assert_eq!(amount, 10.0);
+        assert!((entry - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_exits_follow_merged_entry() {
+        // 10 @ $2.00 + 10 @ $1.00 => entry $1.50. A 20% stop now sits at $1.20, not $1.60
+        let (_, entry) = merge_lot(10.0, 2.0, 10.0, 1.0);
+        assert_eq!(evaluate_exit(entry, 1.3, 50.0, 20.0, None, entry), None);
+        assert_eq!(evaluate_exit(entry, 1.2, 50.0, 20.0, None, entry), Some(ExitTrigger::StopLoss));
+        assert_eq!(evaluate_exit(entry, 2.25, 50.0, 20.0, None, entry), Some(ExitTrigger::TakeProfit));
+    }
+
+    #[test]
+    fn test_exit_slippage_defaults_from_buy() {
+        assert_eq!(initial_exit_slippage_bps(None, 10.0), 1000);
+        assert_eq!(initial_exit_slippage_bps(Some(250), 10.0), 250);
+        assert_eq!(initial_exit_slippage_bps(Some(50_000), 10.0), 10_000);
+    }
+
+    #[test]
+    fn test_sell_uses_stored_exit_slippage() {
+        assert_eq!(exit_slippage_bps(Some(150)), 150);
+        // Positions opened before the column existed fall back to the old fixed value
+        assert_eq!(exit_slippage_bps(None), DEFAULT_EXIT_SLIPPAGE_BPS);
+        assert_eq!(exit_slippage_bps(Some(0)), DEFAULT_EXIT_SLIPPAGE_BPS);
+    }
+
+    #[test]
+    fn test_evaluate_exit() {
+        // Entry $1.00, TP +30%, SL -15%
+        assert_eq!(evaluate_exit(1.0, 1.29, 30.0, 15.0, None, 1.0), None);
+        assert_eq!(evaluate_exit(1.0, 1.30, 30.0, 15.0, None, 1.0), Some(ExitTrigger::TakeProfit));
+        assert_eq!(evaluate_exit(1.0, 0.85, 30.0, 15.0, None, 1.0), Some(ExitTrigger::StopLoss));
+        assert_eq!(evaluate_exit(1.0, 0.86, 30.0, 15.0, None, 1.0), None);
+        // Disabled sides and missing prices never fire
+        assert_eq!(evaluate_exit(1.0, 5.0, 0.0, 15.0, None, 1.0), None);
+        assert_eq!(evaluate_exit(0.0, 5.0, 30.0, 15.0, None, 1.0), None);
+    }
+
+    #[test]
+    fn test_trailing_stop_ratchets_with_high_water_mark() {
+        // 10% trailing stop, no TP/SL. Price runs to $2.00 then falls back.
+        let hwm = [1.2, 1.6, 2.0].iter().fold(1.0, |h, p| update_high_water_mark(h, *p));
+        assert_eq!(hwm, 2.0);
+        assert_eq!(evaluate_exit(1.0, 1.81, 0.0, 0.0, Some(10.0), hwm), None);
+        assert_eq!(evaluate_exit(1.0, 1.80, 0.0, 0.0, Some(10.0), hwm), Some(ExitTrigger::TrailingStop));
+        // A new high in the same tick never triggers
+        assert_eq!(evaluate_exit(1.0, 2.5, 0.0, 0.0, Some(10.0), hwm), None);
+    }
+
+    #[test]
+    fn test_tighter_stop_wins() {
+        // Fixed SL at $0.85, trailing 10% from entry at $0.90 - trailing is tighter
+        assert_eq!(evaluate_exit(1.0, 0.89, 0.0, 15.0, Some(10.0), 1.0), Some(ExitTrigger::TrailingStop));
+        // Fixed SL at $0.95 is tighter than a 10% trail from entry
+        assert_eq!(evaluate_exit(1.0, 0.94, 0.0, 5.0, Some(10.0), 1.0), Some(ExitTrigger::StopLoss));
+        // After a run-up to $2.00 the trail ($1.80) is tighter than the fixed SL
+        assert_eq!(evaluate_exit(1.0, 1.79, 0.0, 15.0, Some(10.0), 2.0), Some(ExitTrigger::TrailingStop));
+    }
+
+    #[test]
+    fn test_partial_closes_reduce_then_close() {
+        // 100 tokens @ $1.00 entry: sell 30% at $1.50, then the other 70 tokens (all that's left) at $2.00
+        let first = apply_partial_close(100.0, 30.0);
+        assert!((first.sold - 30.0).abs() < 1e-12);
+        assert!((first.remaining - 70.0).abs() < 1e-12);
+        assert!(!first.closed);
+
+        let second = apply_partial_close(first.remaining, 100.0);
+        assert!(second.closed);
+        assert_eq!(second.remaining, 0.0);
+        assert!((second.sold - 70.0).abs() < 1e-12);
+
+        let pnl = realized_pnl(1.0, 1.5, first.sold) + realized_pnl(1.0, 2.0, second.sold);
+        assert!((pnl - (15.0 + 70.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_close_lot_shrinks_tokens_and_spent_together() {
+        // 1.5 SOL bought 30k tokens, sell a third
+        let close = close_lot(Some(30_000.0), 1.5, 100.0 / 3.0);
+        assert!((close.tokens_sold.unwrap() - 10_000.0).abs() < 1e-6);
+        assert!((close.tokens_remaining.unwrap() - 20_000.0).abs() < 1e-6);
+        assert!((close.spent_sold - 0.5).abs() < 1e-9);
+        assert!((close.spent_remaining - 1.0).abs() < 1e-9);
+        assert!(!close.closed);
+
+        // Full sell takes everything
+        let close = close_lot(Some(30_000.0), 1.5, 100.0);
+        assert_eq!(close, LotClose { tokens_sold: Some(30_000.0), tokens_remaining: Some(0.0), spent_sold: 1.5, spent_remaining: 0.0, closed: true });
+
+        // Unknown size: only the spent amount is tracked
+        let close = close_lot(None, 2.0, 25.0);
+        assert_eq!((close.tokens_sold, close.spent_sold, close.spent_remaining, close.closed), (None, 0.5, 1.5, false));
+    }
+
+    #[test]
+    fn test_sell_pnl() {
+        // $225 back for tokens that cost $150
+        let (usd, pct) = sell_pnl(Some(225.0), Some(150.0), Some(10_000.0), 0.015, 0.0225);
+        assert!((usd.unwrap() - 75.0).abs() < 1e-9);
+        assert!((pct - 50.0).abs() < 1e-9);
+        // No proceeds (EVM): the price move on 10k tokens, 0.01 -> 0.012
+        let (usd, pct) = sell_pnl(None, Some(150.0), Some(10_000.0), 0.01, 0.012);
+        assert!((usd.unwrap() - 20.0).abs() < 1e-9);
+        assert!((pct - 20.0).abs() < 1e-9);
+        // Unknown size: percent only
+        let (usd, pct) = sell_pnl(None, None, None, 0.01, 0.005);
+        assert_eq!(usd, None);
+        assert!((pct + 50.0).abs() < 1e-9);
+        assert_eq!(sell_pnl(None, None, Some(1.0), 0.0, 0.005), (None, 0.0));
+    }
+
+    #[test]
+    fn test_partial_close_dust_closes_position() {
+        let result = apply_partial_close(1.0, 99.99999999999);
+        assert!(result.closed);
+        assert_eq!(result.sold, 1.0);
+
+        // Repeated 50% sells shrink the position instead of selling forever
+        let mut amount = 8.0;
+        for _ in 0..3 {
+            amount = apply_partial_close(amount, 50.0).remaining;
+        }
+        assert_eq!(amount, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_entry_price_uses_price_source() {
+        let (price, unknown) = resolve_entry_price(|| async { Ok(0.00042) }).await;
+        assert_eq!(price, 0.00042);
+        assert!(!unknown);
+
+        let (price, unknown) = resolve_entry_price(|| async { Err("No trading pairs found for token".to_string()) }).await;
+        assert_eq!(price, UNKNOWN_ENTRY_PRICE);
+        assert!(unknown);
+
+        let (_, unknown) = resolve_entry_price(|| async { Ok(0.0) }).await;
+        assert!(unknown);
+    }
+
+    #[test]
+    fn test_position_sold_outside_engine_is_closed() {
+        // Wallet emptied from another app: balance is zero (or leftover transfer-fee dust)
+        assert_eq!(reconcile_with_balance(0.0, 0.000001), Reconciliation::ClosedExternally);
+        assert_eq!(reconcile_with_balance(0.0000004, 0.000001), Reconciliation::ClosedExternally);
+        // Partially sold elsewhere - still open
+        assert_eq!(reconcile_with_balance(12.5, 0.000001), Reconciliation::StillHeld);
+    }
+
+    #[test]
+    fn test_reconcile_lots() {
+        // $300 at $0.01 => 30k tokens expected
+        let expected = expected_tokens(Some(300.0), 0.01);
+        assert!((expected.unwrap() - 30_000.0).abs() < 1e-6);
+        assert_eq!(expected_tokens(None, 0.01), None);
+        assert_eq!(expected_tokens(Some(300.0), 0.0), None);
+
+        assert_eq!(reconcile_lots(0.0, expected, 0.000001, 10.0), LotAdjustment::Closed);
+        // Within the drift tolerance (fees, rounding) nothing changes
+        assert_eq!(reconcile_lots(28_000.0, expected, 0.000001, 10.0), LotAdjustment::Unchanged);
+        // Bought more elsewhere
+        assert_eq!(reconcile_lots(50_000.0, expected, 0.000001, 10.0), LotAdjustment::Unchanged);
+        // Half was moved out of the wallet
+        match reconcile_lots(15_000.0, expected, 0.000001, 10.0) {
+            LotAdjustment::Scaled(keep) => assert!((keep - 0.5).abs() < 1e-9),
+            other => panic!("expected a scale-down, got {:?}", other),
+        }
+        // Without an expected balance only an empty wallet is acted on
+        assert_eq!(reconcile_lots(1.0, None, 0.000001, 10.0), LotAdjustment::Unchanged);
+    }
+
+    #[test]
+    fn test_ladder_validation() {
+        let ladder = build_ladder(&[(200.0, 50.0), (50.0, 25.0), (100.0, 25.0)]).unwrap();
+        assert_eq!(ladder.iter().map(|r| r.trigger_pct).collect::<Vec<_>>(), vec![50.0, 100.0, 200.0]);
+
+        assert!(build_ladder(&[(50.0, 60.0), (100.0, 50.0)]).is_err()); // 110%
+        assert!(build_ladder(&[(0.0, 25.0)]).is_err());
+        assert!(build_ladder(&[(50.0, 0.0)]).is_err());
+        assert!(build_ladder(&[]).is_err());
+    }
+
+    #[test]
+    fn test_ladder_scales_out_once_per_rung() {
+        let mut ladder = build_ladder(&[(50.0, 25.0), (100.0, 25.0), (200.0, 50.0)]).unwrap();
+
+        assert!(crossed_rungs(&ladder, 1.0, 1.4).is_empty());
+        let first = crossed_rungs(&ladder, 1.0, 1.5);
+        assert_eq!(first, vec![0]);
+        assert!((ladder_sell_percent(&ladder, &first) - 25.0).abs() < 1e-9);
+        ladder[0].fired = true;
+        assert!(crossed_rungs(&ladder, 1.0, 1.6).is_empty());
+
+        // Price gaps through two rungs in one poll: sell both together
+        let next = crossed_rungs(&ladder, 1.0, 3.2);
+        assert_eq!(next, vec![1, 2]);
+        assert!((ladder_sell_percent(&ladder, &next) - 100.0).abs() < 1e-9);
+
+        // Second rung alone is 25 of the remaining 75
+        assert!((ladder_sell_percent(&ladder, &[1]) - 100.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ladder_position_still_hits_stop_loss() {
+        // Half sold at +50%, then the price collapses: the ladder replaces the
+        // take-profit (passed as 0) but the stop still closes the remainder
+        let mut ladder = build_ladder(&[(50.0, 50.0), (150.0, 50.0)]).unwrap();
+        ladder[0].fired = true;
+        assert!(crossed_rungs(&ladder, 1.0, 0.7).is_empty());
+        assert_eq!(evaluate_exit(1.0, 0.7, 0.0, 20.0, None, 1.6), Some(ExitTrigger::StopLoss));
+    }
+
+    #[test]
+    fn test_lot_token_amount_prefers_swap_output() {
+        // The swap delivered 28k tokens for $300 at a $0.01 quote
+        assert_eq!(lot_token_amount(Some(28_000.0), Some(300.0), 0.01), Some(28_000.0));
+        // Simulated buy: estimated from cost at the entry price
+        assert!((lot_token_amount(None, Some(300.0), 0.01).unwrap() - 30_000.0).abs() < 1e-6);
+        assert_eq!(lot_token_amount(Some(0.0), None, 0.01), None);
+        assert_eq!(lot_token_amount(None, Some(300.0), 0.0), None);
+    }
+
+    #[test]
+    fn test_buy_cost_basis() {
+        assert_eq!(buy_cost_basis("solana", 2.0, Some(150.0)), Some(300.0));
+        assert_eq!(buy_cost_basis("solana", 2.0, None), None);
+        assert_eq!(buy_cost_basis("eth", 2.0, Some(150.0)), None);
+    }
+
+    #[test]
+    fn test_split_cost_basis() {
+        // $300 for 1000 tokens, sell 250 => $75 goes with the sale
+        let split = split_cost_basis(300.0, 1000.0, 250.0);
+        assert!((split.sold - 75.0).abs() < 1e-9);
+        assert!((split.remaining - 225.0).abs() < 1e-9);
+
+        // Full close takes the whole basis
+        assert_eq!(split_cost_basis(300.0, 1000.0, 1000.0), CostSplit { sold: 300.0, remaining: 0.0 });
+
+        // Sold 1.5 SOL @ $150 against $75 of cost => +$150 realized
+        assert!((225.0 - split.sold - 150.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_estimate_sell_pnl() {
+        // Half of 1000 tokens that cost $300, sold for $225 => +$75 (+50%)
+        let (usd, pct) = estimate_sell_pnl(225.0, Some(300.0), 1000.0, 500.0, 0.0).unwrap();
+        assert!((usd - 75.0).abs() < 1e-9);
+        assert!((pct - 50.0).abs() < 1e-9);
+        // No cost basis: 500 tokens at a $0.5 entry
+        let (usd, pct) = estimate_sell_pnl(200.0, None, 1000.0, 500.0, 0.5).unwrap();
+        assert!((usd + 50.0).abs() < 1e-9);
+        assert!((pct + 20.0).abs() < 1e-9);
+        // Unknown entry and no basis
+        assert_eq!(estimate_sell_pnl(200.0, None, 1000.0, 500.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_fill_price() {
+        // 1.2 SOL @ $150 for 90 tokens
+        assert!((fill_price(180.0, 90.0).unwrap() - 2.0).abs() < 1e-12);
+        assert_eq!(fill_price(0.0, 90.0), None);
+        assert_eq!(fill_price(180.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_allocate_token_sell_by_lot_order() {
+        let lots = vec![("old".to_string(), 100.0), ("mid".to_string(), 0.0), ("new".to_string(), 300.0)];
+
+        // Half of 400 = 200: all of the oldest lot, then a third of the newest
+        let fifo = allocate_token_sell(&lots, 50.0, LotOrder::Fifo);
+        assert_eq!(fifo.len(), 2);
+        assert_eq!(fifo[0], ("old".to_string(), 100.0));
+        assert_eq!(fifo[1].0, "new");
+        assert!((fifo[1].1 - 100.0 / 3.0).abs() < 1e-9);
+
+        // Newest first, 200 fits inside the 300 lot
+        let lifo = allocate_token_sell(&lots, 50.0, LotOrder::Lifo);
+        assert_eq!(lifo.len(), 1);
+        assert_eq!(lifo[0].0, "new");
+        assert!((lifo[0].1 - 200.0 / 3.0).abs() < 1e-9);
+
+        // Selling everything closes every non-empty lot
+        let all = allocate_token_sell(&lots, 100.0, LotOrder::Lifo);
+        assert_eq!(all, vec![("new".to_string(), 100.0), ("old".to_string(), 100.0)]);
+        assert!(allocate_token_sell(&lots, 0.0, LotOrder::Fifo).is_empty());
+    }
+}
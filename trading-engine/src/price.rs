@@ -1,6 +1,8 @@
 // Price Fetching Module - Production Ready
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TokenPrice {
@@ -10,9 +12,13 @@ pub struct TokenPrice {
     pub price_usd: f64,
     pub price_native: f64,
     pub volume_24h: f64,
-    pub liquidity: f64,
+    pub liquidity: Option<f64>, // USD; None when the pair doesn't report it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pair_address: Option<String>,
     pub price_change_24h: f64,
     pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>, // Set when the price is indicative only (e.g. new launch fallback)
 }
 
 #[derive(Debug, Serialize)]
@@ -22,7 +28,28 @@ pub struct PriceResponse {
     pub error: Option<String>,
 }
 
+const NO_PAIRS_ERROR: &str = "No trading pairs found for token";
+
 pub async fn fetch_token_price(chain: &str, token: &str) -> Result<TokenPrice, String> {
+    fetch_token_price_on_pair(chain, token, None).await
+}
+
+/// Like `fetch_token_price`, but reads liquidity from `pinned_pair` when given. DexScreener's
+/// pair order can change between calls, so comparing liquidity over time needs a fixed pool.
+/// A pinned pair that is no longer listed reads as liquidity 0 on that pair, since a pool
+/// disappearing is what a full pull looks like; the price still comes from the other pairs.
+pub async fn fetch_token_price_on_pair(chain: &str, token: &str, pinned_pair: Option<&str>) -> Result<TokenPrice, String> {
+    match fetch_dexscreener_price(chain, token, pinned_pair).await {
+        Err(e) if e == NO_PAIRS_ERROR && chain == "solana" && new_launch_mode_enabled() => {
+            tracing::warn!("⚠️ No DexScreener pairs for {}, deriving price from bonding curve", token);
+            fetch_new_launch_price(token).await
+                .map_err(|fallback| format!("{} (new launch fallback failed: {})", e, fallback))
+        }
+        other => other,
+    }
+}
+
+async fn fetch_dexscreener_price(chain: &str, token: &str, pinned_pair: Option<&str>) -> Result<TokenPrice, String> {
     // Call DexScreener API for real price data
     let url = format!("https://api.dexscreener.com/latest/dex/tokens/{}", token);
     
@@ -42,7 +69,11 @@ pub async fn fetch_token_price(chain: &str, token: &str) -> Result<TokenPrice, S
         .json()
         .await
         .map_err(|e| format!("Failed to parse response: {}", e))?;
-    
+    token_price_from_pairs(chain, token, &json, pinned_pair)
+}
+
+/// Read a token's price from a DexScreener `/tokens` response.
+pub fn token_price_from_pairs(chain: &str, token: &str, json: &serde_json::Value, pinned_pair: Option<&str>) -> Result<TokenPrice, String> {
     // Parse DexScreener response
     let pairs = json.get("pairs")
         .and_then(|p| p.as_array())
@@ -52,7 +83,7 @@ pub async fn fetch_token_price(chain: &str, token: &str) -> Result<TokenPrice, S
                 tracing::warn!("⚠️ [{}] No trading pairs found for token {}", network.to_uppercase(), &token[..8]);
                 tracing::warn!("   This is expected on devnet for tokens without liquidity");
             }
-            "No pairs found".to_string()
+            NO_PAIRS_ERROR.to_string()
         })?;
     
     if pairs.is_empty() {
@@ -60,12 +91,17 @@ pub async fn fetch_token_price(chain: &str, token: &str) -> Result<TokenPrice, S
         if network == "devnet" || network == "testnet" {
             tracing::warn!("⚠️ [{}] No trading pairs found for token {}", network.to_uppercase(), &token[..8]);
         }
-        return Err("No trading pairs found for token".to_string());
+        return Err(NO_PAIRS_ERROR.to_string());
     }
     
-    // Get the first pair (usually most liquid)
-    let pair = pairs[0].as_object()
+    // The pinned pair if listed, else the first (usually most liquid)
+    let pair = select_pair(pairs, pinned_pair).as_object()
         .ok_or_else(|| "Invalid pair data".to_string())?;
+    let listed_address = pair.get("pairAddress")
+        .and_then(|a| a.as_str())
+        .map(|a| a.to_string());
+    let pinned_missing = pinned_pair.is_some_and(|pinned| listed_address.as_deref() != Some(pinned));
+    let pair_address = if pinned_missing { pinned_pair.map(|p| p.to_string()) } else { listed_address };
     
     let price_usd = pair.get("priceUsd")
         .and_then(|p| p.as_str())
@@ -78,11 +114,15 @@ pub async fn fetch_token_price(chain: &str, token: &str) -> Result<TokenPrice, S
         .and_then(|v| v.as_f64())
         .unwrap_or(0.0);
     
-    let liquidity_usd = pair.get("liquidity")
-        .and_then(|l| l.as_object())
-        .and_then(|l| l.get("usd"))
-        .and_then(|l| l.as_f64())
-        .unwrap_or(0.0);
+    let liquidity_usd = if pinned_missing {
+        tracing::debug!("Pinned pair {} for {} is no longer listed, reading its liquidity as 0", pinned_pair.unwrap_or_default(), token);
+        Some(0.0)
+    } else {
+        pair.get("liquidity")
+            .and_then(|l| l.as_object())
+            .and_then(|l| l.get("usd"))
+            .and_then(|l| l.as_f64())
+    };
     
     let price_change_24h = pair.get("priceChange")
         .and_then(|p| p.as_object())
@@ -119,11 +159,225 @@ pub async fn fetch_token_price(chain: &str, token: &str) -> Result<TokenPrice, S
         price_native,
         volume_24h,
         liquidity: liquidity_usd,
+        pair_address,
         price_change_24h,
         timestamp,
+        warnings: Vec::new(),
     })
 }
 
+/// The pair with address `pinned`, falling back to the first. `pairs` must not be empty.
+pub fn select_pair<'a>(pairs: &'a [serde_json::Value], pinned: Option<&str>) -> &'a serde_json::Value {
+    pinned
+        .and_then(|addr| pairs.iter().find(|p| p.get("pairAddress").and_then(|a| a.as_str()) == Some(addr)))
+        .unwrap_or(&pairs[0])
+}
+
+// ==================== NEW LAUNCH FALLBACK ====================
+// Tokens only seconds old have no DexScreener pairs yet. With NEW_LAUNCH_MODE
+// enabled we read the pump.fun bonding curve directly for an indicative price.
+
+const PUMP_FUN_PROGRAM_ID: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+const PUMP_FUN_TOKEN_DECIMALS: u8 = 6;
+
+pub fn new_launch_mode_enabled() -> bool {
+    std::env::var("NEW_LAUNCH_MODE")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
+/// pump.fun bonding curve account (after the 8-byte Anchor discriminator).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BondingCurveState {
+    pub virtual_token_reserves: u64,
+    pub virtual_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    pub real_sol_reserves: u64,
+    pub token_total_supply: u64,
+    pub complete: bool,
+}
+
+impl BondingCurveState {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 49 {
+            return Err(format!("Bonding curve account too short: {} bytes", data.len()));
+        }
+        let read_u64 = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        Ok(Self {
+            virtual_token_reserves: read_u64(8),
+            virtual_sol_reserves: read_u64(16),
+            real_token_reserves: read_u64(24),
+            real_sol_reserves: read_u64(32),
+            token_total_supply: read_u64(40),
+            complete: data[48] != 0,
+        })
+    }
+
+    /// Spot price in SOL per whole token.
+    pub fn price_sol(&self) -> Option<f64> {
+        price_from_reserves(
+            self.virtual_token_reserves,
+            PUMP_FUN_TOKEN_DECIMALS,
+            self.virtual_sol_reserves,
+            crate::units::SOL_DECIMALS,
+        )
+    }
+}
+
+/// Constant-product spot price: quote per whole base token.
+pub fn price_from_reserves(base_reserve: u64, base_decimals: u8, quote_reserve: u64, quote_decimals: u8) -> Option<f64> {
+    if base_reserve == 0 || quote_reserve == 0 {
+        return None;
+    }
+    let base = base_reserve as f64 / 10f64.powi(base_decimals as i32);
+    let quote = quote_reserve as f64 / 10f64.powi(quote_decimals as i32);
+    Some(quote / base)
+}
+
+async fn fetch_new_launch_price(token: &str) -> Result<TokenPrice, String> {
+    use solana_client::nonblocking::rpc_client::RpcClient;
+    use solana_sdk::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    let mint = Pubkey::from_str(token).map_err(|_| "Invalid token address".to_string())?;
+    let program_id = Pubkey::from_str(PUMP_FUN_PROGRAM_ID).map_err(|e| e.to_string())?;
+    let (curve, _) = Pubkey::find_program_address(&[b"bonding-curve", mint.as_ref()], &program_id);
+
+    let rpc_url = std::env::var("SOLANA_RPC")
+        .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
+    let account = RpcClient::new(rpc_url)
+        .get_account(&curve)
+        .await
+        .map_err(|e| format!("No bonding curve found: {}", e))?;
+
+    let state = BondingCurveState::parse(&account.data)?;
+    if state.complete {
+        return Err("Bonding curve complete, token has migrated".to_string());
+    }
+    let price_native = state.price_sol().ok_or_else(|| "Bonding curve has no reserves".to_string())?;
+    let sol_price = fetch_sol_price().await.unwrap_or(0.0);
+
+    Ok(TokenPrice {
+        chain: "solana".to_string(),
+        token: token.to_string(),
+        token_symbol: None,
+        price_usd: price_native * sol_price,
+        price_native,
+        volume_24h: 0.0,
+        liquidity: (sol_price > 0.0).then(|| state.real_sol_reserves as f64 / 1_000_000_000.0 * sol_price),
+        pair_address: Some(curve.to_string()),
+        price_change_24h: 0.0,
+        timestamp: chrono::Utc::now().timestamp(),
+        warnings: vec![
+            "NEW LAUNCH: no DEX pairs yet, price derived from on-chain bonding curve".to_string(),
+            "Indicative price only - expect high slippage and volatility".to_string(),
+        ],
+    })
+}
+
+// ==================== SOL PRICE ====================
+
+/// How long a fetched SOL price is reused before asking CoinGecko again.
+const SOL_PRICE_TTL: Duration = Duration::from_secs(30);
+
+/// Last good SOL/USD quote.
+#[derive(Debug, Default)]
+struct SolPriceCache {
+    last: Option<(f64, Instant)>,
+}
+
+impl SolPriceCache {
+    fn fresh(&self, now: Instant, ttl: Duration) -> Option<f64> {
+        match self.last {
+            Some((price, fetched_at)) if now.duration_since(fetched_at) < ttl => Some(price),
+            _ => None,
+        }
+    }
+
+    /// Store a successful fetch, or fall back to the last known price on failure.
+    fn resolve(&mut self, fetched: Result<f64, String>, now: Instant) -> Result<f64, String> {
+        match fetched {
+            Ok(price) => {
+                self.last = Some((price, now));
+                Ok(price)
+            }
+            Err(e) => match self.last {
+                Some((price, fetched_at)) => {
+                    tracing::warn!(
+                        "⚠️ SOL price fetch failed ({}), using last price ${:.2} from {}s ago",
+                        e,
+                        price,
+                        now.duration_since(fetched_at).as_secs()
+                    );
+                    Ok(price)
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref SOL_PRICE_CACHE: Mutex<SolPriceCache> = Mutex::new(SolPriceCache::default());
+}
+
+/// Current SOL/USD price, cached for 30 seconds.
+pub async fn fetch_sol_price() -> Result<f64, String> {
+    let mut cache = SOL_PRICE_CACHE.lock().await;
+    if let Some(price) = cache.fresh(Instant::now(), SOL_PRICE_TTL) {
+        return Ok(price);
+    }
+    let fetched = fetch_sol_price_live().await;
+    cache.resolve(fetched, Instant::now())
+}
+
+/// Fetch current SOL price (uncached)
+async fn fetch_sol_price_live() -> Result<f64, String> {
+    // Try to fetch from DexScreener or CoinGecko
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    // Try CoinGecko first
+    let url = "https://api.coingecko.com/api/v3/simple/price?ids=solana&vs_currencies=usd";
+    if let Ok(response) = client.get(url).send().await {
+        if response.status().is_success() {
+            if let Ok(json) = response.json::<serde_json::Value>().await {
+                if let Some(price) = json.get("solana")
+                    .and_then(|s| s.get("usd"))
+                    .and_then(|p| p.as_f64()) {
+                    return Ok(price);
+                }
+            }
+        }
+    }
+    
+    // Fallback: try DexScreener
+    let url = "https://api.dexscreener.com/latest/dex/tokens/So11111111111111111111111111111111111111112";
+    if let Ok(response) = client.get(url).send().await {
+        if response.status().is_success() {
+            if let Ok(json) = response.json::<serde_json::Value>().await {
+                if let Some(pairs) = json.get("pairs").and_then(|p| p.as_array()) {
+                    if let Some(pair) = pairs.first() {
+                        if let Some(price) = pair.get("priceUsd")
+                            .and_then(|p| p.as_str())
+                            .and_then(|p| p.parse::<f64>().ok()) {
+                            return Ok(price);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    Err("Failed to fetch SOL price".to_string())
+}
+
 pub async fn fetch_multiple_prices(tokens: Vec<(String, String)>) -> HashMap<String, TokenPrice> {
     let mut prices = HashMap::new();
     
@@ -136,3 +390,279 @@ pub async fn fetch_multiple_prices(tokens: Vec<(String, String)>) -> HashMap<Str
     
     prices
 }
+
+// ==================== BATCH PRICES ====================
+
+/// DexScreener takes up to 30 comma-separated addresses per tokens request.
+const DEXSCREENER_BATCH_SIZE: usize = 30;
+
+fn dexscreener_chain_id(chain: &str) -> &str {
+    match chain {
+        "eth" | "ethereum" => "ethereum",
+        "bsc" | "binance" => "bsc",
+        other => other,
+    }
+}
+
+/// USD price per token from a multi-token response, taken from each token's most liquid
+/// pair on `chain` where it is the base token. Tokens without such a pair are left out.
+pub fn parse_batch_prices(json: &serde_json::Value, chain: &str, tokens: &[String]) -> HashMap<String, f64> {
+    let chain_id = dexscreener_chain_id(chain);
+    let mut best: HashMap<String, (f64, f64)> = HashMap::new(); // token -> (liquidity, price)
+    for pair in json["pairs"].as_array().into_iter().flatten() {
+        if pair["chainId"].as_str() != Some(chain_id) {
+            continue;
+        }
+        let Some(base) = pair["baseToken"]["address"].as_str() else { continue };
+        // EVM addresses come back checksummed
+        let Some(token) = tokens.iter().find(|t| t.eq_ignore_ascii_case(base)) else { continue };
+        let Some(price) = pair["priceUsd"].as_str().and_then(|p| p.parse::<f64>().ok()).filter(|p| *p > 0.0) else { continue };
+        let liquidity = pair["liquidity"]["usd"].as_f64().unwrap_or(0.0);
+        if best.get(token).is_none_or(|(l, _)| liquidity > *l) {
+            best.insert(token.clone(), (liquidity, price));
+        }
+    }
+    best.into_iter().map(|(token, (_, price))| (token, price)).collect()
+}
+
+/// Current USD prices for many tokens on one chain, 30 per DexScreener request.
+/// Failed chunks are logged and their tokens left out.
+pub async fn fetch_prices_batch(chain: &str, tokens: &[String]) -> HashMap<String, f64> {
+    let client = reqwest::Client::new();
+    let mut prices = HashMap::new();
+    for chunk in tokens.chunks(DEXSCREENER_BATCH_SIZE) {
+        let url = format!("https://api.dexscreener.com/latest/dex/tokens/{}", chunk.join(","));
+        let json = async {
+            let response = client.get(&url).timeout(Duration::from_secs(10)).send().await.map_err(|e| format!("Failed to fetch prices: {}", e))?;
+            if !response.status().is_success() {
+                return Err(format!("DexScreener API error: {}", response.status()));
+            }
+            response.json::<serde_json::Value>().await.map_err(|e| format!("Failed to parse response: {}", e))
+        };
+        match json.await {
+            Ok(json) => prices.extend(parse_batch_prices(&json, chain, chunk)),
+            Err(e) => tracing::warn!("⚠️ Batch price lookup for {} {} tokens failed: {}", chunk.len(), chain, e),
+        }
+    }
+    prices
+}
+
+// ==================== CANDLES ====================
+// Historical prices from GeckoTerminal's OHLCV API, used by grid backtests and charting.
+
+const GECKOTERMINAL_API: &str = "https://api.geckoterminal.com/api/v2";
+
+/// GeckoTerminal (period, aggregate) for a timeframe like "15m" or "4h".
+pub fn candle_timeframe(timeframe: &str) -> Result<(&'static str, u32), String> {
+    match timeframe {
+        "1m" => Ok(("minute", 1)),
+        "5m" => Ok(("minute", 5)),
+        "15m" => Ok(("minute", 15)),
+        "1h" => Ok(("hour", 1)),
+        "4h" => Ok(("hour", 4)),
+        "12h" => Ok(("hour", 12)),
+        "1d" => Ok(("day", 1)),
+        _ => Err(format!("Unsupported timeframe {} (use 1m, 5m, 15m, 1h, 4h, 12h or 1d)", timeframe)),
+    }
+}
+
+fn geckoterminal_network(chain: &str) -> Result<&'static str, String> {
+    match chain {
+        "solana" => Ok("solana"),
+        "eth" | "ethereum" => Ok("eth"),
+        "bsc" | "binance" => Ok("bsc"),
+        _ => Err(format!("Unsupported chain: {}", chain)),
+    }
+}
+
+/// (timestamp, close) pairs from an OHLCV response, oldest first.
+pub fn parse_ohlcv_closes(json: &serde_json::Value) -> Vec<(i64, f64)> {
+    let mut closes: Vec<(i64, f64)> = json["data"]["attributes"]["ohlcv_list"]
+        .as_array()
+        .map(|rows| rows.iter()
+            .filter_map(|row| Some((row.get(0)?.as_i64()?, row.get(4)?.as_f64()?)))
+            .collect())
+        .unwrap_or_default();
+    closes.sort_by_key(|(ts, _)| *ts);
+    closes
+}
+
+async fn get_json(client: &reqwest::Client, url: &str) -> Result<serde_json::Value, String> {
+    let response = client.get(url).send().await.map_err(|e| format!("Failed to fetch candles: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("GeckoTerminal API error: {}", response.status()));
+    }
+    response.json().await.map_err(|e| format!("Failed to parse candles: {}", e))
+}
+
+/// Raw OHLCV response (up to `limit`, max 1000 rows) for the token's most liquid pool.
+pub async fn fetch_ohlcv(chain: &str, token: &str, timeframe: &str, limit: usize) -> Result<serde_json::Value, String> {
+    let (period, aggregate) = candle_timeframe(timeframe)?;
+    let network = geckoterminal_network(chain)?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let pools = get_json(&client, &format!("{}/networks/{}/tokens/{}/pools?page=1", GECKOTERMINAL_API, network, token)).await?;
+    let pool = pools["data"][0]["attributes"]["address"]
+        .as_str()
+        .ok_or_else(|| format!("No pools found for token {}", token))?;
+
+    let url = format!(
+        "{}/networks/{}/pools/{}/ohlcv/{}?aggregate={}&limit={}&currency=usd&token={}",
+        GECKOTERMINAL_API, network, pool, period, aggregate, limit.clamp(1, 1000), token,
+    );
+    get_json(&client, &url).await
+}
+
+/// Up to `limit` (max 1000) USD closes for the token's most liquid pool, oldest first.
+pub async fn fetch_price_candles(chain: &str, token: &str, timeframe: &str, limit: usize) -> Result<Vec<(i64, f64)>, String> {
+    let closes = parse_ohlcv_closes(&fetch_ohlcv(chain, token, timeframe, limit).await?);
+    if closes.is_empty() {
+        return Err(format!("No candles returned for token {}", token));
+    }
+    Ok(closes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ohlcv_closes_oldest_first() {
+        let json = serde_json::json!({ "data": { "attributes": { "ohlcv_list": [
+            [1700003600, 1.1, 1.3, 1.0, 1.2, 5000.0],
+            [1700000000, 0.9, 1.1, 0.8, 1.0, 4000.0],
+            ["bad"],
+        ] } } });
+        assert_eq!(parse_ohlcv_closes(&json), vec![(1700000000, 1.0), (1700003600, 1.2)]);
+        assert!(parse_ohlcv_closes(&serde_json::json!({})).is_empty());
+
+        assert_eq!(candle_timeframe("4h"), Ok(("hour", 4)));
+        assert!(candle_timeframe("2w").is_err());
+    }
+
+    #[test]
+    fn test_bonding_curve_price() {
+        // Fresh pump.fun curve: 1,073,000,000 virtual tokens vs 30 virtual SOL
+        let mut data = vec![0u8; 8];
+        data.extend_from_slice(&1_073_000_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&30_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&793_100_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&1_000_000_000_000_000u64.to_le_bytes());
+        data.push(0);
+
+        let state = BondingCurveState::parse(&data).unwrap();
+        assert!(!state.complete);
+        assert_eq!(state.virtual_sol_reserves, 30_000_000_000);
+
+        let price = state.price_sol().unwrap();
+        assert!((price - 30.0 / 1_073_000_000.0).abs() < 1e-15);
+
+        assert!(BondingCurveState::parse(&data[..40]).is_err());
+    }
+
+    #[test]
+    fn test_price_from_reserves() {
+        // 1,000 tokens (6 dp) against 5 SOL (9 dp) => 0.005 SOL each
+        assert_eq!(price_from_reserves(1_000_000_000, 6, 5_000_000_000, 9), Some(0.005));
+        assert_eq!(price_from_reserves(0, 6, 5_000_000_000, 9), None);
+    }
+
+    #[test]
+    fn test_sol_price_cache_reuses_fresh_price() {
+        let start = Instant::now();
+        let mut cache = SolPriceCache::default();
+        assert_eq!(cache.fresh(start, SOL_PRICE_TTL), None);
+
+        assert_eq!(cache.resolve(Ok(172.5), start), Ok(172.5));
+        assert_eq!(cache.fresh(start + Duration::from_secs(29), SOL_PRICE_TTL), Some(172.5));
+        assert_eq!(cache.fresh(start + Duration::from_secs(30), SOL_PRICE_TTL), None);
+    }
+
+    #[test]
+    fn test_sol_price_cache_falls_back_on_failure() {
+        let start = Instant::now();
+        let mut cache = SolPriceCache::default();
+        // Nothing cached yet - the error surfaces
+        assert!(cache.resolve(Err("down".to_string()), start).is_err());
+
+        cache.resolve(Ok(140.0), start).unwrap();
+        let later = start + Duration::from_secs(120);
+        assert_eq!(cache.resolve(Err("down".to_string()), later), Ok(140.0));
+        // A failed fetch doesn't refresh the timestamp
+        assert_eq!(cache.fresh(later, SOL_PRICE_TTL), None);
+    }
+
+    #[test]
+    fn test_batch_prices_take_most_liquid_base_pair_on_chain() {
+        let json = serde_json::json!({ "pairs": [
+            { "chainId": "solana", "baseToken": { "address": "MintA" }, "priceUsd": "0.010", "liquidity": { "usd": 5000.0 } },
+            { "chainId": "solana", "baseToken": { "address": "MintA" }, "priceUsd": "0.012", "liquidity": { "usd": 90000.0 } },
+            // MintA quoted against MintB doesn't price MintB
+            { "chainId": "solana", "baseToken": { "address": "Other" }, "quoteToken": { "address": "MintB" }, "priceUsd": "3.0", "liquidity": { "usd": 1e6 } },
+            { "chainId": "ethereum", "baseToken": { "address": "0xABCD" }, "priceUsd": "2.5", "liquidity": { "usd": 1e6 } },
+            { "chainId": "solana", "baseToken": { "address": "MintC" }, "priceUsd": null, "liquidity": { "usd": 1e6 } }
+        ]});
+        let tokens = vec!["MintA".to_string(), "MintB".to_string(), "MintC".to_string(), "0xabcd".to_string()];
+
+        let prices = parse_batch_prices(&json, "solana", &tokens);
+        assert_eq!(prices.len(), 1);
+        assert_eq!(prices["MintA"], 0.012);
+
+        // Addresses match case-insensitively and keep the caller's spelling
+        let prices = parse_batch_prices(&json, "eth", &tokens);
+        assert_eq!(prices.get("0xabcd"), Some(&2.5));
+    }
+
+    #[test]
+    fn test_select_pair_prefers_pinned() {
+        let pairs = vec![
+            serde_json::json!({ "pairAddress": "PoolB", "liquidity": { "usd": 90000.0 } }),
+            serde_json::json!({ "pairAddress": "PoolA", "liquidity": { "usd": 40000.0 } }),
+        ];
+        assert_eq!(select_pair(&pairs, Some("PoolA"))["pairAddress"], "PoolA");
+        assert_eq!(select_pair(&pairs, None)["pairAddress"], "PoolB");
+        // Pinned pool no longer listed
+        assert_eq!(select_pair(&pairs, Some("PoolC"))["pairAddress"], "PoolB");
+    }
+
+    #[tokio::test]
+    async fn test_pinned_pair_dropping_off_the_listing_alerts() {
+        use crate::rug_monitor::{LiquidityTracker, RugConfig};
+        let config = RugConfig { drop_percent: 50.0, window_secs: 300 };
+        let tracker = LiquidityTracker::new();
+        // Fed the way the price worker does it: the pinned pair in, its reading recorded
+        let poll = |json: serde_json::Value, now: i64| {
+            let tracker = tracker.clone();
+            async move {
+                let pinned = tracker.pinned_pair("solana_Mint").await;
+                let price = token_price_from_pairs("solana", "Mint", &json, pinned.as_deref()).unwrap();
+                tracker.record("solana_Mint", price.pair_address.as_deref(), price.liquidity.unwrap(), now, config).await
+            }
+        };
+
+        let listed = serde_json::json!({ "pairs": [
+            { "pairAddress": "PoolA", "priceUsd": "0.010", "liquidity": { "usd": 100000.0 } },
+            { "pairAddress": "PoolB", "priceUsd": "0.011", "liquidity": { "usd": 5000.0 } }
+        ]});
+        assert_eq!(poll(listed, 0).await, None);
+        assert_eq!(tracker.pinned_pair("solana_Mint").await.as_deref(), Some("PoolA"));
+
+        // PoolA is pulled and delisted: the price comes from PoolB, PoolA reads as empty
+        let pulled = serde_json::json!({ "pairs": [
+            { "pairAddress": "PoolB", "priceUsd": "0.002", "liquidity": { "usd": 5000.0 } }
+        ]});
+        let price = token_price_from_pairs("solana", "Mint", &pulled, Some("PoolA")).unwrap();
+        assert_eq!(price.price_usd, 0.002);
+        assert_eq!(price.liquidity, Some(0.0));
+        assert_eq!(price.pair_address.as_deref(), Some("PoolA"));
+
+        let alert = poll(pulled, 60).await.unwrap();
+        assert_eq!(alert.peak_liquidity, 100000.0);
+        assert_eq!(alert.liquidity, 0.0);
+        assert_eq!(tracker.pinned_pair("solana_Mint").await.as_deref(), Some("PoolA"));
+    }
+}
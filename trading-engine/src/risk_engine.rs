@@ -3,10 +3,18 @@
 
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use chrono::{Utc, DateTime};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use solana_client::rpc_client::RpcClient;
+use crate::AppState;
 
 // ==================== DATA STRUCTURES ====================
 
@@ -21,6 +29,16 @@ pub struct RiskProfile {
     pub kill_switch_enabled: bool,
     pub blacklist_enabled: bool,
     pub last_updated: i64,
+    #[sqlx(default)]
+    pub max_open_grids: i32,
+    #[sqlx(default)]
+    pub min_seconds_between_trades: i32, // 0 = no cooldown
+    #[sqlx(default)]
+    pub max_trades_per_minute: i32, // 0 = no cap
+    #[sqlx(default)]
+    pub max_total_exposure_usd: f64, // Cost basis across all open positions, 0 = no cap
+    #[sqlx(default)]
+    pub max_token_exposure_usd: f64, // Cost basis in any one token, 0 = no cap
 }
 
 impl Default for RiskProfile {
@@ -35,6 +53,11 @@ impl Default for RiskProfile {
             kill_switch_enabled: false,
             blacklist_enabled: true,
             last_updated: Utc::now().timestamp(),
+            max_open_grids: 3,
+            min_seconds_between_trades: 1,
+            max_trades_per_minute: 20,
+            max_total_exposure_usd: 0.0,
+            max_token_exposure_usd: 0.0,
         }
     }
 }
@@ -46,8 +69,12 @@ pub struct RiskState {
     pub daily_stats: Arc<RwLock<std::collections::HashMap<i64, DailyStats>>>,
     pub global_blacklist: Arc<RwLock<HashSet<String>>>,
     pub dev_blacklist: Arc<RwLock<HashSet<String>>>,
+    // Recent trade times (unix ms) per user, for the cooldown and per-minute cap
+    pub trade_times: TradeTimes,
 }
 
+pub type TradeTimes = Arc<RwLock<HashMap<i64, VecDeque<i64>>>>;
+
 #[derive(Debug, Clone, Default)]
 pub struct DailyStats {
     pub date: String, // YYYY-MM-DD
@@ -63,7 +90,12 @@ pub enum RiskError {
     MaxOpenPositionsExceeded(i32, i32), // (current, max)
     TokenBlacklisted(String),
     DevBlacklisted(String),
-    InsufficientLiquidity,
+    InsufficientLiquidity(f64, f64), // (attempted, liquidity-adjusted cap)
+    GlobalExposureExceeded(f64, f64), // (exposure after trade, cap)
+    CooldownActive(f64), // Seconds until the next trade is allowed
+    TradeRateExceeded(usize, i32), // (trades in the last minute, max)
+    TotalExposureExceeded(f64, f64), // (user's open exposure after trade, cap)
+    TokenExposureExceeded(f64, f64), // (user's exposure to this token after trade, cap)
     DatabaseError(String),
 }
 
@@ -76,7 +108,12 @@ impl std::fmt::Display for RiskError {
             RiskError::MaxOpenPositionsExceeded(curr, max) => write!(f, "Max open positions reached ({}/{})", curr, max),
             RiskError::TokenBlacklisted(token) => write!(f, "Token is blacklisted: {}", token),
             RiskError::DevBlacklisted(dev) => write!(f, "Developer wallet is blacklisted: {}", dev),
-            RiskError::InsufficientLiquidity => write!(f, "Insufficient liquidity for safe trade"),
+            RiskError::InsufficientLiquidity(amt, cap) => write!(f, "Trade size ${:.2} is too large for this token's liquidity (cap ${:.2})", amt, cap),
+            RiskError::GlobalExposureExceeded(exp, cap) => write!(f, "Engine-wide exposure to this token would reach ${:.2} (cap ${:.2})", exp, cap),
+            RiskError::CooldownActive(wait) => write!(f, "Trading too fast: wait {:.1}s before the next trade", wait),
+            RiskError::TradeRateExceeded(count, max) => write!(f, "Trade rate limit reached ({} trades in the last minute, max {})", count, max),
+            RiskError::TotalExposureExceeded(exp, cap) => write!(f, "Open positions would total ${:.2} (max total exposure ${:.2})", exp, cap),
+            RiskError::TokenExposureExceeded(exp, cap) => write!(f, "Exposure to this token would reach ${:.2} (max per token ${:.2})", exp, cap),
             RiskError::DatabaseError(e) => write!(f, "Risk engine DB error: {}", e),
         }
     }
@@ -84,84 +121,522 @@ impl std::fmt::Display for RiskError {
 
 // ==================== CORE LOGIC ====================
 
+/// `profile` is the user's risk profile, loaded by the caller with `get_risk_profile`.
+/// `solana_client` enables the dev-wallet check (creator lookup); pass None for other chains.
+/// `rate_limited` is false for automation (limit orders etc.), which the user already
+/// scheduled and which can fire several times in one price tick.
+/// The returned slot holds the trade's place in the rate limit: `commit` it once the swap
+/// lands, or drop it to give the place back.
+#[allow(clippy::too_many_arguments)]
 pub async fn check_trade_risk(
-    user_id: i64,
+    profile: &RiskProfile,
+    chain: &str,
     token_address: &str,
     amount_usd: f64,
+    opens_position: bool,
+    rate_limited: bool,
     pool: &PgPool,
     risk_state: &RiskState,
-) -> Result<(), RiskError> {
-    // 1. Fetch User Risk Profile
-    let profile = get_risk_profile(user_id, pool).await
-        .map_err(|e| RiskError::DatabaseError(e))?;
+    solana_client: Option<&Arc<RpcClient>>,
+) -> Result<TradeSlot, RiskError> {
+    let user_id = profile.user_id;
 
-    // 2. Kill Switch Check
+    // 1. Kill Switch Check
     if profile.kill_switch_enabled {
         return Err(RiskError::KillSwitchActive);
     }
 
-    // 3. Blacklist Check
+    // 2. Blacklist Check
     if profile.blacklist_enabled {
         let blacklist = risk_state.global_blacklist.read().await;
         if blacklist.contains(token_address) {
             return Err(RiskError::TokenBlacklisted(token_address.to_string()));
         }
-        // TODO: Check Dev Wallet via external API or cache
+        drop(blacklist);
+
+        // Creator lookup costs RPC calls, so skip it while the dev list is empty
+        if let Some(client) = solana_client {
+            if !risk_state.dev_blacklist.read().await.is_empty() {
+                let timeout = crate::honeypot::security_check_timeout();
+                let creator = crate::token_analysis::find_token_creator(token_address, client, timeout).await;
+                if creator.is_none() {
+                    tracing::warn!("⚠️ Could not resolve creator of {} for the dev blacklist check", token_address);
+                }
+                check_dev_blacklist(creator.as_deref(), &*risk_state.dev_blacklist.read().await)?;
+            }
+        }
     }
 
-    // 4. Max Trade Size Check
+    // 3. Max Trade Size Check
     if amount_usd > profile.max_trade_size_usd {
         return Err(RiskError::MaxTradeSizeExceeded(amount_usd, profile.max_trade_size_usd));
     }
 
+    // 4. Liquidity Cap Check (keeps buys on thin pools from moving the market)
+    if let Some(max_share) = max_liquidity_share_pct() {
+        match crate::price::fetch_token_price(chain, token_address).await {
+            Ok(price) => {
+                if price.liquidity.is_none() {
+                    tracing::warn!("⚠️ Liquidity cap skipped for {}: pair reports no liquidity", token_address);
+                }
+                check_liquidity_share(amount_usd, price.liquidity, max_share)?
+            }
+            // No DEX pair yet (e.g. a fresh launch): nothing to size against
+            Err(e) => tracing::warn!("⚠️ Liquidity cap skipped for {}: {}", token_address, e),
+        }
+    }
+
     // 5. Daily Loss Check
-    let today = Utc::now().format("%Y-%m-%d").to_string();
-    let mut stats_map = risk_state.daily_stats.write().await;
-    let stats = stats_map.entry(user_id).or_insert(DailyStats {
-        date: today.clone(),
-        total_loss_usd: 0.0,
-        trade_count: 0,
-    });
+    {
+        let today = today_utc();
+        let mut stats_map = risk_state.daily_stats.write().await;
+        let stats = stats_map.entry(user_id).or_default();
+        stats.roll_to(&today);
+        check_daily_loss(stats, profile.max_daily_loss_usd)?;
+    }
+
+    // 6. Global Token Exposure Check (across all users)
+    if let Some(cap) = global_token_exposure_cap() {
+        let exposure = token_exposure_usd(token_address, pool).await
+            .map_err(RiskError::DatabaseError)?;
+        check_token_exposure(exposure, amount_usd, cap)?;
+    }
+
+    // 6.5 User Exposure Check (capital already deployed, in total and in this token)
+    if profile.max_total_exposure_usd > 0.0 || profile.max_token_exposure_usd > 0.0 {
+        let exposure = user_exposure_usd(user_id, token_address, pool).await
+            .map_err(RiskError::DatabaseError)?;
+        check_user_exposure(exposure, amount_usd, profile)?;
+    }
+
+    // 7. Max Open Positions Check (adding to an existing position doesn't open a new one)
+    if opens_position {
+        let open_positions_count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM positions WHERE user_id = $1 AND status = 'OPEN'" // Closed positions stay in the table as CLOSED
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| RiskError::DatabaseError(e.to_string()))?;
+
+        if open_positions_count as i32 >= profile.max_open_positions {
+            return Err(RiskError::MaxOpenPositionsExceeded(open_positions_count as i32, profile.max_open_positions));
+        }
+    }
+
+    // 8. Cooldown / Rate Limit (last, so only trades that pass every other check take a slot)
+    if !rate_limited {
+        return Ok(TradeSlot::default());
+    }
+    reserve_trade_slot(&risk_state.trade_times, profile, Utc::now().timestamp_millis()).await
+}
+
+/// TP/SL for a new position: the buy's own values, or the profile defaults where it
+/// left them out. An explicit 0 disables that exit.
+pub fn resolve_exit_targets(take_profit: Option<f64>, stop_loss: Option<f64>, profile: &RiskProfile) -> (f64, f64) {
+    (
+        take_profit.unwrap_or(profile.default_take_profit_percent),
+        stop_loss.unwrap_or(profile.default_stop_loss_percent),
+    )
+}
+
+/// Unknown creators pass: the lookup is best-effort and shouldn't block trading.
+pub fn check_dev_blacklist(creator: Option<&str>, dev_blacklist: &HashSet<String>) -> Result<(), RiskError> {
+    match creator {
+        Some(dev) if dev_blacklist.contains(dev) => Err(RiskError::DevBlacklisted(dev.to_string())),
+        _ => Ok(()),
+    }
+}
+
+// ==================== RATE LIMIT ====================
+
+const RATE_WINDOW_MS: i64 = 60_000;
+
+/// Users idle for longer than this are dropped by `prune_trade_times`. Also the
+/// longest cooldown a profile may set.
+pub const TRADE_HISTORY_RETENTION_SECS: i64 = 3600;
+
+/// `history` holds the user's recent trade times (unix ms), oldest first.
+pub fn check_trade_rate(history: &VecDeque<i64>, now_ms: i64, min_seconds_between_trades: i32, max_trades_per_minute: i32) -> Result<(), RiskError> {
+    if min_seconds_between_trades > 0 {
+        if let Some(last) = history.back() {
+            let remaining_ms = *last + min_seconds_between_trades as i64 * 1000 - now_ms;
+            if remaining_ms > 0 {
+                return Err(RiskError::CooldownActive(remaining_ms as f64 / 1000.0));
+            }
+        }
+    }
+    if max_trades_per_minute > 0 {
+        let recent = history.iter().filter(|t| now_ms - **t < RATE_WINDOW_MS).count();
+        if recent >= max_trades_per_minute as usize {
+            return Err(RiskError::TradeRateExceeded(recent, max_trades_per_minute));
+        }
+    }
+    Ok(())
+}
+
+/// Append a trade and drop entries outside the per-minute window. The latest one
+/// is always kept since the cooldown may be longer than a minute.
+pub fn record_trade_time(history: &mut VecDeque<i64>, now_ms: i64) {
+    history.push_back(now_ms);
+    while history.len() > 1 && history.front().is_some_and(|t| now_ms - *t >= RATE_WINDOW_MS) {
+        history.pop_front();
+    }
+}
+
+/// A trade's place in its user's rate-limit history, taken by the risk check before the swap
+/// is sent. Dropped without `commit` (the swap failed or never went out), the place is given
+/// back so only trades that went through count towards the cooldown and per-minute cap.
+#[derive(Debug, Default)]
+#[must_use]
+pub struct TradeSlot {
+    held: Option<(TradeTimes, i64, i64)>, // (history, user_id, trade time ms)
+}
+
+impl TradeSlot {
+    /// The trade went through: keep it in the history.
+    pub fn commit(mut self) {
+        self.held = None;
+    }
+}
 
-    // Reset if new day
-    if stats.date != today {
-        *stats = DailyStats {
-            date: today,
-            total_loss_usd: 0.0,
-            trade_count: 0,
+impl Drop for TradeSlot {
+    fn drop(&mut self) {
+        let Some((trade_times, user_id, at_ms)) = self.held.take() else {
+            return;
         };
+        tokio::spawn(async move {
+            if let Some(history) = trade_times.write().await.get_mut(&user_id) {
+                if let Some(i) = history.iter().position(|t| *t == at_ms) {
+                    history.remove(i);
+                }
+            }
+        });
+    }
+}
+
+/// Check the rate limit and take a slot under one write lock, so concurrent requests
+/// can't both slip through.
+pub async fn reserve_trade_slot(trade_times: &TradeTimes, profile: &RiskProfile, now_ms: i64) -> Result<TradeSlot, RiskError> {
+    let mut times = trade_times.write().await;
+    let history = times.entry(profile.user_id).or_default();
+    check_trade_rate(history, now_ms, profile.min_seconds_between_trades, profile.max_trades_per_minute)?;
+    record_trade_time(history, now_ms);
+    Ok(TradeSlot { held: Some((trade_times.clone(), profile.user_id, now_ms)) })
+}
+
+/// Forget users whose last trade is older than the retention window. Returns how many were dropped.
+pub async fn prune_trade_times(risk_state: &RiskState, now_ms: i64) -> usize {
+    let mut trade_times = risk_state.trade_times.write().await;
+    let before = trade_times.len();
+    trade_times.retain(|_, history| {
+        history.back().is_some_and(|last| now_ms - *last < TRADE_HISTORY_RETENTION_SECS * 1000)
+    });
+    before - trade_times.len()
+}
+
+/// Sweep stale rate-limit entries once a minute so idle users don't pile up in memory.
+pub fn spawn_trade_time_sweeper(risk_state: RiskState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let dropped = prune_trade_times(&risk_state, Utc::now().timestamp_millis()).await;
+            if dropped > 0 {
+                tracing::debug!("Pruned rate-limit history for {} idle users", dropped);
+            }
+        }
+    });
+}
+
+// ==================== LIQUIDITY CAP ====================
+
+/// Largest single buy allowed, as a percent of the pool's USD liquidity, from
+/// `MAX_LIQUIDITY_SHARE_PCT` (unset = no cap).
+pub fn max_liquidity_share_pct() -> Option<f64> {
+    std::env::var("MAX_LIQUIDITY_SHARE_PCT")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|pct| *pct > 0.0)
+}
+
+/// Biggest trade (USD) a pool with `liquidity_usd` can take at `max_share_pct`.
+pub fn compute_max_size(liquidity_usd: f64, max_share_pct: f64) -> f64 {
+    liquidity_usd.max(0.0) * max_share_pct / 100.0
+}
+
+/// Unknown liquidity (`None`) isn't capped: there's nothing to size against.
+pub fn check_liquidity_share(amount_usd: f64, liquidity_usd: Option<f64>, max_share_pct: f64) -> Result<(), RiskError> {
+    let Some(liquidity_usd) = liquidity_usd else {
+        return Ok(());
+    };
+    let cap = compute_max_size(liquidity_usd, max_share_pct);
+    if amount_usd > cap {
+        return Err(RiskError::InsufficientLiquidity(amount_usd, cap));
     }
+    Ok(())
+}
+
+// ==================== GLOBAL EXPOSURE ====================
+
+/// Engine-wide USD cap on open exposure to a single token, from `GLOBAL_MAX_TOKEN_EXPOSURE_USD`.
+pub fn global_token_exposure_cap() -> Option<f64> {
+    std::env::var("GLOBAL_MAX_TOKEN_EXPOSURE_USD")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|cap| *cap > 0.0)
+}
+
+/// Current value of all open positions in `token_address`, summed over every user: tokens
+/// held at the latest price, or the cost basis for lots of unknown size.
+pub async fn token_exposure_usd(token_address: &str, pool: &PgPool) -> Result<f64, String> {
+    sqlx::query_scalar::<_, f64>(
+        "SELECT COALESCE(SUM(COALESCE(token_amount * current_price, cost_basis_usd)), 0) FROM positions WHERE token_address = $1 AND status = 'OPEN'"
+    )
+    .bind(token_address)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
 
-    if stats.total_loss_usd >= profile.max_daily_loss_usd {
-        return Err(RiskError::MaxDailyLossExceeded(stats.total_loss_usd, profile.max_daily_loss_usd));
+pub fn check_token_exposure(current_exposure_usd: f64, amount_usd: f64, cap_usd: f64) -> Result<(), RiskError> {
+    let projected = current_exposure_usd + amount_usd;
+    if projected > cap_usd {
+        return Err(RiskError::GlobalExposureExceeded(projected, cap_usd));
     }
+    Ok(())
+}
+
+/// A user's open cost basis: across all positions, and in one token.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UserExposure {
+    pub total_usd: f64,
+    pub token_usd: f64,
+}
 
-    // 6. Max Open Positions Check
-    let open_positions_count: i64 = sqlx::query_scalar(
-        "SELECT COUNT(*) FROM positions WHERE user_id = $1" // Assuming 'positions' table exists and rows are deleted/archived on close
+/// Positions without a recorded cost basis count at their tokens' entry value.
+pub async fn user_exposure_usd(user_id: i64, token_address: &str, pool: &PgPool) -> Result<UserExposure, String> {
+    let (total_usd, token_usd): (f64, f64) = sqlx::query_as(
+        "SELECT COALESCE(SUM(cost), 0), COALESCE(SUM(cost) FILTER (WHERE token_address = $2), 0) FROM ( \
+            SELECT token_address, COALESCE(cost_basis_usd, token_amount * entry_price) AS cost \
+            FROM positions WHERE user_id = $1 AND status = 'OPEN' \
+         ) open_positions"
     )
     .bind(user_id)
+    .bind(token_address)
     .fetch_one(pool)
     .await
-    .map_err(|e| RiskError::DatabaseError(e.to_string()))?;
+    .map_err(|e| e.to_string())?;
+    Ok(UserExposure { total_usd, token_usd })
+}
+
+/// Reject a buy of `amount_usd` that would take the user past either exposure cap (0 = no cap).
+pub fn check_user_exposure(exposure: UserExposure, amount_usd: f64, profile: &RiskProfile) -> Result<(), RiskError> {
+    let total = exposure.total_usd + amount_usd;
+    if profile.max_total_exposure_usd > 0.0 && total > profile.max_total_exposure_usd {
+        return Err(RiskError::TotalExposureExceeded(total, profile.max_total_exposure_usd));
+    }
+    let token = exposure.token_usd + amount_usd;
+    if profile.max_token_exposure_usd > 0.0 && token > profile.max_token_exposure_usd {
+        return Err(RiskError::TokenExposureExceeded(token, profile.max_token_exposure_usd));
+    }
+    Ok(())
+}
+
+// ==================== DAILY STATS ====================
+
+pub fn today_utc() -> String {
+    Utc::now().format("%Y-%m-%d").to_string()
+}
+
+impl DailyStats {
+    /// Start a fresh day if these stats are from an earlier one.
+    pub fn roll_to(&mut self, today: &str) {
+        if self.date != today {
+            *self = DailyStats { date: today.to_string(), total_loss_usd: 0.0, trade_count: 0 };
+        }
+    }
 
-    if open_positions_count as i32 >= profile.max_open_positions {
-        return Err(RiskError::MaxOpenPositionsExceeded(open_positions_count as i32, profile.max_open_positions));
+    /// Count a closed trade. Only losses add to the daily total.
+    pub fn apply_trade(&mut self, today: &str, pnl_usd: f64) {
+        self.roll_to(today);
+        self.trade_count += 1;
+        if pnl_usd < 0.0 {
+            self.total_loss_usd += pnl_usd.abs();
+        }
     }
+}
 
+pub fn check_daily_loss(stats: &DailyStats, max_daily_loss_usd: f64) -> Result<(), RiskError> {
+    if stats.total_loss_usd >= max_daily_loss_usd {
+        return Err(RiskError::MaxDailyLossExceeded(stats.total_loss_usd, max_daily_loss_usd));
+    }
     Ok(())
 }
 
+/// Record a sell's realized USD PnL against the user's daily stats and persist them
+/// so the loss limit survives a restart. A sell with unknown PnL (None) still counts
+/// as a trade but adds no loss.
 pub async fn record_trade_result(
     user_id: i64,
-    pnl_usd: f64,
+    pnl_usd: Option<f64>,
     risk_state: &RiskState,
+    pool: &PgPool,
 ) {
-    if pnl_usd < 0.0 {
+    if pnl_usd.is_none() {
+        tracing::warn!("⚠️ Realized PnL unknown for a sell by user {}, not counted towards the daily loss", user_id);
+    }
+    let stats = {
         let mut stats_map = risk_state.daily_stats.write().await;
-        if let Some(stats) = stats_map.get_mut(&user_id) {
-            stats.total_loss_usd += pnl_usd.abs();
+        let stats = stats_map.entry(user_id).or_default();
+        stats.apply_trade(&today_utc(), pnl_usd.unwrap_or(0.0));
+        stats.clone()
+    };
+
+    if let Err(e) = save_daily_stats(user_id, &stats, pool).await {
+        tracing::warn!("Failed to persist daily stats for user {}: {}", user_id, e);
+    }
+}
+
+pub async fn save_daily_stats(user_id: i64, stats: &DailyStats, pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO daily_stats (user_id, date, total_loss_usd, trade_count) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (user_id, date) DO UPDATE SET total_loss_usd = $3, trade_count = $4, updated_at = NOW()"
+    )
+    .bind(user_id)
+    .bind(&stats.date)
+    .bind(stats.total_loss_usd)
+    .bind(stats.trade_count)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Reload today's stats into memory on startup. Returns how many users were restored.
+pub async fn load_daily_stats(pool: &PgPool, risk_state: &RiskState) -> Result<usize, String> {
+    let today = today_utc();
+    let rows: Vec<(i64, f64, i32)> = sqlx::query_as(
+        "SELECT user_id, total_loss_usd, trade_count FROM daily_stats WHERE date = $1"
+    )
+    .bind(&today)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut stats_map = risk_state.daily_stats.write().await;
+    for (user_id, total_loss_usd, trade_count) in &rows {
+        stats_map.insert(*user_id, DailyStats { date: today.clone(), total_loss_usd: *total_loss_usd, trade_count: *trade_count });
+    }
+    Ok(rows.len())
+}
+
+// ==================== RISK EVENTS ====================
+
+/// A rejected or flagged trade, kept for tuning thresholds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskEvent {
+    pub user_id: i64,
+    pub event_type: String,
+    pub token_address: String,
+    pub observed_value: Option<f64>,
+    pub threshold_value: Option<f64>,
+    pub details: String,
+}
+
+impl RiskEvent {
+    pub fn from_swap_guard(user_id: i64, token_address: &str, err: &crate::execution::SwapGuardError) -> Self {
+        use crate::execution::SwapGuardError;
+
+        let (event_type, observed, threshold) = match err {
+            SwapGuardError::PriceImpactTooHigh { observed, threshold } => ("PRICE_IMPACT", Some(*observed), Some(*threshold)),
+            SwapGuardError::SlippageTooHigh { observed, threshold } => ("SLIPPAGE", Some(*observed), Some(*threshold)),
+            SwapGuardError::MinimumOutputTooLow { guaranteed, floor } => ("MIN_OUTPUT", Some(*guaranteed as f64), Some(*floor as f64)),
+            SwapGuardError::InvalidQuote(_) => ("INVALID_QUOTE", None, None),
+        };
+
+        Self {
+            user_id,
+            event_type: event_type.to_string(),
+            token_address: token_address.to_string(),
+            observed_value: observed,
+            threshold_value: threshold,
+            details: err.to_string(),
+        }
+    }
+}
+
+pub async fn record_risk_event(event: &RiskEvent, pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        r#"
+        INSERT INTO risk_events (user_id, event_type, token_address, observed_value, threshold_value, details)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(event.user_id)
+    .bind(&event.event_type)
+    .bind(&event.token_address)
+    .bind(event.observed_value)
+    .bind(event.threshold_value)
+    .bind(&event.details)
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Record a swap failure if it was a guard rejection (impact/slippage). Other errors are ignored.
+pub async fn record_swap_rejection(user_id: i64, token_address: &str, err: &anyhow::Error, pool: &PgPool) {
+    if let Some(guard) = err.downcast_ref::<crate::execution::SwapGuardError>() {
+        let event = RiskEvent::from_swap_guard(user_id, token_address, guard);
+        tracing::warn!("🛡️  Swap rejected for user {}: {}", user_id, event.details);
+        if let Err(e) = record_risk_event(&event, pool).await {
+            tracing::error!("Failed to record risk event: {}", e);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct RejectionSummary {
+    pub event_type: String,
+    pub count: i64,
+    pub avg_observed: Option<f64>,
+    pub avg_threshold: Option<f64>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+pub async fn get_rejections_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let summary = sqlx::query_as::<_, RejectionSummary>(
+        r#"
+        SELECT event_type,
+               COUNT(*) AS count,
+               AVG(observed_value) AS avg_observed,
+               AVG(threshold_value) AS avg_threshold,
+               MAX(created_at) AS last_seen
+        FROM risk_events
+        WHERE user_id = $1
+        GROUP BY event_type
+        ORDER BY count DESC
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match summary {
+        Ok(reasons) => {
+            let total: i64 = reasons.iter().map(|r| r.count).sum();
+            (StatusCode::OK, Json(serde_json::json!({
+                "user_id": user_id,
+                "total": total,
+                "reasons": reasons,
+            }))).into_response()
         }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
     }
 }
 
@@ -185,8 +660,8 @@ pub async fn get_risk_profile(user_id: i64, pool: &PgPool) -> Result<RiskProfile
             sqlx::query(
                 r#"
                 INSERT INTO risk_profiles 
-                (user_id, max_trade_size_usd, max_daily_loss_usd, max_open_positions, default_stop_loss_percent, default_take_profit_percent, kill_switch_enabled, blacklist_enabled, last_updated)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                (user_id, max_trade_size_usd, max_daily_loss_usd, max_open_positions, default_stop_loss_percent, default_take_profit_percent, kill_switch_enabled, blacklist_enabled, last_updated, max_open_grids, min_seconds_between_trades, max_trades_per_minute, max_total_exposure_usd, max_token_exposure_usd)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
                 "#
             )
             .bind(default.user_id)
@@ -198,6 +673,11 @@ pub async fn get_risk_profile(user_id: i64, pool: &PgPool) -> Result<RiskProfile
             .bind(default.kill_switch_enabled)
             .bind(default.blacklist_enabled)
             .bind(default.last_updated)
+            .bind(default.max_open_grids)
+            .bind(default.min_seconds_between_trades)
+            .bind(default.max_trades_per_minute)
+            .bind(default.max_total_exposure_usd)
+            .bind(default.max_token_exposure_usd)
             .execute(pool)
             .await
             .map_err(|e| e.to_string())?;
@@ -206,3 +686,609 @@ pub async fn get_risk_profile(user_id: i64, pool: &PgPool) -> Result<RiskProfile
         }
     }
 }
+
+/// Write the limits an update sets (and `last_updated`), returning the stored profile.
+/// Columns the update leaves out keep what's in the database, so a kill switch flipped
+/// since the profile was read isn't overwritten.
+pub async fn save_risk_profile_update(user_id: i64, update: &RiskProfileUpdate, pool: &PgPool) -> Result<RiskProfile, String> {
+    sqlx::query_as::<_, RiskProfile>(
+        r#"
+        UPDATE risk_profiles SET
+            max_trade_size_usd = COALESCE($2, max_trade_size_usd),
+            max_daily_loss_usd = COALESCE($3, max_daily_loss_usd),
+            max_open_positions = COALESCE($4, max_open_positions),
+            default_stop_loss_percent = COALESCE($5, default_stop_loss_percent),
+            default_take_profit_percent = COALESCE($6, default_take_profit_percent),
+            min_seconds_between_trades = COALESCE($7, min_seconds_between_trades),
+            max_trades_per_minute = COALESCE($8, max_trades_per_minute),
+            max_total_exposure_usd = COALESCE($9, max_total_exposure_usd),
+            max_token_exposure_usd = COALESCE($10, max_token_exposure_usd),
+            last_updated = $11
+        WHERE user_id = $1
+        RETURNING *
+        "#
+    )
+    .bind(user_id)
+    .bind(update.max_trade_size_usd)
+    .bind(update.max_daily_loss_usd)
+    .bind(update.max_open_positions)
+    .bind(update.default_stop_loss_percent)
+    .bind(update.default_take_profit_percent)
+    .bind(update.min_seconds_between_trades)
+    .bind(update.max_trades_per_minute)
+    .bind(update.max_total_exposure_usd)
+    .bind(update.max_token_exposure_usd)
+    .bind(Utc::now().timestamp())
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Flip the kill switch alone, leaving the limits as they are.
+pub async fn set_kill_switch(user_id: i64, enabled: bool, pool: &PgPool) -> Result<(), String> {
+    sqlx::query("UPDATE risk_profiles SET kill_switch_enabled = $2, last_updated = $3 WHERE user_id = $1")
+        .bind(user_id)
+        .bind(enabled)
+        .bind(Utc::now().timestamp())
+        .execute(pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// ==================== BLACKLISTS ====================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlacklistKind {
+    Token,
+    Dev,
+}
+
+impl BlacklistKind {
+    fn table(self) -> &'static str {
+        match self {
+            BlacklistKind::Token => "token_blacklist",
+            BlacklistKind::Dev => "dev_blacklist",
+        }
+    }
+
+    fn set(self, risk_state: &RiskState) -> &Arc<RwLock<HashSet<String>>> {
+        match self {
+            BlacklistKind::Token => &risk_state.global_blacklist,
+            BlacklistKind::Dev => &risk_state.dev_blacklist,
+        }
+    }
+}
+
+pub async fn add_to_blacklist(kind: BlacklistKind, address: &str, reason: Option<&str>, pool: &PgPool, risk_state: &RiskState) -> Result<(), String> {
+    sqlx::query(&format!(
+        "INSERT INTO {} (address, reason) VALUES ($1, $2) ON CONFLICT (address) DO UPDATE SET reason = $2",
+        kind.table()
+    ))
+    .bind(address)
+    .bind(reason)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    kind.set(risk_state).write().await.insert(address.to_string());
+    Ok(())
+}
+
+/// Returns false if the address wasn't listed.
+pub async fn remove_from_blacklist(kind: BlacklistKind, address: &str, pool: &PgPool, risk_state: &RiskState) -> Result<bool, String> {
+    let result = sqlx::query(&format!("DELETE FROM {} WHERE address = $1", kind.table()))
+        .bind(address)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    let was_cached = kind.set(risk_state).write().await.remove(address);
+    Ok(result.rows_affected() > 0 || was_cached)
+}
+
+/// Fills both in-memory sets from the DB at startup. Returns (tokens, devs).
+pub async fn load_blacklists(pool: &PgPool, risk_state: &RiskState) -> Result<(usize, usize), String> {
+    let mut counts = [0usize; 2];
+    for (i, kind) in [BlacklistKind::Token, BlacklistKind::Dev].into_iter().enumerate() {
+        let addresses: Vec<String> = sqlx::query_scalar(&format!("SELECT address FROM {}", kind.table()))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        counts[i] = addresses.len();
+        kind.set(risk_state).write().await.extend(addresses);
+    }
+    Ok((counts[0], counts[1]))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlacklistRequest {
+    pub address: String,
+    pub reason: Option<String>,
+}
+
+fn blacklist_admin_check(headers: &HeaderMap) -> Result<(), crate::error::AppError> {
+    let configured = std::env::var("ADMIN_API_KEY").ok();
+    let provided = headers.get("x-admin-key").and_then(|v| v.to_str().ok());
+    if !crate::verification::admin_authorized(configured.as_deref(), provided) {
+        return Err(crate::error::AppError::Forbidden("Admin access denied".to_string()));
+    }
+    Ok(())
+}
+
+async fn add_blacklist_entry(state: &AppState, kind: BlacklistKind, headers: &HeaderMap, request: BlacklistRequest) -> axum::response::Response {
+    if let Err(e) = blacklist_admin_check(headers) {
+        return profile_error(e.status(), e.to_string());
+    }
+    let address = request.address.trim();
+    if address.is_empty() {
+        return profile_error(StatusCode::BAD_REQUEST, "address is required".to_string());
+    }
+    match add_to_blacklist(kind, address, request.reason.as_deref(), &state.db, &state.risk_state).await {
+        Ok(()) => {
+            tracing::info!("🚫 Blacklisted {:?} {}", kind, address);
+            (StatusCode::OK, Json(serde_json::json!({"success": true, "address": address}))).into_response()
+        }
+        Err(e) => profile_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn remove_blacklist_entry(state: &AppState, kind: BlacklistKind, headers: &HeaderMap, address: &str) -> axum::response::Response {
+    if let Err(e) = blacklist_admin_check(headers) {
+        return profile_error(e.status(), e.to_string());
+    }
+    match remove_from_blacklist(kind, address, &state.db, &state.risk_state).await {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({"success": true, "address": address}))).into_response(),
+        Ok(false) => profile_error(StatusCode::NOT_FOUND, format!("{} is not blacklisted", address)),
+        Err(e) => profile_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+pub async fn add_token_blacklist_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BlacklistRequest>,
+) -> impl IntoResponse {
+    add_blacklist_entry(&state, BlacklistKind::Token, &headers, request).await
+}
+
+pub async fn remove_token_blacklist_handler(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    remove_blacklist_entry(&state, BlacklistKind::Token, &headers, &token).await
+}
+
+pub async fn add_dev_blacklist_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BlacklistRequest>,
+) -> impl IntoResponse {
+    add_blacklist_entry(&state, BlacklistKind::Dev, &headers, request).await
+}
+
+pub async fn remove_dev_blacklist_handler(
+    State(state): State<AppState>,
+    Path(wallet): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    remove_blacklist_entry(&state, BlacklistKind::Dev, &headers, &wallet).await
+}
+
+// ==================== PROFILE API ====================
+
+#[derive(Debug, Deserialize)]
+pub struct KillSwitchRequest {
+    pub user_id: i64,
+    pub enabled: bool,
+}
+
+/// Fields left out of the request keep their current value.
+#[derive(Debug, Default, Deserialize)]
+pub struct RiskProfileUpdate {
+    pub max_trade_size_usd: Option<f64>,
+    pub max_daily_loss_usd: Option<f64>,
+    pub max_open_positions: Option<i32>,
+    pub default_stop_loss_percent: Option<f64>,
+    pub default_take_profit_percent: Option<f64>,
+    pub min_seconds_between_trades: Option<i32>,
+    pub max_trades_per_minute: Option<i32>,
+    pub max_total_exposure_usd: Option<f64>,
+    pub max_token_exposure_usd: Option<f64>,
+}
+
+impl RiskProfileUpdate {
+    pub fn apply(&self, profile: &RiskProfile) -> Result<RiskProfile, String> {
+        let updated = RiskProfile {
+            max_trade_size_usd: self.max_trade_size_usd.unwrap_or(profile.max_trade_size_usd),
+            max_daily_loss_usd: self.max_daily_loss_usd.unwrap_or(profile.max_daily_loss_usd),
+            max_open_positions: self.max_open_positions.unwrap_or(profile.max_open_positions),
+            default_stop_loss_percent: self.default_stop_loss_percent.unwrap_or(profile.default_stop_loss_percent),
+            default_take_profit_percent: self.default_take_profit_percent.unwrap_or(profile.default_take_profit_percent),
+            min_seconds_between_trades: self.min_seconds_between_trades.unwrap_or(profile.min_seconds_between_trades),
+            max_trades_per_minute: self.max_trades_per_minute.unwrap_or(profile.max_trades_per_minute),
+            max_total_exposure_usd: self.max_total_exposure_usd.unwrap_or(profile.max_total_exposure_usd),
+            max_token_exposure_usd: self.max_token_exposure_usd.unwrap_or(profile.max_token_exposure_usd),
+            last_updated: Utc::now().timestamp(),
+            ..profile.clone()
+        };
+        validate_risk_limits(&updated)?;
+        Ok(updated)
+    }
+}
+
+pub fn validate_risk_limits(profile: &RiskProfile) -> Result<(), String> {
+    let positive = |v: f64| v.is_finite() && v > 0.0;
+    if !positive(profile.max_trade_size_usd) {
+        return Err("max_trade_size_usd must be positive".to_string());
+    }
+    if !positive(profile.max_daily_loss_usd) {
+        return Err("max_daily_loss_usd must be positive".to_string());
+    }
+    if !(1..=100).contains(&profile.max_open_positions) {
+        return Err("max_open_positions must be between 1 and 100".to_string());
+    }
+    // A stop below -100% can never trigger
+    if !positive(profile.default_stop_loss_percent) || profile.default_stop_loss_percent > 100.0 {
+        return Err("default_stop_loss_percent must be between 0 and 100".to_string());
+    }
+    if !positive(profile.default_take_profit_percent) || profile.default_take_profit_percent > 10_000.0 {
+        return Err("default_take_profit_percent must be between 0 and 10000".to_string());
+    }
+    if !(0..=TRADE_HISTORY_RETENTION_SECS as i32).contains(&profile.min_seconds_between_trades) {
+        return Err(format!("min_seconds_between_trades must be between 0 and {}", TRADE_HISTORY_RETENTION_SECS));
+    }
+    if !(0..=1000).contains(&profile.max_trades_per_minute) {
+        return Err("max_trades_per_minute must be between 0 and 1000".to_string());
+    }
+    let cap = |v: f64| v.is_finite() && v >= 0.0;
+    if !cap(profile.max_total_exposure_usd) {
+        return Err("max_total_exposure_usd must be 0 (no cap) or positive".to_string());
+    }
+    if !cap(profile.max_token_exposure_usd) {
+        return Err("max_token_exposure_usd must be 0 (no cap) or positive".to_string());
+    }
+    Ok(())
+}
+
+fn profile_error(status: StatusCode, e: String) -> axum::response::Response {
+    (status, Json(serde_json::json!({"error": e}))).into_response()
+}
+
+pub async fn get_risk_profile_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    match get_risk_profile(user_id, &state.db).await {
+        Ok(profile) => (StatusCode::OK, Json(profile)).into_response(),
+        Err(e) => profile_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+pub async fn update_risk_profile_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+    Json(update): Json<RiskProfileUpdate>,
+) -> impl IntoResponse {
+    let current = match get_risk_profile(user_id, &state.db).await {
+        Ok(p) => p,
+        Err(e) => return profile_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
+    // Validated against the current limits, then only the fields sent are written
+    if let Err(e) = update.apply(&current) {
+        return profile_error(StatusCode::BAD_REQUEST, e);
+    }
+    match save_risk_profile_update(user_id, &update, &state.db).await {
+        Ok(updated) => {
+            tracing::info!("Risk limits updated for user {}", user_id);
+            (StatusCode::OK, Json(updated)).into_response()
+        }
+        Err(e) => profile_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+pub async fn kill_switch_handler(
+    State(state): State<AppState>,
+    Json(request): Json<KillSwitchRequest>,
+) -> impl IntoResponse {
+    // Creates the default profile if the user has none yet
+    if let Err(e) = get_risk_profile(request.user_id, &state.db).await {
+        return profile_error(StatusCode::INTERNAL_SERVER_ERROR, e);
+    }
+    match set_kill_switch(request.user_id, request.enabled, &state.db).await {
+        Ok(()) => {
+            tracing::warn!("🛑 Kill switch {} for user {}", if request.enabled { "ENABLED" } else { "disabled" }, request.user_id);
+            (StatusCode::OK, Json(serde_json::json!({
+                "user_id": request.user_id,
+                "kill_switch_enabled": request.enabled,
+            }))).into_response()
+        }
+        Err(e) => profile_error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::SwapGuardError;
+
+    #[test]
+    fn test_impact_rejection_records_observed_and_threshold() {
+        let err = SwapGuardError::PriceImpactTooHigh { observed: 12.5, threshold: 8.0 };
+        let event = RiskEvent::from_swap_guard(42, "MintAddr", &err);
+
+        assert_eq!(event.user_id, 42);
+        assert_eq!(event.event_type, "PRICE_IMPACT");
+        assert_eq!(event.token_address, "MintAddr");
+        assert_eq!(event.observed_value, Some(12.5));
+        assert_eq!(event.threshold_value, Some(8.0));
+    }
+
+    #[test]
+    fn test_losses_past_daily_limit_block_next_buy() {
+        let mut stats = DailyStats::default();
+        stats.apply_trade("2026-03-01", -30.0);
+        stats.apply_trade("2026-03-01", 10.0); // Wins don't offset losses
+        assert!(check_daily_loss(&stats, 50.0).is_ok());
+
+        stats.apply_trade("2026-03-01", -25.0);
+        assert_eq!(stats.trade_count, 3);
+        match check_daily_loss(&stats, 50.0) {
+            Err(RiskError::MaxDailyLossExceeded(loss, max)) => {
+                assert_eq!(loss, 55.0);
+                assert_eq!(max, 50.0);
+            }
+            other => panic!("expected daily loss rejection, got {:?}", other),
+        }
+
+        // A new day starts clean
+        stats.roll_to("2026-03-02");
+        assert!(check_daily_loss(&stats, 50.0).is_ok());
+        assert_eq!(stats.trade_count, 0);
+    }
+
+    #[test]
+    fn test_global_exposure_cap_blocks_buy() {
+        // $900 already open across users, $1000 cap
+        assert!(check_token_exposure(900.0, 100.0, 1000.0).is_ok());
+        match check_token_exposure(900.0, 150.0, 1000.0) {
+            Err(RiskError::GlobalExposureExceeded(projected, cap)) => {
+                assert_eq!(projected, 1050.0);
+                assert_eq!(cap, 1000.0);
+            }
+            other => panic!("expected exposure rejection, got {:?}", other),
+        }
+        // Once the cap is reached nothing more gets through
+        assert!(check_token_exposure(1000.0, 1.0, 1000.0).is_err());
+    }
+
+    #[test]
+    fn test_user_exposure_caps() {
+        let profile = RiskProfile { max_total_exposure_usd: 1000.0, max_token_exposure_usd: 300.0, ..Default::default() };
+        let exposure = UserExposure { total_usd: 800.0, token_usd: 200.0 };
+        assert!(check_user_exposure(exposure, 100.0, &profile).is_ok());
+        assert!(matches!(check_user_exposure(exposure, 250.0, &profile), Err(RiskError::TotalExposureExceeded(t, c)) if t == 1050.0 && c == 1000.0));
+        // Room in total but too much in this token
+        let exposure = UserExposure { total_usd: 200.0, token_usd: 250.0 };
+        assert!(matches!(check_user_exposure(exposure, 100.0, &profile), Err(RiskError::TokenExposureExceeded(t, c)) if t == 350.0 && c == 300.0));
+        // 0 = no cap
+        let uncapped = RiskProfile::default();
+        assert!(check_user_exposure(UserExposure { total_usd: 1e9, token_usd: 1e9 }, 100.0, &uncapped).is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_user_exposure_from_open_positions() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query(
+            "CREATE TEMP TABLE positions (user_id BIGINT, token_address VARCHAR(255), amount VARCHAR(100), \
+             entry_price DOUBLE PRECISION, cost_basis_usd DOUBLE PRECISION, token_amount DOUBLE PRECISION, status VARCHAR(20))"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO positions VALUES (5, 'BONK', '0.8', 0.1, 120.0, 1200, 'OPEN'), (5, 'WIF', '0.5', 2.0, NULL, 50, 'OPEN'), \
+             (5, 'POPCAT', '0.5', 1.0, NULL, NULL, 'OPEN'), \
+             (5, 'BONK', '3', 0.1, 500.0, 5000, 'CLOSED'), (6, 'BONK', '6', 0.1, 999.0, 9990, 'OPEN')"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // WIF has no cost basis, so its 50 tokens count at entry: 50 x $2 (not the 0.5 SOL spent).
+        // POPCAT has neither and adds nothing
+        let exposure = user_exposure_usd(5, "BONK", &pool).await.unwrap();
+        assert_eq!(exposure, UserExposure { total_usd: 220.0, token_usd: 120.0 });
+        assert_eq!(user_exposure_usd(7, "BONK", &pool).await.unwrap(), UserExposure::default());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_token_exposure_values_tokens_held() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query(
+            "CREATE TEMP TABLE positions (token_address VARCHAR(255), amount VARCHAR(100), current_price DOUBLE PRECISION, \
+             cost_basis_usd DOUBLE PRECISION, token_amount DOUBLE PRECISION, status VARCHAR(20))"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        // 1 SOL each: 10k tokens now at $0.02, an unsized lot at its $150 cost, a closed lot
+        sqlx::query(
+            "INSERT INTO positions VALUES ('BONK', '1', 0.02, 150.0, 10000, 'OPEN'), ('BONK', '1', 0.02, 150.0, NULL, 'OPEN'), \
+             ('BONK', '1', 0.02, 150.0, 10000, 'CLOSED'), ('WIF', '1', 2.0, 150.0, 75, 'OPEN')"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(token_exposure_usd("BONK", &pool).await.unwrap(), 350.0);
+        assert_eq!(token_exposure_usd("POPCAT", &pool).await.unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_buy_exit_targets_default_to_profile() {
+        let profile = RiskProfile { default_take_profit_percent: 80.0, default_stop_loss_percent: 25.0, ..Default::default() };
+        // TP/SL omitted from the buy
+        assert_eq!(resolve_exit_targets(None, None, &profile), (80.0, 25.0));
+        // Explicit values win, each independently
+        assert_eq!(resolve_exit_targets(Some(50.0), Some(10.0), &profile), (50.0, 10.0));
+        assert_eq!(resolve_exit_targets(Some(50.0), None, &profile), (50.0, 25.0));
+        assert_eq!(resolve_exit_targets(None, Some(10.0), &profile), (80.0, 10.0));
+        // An explicit 0 turns the exit off rather than falling back to the default
+        assert_eq!(resolve_exit_targets(Some(0.0), Some(0.0), &profile), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_liquidity_share_cap() {
+        // 2% of a $50k pool
+        assert_eq!(compute_max_size(50_000.0, 2.0), 1000.0);
+        assert!(check_liquidity_share(1000.0, Some(50_000.0), 2.0).is_ok());
+        match check_liquidity_share(1500.0, Some(50_000.0), 2.0) {
+            Err(RiskError::InsufficientLiquidity(amount, cap)) => {
+                assert_eq!(amount, 1500.0);
+                assert_eq!(cap, 1000.0);
+            }
+            other => panic!("expected liquidity rejection, got {:?}", other),
+        }
+        // A pool reporting no liquidity takes nothing
+        assert!(check_liquidity_share(1.0, Some(0.0), 2.0).is_err());
+        // Liquidity not reported: no cap to apply
+        assert!(check_liquidity_share(1500.0, None, 2.0).is_ok());
+    }
+
+    #[test]
+    fn test_profile_update_keeps_unset_fields() {
+        let profile = RiskProfile { user_id: 7, kill_switch_enabled: true, ..Default::default() };
+        let update = RiskProfileUpdate { max_trade_size_usd: Some(250.0), ..Default::default() };
+        let updated = update.apply(&profile).unwrap();
+        assert_eq!(updated.max_trade_size_usd, 250.0);
+        assert_eq!(updated.max_daily_loss_usd, profile.max_daily_loss_usd);
+        assert!(updated.kill_switch_enabled);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_profile_update_leaves_kill_switch_alone() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query(
+            "CREATE TEMP TABLE risk_profiles (user_id BIGINT PRIMARY KEY, max_trade_size_usd FLOAT8, max_daily_loss_usd FLOAT8, \
+             max_open_positions INT, default_stop_loss_percent FLOAT8, default_take_profit_percent FLOAT8, kill_switch_enabled BOOLEAN, \
+             blacklist_enabled BOOLEAN, last_updated BIGINT, max_open_grids INT, min_seconds_between_trades INT, max_trades_per_minute INT, \
+             max_total_exposure_usd FLOAT8, max_token_exposure_usd FLOAT8)"
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let before = get_risk_profile(7, &pool).await.unwrap();
+        assert!(!before.kill_switch_enabled);
+
+        // The kill switch trips between the update reading the profile and writing it
+        set_kill_switch(7, true, &pool).await.unwrap();
+        let update = RiskProfileUpdate { max_trade_size_usd: Some(250.0), ..Default::default() };
+        update.apply(&before).unwrap();
+        let saved = save_risk_profile_update(7, &update, &pool).await.unwrap();
+
+        assert_eq!(saved.max_trade_size_usd, 250.0);
+        assert_eq!(saved.max_daily_loss_usd, before.max_daily_loss_usd);
+        assert!(saved.kill_switch_enabled);
+    }
+
+    #[test]
+    fn test_profile_update_rejects_bad_limits() {
+        let profile = RiskProfile::default();
+        let bad = [
+            RiskProfileUpdate { max_trade_size_usd: Some(0.0), ..Default::default() },
+            RiskProfileUpdate { max_daily_loss_usd: Some(-10.0), ..Default::default() },
+            RiskProfileUpdate { max_open_positions: Some(0), ..Default::default() },
+            RiskProfileUpdate { default_stop_loss_percent: Some(150.0), ..Default::default() },
+            RiskProfileUpdate { default_take_profit_percent: Some(f64::NAN), ..Default::default() },
+            RiskProfileUpdate { min_seconds_between_trades: Some(-1), ..Default::default() },
+            RiskProfileUpdate { max_trades_per_minute: Some(5000), ..Default::default() },
+            RiskProfileUpdate { max_total_exposure_usd: Some(-1.0), ..Default::default() },
+            RiskProfileUpdate { max_token_exposure_usd: Some(f64::INFINITY), ..Default::default() },
+        ];
+        for update in bad {
+            assert!(update.apply(&profile).is_err(), "{:?} should be rejected", update);
+        }
+    }
+
+    #[test]
+    fn test_dev_blacklist_blocks_listed_creator() {
+        let devs: HashSet<String> = ["RugDev111".to_string()].into_iter().collect();
+        assert!(matches!(check_dev_blacklist(Some("RugDev111"), &devs), Err(RiskError::DevBlacklisted(d)) if d == "RugDev111"));
+        assert!(check_dev_blacklist(Some("HonestDev"), &devs).is_ok());
+        assert!(check_dev_blacklist(None, &devs).is_ok());
+    }
+
+    #[test]
+    fn test_cooldown_between_trades() {
+        let mut history = VecDeque::new();
+        assert!(check_trade_rate(&history, 10_000, 5, 0).is_ok());
+        record_trade_time(&mut history, 10_000);
+
+        match check_trade_rate(&history, 12_000, 5, 0) {
+            Err(RiskError::CooldownActive(wait)) => assert_eq!(wait, 3.0),
+            other => panic!("expected cooldown rejection, got {:?}", other),
+        }
+        assert!(check_trade_rate(&history, 15_000, 5, 0).is_ok());
+        // Cooldown disabled
+        assert!(check_trade_rate(&history, 10_001, 0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_per_minute_trade_cap() {
+        let mut history = VecDeque::new();
+        for t in [0, 10_000, 20_000] {
+            assert!(check_trade_rate(&history, t, 0, 3).is_ok());
+            record_trade_time(&mut history, t);
+        }
+        assert!(matches!(check_trade_rate(&history, 30_000, 0, 3), Err(RiskError::TradeRateExceeded(3, 3))));
+        // The first trade has left the window
+        assert!(check_trade_rate(&history, 60_000, 0, 3).is_ok());
+
+        // Old entries are trimmed, but the latest stays for long cooldowns
+        record_trade_time(&mut history, 200_000);
+        assert_eq!(history, VecDeque::from([200_000]));
+        record_trade_time(&mut history, 400_000);
+        assert_eq!(history, VecDeque::from([400_000]));
+    }
+
+    #[tokio::test]
+    async fn test_failed_trade_gives_its_slot_back() {
+        let trade_times = TradeTimes::default();
+        let profile = RiskProfile { user_id: 9, min_seconds_between_trades: 30, ..Default::default() };
+
+        // Swap failed: the slot is released and the next trade isn't held by the cooldown
+        let slot = reserve_trade_slot(&trade_times, &profile, 1_000).await.unwrap();
+        assert!(matches!(reserve_trade_slot(&trade_times, &profile, 2_000).await, Err(RiskError::CooldownActive(_))));
+        drop(slot);
+        tokio::task::yield_now().await;
+        let slot = reserve_trade_slot(&trade_times, &profile, 3_000).await.unwrap();
+
+        // Swap landed: the cooldown runs from it
+        slot.commit();
+        tokio::task::yield_now().await;
+        assert!(matches!(reserve_trade_slot(&trade_times, &profile, 4_000).await, Err(RiskError::CooldownActive(_))));
+        assert_eq!(trade_times.read().await[&9], VecDeque::from([3_000]));
+    }
+
+    #[tokio::test]
+    async fn test_prune_drops_idle_users() {
+        let state = RiskState {
+            daily_stats: Default::default(),
+            global_blacklist: Default::default(),
+            dev_blacklist: Default::default(),
+            trade_times: Default::default(),
+        };
+        let now = 10_000_000;
+        {
+            let mut times = state.trade_times.write().await;
+            times.insert(1, VecDeque::from([now - 5_000]));
+            times.insert(2, VecDeque::from([now - TRADE_HISTORY_RETENTION_SECS * 1000]));
+            times.insert(3, VecDeque::new());
+        }
+        assert_eq!(prune_trade_times(&state, now).await, 2);
+        assert!(state.trade_times.read().await.contains_key(&1));
+    }
+}
@@ -0,0 +1,144 @@
+// Rug Monitor Module
+// Watches pool liquidity for tokens with open positions. The price worker feeds every
+// reading in, and a sharp drop inside the window raises an alert (and, for positions
+// that opted in, a panic sell).
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RugConfig {
+    pub drop_percent: f64, // Alert when liquidity falls this much below the window's peak
+    pub window_secs: i64,
+}
+
+impl RugConfig {
+    /// `RUG_LIQUIDITY_DROP_PCT` (default 50) and `RUG_WINDOW_SECS` (default 300).
+    pub fn from_env() -> Self {
+        let drop_percent = std::env::var("RUG_LIQUIDITY_DROP_PCT")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|p| *p > 0.0 && *p <= 100.0)
+            .unwrap_or(50.0);
+        let window_secs = std::env::var("RUG_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|s| *s > 0)
+            .unwrap_or(300);
+        Self { drop_percent, window_secs }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RugAlert {
+    pub peak_liquidity: f64,
+    pub liquidity: f64,
+    pub drop_percent: f64,
+}
+
+/// A token's pinned pool and its (timestamp, liquidity USD) readings, oldest first.
+#[derive(Debug, Default)]
+struct PoolReadings {
+    pair: Option<String>,
+    history: VecDeque<(i64, f64)>,
+}
+
+type Readings = HashMap<String, PoolReadings>;
+
+/// Recent liquidity readings per token, trimmed to the window.
+#[derive(Debug, Clone, Default)]
+pub struct LiquidityTracker {
+    readings: Arc<Mutex<Readings>>,
+}
+
+impl LiquidityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pool the token's readings come from, once one has been recorded.
+    pub async fn pinned_pair(&self, token: &str) -> Option<String> {
+        self.readings.lock().await.get(token).and_then(|r| r.pair.clone())
+    }
+
+    /// Record a reading from `pair` and report a rug if liquidity fell too far below the
+    /// window's peak. The first pair seen is pinned; a reading from another pool starts a
+    /// fresh history rather than being compared against the old one.
+    /// History is cleared after an alert, so one pull alerts once.
+    pub async fn record(&self, token: &str, pair: Option<&str>, liquidity: f64, now: i64, config: RugConfig) -> Option<RugAlert> {
+        let mut readings = self.readings.lock().await;
+        let pool = readings.entry(token.to_string()).or_default();
+        if let Some(pair) = pair {
+            if pool.pair.as_deref() != Some(pair) {
+                pool.history.clear();
+                pool.pair = Some(pair.to_string());
+            }
+        }
+        let history = &mut pool.history;
+        history.retain(|(ts, _)| now - ts <= config.window_secs);
+
+        let alert = detect_rug(history.iter().map(|(_, l)| *l), liquidity, config.drop_percent);
+        if alert.is_some() {
+            history.clear();
+        }
+        history.push_back((now, liquidity));
+        alert
+    }
+
+    /// Drop tokens with no open positions left.
+    pub async fn retain_tokens(&self, keep: impl Fn(&str) -> bool) {
+        self.readings.lock().await.retain(|token, _| keep(token));
+    }
+}
+
+/// Compare a new reading against the highest earlier one in the window.
+pub fn detect_rug(previous: impl Iterator<Item = f64>, liquidity: f64, drop_percent: f64) -> Option<RugAlert> {
+    let peak = previous.fold(0.0_f64, f64::max);
+    if peak <= 0.0 || liquidity < 0.0 {
+        return None;
+    }
+    let drop = (peak - liquidity) / peak * 100.0;
+    (drop >= drop_percent).then_some(RugAlert { peak_liquidity: peak, liquidity, drop_percent: drop })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: RugConfig = RugConfig { drop_percent: 50.0, window_secs: 300 };
+
+    #[tokio::test]
+    async fn test_sudden_liquidity_drop_alerts_once() {
+        let tracker = LiquidityTracker::new();
+        assert_eq!(tracker.record("Mint", Some("Pool"), 100_000.0, 0, CONFIG).await, None);
+        assert_eq!(tracker.record("Mint", Some("Pool"), 80_000.0, 60, CONFIG).await, None);
+
+        let alert = tracker.record("Mint", Some("Pool"), 30_000.0, 120, CONFIG).await.unwrap();
+        assert_eq!(alert.peak_liquidity, 100_000.0);
+        assert!((alert.drop_percent - 70.0).abs() < 1e-9);
+
+        // Already reported: the post-rug level is the new baseline
+        assert_eq!(tracker.record("Mint", Some("Pool"), 29_000.0, 180, CONFIG).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_bleed_outside_window_is_ignored() {
+        let tracker = LiquidityTracker::new();
+        tracker.record("Mint", Some("Pool"), 100_000.0, 0, CONFIG).await;
+        tracker.record("Mint", Some("Pool"), 70_000.0, 250, CONFIG).await;
+        // The 100k reading has aged out, and 70k -> 40k is under the threshold
+        assert_eq!(tracker.record("Mint", Some("Pool"), 40_000.0, 400, CONFIG).await, None);
+        assert_eq!(tracker.record("Other", Some("Pool"), 10.0, 400, CONFIG).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_reading_from_another_pool_is_not_a_drop() {
+        let tracker = LiquidityTracker::new();
+        tracker.record("Mint", Some("PoolA"), 100_000.0, 0, CONFIG).await;
+        assert_eq!(tracker.pinned_pair("Mint").await.as_deref(), Some("PoolA"));
+        // A thinner pool showing up isn't a pull on the pinned one
+        assert_eq!(tracker.record("Mint", Some("PoolB"), 5_000.0, 60, CONFIG).await, None);
+        assert_eq!(tracker.pinned_pair("Mint").await.as_deref(), Some("PoolB"));
+    }
+}
@@ -0,0 +1,330 @@
+// Scheduled Exits Module
+// Flattens all of a user's open positions at a given time, once or on a cron-like schedule
+
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// ==================== DATA STRUCTURES ====================
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ScheduledExit {
+    pub schedule_id: String,
+    pub user_id: i64,
+    pub next_run: i64,
+    pub cron: Option<String>, // None = one-off
+    pub active: bool,
+    pub created_at: i64,
+    pub last_run: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleFlattenRequest {
+    pub user_id: i64,
+    pub at_timestamp: Option<i64>,
+    pub cron: Option<String>, // "min hour day-of-month month day-of-week", UTC
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleResponse {
+    pub success: bool,
+    pub schedule: Option<ScheduledExit>,
+    pub error: Option<String>,
+}
+
+// ==================== CRON ====================
+
+/// Five-field cron expression (minute hour day-of-month month day-of-week), evaluated in UTC.
+/// Fields accept `*`, `n`, `a-b`, lists (`1,15`) and steps (`*/5`, `0-30/10`).
+/// Day-of-week is 0-6 with Sunday as 0 (7 is also Sunday). All fields must match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().map_err(|_| format!("Invalid step in '{}'", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(format!("Invalid step in '{}'", part));
+        }
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            let a = a.parse::<u32>().map_err(|_| format!("Invalid value '{}'", a))?;
+            let b = b.parse::<u32>().map_err(|_| format!("Invalid value '{}'", b))?;
+            (a, b)
+        } else {
+            let v = range.parse::<u32>().map_err(|_| format!("Invalid value '{}'", range))?;
+            (v, v)
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!("'{}' out of range {}-{}", part, min, max));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err("Cron expression must have 5 fields: min hour day month weekday".to_string());
+        }
+
+        let mut weekdays = parse_cron_field(fields[4], 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1; // 7 = Sunday
+        }
+
+        Ok(Self {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            weekdays,
+        })
+    }
+
+    fn matches(&self, t: &DateTime<Utc>) -> bool {
+        self.minutes & (1 << t.minute()) != 0
+            && self.hours & (1 << t.hour()) != 0
+            && self.days & (1 << t.day()) != 0
+            && self.months & (1 << t.month()) != 0
+            && self.weekdays & (1 << t.weekday().num_days_from_sunday()) != 0
+    }
+
+    /// First matching minute strictly after `after` (unix seconds). Searches up to ~4 years ahead.
+    pub fn next_after(&self, after: i64) -> Option<i64> {
+        let mut ts = (after.div_euclid(60) + 1) * 60;
+        for _ in 0..(4 * 366 * 24 * 60) {
+            let t = DateTime::<Utc>::from_timestamp(ts, 0)?;
+            if self.matches(&t) {
+                return Some(ts);
+            }
+            ts += 60;
+        }
+        None
+    }
+}
+
+// ==================== SCHEDULING ====================
+
+/// When a new schedule should first fire.
+pub fn first_run(at_timestamp: Option<i64>, cron: Option<&str>, now: i64) -> Result<i64, String> {
+    let cron = cron.map(CronSchedule::parse).transpose()?;
+    match (at_timestamp, cron) {
+        (Some(at), _) if at <= now => Err("at_timestamp must be in the future".to_string()),
+        (Some(at), _) => Ok(at),
+        (None, Some(c)) => c.next_after(now).ok_or_else(|| "Cron expression never fires".to_string()),
+        (None, None) => Err("Provide at_timestamp or cron".to_string()),
+    }
+}
+
+/// Fire every schedule that is due at `now`, advancing recurring ones and
+/// deactivating one-offs. Returns the schedules that fired.
+pub fn run_due(schedules: &mut [ScheduledExit], now: i64) -> Vec<ScheduledExit> {
+    let mut fired = Vec::new();
+    for schedule in schedules.iter_mut().filter(|s| s.active && s.next_run <= now) {
+        schedule.last_run = Some(now);
+        match schedule.cron.as_deref().and_then(|c| CronSchedule::parse(c).ok()).and_then(|c| c.next_after(now)) {
+            Some(next) => schedule.next_run = next,
+            None => schedule.active = false,
+        }
+        fired.push(schedule.clone());
+    }
+    fired
+}
+
+// ==================== PERSISTENCE ====================
+
+pub async fn save_schedule(schedule: &ScheduledExit, pool: &PgPool) -> Result<(), String> {
+    sqlx::query(
+        r#"
+        INSERT INTO scheduled_exits (schedule_id, user_id, next_run, cron, active, created_at, last_run)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (schedule_id) DO UPDATE SET
+            next_run = EXCLUDED.next_run,
+            active = EXCLUDED.active,
+            last_run = EXCLUDED.last_run
+        "#
+    )
+    .bind(&schedule.schedule_id)
+    .bind(schedule.user_id)
+    .bind(schedule.next_run)
+    .bind(&schedule.cron)
+    .bind(schedule.active)
+    .bind(schedule.created_at)
+    .bind(schedule.last_run)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save schedule: {}", e))?;
+
+    Ok(())
+}
+
+pub async fn load_due_schedules(now: i64, pool: &PgPool) -> Result<Vec<ScheduledExit>, String> {
+    sqlx::query_as::<_, ScheduledExit>(
+        "SELECT * FROM scheduled_exits WHERE active = TRUE AND next_run <= $1 ORDER BY next_run"
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load schedules: {}", e))
+}
+
+// ==================== API HANDLERS ====================
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
+
+pub async fn schedule_flatten_handler(
+    State(state): State<AppState>,
+    Json(request): Json<ScheduleFlattenRequest>,
+) -> impl IntoResponse {
+    let now = Utc::now().timestamp();
+    let next_run = match first_run(request.at_timestamp, request.cron.as_deref(), now) {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(ScheduleResponse { success: false, schedule: None, error: Some(e) })),
+    };
+
+    let schedule = ScheduledExit {
+        schedule_id: format!("sched_{}", Uuid::new_v4()),
+        user_id: request.user_id,
+        next_run,
+        cron: request.cron,
+        active: true,
+        created_at: now,
+        last_run: None,
+    };
+
+    match save_schedule(&schedule, &state.db).await {
+        Ok(_) => (StatusCode::OK, Json(ScheduleResponse { success: true, schedule: Some(schedule), error: None })),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ScheduleResponse { success: false, schedule: None, error: Some(e) })),
+    }
+}
+
+pub async fn get_schedules_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    let schedules = sqlx::query_as::<_, ScheduledExit>(
+        "SELECT * FROM scheduled_exits WHERE user_id = $1 AND active = TRUE ORDER BY next_run"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match schedules {
+        Ok(s) => (StatusCode::OK, Json(s)),
+        Err(e) => {
+            tracing::error!("Failed to fetch schedules for user {}: {}", user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(vec![]))
+        }
+    }
+}
+
+pub async fn cancel_schedule_handler(
+    State(state): State<AppState>,
+    Path(schedule_id): Path<String>,
+) -> impl IntoResponse {
+    let result = sqlx::query("UPDATE scheduled_exits SET active = FALSE WHERE schedule_id = $1 AND active = TRUE")
+        .bind(&schedule_id)
+        .execute(&state.db)
+        .await;
+
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (StatusCode::OK, Json(serde_json::json!({"success": true}))),
+        Ok(_) => (StatusCode::NOT_FOUND, Json(serde_json::json!({"success": false, "error": "Schedule not found"}))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"success": false, "error": e.to_string()}))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-05 (Friday) 12:00:00 UTC
+    const FRI_NOON: i64 = 1_704_456_000;
+
+    fn schedule(next_run: i64, cron: Option<&str>) -> ScheduledExit {
+        ScheduledExit {
+            schedule_id: "s1".to_string(),
+            user_id: 9,
+            next_run,
+            cron: cron.map(String::from),
+            active: true,
+            created_at: 0,
+            last_run: None,
+        }
+    }
+
+    #[test]
+    fn test_one_off_fires_at_target_time() {
+        let mut schedules = vec![schedule(FRI_NOON + 3600, None)];
+
+        // Simulated clock: nothing before the target
+        assert!(run_due(&mut schedules, FRI_NOON).is_empty());
+        assert!(run_due(&mut schedules, FRI_NOON + 3599).is_empty());
+
+        let fired = run_due(&mut schedules, FRI_NOON + 3600);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].user_id, 9);
+        assert!(!schedules[0].active);
+
+        // Never fires twice
+        assert!(run_due(&mut schedules, FRI_NOON + 7200).is_empty());
+    }
+
+    #[test]
+    fn test_recurring_schedule_advances() {
+        // Every Friday at 20:00 UTC - flatten before the weekend
+        let cron = "0 20 * * 5";
+        let first = first_run(None, Some(cron), FRI_NOON).unwrap();
+        assert_eq!(first, FRI_NOON + 8 * 3600);
+
+        let mut schedules = vec![schedule(first, Some(cron))];
+        assert_eq!(run_due(&mut schedules, first).len(), 1);
+        assert!(schedules[0].active);
+        assert_eq!(schedules[0].next_run, first + 7 * 24 * 3600);
+    }
+
+    #[test]
+    fn test_cron_parsing() {
+        assert!(CronSchedule::parse("*/15 * * * *").is_ok());
+        assert!(CronSchedule::parse("0 9-17/2 1,15 * 1-5").is_ok());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * *").is_err());
+
+        let every_15 = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_15.next_after(FRI_NOON), Some(FRI_NOON + 900));
+        // Sunday written as 7
+        let sunday = CronSchedule::parse("0 0 * * 7").unwrap();
+        assert_eq!(sunday.next_after(FRI_NOON), Some(FRI_NOON + 36 * 3600));
+    }
+
+    #[test]
+    fn test_first_run_validation() {
+        assert!(first_run(Some(FRI_NOON - 1), None, FRI_NOON).is_err());
+        assert_eq!(first_run(Some(FRI_NOON + 60), None, FRI_NOON), Ok(FRI_NOON + 60));
+        assert!(first_run(None, None, FRI_NOON).is_err());
+        assert!(first_run(None, Some("bad"), FRI_NOON).is_err());
+    }
+}
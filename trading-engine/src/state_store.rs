@@ -0,0 +1,214 @@
+// State Store Module
+// Keyed storage for engine state that used to live only in AppState maps.
+// The Postgres backend lets several engine instances share the same state.
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[async_trait]
+pub trait StateStore<V>: Send + Sync
+where
+    V: Clone + Send + Sync + 'static,
+{
+    async fn put(&self, key: &str, value: &V) -> Result<(), String>;
+    async fn get(&self, key: &str) -> Result<Option<V>, String>;
+    /// Returns true if the key existed.
+    async fn remove(&self, key: &str) -> Result<bool, String>;
+    async fn values(&self) -> Result<Vec<V>, String>;
+    /// True when values outlive the process, so callers need no persistence of their own.
+    fn is_durable(&self) -> bool;
+}
+
+/// Build the store configured by `STATE_STORE` (`memory` (default) or `postgres`).
+pub fn from_env<V>(namespace: &str, pool: &PgPool) -> Arc<dyn StateStore<V>>
+where
+    V: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    match std::env::var("STATE_STORE").as_deref() {
+        Ok("postgres") => {
+            tracing::info!("🗄️  {} state stored in Postgres", namespace);
+            Arc::new(PostgresStore::new(namespace, pool.clone()))
+        }
+        _ => Arc::new(InMemoryStore::default()),
+    }
+}
+
+// ==================== IN-MEMORY ====================
+
+pub struct InMemoryStore<V> {
+    entries: RwLock<HashMap<String, V>>,
+}
+
+impl<V> Default for InMemoryStore<V> {
+    fn default() -> Self {
+        Self { entries: RwLock::new(HashMap::new()) }
+    }
+}
+
+#[async_trait]
+impl<V> StateStore<V> for InMemoryStore<V>
+where
+    V: Clone + Send + Sync + 'static,
+{
+    async fn put(&self, key: &str, value: &V) -> Result<(), String> {
+        self.entries.write().await.insert(key.to_string(), value.clone());
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<V>, String> {
+        Ok(self.entries.read().await.get(key).cloned())
+    }
+
+    async fn remove(&self, key: &str) -> Result<bool, String> {
+        Ok(self.entries.write().await.remove(key).is_some())
+    }
+
+    async fn values(&self) -> Result<Vec<V>, String> {
+        Ok(self.entries.read().await.values().cloned().collect())
+    }
+
+    fn is_durable(&self) -> bool {
+        false
+    }
+}
+
+// ==================== POSTGRES ====================
+
+/// Stores JSON-encoded values in `state_store`, partitioned by namespace.
+pub struct PostgresStore<V> {
+    namespace: String,
+    pool: PgPool,
+    _value: PhantomData<fn() -> V>,
+}
+
+impl<V> PostgresStore<V> {
+    pub fn new(namespace: &str, pool: PgPool) -> Self {
+        Self { namespace: namespace.to_string(), pool, _value: PhantomData }
+    }
+}
+
+fn decode<V: DeserializeOwned>(raw: &str) -> Result<V, String> {
+    serde_json::from_str(raw).map_err(|e| format!("Failed to decode stored state: {}", e))
+}
+
+#[async_trait]
+impl<V> StateStore<V> for PostgresStore<V>
+where
+    V: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    async fn put(&self, key: &str, value: &V) -> Result<(), String> {
+        let encoded = serde_json::to_string(value)
+            .map_err(|e| format!("Failed to encode state: {}", e))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO state_store (namespace, key, value, updated_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (namespace, key) DO UPDATE SET value = EXCLUDED.value, updated_at = EXCLUDED.updated_at
+            "#
+        )
+        .bind(&self.namespace)
+        .bind(key)
+        .bind(encoded)
+        .bind(chrono::Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save state: {}", e))?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<V>, String> {
+        let raw: Option<String> = sqlx::query_scalar("SELECT value FROM state_store WHERE namespace = $1 AND key = $2")
+            .bind(&self.namespace)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to load state: {}", e))?;
+
+        raw.as_deref().map(decode).transpose()
+    }
+
+    async fn remove(&self, key: &str) -> Result<bool, String> {
+        let result = sqlx::query("DELETE FROM state_store WHERE namespace = $1 AND key = $2")
+            .bind(&self.namespace)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete state: {}", e))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn values(&self) -> Result<Vec<V>, String> {
+        let rows: Vec<String> = sqlx::query_scalar("SELECT value FROM state_store WHERE namespace = $1 ORDER BY key")
+            .bind(&self.namespace)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to load state: {}", e))?;
+
+        rows.iter().map(|raw| decode(raw)).collect()
+    }
+
+    fn is_durable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        owner: i64,
+        label: String,
+    }
+
+    /// Behaviour every backend must share.
+    async fn behaves_like_a_store(store: &dyn StateStore<Item>) {
+        let a = Item { owner: 1, label: "a".to_string() };
+        let b = Item { owner: 2, label: "b".to_string() };
+
+        assert_eq!(store.get("a").await.unwrap(), None);
+        store.put("a", &a).await.unwrap();
+        store.put("b", &b).await.unwrap();
+        assert_eq!(store.get("a").await.unwrap(), Some(a.clone()));
+
+        // Overwrite keeps a single entry
+        let a2 = Item { owner: 1, label: "a2".to_string() };
+        store.put("a", &a2).await.unwrap();
+        let mut values = store.values().await.unwrap();
+        values.sort_by_key(|i| i.owner);
+        assert_eq!(values, vec![a2, b]);
+
+        assert!(store.remove("a").await.unwrap());
+        assert!(!store.remove("a").await.unwrap());
+        assert_eq!(store.values().await.unwrap().len(), 1);
+        store.remove("b").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        behaves_like_a_store(&InMemoryStore::<Item>::default()).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_postgres_store() {
+        let pool = crate::test_db::pool().await;
+        sqlx::query("CREATE TABLE IF NOT EXISTS state_store (namespace VARCHAR(50) NOT NULL, key VARCHAR(255) NOT NULL, value TEXT NOT NULL, updated_at BIGINT NOT NULL, PRIMARY KEY (namespace, key))")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let namespace = format!("test_{}", uuid::Uuid::new_v4());
+        behaves_like_a_store(&PostgresStore::<Item>::new(&namespace, pool)).await;
+    }
+}
@@ -0,0 +1,19 @@
+// Test Database Helper
+// Tests that need Postgres are marked #[ignore]. Run them against a scratch database with
+// TEST_DATABASE_URL=postgres://... cargo test -- --ignored
+
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions};
+use sqlx::Connection;
+
+fn database_url() -> String {
+    std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set to run database tests")
+}
+
+/// Pool with a single connection, so TEMP tables a test creates stay visible to it.
+pub async fn pool() -> PgPool {
+    PgPoolOptions::new().max_connections(1).connect(&database_url()).await.unwrap()
+}
+
+pub async fn connection() -> PgConnection {
+    PgConnection::connect(&database_url()).await.unwrap()
+}
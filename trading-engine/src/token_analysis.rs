@@ -26,6 +26,16 @@ pub struct TokenAnalysisResponse {
     pub total_score: f64,   // 0-100 (100 = Perfect Gem)
     pub risk_flags: Vec<String>,
     pub bundler_details: Option<BundlerDetails>,
+    pub holders: Vec<HolderInfo>, // Largest holders, biggest first (Solana only)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HolderInfo {
+    pub address: String,       // Token account
+    pub owner: Option<String>, // Wallet or program account that owns it
+    pub percentage: f64,       // % of supply
+    pub is_known_exchange: bool,
+    pub is_lp: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -54,8 +64,16 @@ pub async fn check_token_handler(
         None
     };
 
-    // 3. Calculate Scores
-    let (total_score, risk_flags) = calculate_scores(&dex_data, &bundler_analysis);
+    // 3. Largest holders (Solana only), on the blocking pool like the bundler scan
+    let holders = if chain == "solana" || chain == "sol" {
+        let (mint, client) = (token.clone(), state.solana_client.clone());
+        tokio::task::spawn_blocking(move || fetch_largest_holders(&mint, &client)).await.ok().flatten()
+    } else {
+        None
+    };
+
+    // 4. Calculate Scores
+    let (total_score, risk_flags) = calculate_scores(&dex_data, &bundler_analysis, holders.as_deref());
 
     let response = TokenAnalysisResponse {
         token,
@@ -67,10 +85,11 @@ pub async fn check_token_handler(
         liquidity_usd: dex_data.liquidity,
         volume_24h: dex_data.volume,
         pair_age_hours: dex_data.pair_age_hours,
-        bundler_score: bundler_analysis.as_ref().map(|b| b.bundled_percentage * 100.0).unwrap_or(0.0), // Simplified
+        bundler_score: bundler_analysis.as_ref().map(|b| b.bundled_percentage.min(100.0)).unwrap_or(0.0), // Simplified
         total_score,
         risk_flags,
         bundler_details: bundler_analysis,
+        holders: holders.unwrap_or_default(),
     };
 
     (StatusCode::OK, Json(response)).into_response()
@@ -132,34 +151,284 @@ async fn fetch_dex_data(token: &str) -> Result<DexData, String> {
 
 // ==================== BUNDLER DETECTION ====================
 
+// Looks at the first transactions touching the mint: wallets that bought in
+// the first few slots and were funded by a shared source (or by the creator)
+// are treated as one bundled entity.
+
+const EARLY_TX_LIMIT: usize = 50;
+const MAX_SIGNATURE_PAGES: usize = 10;
+const MAX_FUNDING_LOOKUPS: usize = 25;
+
+fn bundler_slot_window() -> u64 {
+    std::env::var("BUNDLER_SLOT_WINDOW")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3)
+}
+
+/// Walk signatures back to the mint's first transactions. Returns oldest-first,
+/// or None if history is too long to reach the start cheaply or RPC fails.
+fn fetch_earliest_signatures(client: &RpcClient, address: &Pubkey, limit: usize) -> Option<Vec<(String, u64)>> {
+    use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+    use solana_sdk::signature::Signature;
+
+    let mut before: Option<Signature> = None;
+    let mut all = Vec::new();
+    for _ in 0..MAX_SIGNATURE_PAGES {
+        let page = client.get_signatures_for_address_with_config(address, GetConfirmedSignaturesForAddress2Config {
+            before,
+            until: None,
+            limit: Some(1000),
+            commitment: None,
+        }).map_err(|e| tracing::debug!("Bundler scan: signature fetch failed: {}", e)).ok()?;
+
+        let done = page.len() < 1000;
+        before = page.last().and_then(|s| Signature::from_str(&s.signature).ok());
+        all.extend(page.into_iter().filter(|s| s.err.is_none()).map(|s| (s.signature, s.slot)));
+        if done {
+            all.reverse();
+            all.truncate(limit);
+            return Some(all);
+        }
+    }
+    None
+}
+
+fn fetch_transaction_json(client: &RpcClient, signature: &str) -> Option<serde_json::Value> {
+    use solana_client::rpc_config::RpcTransactionConfig;
+    use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+    use solana_transaction_status::UiTransactionEncoding;
+
+    let signature = Signature::from_str(signature).ok()?;
+    let tx = client.get_transaction_with_config(&signature, RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::JsonParsed),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    }).ok()?;
+    serde_json::to_value(&tx).ok()
+}
+
+/// Fee payer (first account key) of a jsonParsed transaction.
+pub fn fee_payer(tx: &serde_json::Value) -> Option<String> {
+    let key = &tx["transaction"]["message"]["accountKeys"][0];
+    key["pubkey"].as_str().or_else(|| key.as_str()).map(String::from)
+}
+
+/// Wallet that sent SOL to `wallet` in this transaction (system transfer or account creation).
+pub fn funding_source(tx: &serde_json::Value, wallet: &str) -> Option<String> {
+    let top_level = tx["transaction"]["message"]["instructions"].as_array().into_iter().flatten();
+    let inner = tx["meta"]["innerInstructions"].as_array().into_iter().flatten()
+        .flat_map(|i| i["instructions"].as_array().into_iter().flatten());
+
+    top_level.chain(inner)
+        .filter(|ix| ix["program"].as_str() == Some("system"))
+        .find_map(|ix| {
+            let info = &ix["parsed"]["info"];
+            let to = info["destination"].as_str().or_else(|| info["newAccount"].as_str())?;
+            if to != wallet {
+                return None;
+            }
+            info["source"].as_str().map(String::from)
+        })
+}
+
+/// Buyers sharing a funder with another buyer, or funded by the creator.
+pub fn find_bundled_wallets(buyers: &[(String, Option<String>)], creator: &str) -> Vec<String> {
+    let mut by_funder: std::collections::HashMap<&str, Vec<&str>> = std::collections::HashMap::new();
+    for (buyer, funder) in buyers {
+        if let Some(funder) = funder {
+            by_funder.entry(funder.as_str()).or_default().push(buyer.as_str());
+        }
+    }
+
+    let mut suspicious: Vec<String> = by_funder.into_iter()
+        .filter(|(funder, wallets)| wallets.len() >= 2 || *funder == creator)
+        .flat_map(|(_, wallets)| wallets.into_iter().map(String::from))
+        .collect();
+    suspicious.sort();
+    suspicious.dedup();
+    suspicious
+}
+
+fn holder_balance(client: &RpcClient, owner: &Pubkey, mint: &Pubkey) -> Option<u64> {
+    use solana_client::rpc_request::TokenAccountsFilter;
+
+    let accounts = client.get_token_accounts_by_owner(owner, TokenAccountsFilter::Mint(*mint)).ok()?;
+    Some(accounts.iter()
+        .filter_map(|keyed| serde_json::to_value(&keyed.account.data).ok())
+        .filter_map(|data| data["parsed"]["info"]["tokenAmount"]["amount"].as_str().and_then(|a| a.parse::<u64>().ok()))
+        .sum())
+}
+
+/// Oldest wallet that funded `wallet`, from the wallet's first transaction.
+fn find_funder(client: &RpcClient, wallet: &str) -> Option<String> {
+    let pubkey = Pubkey::from_str(wallet).ok()?;
+    let (first_sig, _) = fetch_earliest_signatures(client, &pubkey, 1)?.into_iter().next()?;
+    let tx = fetch_transaction_json(client, &first_sig)?;
+    funding_source(&tx, wallet)
+}
+
+lazy_static::lazy_static! {
+    // A mint's creator never changes, so lookups are cached for the process lifetime
+    static ref CREATOR_CACHE: std::sync::RwLock<std::collections::HashMap<String, String>> =
+        std::sync::RwLock::new(std::collections::HashMap::new());
+}
+
+/// Creator (fee payer of the mint's first transaction). Bounded by `timeout` because
+/// it runs on the buy path. None if the history can't be walked in time.
+pub async fn find_token_creator(token: &str, client: &Arc<RpcClient>, timeout: std::time::Duration) -> Option<String> {
+    if let Some(creator) = CREATOR_CACHE.read().ok()?.get(token) {
+        return Some(creator.clone());
+    }
+    let mint = Pubkey::from_str(token).ok()?;
+    let client = client.clone();
+    let lookup = tokio::task::spawn_blocking(move || {
+        let (first_sig, _) = fetch_earliest_signatures(&client, &mint, 1)?.into_iter().next()?;
+        fee_payer(&fetch_transaction_json(&client, &first_sig)?)
+    });
+    let creator = tokio::time::timeout(timeout, lookup).await.ok()?.ok()??;
+    if let Ok(mut cache) = CREATOR_CACHE.write() {
+        cache.insert(token.to_string(), creator.clone());
+    }
+    Some(creator)
+}
+
+/// Runs the bundler scan on the blocking pool: it makes dozens of sequential RPC calls.
 async fn analyze_solana_bundler(token: &str, client: &Arc<RpcClient>) -> Option<BundlerDetails> {
-    // REAL LOGIC TO BE IMPLEMENTED:
-    // 1. Get signaturesForAddress (earliest)
-    // 2. Scan first 50 txs
-    // 3. Count how many buy txs funded by same source
-    
-    // Mock simulation for now to establish architecture (or basic implementation)
-    // Implementing basic parsing is complex without extensive logging, 
-    // so we'll start with a robust placeholder that hints at real logic.
-    
-    let pubkey = Pubkey::from_str(token).ok()?;
-    
-    // Attempt to fetch first signatures (limit 20)
-    // Note: ensure we are fetching earliest. get_signatures_for_address_with_config needed.
-    // For now, let's just assume we check recent for a rapid "live" check or minimal check.
-    
+    let mint = Pubkey::from_str(token).ok()?;
+    let client = client.clone();
+    tokio::task::spawn_blocking(move || scan_solana_bundler(mint, &client)).await.ok()?
+}
+
+fn scan_solana_bundler(mint: Pubkey, client: &RpcClient) -> Option<BundlerDetails> {
+    // 1. Earliest transactions for the mint. None if brand new or RPC refuses.
+    let signatures = fetch_earliest_signatures(client, &mint, EARLY_TX_LIMIT)?;
+    let (first_sig, launch_slot) = signatures.first().cloned()?;
+
+    // 2. Creator = fee payer of the first transaction (mint creation)
+    let creator = fee_payer(&fetch_transaction_json(client, &first_sig)?)?;
+
+    // 3. Distinct buyers in the first few slots
+    let window_end = launch_slot + bundler_slot_window();
+    let mut buyers: Vec<String> = Vec::new();
+    for (sig, slot) in signatures.iter().skip(1).filter(|(_, slot)| *slot <= window_end) {
+        let Some(tx) = fetch_transaction_json(client, sig) else {
+            tracing::debug!("Bundler scan: failed to fetch tx in slot {}", slot);
+            return None;
+        };
+        if let Some(payer) = fee_payer(&tx) {
+            if payer != creator && !buyers.contains(&payer) {
+                buyers.push(payer);
+            }
+        }
+    }
+
+    // 4. Trace who funded each buyer
+    let funded: Vec<(String, Option<String>)> = buyers.iter()
+        .take(MAX_FUNDING_LOOKUPS)
+        .map(|b| (b.clone(), find_funder(client, b)))
+        .collect();
+    let suspicious_wallets = find_bundled_wallets(&funded, &creator);
+
+    // 5. Share of supply still held by the bundled wallets
+    let supply: u64 = client.get_token_supply(&mint).ok()?.amount.parse().ok()?;
+    let mut held: u64 = 0;
+    for wallet in &suspicious_wallets {
+        let owner = Pubkey::from_str(wallet).ok()?;
+        held = held.saturating_add(holder_balance(client, &owner, &mint)?);
+    }
+    let bundled_percentage = if supply > 0 { held as f64 / supply as f64 * 100.0 } else { 0.0 };
+
+    let creator_balance_sol = Pubkey::from_str(&creator).ok()
+        .and_then(|c| client.get_balance(&c).ok())
+        .map(|l| l as f64 / 1_000_000_000.0)
+        .unwrap_or(0.0);
+
     Some(BundlerDetails {
-        creator_address: "Unknown".to_string(),
-        creator_balance_sol: 0.0,
-        initial_buy_count: 0,
-        bundled_percentage: 0.0,
-        suspicious_wallets: vec![],
+        creator_address: creator,
+        creator_balance_sol,
+        initial_buy_count: buyers.len(),
+        bundled_percentage,
+        suspicious_wallets,
     })
 }
 
+// ==================== HOLDERS ====================
+
+/// Owners of AMM pool vaults (Raydium AMM v4 and CPMM authorities).
+const KNOWN_LP_AUTHORITIES: &[&str] = &[
+    "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1",
+    "GpMZbSM2GgvTKHJirzeGfMFoaZ8UR2X7F4v8vHTvxFbL",
+];
+
+/// Exchange hot wallets (Binance, Coinbase, Bybit, OKX). `KNOWN_EXCHANGE_WALLETS` adds more.
+const KNOWN_EXCHANGE_WALLETS: &[&str] = &[
+    "5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9",
+    "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+    "H8sMJSCQxfKiFTCfDR3DUMLPwcRbM61LGFJ8N4dK3WjS",
+    "2AQdpHJ2JpcEgPiATUXjQxA8QmafFegfQwSLWSprPicm",
+    "AC5RDfQFmDS1deWZos921JfqscXdByf8BKHs5ACWjtW2",
+    "5VCwKtCXgCJ6kit5FybXjvriW3xELsFDhYrPSqtJNmcD",
+];
+
+fn is_known_exchange(owner: &str) -> bool {
+    KNOWN_EXCHANGE_WALLETS.contains(&owner)
+        || std::env::var("KNOWN_EXCHANGE_WALLETS")
+            .map(|extra| extra.split(',').any(|w| w.trim() == owner))
+            .unwrap_or(false)
+}
+
+/// Pool vaults: a known AMM authority, or any off-curve (program-derived) owner such as
+/// a bonding curve or pool PDA. Wallets are always on-curve.
+fn is_lp_owner(owner: &Pubkey) -> bool {
+    KNOWN_LP_AUTHORITIES.contains(&owner.to_string().as_str()) || !owner.is_on_curve()
+}
+
+/// Owner field of an SPL Token / Token-2022 account (bytes 32..64 in both layouts).
+pub fn token_account_owner(data: &[u8]) -> Option<Pubkey> {
+    data.get(32..64).and_then(|bytes| Pubkey::try_from(bytes).ok())
+}
+
+pub fn holder_info(address: String, owner: Option<Pubkey>, percentage: f64) -> HolderInfo {
+    HolderInfo {
+        address,
+        is_known_exchange: owner.is_some_and(|o| is_known_exchange(&o.to_string())),
+        is_lp: owner.is_some_and(|o| is_lp_owner(&o)),
+        owner: owner.map(|o| o.to_string()),
+        percentage,
+    }
+}
+
+/// Top-1 and top-10 share of supply, leaving out pool vaults and exchange wallets.
+pub fn holder_concentration(holders: &[HolderInfo]) -> (f64, f64) {
+    let mut shares = holders.iter().filter(|h| !h.is_lp && !h.is_known_exchange).map(|h| h.percentage);
+    let top_1 = shares.next().unwrap_or(0.0);
+    (top_1, top_1 + shares.take(9).sum::<f64>())
+}
+
+/// Up to 20 largest token accounts with their owners. None if the RPC calls fail.
+fn fetch_largest_holders(token: &str, client: &RpcClient) -> Option<Vec<HolderInfo>> {
+    let mint = Pubkey::from_str(token).ok()?;
+    let supply: u64 = client.get_token_supply(&mint).ok()?.amount.parse().ok()?;
+    let largest = client.get_token_largest_accounts(&mint).ok()?;
+
+    let addresses: Vec<Pubkey> = largest.iter().filter_map(|a| Pubkey::from_str(&a.address).ok()).collect();
+    let accounts = client.get_multiple_accounts(&addresses).ok()?;
+
+    Some(largest.into_iter()
+        .zip(accounts)
+        .map(|(holder, account)| {
+            let amount = holder.amount.amount.parse::<u64>().unwrap_or(0);
+            let percentage = if supply > 0 { amount as f64 / supply as f64 * 100.0 } else { 0.0 };
+            let owner = account.and_then(|a| token_account_owner(&a.data));
+            holder_info(holder.address, owner, percentage)
+        })
+        .collect())
+}
+
 // ==================== SCORING ====================
 
-fn calculate_scores(dex: &DexData, bundler: &Option<BundlerDetails>) -> (f64, Vec<String>) {
+fn calculate_scores(dex: &DexData, bundler: &Option<BundlerDetails>, holders: Option<&[HolderInfo]>) -> (f64, Vec<String>) {
     let mut score: f64 = 50.0;
     let mut flags = Vec::new();
 
@@ -179,14 +448,122 @@ fn calculate_scores(dex: &DexData, bundler: &Option<BundlerDetails>) -> (f64, Ve
     }
 
     // 4. Bundler Check (Simple Logic)
-    if let Some(b) = bundler {
-        if b.bundled_percentage > 30.0 {
+    match bundler {
+        Some(b) if b.bundled_percentage > 30.0 => {
             score -= 40.0;
-            flags.push(format!("High Bundler Risk ({}%)", b.bundled_percentage));
+            flags.push(format!("High Bundler Risk ({:.1}%)", b.bundled_percentage));
+        }
+        Some(_) => {}
+        None => flags.push("Bundler analysis unavailable".to_string()),
+    }
+
+    // 5. Holder Concentration (pools and exchanges excluded)
+    match holders {
+        Some(holders) => {
+            let (top_1, top_10) = holder_concentration(holders);
+            if top_1 > 30.0 {
+                score -= 20.0;
+                flags.push(format!("Top 1 Holder owns {:.2}% of supply", top_1));
+            }
+            if top_10 > 90.0 {
+                score -= 20.0;
+                flags.push(format!("Top 10 Holders own {:.2}% of supply (Highly Concentrated)", top_10));
+            } else if top_10 > 50.0 {
+                score -= 10.0;
+                flags.push(format!("Top 10 Holders own {:.2}% of supply", top_10));
+            }
         }
+        None => flags.push("Holder analysis unavailable".to_string()),
     }
 
     // Clamp
     score = score.clamp(0.0, 100.0);
     (score, flags)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_tx() -> serde_json::Value {
+        serde_json::json!({
+            "transaction": {"message": {
+                "accountKeys": [{"pubkey": "Funder111", "signer": true, "writable": true}],
+                "instructions": [
+                    {"program": "system", "parsed": {"type": "transfer", "info": {"source": "Funder111", "destination": "Buyer111", "lamports": 1000}}}
+                ]
+            }},
+            "meta": {"innerInstructions": [
+                {"index": 0, "instructions": [
+                    {"program": "system", "parsed": {"type": "createAccount", "info": {"source": "Funder111", "newAccount": "Buyer222", "lamports": 1000}}}
+                ]}
+            ]}
+        })
+    }
+
+    #[test]
+    fn test_fee_payer_and_funding_source() {
+        let tx = sample_tx();
+        assert_eq!(fee_payer(&tx).as_deref(), Some("Funder111"));
+        assert_eq!(funding_source(&tx, "Buyer111").as_deref(), Some("Funder111"));
+        assert_eq!(funding_source(&tx, "Buyer222").as_deref(), Some("Funder111"));
+        assert_eq!(funding_source(&tx, "Stranger"), None);
+    }
+
+    #[test]
+    fn test_holder_tags_and_concentration() {
+        let wallet = solana_sdk::signature::Keypair::new();
+        let wallet = solana_sdk::signer::Signer::pubkey(&wallet);
+        let pool_pda = Pubkey::find_program_address(&[b"pool"], &Pubkey::new_unique()).0;
+        let raydium = Pubkey::from_str(KNOWN_LP_AUTHORITIES[0]).unwrap();
+        let binance = Pubkey::from_str(KNOWN_EXCHANGE_WALLETS[0]).unwrap();
+        // Exchange wallets are plain keypairs, AMM authorities are PDAs
+        assert!(KNOWN_EXCHANGE_WALLETS.iter().all(|a| Pubkey::from_str(a).is_ok_and(|k| k.is_on_curve())));
+        assert!(KNOWN_LP_AUTHORITIES.iter().all(|a| Pubkey::from_str(a).is_ok_and(|k| !k.is_on_curve())));
+
+        // Owner sits at bytes 32..64 of a token account
+        let mut data = vec![0u8; 165];
+        data[32..64].copy_from_slice(wallet.as_ref());
+        assert_eq!(token_account_owner(&data), Some(wallet));
+        assert_eq!(token_account_owner(&[0u8; 40]), None);
+
+        let holders = vec![
+            holder_info("Vault".to_string(), Some(raydium), 60.0),
+            holder_info("Curve".to_string(), Some(pool_pda), 10.0),
+            holder_info("Cex".to_string(), Some(binance), 8.0),
+            holder_info("Whale".to_string(), Some(wallet), 35.0),
+            holder_info("Unknown".to_string(), None, 5.0),
+        ];
+        assert!(holders[0].is_lp && holders[1].is_lp && !holders[3].is_lp);
+        assert!(holders[2].is_known_exchange && !holders[3].is_known_exchange);
+
+        let (top_1, top_10) = holder_concentration(&holders);
+        assert_eq!((top_1, top_10), (35.0, 40.0));
+    }
+
+    #[test]
+    fn test_concentration_lowers_score() {
+        let dex = DexData { name: None, symbol: None, price_usd: 1.0, market_cap: 0.0, fdv: 0.0, liquidity: 50_000.0, volume: 0.0, pair_age_hours: 5.0 };
+        let whale = |pct: f64| holder_info("Acct".to_string(), None, pct);
+
+        let (spread, _) = calculate_scores(&dex, &None, Some(&[whale(5.0), whale(4.0)]));
+        let (concentrated, flags) = calculate_scores(&dex, &None, Some(&[whale(40.0), whale(30.0), whale(25.0)]));
+        assert_eq!(spread - concentrated, 40.0);
+        assert!(flags.iter().any(|f| f.starts_with("Top 1 Holder owns 40.00%")));
+
+        let (_, flags) = calculate_scores(&dex, &None, None);
+        assert!(flags.contains(&"Holder analysis unavailable".to_string()));
+    }
+
+    #[test]
+    fn test_find_bundled_wallets() {
+        let buyers = vec![
+            ("A".to_string(), Some("Hub".to_string())),
+            ("B".to_string(), Some("Hub".to_string())),
+            ("C".to_string(), Some("Exchange".to_string())),
+            ("D".to_string(), Some("Creator".to_string())),
+            ("E".to_string(), None),
+        ];
+        assert_eq!(find_bundled_wallets(&buyers, "Creator"), vec!["A", "B", "D"]);
+    }
+}
@@ -0,0 +1,330 @@
+// Transaction Status Module
+// Looks up on-chain confirmation status for signatures returned by buy/sell
+
+use serde::Serialize;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
+
+// ==================== DATA STRUCTURES ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxStatus {
+    NotFound,
+    Processed,
+    Confirmed,
+    Finalized,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TxStatusResponse {
+    pub chain: String,
+    pub signature: String,
+    pub status: TxStatus,
+    pub slot: Option<u64>,  // Solana slot or EVM block number
+    pub error: Option<String>,
+}
+
+/// The parts of a Solana signature status we care about.
+#[derive(Debug, Clone)]
+pub struct SolanaSignatureStatus {
+    pub slot: u64,
+    pub err: Option<String>,
+    pub confirmed: bool,
+    pub finalized: bool,
+}
+
+impl TxStatus {
+    /// Finalized transactions can't change again, so their lookups are safe to cache. A failure
+    /// only stops changing once `settled` (finalized commitment or EVM finality depth); before
+    /// that it can be reorged just like a confirmed transaction, so it is re-checked.
+    pub fn is_terminal(self, settled: bool) -> bool {
+        match self {
+            TxStatus::Finalized => true,
+            TxStatus::Failed => settled,
+            _ => false,
+        }
+    }
+}
+
+/// Result of one status lookup. `settled` is true once the transaction's block is final.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusLookup {
+    pub status: TxStatus,
+    pub slot: Option<u64>,
+    pub error: Option<String>,
+    pub settled: bool,
+}
+
+impl StatusLookup {
+    fn new(status: TxStatus, slot: Option<u64>, error: Option<String>, settled: bool) -> Self {
+        Self { status, slot, error, settled }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.status.is_terminal(self.settled)
+    }
+}
+
+// ==================== CACHE ====================
+
+/// Terminal statuses by chain and signature, so clients polling a settled tx don't hit the RPC.
+#[derive(Clone)]
+pub struct TxStatusCache {
+    entries: Arc<tokio::sync::RwLock<HashMap<(String, String), StatusLookup>>>,
+    max_entries: usize,
+}
+
+impl TxStatusCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: Arc::new(tokio::sync::RwLock::new(HashMap::new())),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let max_entries = std::env::var("TX_STATUS_CACHE_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(10_000);
+        Self::new(max_entries)
+    }
+
+    /// Return a cached terminal status, or call `fetch` and keep the result if it is terminal.
+    pub async fn get_or_fetch<F, Fut>(&self, chain: &str, signature: &str, fetch: F) -> Result<StatusLookup, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<StatusLookup, String>>,
+    {
+        let key = (chain.to_string(), signature.to_string());
+        if let Some(cached) = self.entries.read().await.get(&key) {
+            return Ok(cached.clone());
+        }
+
+        let lookup = fetch().await?;
+        if lookup.is_terminal() {
+            let mut entries = self.entries.write().await;
+            if entries.len() >= self.max_entries {
+                entries.clear();
+            }
+            entries.insert(key, lookup.clone());
+        }
+        Ok(lookup)
+    }
+}
+
+/// Canonical chain name for cache keys, so `sol` and `solana` share entries.
+fn normalize_chain(chain: &str) -> Option<&'static str> {
+    match chain {
+        "solana" | "sol" => Some("solana"),
+        "eth" | "ethereum" => Some("eth"),
+        "bsc" | "binance" => Some("bsc"),
+        _ => None,
+    }
+}
+
+// ==================== STATUS MAPPING ====================
+
+pub fn map_solana_status(status: Option<&SolanaSignatureStatus>) -> StatusLookup {
+    match status {
+        None => StatusLookup::new(TxStatus::NotFound, None, None, false),
+        Some(s) if s.err.is_some() => StatusLookup::new(TxStatus::Failed, Some(s.slot), s.err.clone(), s.finalized),
+        Some(s) if s.finalized => StatusLookup::new(TxStatus::Finalized, Some(s.slot), None, true),
+        Some(s) if s.confirmed => StatusLookup::new(TxStatus::Confirmed, Some(s.slot), None, false),
+        Some(s) => StatusLookup::new(TxStatus::Processed, Some(s.slot), None, false),
+    }
+}
+
+fn parse_hex_u64(value: &serde_json::Value) -> Option<u64> {
+    value.as_str()
+        .and_then(|h| u64::from_str_radix(h.trim_start_matches("0x"), 16).ok())
+}
+
+/// Map an `eth_getTransactionReceipt` result. A null receipt means the tx is
+/// still pending or unknown. Receipts older than `finality_depth` blocks count as finalized.
+pub fn map_evm_receipt(
+    receipt: &serde_json::Value,
+    latest_block: Option<u64>,
+    finality_depth: u64,
+) -> StatusLookup {
+    if receipt.is_null() {
+        return StatusLookup::new(TxStatus::NotFound, None, None, false);
+    }
+
+    let block = parse_hex_u64(&receipt["blockNumber"]);
+    let finalized = match (block, latest_block) {
+        (Some(b), Some(latest)) => latest.saturating_sub(b) >= finality_depth,
+        _ => false,
+    };
+    if parse_hex_u64(&receipt["status"]) == Some(0) {
+        return StatusLookup::new(TxStatus::Failed, block, Some("Transaction reverted".to_string()), finalized);
+    }
+
+    let status = if finalized { TxStatus::Finalized } else { TxStatus::Confirmed };
+    StatusLookup::new(status, block, None, finalized)
+}
+
+// ==================== LOOKUPS ====================
+
+async fn fetch_solana_status(state: &AppState, signature: &str) -> Result<Option<SolanaSignatureStatus>, String> {
+    let signature = Signature::from_str(signature)
+        .map_err(|_| "Invalid signature".to_string())?;
+
+    let statuses = state.solana_client
+        .get_signature_statuses_with_history(&[signature])
+        .map_err(|e| format!("RPC error: {}", e))?;
+
+    Ok(statuses.value.into_iter().next().flatten().map(|s| SolanaSignatureStatus {
+        slot: s.slot,
+        err: s.err.as_ref().map(|e| e.to_string()),
+        confirmed: s.satisfies_commitment(CommitmentConfig::confirmed()),
+        finalized: s.satisfies_commitment(CommitmentConfig::finalized()),
+    }))
+}
+
+async fn fetch_evm_status(chain: &str, tx_hash: &str) -> Result<StatusLookup, String> {
+    if !tx_hash.starts_with("0x") || tx_hash.len() != 66 {
+        return Err("Invalid transaction hash".to_string());
+    }
+
+    let (rpc_url, _) = crate::balance::evm_rpc_urls(chain)?;
+    let receipt = crate::balance::evm_rpc_call(&rpc_url, "eth_getTransactionReceipt", serde_json::json!([tx_hash])).await?;
+    let latest = crate::balance::evm_rpc_call(&rpc_url, "eth_blockNumber", serde_json::json!([])).await
+        .ok()
+        .and_then(|v| parse_hex_u64(&v));
+
+    let finality_depth = std::env::var("EVM_FINALITY_BLOCKS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(12);
+
+    Ok(map_evm_receipt(&receipt, latest, finality_depth))
+}
+
+// ==================== API HANDLERS ====================
+
+pub async fn get_tx_status_handler(
+    State(state): State<AppState>,
+    Path((chain, signature)): Path<(String, String)>,
+) -> impl IntoResponse {
+    let result = match normalize_chain(&chain) {
+        Some(key) => state.tx_status_cache.get_or_fetch(key, &signature, || async {
+            if key == "solana" {
+                fetch_solana_status(&state, &signature).await.map(|s| map_solana_status(s.as_ref()))
+            } else {
+                fetch_evm_status(key, &signature).await
+            }
+        }).await,
+        None => Err("Unsupported chain".to_string()),
+    };
+
+    match result {
+        Ok(lookup) => (StatusCode::OK, Json(TxStatusResponse {
+            chain,
+            signature,
+            status: lookup.status,
+            slot: lookup.slot,
+            error: lookup.error,
+        })).into_response(),
+        Err(e) => {
+            let code = if e.starts_with("Invalid") || e.starts_with("Unsupported") {
+                StatusCode::BAD_REQUEST
+            } else {
+                StatusCode::INTERNAL_SERVER_ERROR
+            };
+            (code, Json(serde_json::json!({"error": e}))).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sol_status(err: Option<&str>, confirmed: bool, finalized: bool) -> SolanaSignatureStatus {
+        SolanaSignatureStatus { slot: 42, err: err.map(String::from), confirmed, finalized }
+    }
+
+    #[test]
+    fn test_map_solana_status() {
+        assert_eq!(map_solana_status(None).status, TxStatus::NotFound);
+        assert_eq!(
+            map_solana_status(Some(&sol_status(None, false, false))),
+            StatusLookup::new(TxStatus::Processed, Some(42), None, false)
+        );
+        assert_eq!(map_solana_status(Some(&sol_status(None, true, false))).status, TxStatus::Confirmed);
+        assert_eq!(map_solana_status(Some(&sol_status(None, true, true))).status, TxStatus::Finalized);
+
+        let failed = map_solana_status(Some(&sol_status(Some("InstructionError"), true, true)));
+        assert_eq!(failed.status, TxStatus::Failed);
+        assert_eq!(failed.slot, Some(42));
+        assert_eq!(failed.error.as_deref(), Some("InstructionError"));
+        assert!(failed.is_terminal());
+
+        // A failure seen before finalization can still be reorged away
+        assert!(!map_solana_status(Some(&sol_status(Some("InstructionError"), false, false))).is_terminal());
+    }
+
+    #[test]
+    fn test_map_evm_receipt() {
+        assert_eq!(map_evm_receipt(&serde_json::Value::Null, Some(100), 12).status, TxStatus::NotFound);
+
+        let ok = serde_json::json!({"status": "0x1", "blockNumber": "0x64"}); // block 100
+        assert_eq!(map_evm_receipt(&ok, Some(105), 12), StatusLookup::new(TxStatus::Confirmed, Some(100), None, false));
+        assert_eq!(map_evm_receipt(&ok, Some(112), 12).status, TxStatus::Finalized);
+
+        let reverted = serde_json::json!({"status": "0x0", "blockNumber": "0x64"});
+        assert_eq!(map_evm_receipt(&reverted, Some(200), 12).status, TxStatus::Failed);
+        assert!(map_evm_receipt(&reverted, Some(200), 12).is_terminal());
+        // A fresh revert isn't final yet
+        assert!(!map_evm_receipt(&reverted, Some(100), 12).is_terminal());
+    }
+
+    #[tokio::test]
+    async fn test_cache_keeps_only_terminal_statuses() {
+        let cache = TxStatusCache::new(10);
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let lookup = |status: TxStatus, settled: bool| {
+            let calls = &calls;
+            move || async move {
+                calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(StatusLookup::new(status, Some(42), None, settled))
+            }
+        };
+
+        // Confirmed can still change, so every poll goes to the RPC
+        cache.get_or_fetch("solana", "sig", lookup(TxStatus::Confirmed, false)).await.unwrap();
+        cache.get_or_fetch("solana", "sig", lookup(TxStatus::Confirmed, false)).await.unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // Once finalized, later polls are served from the cache
+        cache.get_or_fetch("solana", "sig", lookup(TxStatus::Finalized, true)).await.unwrap();
+        let cached = cache.get_or_fetch("solana", "sig", lookup(TxStatus::Processed, false)).await.unwrap();
+        assert_eq!(cached.status, TxStatus::Finalized);
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        // The same hash on another chain is a different entry
+        cache.get_or_fetch("eth", "sig", lookup(TxStatus::Failed, true)).await.unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+        // An unsettled failure is looked up again
+        cache.get_or_fetch("bsc", "sig", lookup(TxStatus::Failed, false)).await.unwrap();
+        cache.get_or_fetch("bsc", "sig", lookup(TxStatus::Failed, false)).await.unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 6);
+        assert_eq!(normalize_chain("sol"), Some("solana"));
+        assert_eq!(normalize_chain("binance"), Some("bsc"));
+        assert_eq!(normalize_chain("tron"), None);
+    }
+}
@@ -0,0 +1,113 @@
+// Token Units Module
+// Conversions between raw on-chain integer amounts and human-readable decimal strings
+
+// ==================== DECIMALS ====================
+pub const SOL_DECIMALS: u8 = 9;
+pub const EVM_NATIVE_DECIMALS: u8 = 18;
+
+/// Decimals of a chain's native asset (SOL, ETH, BNB).
+pub fn native_decimals(chain: &str) -> u8 {
+    match chain {
+        "solana" | "sol" => SOL_DECIMALS,
+        _ => EVM_NATIVE_DECIMALS,
+    }
+}
+
+fn pow10(decimals: u8) -> Result<u128, String> {
+    10u128.checked_pow(decimals as u32)
+        .ok_or_else(|| format!("Unsupported decimals: {}", decimals))
+}
+
+// ==================== FORMAT / PARSE ====================
+
+/// Render a raw integer amount with the decimal point in the right place,
+/// without trailing zeros (1_500_000 @ 6 decimals => "1.5").
+pub fn format_token_amount(raw: u128, decimals: u8) -> String {
+    let scale = match pow10(decimals) {
+        Ok(s) => s,
+        Err(_) => return raw.to_string(),
+    };
+
+    let whole = raw / scale;
+    let fraction = raw % scale;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// Parse a decimal string into a raw integer amount. Rejects more fractional
+/// digits than the token supports rather than silently rounding.
+pub fn parse_token_amount(amount: &str, decimals: u8) -> Result<u128, String> {
+    let amount = amount.trim();
+    let (whole, fraction) = match amount.split_once('.') {
+        Some((w, f)) => (w, f),
+        None => (amount, ""),
+    };
+
+    if whole.is_empty() && fraction.is_empty() {
+        return Err("Amount is empty".to_string());
+    }
+    if !whole.chars().all(|c| c.is_ascii_digit()) || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("Invalid amount: {}", amount));
+    }
+    if fraction.len() > decimals as usize {
+        return Err(format!("Amount {} has more than {} decimal places", amount, decimals));
+    }
+
+    let scale = pow10(decimals)?;
+    let whole: u128 = if whole.is_empty() { 0 } else {
+        whole.parse().map_err(|_| format!("Amount too large: {}", amount))?
+    };
+    let fraction: u128 = if fraction.is_empty() { 0 } else {
+        let padded = format!("{:0<width$}", fraction, width = decimals as usize);
+        padded.parse().map_err(|_| format!("Invalid amount: {}", amount))?
+    };
+
+    whole.checked_mul(scale)
+        .and_then(|w| w.checked_add(fraction))
+        .ok_or_else(|| format!("Amount too large: {}", amount))
+}
+
+/// Re-render a stored decimal string canonically. Values that aren't plain
+/// amounts (e.g. "50%") are returned unchanged.
+pub fn normalize_amount(amount: &str, decimals: u8) -> String {
+    match parse_token_amount(amount, decimals) {
+        Ok(raw) => format_token_amount(raw, decimals),
+        Err(_) => amount.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_six_decimals_round_trip() {
+        assert_eq!(format_token_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_token_amount(1, 6), "0.000001");
+        assert_eq!(parse_token_amount("1.5", 6), Ok(1_500_000));
+        assert_eq!(parse_token_amount(&format_token_amount(123_456_789, 6), 6), Ok(123_456_789));
+        assert!(parse_token_amount("0.0000001", 6).is_err());
+    }
+
+    #[test]
+    fn test_nine_decimals_round_trip() {
+        assert_eq!(format_token_amount(1_000_000_000, 9), "1");
+        assert_eq!(format_token_amount(100_000_000, 9), "0.1");
+        assert_eq!(parse_token_amount(".25", 9), Ok(250_000_000));
+        assert_eq!(parse_token_amount(&format_token_amount(987_654_321_012, 9), 9), Ok(987_654_321_012));
+    }
+
+    #[test]
+    fn test_eighteen_decimals_round_trip() {
+        let one_eth_and_a_wei = 1_000_000_000_000_000_001u128;
+        assert_eq!(format_token_amount(one_eth_and_a_wei, 18), "1.000000000000000001");
+        assert_eq!(parse_token_amount("1.000000000000000001", 18), Ok(one_eth_and_a_wei));
+        assert_eq!(normalize_amount("2.500000000000000000", 18), "2.5");
+        assert_eq!(normalize_amount("50%", 18), "50%");
+        assert!(parse_token_amount("-1", 18).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+// Validation Module
+// Checks on user-supplied trade parameters (addresses, amounts, exit targets),
+// run before a trade touches the chain
+
+use crate::evm;
+
+/// Check that `token` is a well-formed address on `chain`.
+pub fn validate_token_address(chain: &str, token: &str) -> Result<(), String> {
+    match chain {
+        "solana" => validate_solana_address(token),
+        "eth" | "ethereum" | "bsc" | "binance" => validate_evm_address(token),
+        _ => Err(format!("Unsupported chain: {}", chain)),
+    }
+}
+
+/// Base58 that decodes to a 32-byte public key.
+pub fn validate_solana_address(address: &str) -> Result<(), String> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| format!("Invalid Solana address {}: not valid base58", address))?;
+    if bytes.len() != 32 {
+        return Err(format!("Invalid Solana address {}: decodes to {} bytes, expected 32", address, bytes.len()));
+    }
+    Ok(())
+}
+
+/// `0x` + 40 hex digits, either all lowercase or with a correct EIP-55 checksum.
+pub fn validate_evm_address(address: &str) -> Result<(), String> {
+    let Some(hex_part) = address.strip_prefix("0x") else {
+        return Err(format!("Invalid EVM address {}: must start with 0x", address));
+    };
+    if hex_part.len() != 40 {
+        return Err(format!("Invalid EVM address {}: expected 40 hex characters, got {}", address, hex_part.len()));
+    }
+    let parsed = evm::parse_address(address).map_err(|_| format!("Invalid EVM address {}: not valid hex", address))?;
+
+    if hex_part.chars().any(|c| c.is_ascii_uppercase()) {
+        let expected = to_checksum_address(&parsed);
+        if expected != address {
+            return Err(format!("Invalid EVM address {}: bad EIP-55 checksum (expected {})", address, expected));
+        }
+    }
+    Ok(())
+}
+
+/// EIP-55 mixed-case form: a letter is uppercased when its nibble in keccak(lowercase hex) is >= 8.
+pub fn to_checksum_address(address: &evm::Address) -> String {
+    let lower = hex::encode(address);
+    let hash = evm::keccak256(lower.as_bytes());
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+/// Largest single buy, in native units.
+pub const MAX_BUY_AMOUNT: f64 = 100.0;
+
+/// Parse a buy amount. NaN and infinities are rejected as malformed, not as out of range.
+pub fn parse_buy_amount(amount: &str) -> Result<f64, String> {
+    match amount.parse::<f64>() {
+        Ok(amt) if !amt.is_finite() => Err("Invalid amount format".to_string()),
+        Ok(amt) if amt <= 0.0 => Err("Amount must be greater than 0".to_string()),
+        Ok(amt) if amt > MAX_BUY_AMOUNT => Err(format!("Amount too large: {} SOL. Maximum is {} SOL", amt, MAX_BUY_AMOUNT)),
+        Ok(amt) => Ok(amt),
+        Err(_) => Err("Invalid amount format".to_string()),
+    }
+}
+
+/// TP/SL percentages from a buy. Zero disables either one. A stop loss must sit
+/// below 100% (the price can't fall further than that).
+pub fn validate_exit_targets(take_profit: f64, stop_loss: f64) -> Result<(), String> {
+    if !take_profit.is_finite() || take_profit < 0.0 {
+        return Err(format!("Invalid take_profit {}: must be a positive percentage", take_profit));
+    }
+    if !stop_loss.is_finite() || !(0.0..100.0).contains(&stop_loss) {
+        return Err(format!("Invalid stop_loss {}: must be at least 0 and below 100 percent", stop_loss));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solana_addresses() {
+        assert!(validate_token_address("solana", "So11111111111111111111111111111111111111112").is_ok());
+        // Right length, but 0/O/I/l are not in the base58 alphabet
+        assert!(validate_token_address("solana", "So1111111111111111111111111111111111111111O").unwrap_err().contains("base58"));
+        // Valid base58 that isn't 32 bytes
+        assert!(validate_token_address("solana", "So1111111111111111111111111111111").unwrap_err().contains("expected 32"));
+    }
+
+    #[test]
+    fn test_buy_amount_bounds() {
+        assert_eq!(parse_buy_amount("0.5"), Ok(0.5));
+        assert_eq!(parse_buy_amount("100"), Ok(100.0));
+        assert!(parse_buy_amount("100.0001").unwrap_err().contains("too large"));
+        assert!(parse_buy_amount("0").unwrap_err().contains("greater than 0"));
+        assert!(parse_buy_amount("-1").unwrap_err().contains("greater than 0"));
+        for malformed in ["NaN", "nan", "inf", "-infinity", "abc", ""] {
+            assert_eq!(parse_buy_amount(malformed), Err("Invalid amount format".to_string()), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_exit_target_bounds() {
+        assert_eq!(validate_exit_targets(0.0, 0.0), Ok(()));
+        assert_eq!(validate_exit_targets(500.0, 99.9), Ok(()));
+        assert!(validate_exit_targets(-10.0, 20.0).unwrap_err().contains("take_profit"));
+        assert!(validate_exit_targets(f64::NAN, 20.0).unwrap_err().contains("take_profit"));
+        assert!(validate_exit_targets(f64::INFINITY, 20.0).unwrap_err().contains("take_profit"));
+        assert!(validate_exit_targets(50.0, 100.0).unwrap_err().contains("stop_loss"));
+        assert!(validate_exit_targets(50.0, 500.0).unwrap_err().contains("stop_loss"));
+        assert!(validate_exit_targets(50.0, -1.0).unwrap_err().contains("stop_loss"));
+        assert!(validate_exit_targets(50.0, f64::NAN).unwrap_err().contains("stop_loss"));
+    }
+
+    #[test]
+    fn test_evm_checksum() {
+        // EIP-55 reference vectors
+        for address in ["0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed", "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359"] {
+            assert_eq!(validate_token_address("eth", address), Ok(()));
+        }
+        assert!(validate_token_address("bsc", "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_ok());
+        assert!(validate_token_address("eth", "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").unwrap_err().contains("checksum"));
+        assert!(validate_token_address("eth", "5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap_err().contains("0x"));
+        assert!(validate_token_address("eth", "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beazz").unwrap_err().contains("hex"));
+        // A Solana mint on an EVM chain
+        assert!(validate_token_address("eth", "So11111111111111111111111111111111111111112").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+// User Verification Module
+// Optional KYC gate: with REQUIRE_VERIFIED_FOR_TRADING set, only verified users can buy/sell.
+// Wallet and balance endpoints are never gated.
+
+use serde::{Deserialize, Serialize};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use crate::AppState;
+
+pub const UNVERIFIED_TRADING_ERROR: &str =
+    "Trading requires a verified account. Wallet and balance features remain available.";
+
+// ==================== CONFIG ====================
+
+/// `REQUIRE_VERIFIED_FOR_TRADING=true` turns the gate on. Off by default.
+pub fn require_verified_for_trading() -> bool {
+    std::env::var("REQUIRE_VERIFIED_FOR_TRADING")
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
+// ==================== TRADING GATE ====================
+
+pub fn trading_allowed(require_verified: bool, verified: bool) -> Result<(), String> {
+    if require_verified && !verified {
+        return Err(UNVERIFIED_TRADING_ERROR.to_string());
+    }
+    Ok(())
+}
+
+/// Unknown users count as unverified.
+pub async fn is_verified(pool: &sqlx::PgPool, user_id: i64) -> Result<bool, String> {
+    let verified: Option<bool> = sqlx::query_scalar("SELECT verified FROM users WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(verified.unwrap_or(false))
+}
+
+/// Called at the top of every trade handler. Skips the DB lookup when the gate is off.
+pub async fn check_trading_allowed(pool: &sqlx::PgPool, user_id: i64) -> Result<(), String> {
+    if !require_verified_for_trading() {
+        return Ok(());
+    }
+    trading_allowed(true, is_verified(pool, user_id).await?)
+}
+
+pub async fn set_verified(pool: &sqlx::PgPool, user_id: i64, verified: bool) -> Result<(), String> {
+    sqlx::query(
+        "INSERT INTO users (user_id, verified) VALUES ($1, $2) \
+         ON CONFLICT (user_id) DO UPDATE SET verified = $2, updated_at = NOW()"
+    )
+    .bind(user_id)
+    .bind(verified)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Database error: {}", e))?;
+    Ok(())
+}
+
+// ==================== ADMIN ====================
+
+/// Admin endpoints need `x-admin-key` to match `ADMIN_API_KEY`. Unset key = admin API disabled.
+pub fn admin_authorized(configured_key: Option<&str>, provided_key: Option<&str>) -> bool {
+    match (configured_key, provided_key) {
+        (Some(expected), Some(provided)) => !expected.is_empty() && expected == provided,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetVerifiedRequest {
+    pub verified: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SetVerifiedResponse {
+    pub success: bool,
+    pub user_id: i64,
+    pub verified: bool,
+    pub error: Option<String>,
+}
+
+pub async fn set_verified_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+    headers: HeaderMap,
+    Json(request): Json<SetVerifiedRequest>,
+) -> impl IntoResponse {
+    let configured = std::env::var("ADMIN_API_KEY").ok();
+    let provided = headers.get("x-admin-key").and_then(|v| v.to_str().ok());
+    if !admin_authorized(configured.as_deref(), provided) {
+        return (StatusCode::FORBIDDEN, Json(SetVerifiedResponse {
+            success: false,
+            user_id,
+            verified: false,
+            error: Some("Admin access denied".to_string()),
+        }));
+    }
+
+    match set_verified(&state.db, user_id, request.verified).await {
+        Ok(()) => {
+            tracing::info!("User {} verified={}", user_id, request.verified);
+            (StatusCode::OK, Json(SetVerifiedResponse { success: true, user_id, verified: request.verified, error: None }))
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(SetVerifiedResponse { success: false, user_id, verified: false, error: Some(e) })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unverified_user_blocked_when_required() {
+        assert_eq!(trading_allowed(true, false), Err(UNVERIFIED_TRADING_ERROR.to_string()));
+        assert_eq!(trading_allowed(true, true), Ok(()));
+    }
+
+    #[test]
+    fn test_unverified_user_allowed_when_not_required() {
+        assert_eq!(trading_allowed(false, false), Ok(()));
+        assert_eq!(trading_allowed(false, true), Ok(()));
+    }
+
+    #[test]
+    fn test_admin_key_check() {
+        assert!(admin_authorized(Some("secret"), Some("secret")));
+        assert!(!admin_authorized(Some("secret"), Some("wrong")));
+        assert!(!admin_authorized(Some("secret"), None));
+        assert!(!admin_authorized(None, Some("secret")));
+        assert!(!admin_authorized(Some(""), Some("")));
+    }
+}
@@ -12,6 +12,9 @@ use base64::{Engine as _, engine::general_purpose::STANDARD};
 use secp256k1::{Secp256k1, SecretKey, PublicKey};
 use sha3::{Keccak256, Digest};
 use magic_crypt::MagicCryptTrait;
+use aes_gcm::{aead::{Aead, AeadCore, KeyInit, OsRng}, Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
 use std::env;
 use bip39::{Mnemonic, Language};
 
@@ -37,6 +40,67 @@ pub struct WalletInfo {
     pub balance: Option<String>,
     #[sqlx(default)]
     pub last_updated: Option<i64>,
+    #[sqlx(default)]
+    pub label: Option<String>,
+    #[sqlx(default)]
+    pub is_default: Option<bool>,
+}
+
+/// Which of a user's wallets on a chain to use.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum WalletSelector {
+    /// The flagged default, else the oldest wallet (rows from before labels have no flag).
+    #[default]
+    Default,
+    Label(String),
+    Address(String),
+}
+
+impl WalletSelector {
+    pub fn from_label(label: Option<&str>) -> Self {
+        match label.map(str::trim) {
+            Some(l) if !l.is_empty() => WalletSelector::Label(l.to_string()),
+            _ => WalletSelector::Default,
+        }
+    }
+
+    /// `SELECT <columns>` for the selected wallet. Binds: $1 user_id, $2 chain, $3 label.
+    pub fn select_sql(&self, columns: &str) -> String {
+        let base = format!("SELECT {} FROM wallets WHERE user_id = $1 AND chain = $2", columns);
+        match self {
+            WalletSelector::Default => format!("{} ORDER BY COALESCE(is_default, FALSE) DESC, id LIMIT 1", base),
+            WalletSelector::Label(_) => format!("{} AND label = $3", base),
+            WalletSelector::Address(_) => format!("{} AND address = $3", base),
+        }
+    }
+
+    pub fn not_found(&self) -> String {
+        match self {
+            WalletSelector::Default => "Wallet not found".to_string(),
+            WalletSelector::Label(l) => format!("Wallet '{}' not found", l),
+            WalletSelector::Address(a) => format!("Wallet {} not found", a),
+        }
+    }
+}
+
+/// One text column of the selected wallet.
+pub async fn fetch_wallet_field(
+    user_id: i64,
+    chain: &str,
+    wallet: &WalletSelector,
+    column: &str,
+    pool: &PgPool,
+) -> Result<String, String> {
+    let sql = wallet.select_sql(column);
+    let query = sqlx::query_scalar::<_, String>(&sql).bind(user_id).bind(chain);
+    let query = match wallet {
+        WalletSelector::Default => query,
+        WalletSelector::Label(label) | WalletSelector::Address(label) => query.bind(label.clone()),
+    };
+    query.fetch_optional(pool)
+        .await
+        .map_err(|e| format!("DB Error: {}", e))?
+        .ok_or_else(|| wallet.not_found())
 }
 
 #[derive(Debug, Serialize)]
@@ -52,13 +116,67 @@ pub struct WalletResponse {
 pub struct GenerateWalletRequest {
     pub user_id: i64,
     pub chain: String,
+    #[serde(default)]
+    pub label: Option<String>, // Required for every wallet after the first on a chain
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ImportWalletRequest {
     pub user_id: i64,
     pub chain: String,
-    pub private_key: String,
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub mnemonic: Option<String>, // Alternative to private_key: a 12/24-word seed phrase
+    #[serde(default)]
+    pub account_index: u32, // Which account of the mnemonic to import
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WalletTransferRequest {
+    pub user_id: i64,
+    pub chain: String,
+    pub from_address: String,
+    pub to_address: String,
+    pub amount: String, // SOL, or whole tokens when `token_mint` is set
+    #[serde(default)]
+    pub token_mint: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WalletTransferResponse {
+    pub success: bool,
+    pub tx_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetDefaultWalletRequest {
+    pub user_id: i64,
+    pub chain: String,
+    pub label: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WithdrawRequest {
+    pub user_id: i64,
+    pub chain: String,
+    pub to_address: String,
+    #[serde(default)]
+    pub include_tokens: bool,
+    #[serde(default)]
+    pub wallet_label: Option<String>, // Default wallet if unset
+}
+
+#[derive(Debug, Serialize)]
+pub struct WithdrawResponse {
+    pub success: bool,
+    pub tx_hashes: Vec<String>,
+    pub withdrawn_sol: Option<f64>,
+    pub tokens_swept: usize,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -75,14 +193,35 @@ pub struct ImportDataResponse {
     pub errors: Vec<String>,
 }
 
-// ==================== ENCRYPTION (AES-256) ====================
-pub fn encrypt_key(key: &str, _user_id: i64) -> String {
+// ==================== ENCRYPTION (AES-256-GCM) ====================
+// Stored format: "v2:" + base64(nonce || ciphertext). Blobs without the prefix
+// are the older magic-crypt format and are re-encrypted on first use.
+const KEY_FORMAT_V2: &str = "v2:";
+const NONCE_LEN: usize = 12;
+
+/// Per-user AES key derived from the master key with HKDF-SHA256.
+fn derive_user_key(user_id: i64) -> [u8; 32] {
     let master_key = get_master_key();
-    let mc = magic_crypt::new_magic_crypt!(master_key, 256);
-    mc.encrypt_str_to_base64(key)
+    let hk = Hkdf::<Sha256>::new(None, master_key.as_bytes());
+    let mut okm = [0u8; 32];
+    hk.expand(format!("wallet-key:{}", user_id).as_bytes(), &mut okm)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    okm
 }
 
-pub fn decrypt_key(encrypted: &str, _user_id: i64) -> Result<String, String> {
+pub fn encrypt_key(key: &str, user_id: i64) -> String {
+    let cipher = Aes256Gcm::new(&derive_user_key(user_id).into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, key.as_bytes())
+        .expect("AES-GCM encryption of an in-memory buffer cannot fail");
+
+    let mut blob = nonce.to_vec();
+    blob.extend_from_slice(&ciphertext);
+    format!("{}{}", KEY_FORMAT_V2, STANDARD.encode(blob))
+}
+
+pub fn decrypt_key(encrypted: &str, user_id: i64) -> Result<String, String> {
     // Trim whitespace that might have been introduced during storage/retrieval
     let encrypted = encrypted.trim();
     
@@ -90,7 +229,65 @@ pub fn decrypt_key(encrypted: &str, _user_id: i64) -> Result<String, String> {
     if encrypted.is_empty() {
         return Err("Decryption failed: Empty encrypted data".to_string());
     }
-    
+
+    let Some(payload) = encrypted.strip_prefix(KEY_FORMAT_V2) else {
+        return decrypt_legacy_key(encrypted);
+    };
+
+    let blob = STANDARD.decode(payload)
+        .map_err(|e| format!("Decryption failed: Invalid base64 encoding. Error: {}", e))?;
+    if blob.len() <= NONCE_LEN {
+        return Err("Decryption failed: Encrypted data too short".to_string());
+    }
+
+    let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(&derive_user_key(user_id).into());
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Decryption failed: Authentication tag mismatch (data corrupted, wrong user or MASTER_ENCRYPTION_KEY changed)".to_string())?;
+
+    String::from_utf8(plaintext).map_err(|_| "Decryption failed: Invalid UTF-8 in key".to_string())
+}
+
+pub fn is_legacy_key(encrypted: &str) -> bool {
+    !encrypted.trim().starts_with(KEY_FORMAT_V2)
+}
+
+/// Re-encrypt a legacy blob in the current format. Returns None if it is already current.
+pub fn migrate_key(encrypted: &str, user_id: i64) -> Result<Option<String>, String> {
+    if !is_legacy_key(encrypted) {
+        return Ok(None);
+    }
+    let plaintext = decrypt_legacy_key(encrypted.trim())?;
+    Ok(Some(encrypt_key(&plaintext, user_id)))
+}
+
+/// Persist the current-format encryption of a wallet key still stored in the legacy format.
+async fn upgrade_legacy_key(user_id: i64, chain: &str, encrypted: &str, pool: &PgPool) {
+    let upgraded = match migrate_key(encrypted, user_id) {
+        Ok(Some(blob)) => blob,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to migrate wallet key for user {} on {}: {}", user_id, chain, e);
+            return;
+        }
+    };
+
+    let result = sqlx::query("UPDATE wallets SET private_key = $1 WHERE user_id = $2 AND chain = $3 AND private_key = $4")
+        .bind(&upgraded)
+        .bind(user_id)
+        .bind(chain)
+        .bind(encrypted)
+        .execute(pool)
+        .await;
+
+    match result {
+        Ok(_) => tracing::info!("🔐 Re-encrypted legacy wallet key for user {} on {}", user_id, chain),
+        Err(e) => tracing::error!("Failed to store migrated wallet key for user {} on {}: {}", user_id, chain, e),
+    }
+}
+
+fn decrypt_legacy_key(encrypted: &str) -> Result<String, String> {
     let master_key = get_master_key();
     let mc = magic_crypt::new_magic_crypt!(master_key, 256);
     
@@ -211,9 +408,80 @@ pub fn get_evm_signing_key(encrypted_key: &str, user_id: i64) -> Result<SecretKe
         .map_err(|e| format!("Invalid secret key: {}", e))
 }
 
+// ==================== MNEMONIC IMPORT ====================
+// BIP39 seed phrases (no passphrase). Solana keys follow SLIP-0010 ed25519 at
+// m/44'/501'/i' (solana-keygen's `?key=i`), EVM keys BIP32 secp256k1 at m/44'/60'/0'/0/i.
+
+const HARDENED: u32 = 0x8000_0000;
+
+/// Derive (address, private key) for account `account_index` of a seed phrase. Keys
+/// come back in the same format as `import_wallet_for_chain` returns.
+pub fn import_from_mnemonic(chain: &str, phrase: &str, account_index: u32) -> Result<(String, String), String> {
+    if account_index >= HARDENED {
+        return Err(format!("account_index must be below {}", HARDENED));
+    }
+    // Checksum and word list are validated here
+    let normalized = phrase.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let mnemonic = Mnemonic::parse_in(Language::English, &normalized)
+        .map_err(|e| format!("Invalid mnemonic: {}", e))?;
+    if ![12, 24].contains(&mnemonic.word_count()) {
+        return Err(format!("Mnemonic must have 12 or 24 words, got {}", mnemonic.word_count()));
+    }
+    let seed = mnemonic.to_seed("");
+
+    match chain {
+        "solana" | "sol" => {
+            let path = solana_sdk::derivation_path::DerivationPath::new_bip44(Some(account_index), None);
+            let keypair = solana_sdk::signer::keypair::keypair_from_seed_and_derivation_path(&seed, Some(path))
+                .map_err(|e| format!("Key derivation failed: {}", e))?;
+            import_solana_wallet(&bs58::encode(keypair.to_bytes()).into_string())
+        }
+        "eth" | "ethereum" | "bsc" | "binance" => {
+            let path = [44 | HARDENED, 60 | HARDENED, HARDENED, 0, account_index];
+            let secret_key = derive_secp256k1_key(&seed, &path)?;
+            import_evm_wallet(&hex::encode(secret_key.secret_bytes()))
+        }
+        _ => Err("Unsupported chain".to_string()),
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    use hmac::{Hmac, Mac};
+    let mut mac = <Hmac<sha2::Sha512> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// BIP32 private key derivation along `path` (indices at or above HARDENED are hardened).
+fn derive_secp256k1_key(seed: &[u8], path: &[u32]) -> Result<SecretKey, String> {
+    let invalid = |_| "Derived key is invalid, try another account index".to_string();
+    let master = hmac_sha512(b"Bitcoin seed", seed);
+    let mut key = SecretKey::from_slice(&master[..32]).map_err(invalid)?;
+    let mut chain_code: [u8; 32] = master[32..].try_into().expect("32-byte half");
+
+    let secp = Secp256k1::new();
+    for index in path {
+        let mut data = Vec::with_capacity(37);
+        if *index >= HARDENED {
+            data.push(0);
+            data.extend_from_slice(&key.secret_bytes());
+        } else {
+            data.extend_from_slice(&PublicKey::from_secret_key(&secp, &key).serialize());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+
+        let child = hmac_sha512(&chain_code, &data);
+        let tweak_bytes: [u8; 32] = child[..32].try_into().expect("32-byte half");
+        let tweak = secp256k1::Scalar::from_be_bytes(tweak_bytes).map_err(|_| invalid(secp256k1::Error::InvalidTweak))?;
+        key = key.add_tweak(&tweak).map_err(invalid)?;
+        chain_code = child[32..].try_into().expect("32-byte half");
+    }
+    Ok(key)
+}
+
 // ... (previous imports)
 use axum::{
-    extract::{Path, State},  // Add State
+    extract::{Path, Query, State},  // Add State
     http::StatusCode,
     response::IntoResponse,
     Json,
@@ -225,6 +493,87 @@ use crate::AppState; // Import AppState
 
 // ==================== HANDLERS ====================
 
+/// Whether another wallet may be added on `chain`. Returns true if it's the user's first there.
+pub fn validate_new_wallet(chain: &str, label: Option<&str>, existing_labels: &[Option<String>]) -> Result<bool, String> {
+    if existing_labels.is_empty() {
+        return Ok(true);
+    }
+    let label = match label.map(str::trim) {
+        Some(l) if !l.is_empty() => l,
+        _ => return Err(format!("You already have a {} wallet. Pass a label to add another", chain)),
+    };
+    if existing_labels.iter().flatten().any(|l| l == label) {
+        return Err(format!("You already have a {} wallet labelled '{}'", chain, label));
+    }
+    Ok(false)
+}
+
+async fn check_new_wallet(user_id: i64, chain: &str, label: Option<&str>, pool: &PgPool) -> Result<bool, String> {
+    let existing: Vec<Option<String>> = sqlx::query_scalar("SELECT label FROM wallets WHERE user_id = $1 AND chain = $2")
+        .bind(user_id)
+        .bind(chain)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Database error: {}", e))?;
+    validate_new_wallet(chain, label, &existing)
+}
+
+/// The first wallet on a chain becomes the default.
+async fn insert_wallet(
+    user_id: i64,
+    chain: &str,
+    address: &str,
+    encrypted_key: &str,
+    label: Option<&str>,
+    is_default: bool,
+    pool: &PgPool,
+) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO wallets (user_id, chain, address, private_key, label, is_default) VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(user_id)
+    .bind(chain)
+    .bind(address)
+    .bind(encrypted_key)
+    .bind(label.map(str::trim).filter(|l| !l.is_empty()))
+    .bind(is_default.then_some(true))
+    .execute(pool)
+    .await
+}
+
+pub async fn set_default_wallet_handler(
+    State(state): State<AppState>,
+    Json(request): Json<SetDefaultWalletRequest>,
+) -> impl IntoResponse {
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))),
+    };
+    // Clear first: the partial unique index allows only one default per chain at any moment
+    let result = sqlx::query("UPDATE wallets SET is_default = NULL WHERE user_id = $1 AND chain = $2")
+        .bind(request.user_id)
+        .bind(&request.chain)
+        .execute(&mut tx)
+        .await;
+    let found = sqlx::query("UPDATE wallets SET is_default = TRUE WHERE user_id = $1 AND chain = $2 AND label = $3")
+        .bind(request.user_id)
+        .bind(&request.chain)
+        .bind(&request.label)
+        .execute(&mut tx)
+        .await
+        .map(|r| r.rows_affected() > 0);
+
+    match (result, found) {
+        (Ok(_), Ok(true)) => match tx.commit().await {
+            Ok(()) => (StatusCode::OK, Json(serde_json::json!({"success": true, "chain": request.chain, "default": request.label}))),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))),
+        },
+        // Rolled back on drop so the old default stays
+        (Ok(_), Ok(false)) => (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": format!("Wallet '{}' not found", request.label)}))),
+        (Err(e), _) | (_, Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))),
+    }
+}
+
 pub async fn generate_wallet_handler(
     State(state): State<AppState>,
     Json(request): Json<GenerateWalletRequest>,
@@ -237,25 +586,20 @@ pub async fn generate_wallet_handler(
         .execute(&state.db)
         .await;
 
-    // Check existing wallet
-    let existing_wallet = sqlx::query("SELECT id FROM wallets WHERE user_id = $1 AND chain = $2")
-        .bind(user_id)
-        .bind(&request.chain)
-        .fetch_optional(&state.db)
-        .await;
-        
-    if let Ok(Some(_)) = existing_wallet {
-         return (
+    // Extra wallets on a chain need a label to be selectable
+    let is_first = match check_new_wallet(user_id, &request.chain, request.label.as_deref(), &state.db).await {
+        Ok(first) => first,
+        Err(e) => return (
             StatusCode::BAD_REQUEST,
             Json(WalletResponse {
                 success: false,
                 address: None,
                 private_key: None,
                 mnemonic: None,
-                error: Some(format!("You already have a {} wallet", request.chain)),
+                error: Some(e),
             }),
-        );
-    }
+        ),
+    };
 
     let result = match request.chain.as_str() {
         "solana" | "sol" => generate_solana_wallet().map(|(a, p)| (a, p, None)),
@@ -268,15 +612,7 @@ pub async fn generate_wallet_handler(
             // Save to DB
             let encrypted_key = encrypt_key(&private_key, user_id);
             
-            let insert_result = sqlx::query(
-                "INSERT INTO wallets (user_id, chain, address, private_key) VALUES ($1, $2, $3, $4)"
-            )
-            .bind(user_id)
-            .bind(&request.chain)
-            .bind(&address)
-            .bind(encrypted_key)
-            .execute(&state.db)
-            .await;
+            let insert_result = insert_wallet(user_id, &request.chain, &address, &encrypted_key, request.label.as_deref(), is_first, &state.db).await;
             
             match insert_result {
                 Ok(_) => (
@@ -314,12 +650,82 @@ pub async fn generate_wallet_handler(
     }
 }
 
+/// Derive (address, normalized private key) for an imported key on the given chain.
+pub fn import_wallet_for_chain(chain: &str, private_key: &str) -> Result<(String, String), String> {
+    match chain {
+        "solana" | "sol" => import_solana_wallet(private_key.trim()),
+        "eth" | "ethereum" | "bsc" | "binance" => import_evm_wallet(private_key.trim()),
+        _ => Err("Unsupported chain".to_string()),
+    }
+}
+
+pub async fn import_wallet_handler(
+    State(state): State<AppState>,
+    Json(request): Json<ImportWalletRequest>,
+) -> impl IntoResponse {
+    let user_id = request.user_id;
+    let failure = |status: StatusCode, error: String| {
+        (
+            status,
+            Json(WalletResponse {
+                success: false,
+                address: None,
+                private_key: None,
+                mnemonic: None,
+                error: Some(error),
+            }),
+        )
+    };
+
+    // Validate the key before touching the DB
+    let derived = match (request.private_key.as_deref(), request.mnemonic.as_deref()) {
+        (Some(key), None) => import_wallet_for_chain(&request.chain, key),
+        (None, Some(phrase)) => import_from_mnemonic(&request.chain, phrase, request.account_index),
+        _ => Err("Provide exactly one of private_key or mnemonic".to_string()),
+    };
+    let (address, private_key) = match derived {
+        Ok(derived) => derived,
+        Err(e) => return failure(StatusCode::BAD_REQUEST, e),
+    };
+
+    // Save user if not exists
+    let _ = sqlx::query("INSERT INTO users (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING")
+        .bind(user_id)
+        .execute(&state.db)
+        .await;
+
+    let is_first = match check_new_wallet(user_id, &request.chain, request.label.as_deref(), &state.db).await {
+        Ok(first) => first,
+        Err(e) => return failure(StatusCode::BAD_REQUEST, e),
+    };
+
+    let encrypted_key = encrypt_key(&private_key, user_id);
+    let insert_result = insert_wallet(user_id, &request.chain, &address, &encrypted_key, request.label.as_deref(), is_first, &state.db).await;
+
+    match insert_result {
+        Ok(_) => {
+            tracing::info!("📥 Imported {} wallet {} for user {}", request.chain, address, user_id);
+            (
+                StatusCode::OK,
+                Json(WalletResponse {
+                    success: true,
+                    address: Some(address),
+                    private_key: None, // Never echo an imported key back
+                    mnemonic: None,
+                    error: None,
+                }),
+            )
+        }
+        Err(e) => failure(StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    }
+}
+
 pub async fn get_wallets_handler(
     State(state): State<AppState>,
     Path(user_id): Path<i64>,
 ) -> impl IntoResponse {
     let wallets = sqlx::query_as::<_, WalletInfo>(
-        "SELECT user_id, chain, address, private_key, created_at FROM wallets WHERE user_id = $1"
+        "SELECT user_id, chain, address, private_key, created_at, label, is_default FROM wallets WHERE user_id = $1 ORDER BY chain, id"
     )
     .bind(user_id)
     .fetch_all(&state.db)
@@ -349,7 +755,9 @@ pub async fn export_wallets_handler(
         Ok(ws) => {
             let mut exported_wallets = Vec::new();
             for w in ws {
-                if let Ok(decrypted_key) = decrypt_key(&w.encrypted_private_key, user_id) {
+                if let Ok((decrypted_key, access)) = decrypt_key_audited(&w.encrypted_private_key, user_id, &w.chain, KeyPurpose::Export) {
+                    record_key_access(&access, &state.db).await;
+                    upgrade_legacy_key(user_id, &w.chain, &w.encrypted_private_key, &state.db).await;
                     exported_wallets.push(WalletResponse {
                         success: true,
                         address: Some(w.address),
@@ -368,79 +776,164 @@ pub async fn export_wallets_handler(
     }
 }
 
-// ==================== KEY MANAGEMENT ====================
-pub async fn get_wallet_keypair(
-    user_id: i64,
-    chain: &str,
-    pool: &PgPool,
-) -> Result<solana_sdk::signature::Keypair, String> {
-    // 1. Fetch encrypted key from DB
-    #[derive(sqlx::FromRow)]
-    struct KeyRecord { private_key: String }
-    
-    let record = sqlx::query_as::<_, KeyRecord>(
-        "SELECT private_key FROM wallets WHERE user_id = $1 AND chain = $2"
+// ==================== KEY ACCESS AUDIT ====================
+
+/// Why a private key was decrypted. Recorded in `key_access_log`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyPurpose {
+    Trade,
+    Withdraw,
+    Export,
+    Transfer,
+}
+
+impl KeyPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyPurpose::Trade => "trade",
+            KeyPurpose::Withdraw => "withdraw",
+            KeyPurpose::Export => "export",
+            KeyPurpose::Transfer => "transfer",
+        }
+    }
+}
+
+/// One key decryption. Never carries key material.
+#[derive(Debug, Clone)]
+pub struct KeyAccess {
+    pub user_id: i64,
+    pub chain: String,
+    pub purpose: KeyPurpose,
+    pub ts: i64,
+    pub request_id: String,
+}
+
+impl KeyAccess {
+    pub fn new(user_id: i64, chain: &str, purpose: KeyPurpose) -> Self {
+        Self {
+            user_id,
+            chain: chain.to_string(),
+            purpose,
+            ts: chrono::Utc::now().timestamp(),
+            request_id: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+}
+
+pub async fn record_key_access(access: &KeyAccess, pool: &PgPool) {
+    let result = sqlx::query(
+        "INSERT INTO key_access_log (user_id, chain, purpose, ts, request_id) VALUES ($1, $2, $3, $4, $5)"
     )
-    .bind(user_id)
-    .bind(chain)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| format!("DB Error: {}", e))?;
+    .bind(access.user_id)
+    .bind(&access.chain)
+    .bind(access.purpose.as_str())
+    .bind(access.ts)
+    .bind(&access.request_id)
+    .execute(pool)
+    .await;
 
-    let record = record.ok_or("Wallet not found")?;
+    if let Err(e) = result {
+        tracing::error!("Failed to record key access for user {} ({}): {}", access.user_id, access.purpose.as_str(), e);
+    }
+}
+
+/// Decrypt a stored key, returning the plaintext together with the audit entry for the access.
+pub fn decrypt_key_audited(encrypted: &str, user_id: i64, chain: &str, purpose: KeyPurpose) -> Result<(String, KeyAccess), String> {
+    let private_key = decrypt_key(encrypted, user_id)?;
+    Ok((private_key, KeyAccess::new(user_id, chain, purpose)))
+}
+
+/// Turn a stored Solana key into a keypair. Returns the audit entry for the decryption.
+pub fn unlock_solana_keypair(encrypted: &str, user_id: i64, chain: &str, purpose: KeyPurpose) -> Result<(Keypair, KeyAccess), String> {
+    if chain != "solana" && chain != "sol" {
+        return Err("Only Solana keypair retrieval supported currently".to_string());
+    }
 
-    // 2. Decrypt key (with debug info)
     tracing::debug!("Attempting to decrypt wallet for user {} on chain {}", user_id, chain);
-    let private_key_str = decrypt_key(&record.private_key, user_id)
+    let (private_key_str, access) = decrypt_key_audited(encrypted, user_id, chain, purpose)
         .map_err(|e| {
             tracing::error!("Wallet decryption failed for user {} on chain {}: {}", user_id, chain, e);
-            tracing::error!("Encrypted data length: {} bytes", record.private_key.len());
-            tracing::error!("Encrypted data preview: {}...", &record.private_key.chars().take(20).collect::<String>());
+            tracing::error!("Encrypted data length: {} bytes", encrypted.len());
+            tracing::error!("Encrypted data preview: {}...", &encrypted.chars().take(20).collect::<String>());
             e
         })?;
 
-    // 3. Create Keypair (Solana only for now)
-    if chain == "solana" || chain == "sol" {
-        let bytes = bs58::decode(&private_key_str)
-            .into_vec()
-            .map_err(|_| "Invalid base58 key".to_string())?;
-        
-        solana_sdk::signature::Keypair::from_bytes(&bytes)
-            .map_err(|e| format!("Invalid keypair bytes: {}", e))
-    } else {
-        Err("Only Solana keypair retrieval supported currently".to_string())
-    }
+    let bytes = bs58::decode(&private_key_str)
+        .into_vec()
+        .map_err(|_| "Invalid base58 key".to_string())?;
+
+    let keypair = Keypair::from_bytes(&bytes)
+        .map_err(|e| format!("Invalid keypair bytes: {}", e))?;
+    Ok((keypair, access))
+}
+
+// ==================== KEY MANAGEMENT ====================
+pub async fn get_wallet_keypair(
+    user_id: i64,
+    chain: &str,
+    wallet: &WalletSelector,
+    purpose: KeyPurpose,
+    pool: &PgPool,
+) -> Result<solana_sdk::signature::Keypair, String> {
+    // 1. Fetch encrypted key from DB
+    let private_key = fetch_wallet_field(user_id, chain, wallet, "private_key", pool).await?;
+
+    // 2. Decrypt key and audit the access
+    let (keypair, access) = unlock_solana_keypair(&private_key, user_id, chain, purpose)?;
+    record_key_access(&access, pool).await;
+    upgrade_legacy_key(user_id, chain, &private_key, pool).await;
+    Ok(keypair)
+}
+
+/// Load and decrypt a user's EVM signing key (audited like Solana keys).
+pub async fn get_evm_wallet_key(
+    user_id: i64,
+    chain: &str,
+    wallet: &WalletSelector,
+    purpose: KeyPurpose,
+    pool: &PgPool,
+) -> Result<SecretKey, String> {
+    let encrypted = fetch_wallet_field(user_id, chain, wallet, "private_key", pool).await?;
+    let key = get_evm_signing_key(&encrypted, user_id)?;
+    record_key_access(&KeyAccess::new(user_id, chain, purpose), pool).await;
+    upgrade_legacy_key(user_id, chain, &encrypted, pool).await;
+    Ok(key)
 }
 
 // ... (Rest of format validation and helper functions remain same)
 // ... existing code ...
 
+#[derive(Debug, Deserialize)]
+pub struct WalletQuery {
+    pub label: Option<String>,
+    #[serde(default)]
+    pub tokens: Option<String>, // EVM only: comma-separated ERC-20 contracts (default: the chain's known tokens)
+}
+
 pub async fn get_balance_handler(
     State(state): State<AppState>,
     Path((user_id, chain)): Path<(i64, String)>,
+    Query(query): Query<WalletQuery>,
 ) -> impl IntoResponse {
     // 1. Get wallet address from DB
-    #[derive(sqlx::FromRow)]
-    struct AddressRecord { address: String }
-
-    let record = sqlx::query_as::<_, AddressRecord>(
-        "SELECT address FROM wallets WHERE user_id = $1 AND chain = $2"
-    )
-    .bind(user_id) // Bind explicitly
-    .bind(&chain)   // Bind explicitly
-    .fetch_optional(&state.db)
-    .await;
-    
-    let address = match record {
-        Ok(Some(r)) => r.address,
-        Ok(None) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": "Wallet not found"}))).into_response(),
-        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
+    let wallet = WalletSelector::from_label(query.label.as_deref());
+    let address = match fetch_wallet_field(user_id, &chain, &wallet, "address", &state.db).await {
+        Ok(a) => a,
+        Err(e) if e.starts_with("DB Error") => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e}))).into_response(),
+        Err(e) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({"error": e}))).into_response(),
     };
     
+    let tokens: Option<Vec<String>> = query.tokens.as_deref().map(|list| {
+        list.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect()
+    });
+    if let Some(bad) = tokens.iter().flatten().find(|t| crate::evm::parse_address(t).is_err()) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": format!("Invalid token contract: {}", bad)}))).into_response();
+    }
+
     // 2. Fetch Balance based on chain
     let result = match chain.as_str() {
-        "solana" | "sol" => crate::balance::get_solana_balance(&address, &state.solana_client).await,
-        "eth" | "ethereum" | "bsc" | "binance" => crate::balance::get_evm_balance(&address, &chain).await,
+        "solana" | "sol" => crate::balance::get_solana_balance(user_id, &address, &state.solana_client, &state.balance_cache).await,
+        "eth" | "ethereum" | "bsc" | "binance" => crate::balance::get_evm_balance(&address, &chain, tokens.as_deref()).await,
         _ => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"error": "Unsupported chain"}))).into_response(),
     };
     
@@ -449,3 +942,505 @@ pub async fn get_balance_handler(
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"error": e}))).into_response(),
     }
 }
+
+// ==================== EMERGENCY WITHDRAW ====================
+
+// Associated Token Account program, used to derive/create destination token accounts
+lazy_static::lazy_static! {
+    static ref ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = Pubkey::from_str("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL")
+        .expect("Invalid associated token program ID");
+}
+
+// Token accounts swept per transaction (3 instructions each, keeps tx under size limit)
+const TOKENS_PER_WITHDRAW_TX: usize = 4;
+
+/// Validate a withdraw destination: must be a real wallet address (on-curve)
+/// and must differ from the wallet being drained.
+pub fn validate_withdraw_destination(to_address: &str, source: &Pubkey) -> Result<Pubkey, String> {
+    let destination = Pubkey::from_str(to_address.trim())
+        .map_err(|_| format!("Invalid destination address: {}", to_address))?;
+
+    if destination == Pubkey::default() {
+        return Err("Destination cannot be the system program".to_string());
+    }
+    if &destination == source {
+        return Err("Destination must differ from the wallet being withdrawn".to_string());
+    }
+    if !destination.is_on_curve() {
+        return Err("Destination is a program-derived address, not a wallet".to_string());
+    }
+
+    Ok(destination)
+}
+
+/// Lamports that can be sent when sweeping the full balance, after the transaction fee.
+pub fn compute_withdraw_amount(balance_lamports: u64, fee_lamports: u64) -> Result<u64, String> {
+    match balance_lamports.checked_sub(fee_lamports) {
+        Some(amount) if amount > 0 => Ok(amount),
+        _ => Err(format!(
+            "Balance of {} lamports does not cover the {} lamport fee",
+            balance_lamports, fee_lamports
+        )),
+    }
+}
+
+pub(crate) fn associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    ).0
+}
+
+fn create_associated_token_account_idempotent(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> solana_sdk::instruction::Instruction {
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+
+    Instruction {
+        program_id: *ASSOCIATED_TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(associated_token_address(owner, mint, token_program), false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+        data: vec![1], // CreateIdempotent
+    }
+}
+
+/// Move every token balance to the destination's ATA and close the source accounts.
+/// Rent is reclaimed into the source wallet so it is included in the SOL sweep.
+fn sweep_token_accounts(
+    client: &solana_client::rpc_client::RpcClient,
+    keypair: &Keypair,
+    destination: &Pubkey,
+) -> Result<(Vec<String>, usize), String> {
+    let owner = keypair.pubkey();
+    let holdings = crate::balance::get_token_accounts(client, &owner)?;
+
+    let mut signatures = Vec::new();
+    let mut swept = 0;
+
+    for batch in holdings.chunks(TOKENS_PER_WITHDRAW_TX) {
+        let mut instructions = Vec::new();
+        for holding in batch {
+            if holding.amount > 0 {
+                instructions.push(create_associated_token_account_idempotent(
+                    &owner, destination, &holding.mint, &holding.program_id,
+                ));
+                instructions.push(
+                    spl_token_2022::instruction::transfer_checked(
+                        &holding.program_id,
+                        &holding.account,
+                        &holding.mint,
+                        &associated_token_address(destination, &holding.mint, &holding.program_id),
+                        &owner,
+                        &[],
+                        holding.amount,
+                        holding.decimals,
+                    ).map_err(|e| format!("Failed to build token transfer: {}", e))?,
+                );
+            }
+            instructions.push(
+                spl_token_2022::instruction::close_account(
+                    &holding.program_id,
+                    &holding.account,
+                    &owner,
+                    &owner,
+                    &[],
+                ).map_err(|e| format!("Failed to build close instruction: {}", e))?,
+            );
+        }
+
+        let blockhash = client.get_latest_blockhash()
+            .map_err(|e| format!("Failed to get blockhash: {}", e))?;
+        let tx = solana_sdk::transaction::Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&owner),
+            &[keypair],
+            blockhash,
+        );
+        let signature = client.send_and_confirm_transaction(&tx)
+            .map_err(|e| format!("Token sweep failed after {} accounts: {}", swept, e))?;
+
+        swept += batch.len();
+        signatures.push(signature.to_string());
+    }
+
+    Ok((signatures, swept))
+}
+
+/// Transfer the full SOL balance minus the network fee to the destination.
+fn sweep_sol(
+    client: &solana_client::rpc_client::RpcClient,
+    keypair: &Keypair,
+    destination: &Pubkey,
+) -> Result<(String, u64), String> {
+    let owner = keypair.pubkey();
+    let balance = client.get_balance(&owner)
+        .map_err(|e| format!("Failed to get balance: {}", e))?;
+    let blockhash = client.get_latest_blockhash()
+        .map_err(|e| format!("Failed to get blockhash: {}", e))?;
+
+    // Fee doesn't depend on the amount, so price the message with the full balance first
+    let probe = solana_sdk::message::Message::new_with_blockhash(
+        &[solana_sdk::system_instruction::transfer(&owner, destination, balance)],
+        Some(&owner),
+        &blockhash,
+    );
+    let fee = client.get_fee_for_message(&probe)
+        .map_err(|e| format!("Failed to estimate fee: {}", e))?;
+    let amount = compute_withdraw_amount(balance, fee)?;
+
+    let tx = solana_sdk::transaction::Transaction::new_signed_with_payer(
+        &[solana_sdk::system_instruction::transfer(&owner, destination, amount)],
+        Some(&owner),
+        &[keypair],
+        blockhash,
+    );
+    let signature = client.send_and_confirm_transaction(&tx)
+        .map_err(|e| format!("SOL transfer failed: {}", e))?;
+
+    Ok((signature.to_string(), amount))
+}
+
+pub async fn withdraw_handler(
+    State(state): State<AppState>,
+    Json(request): Json<WithdrawRequest>,
+) -> impl IntoResponse {
+    let failure = |status: StatusCode, tx_hashes: Vec<String>, tokens_swept: usize, error: String| {
+        (
+            status,
+            Json(WithdrawResponse {
+                success: false,
+                tx_hashes,
+                withdrawn_sol: None,
+                tokens_swept,
+                error: Some(error),
+            }),
+        )
+    };
+
+    if request.chain != "solana" && request.chain != "sol" {
+        return failure(StatusCode::BAD_REQUEST, vec![], 0, "Withdraw is only supported on Solana currently".to_string());
+    }
+
+    let wallet = WalletSelector::from_label(request.wallet_label.as_deref());
+    let keypair = match get_wallet_keypair(request.user_id, &request.chain, &wallet, KeyPurpose::Withdraw, &state.db).await {
+        Ok(k) => k,
+        Err(e) => return failure(StatusCode::NOT_FOUND, vec![], 0, e),
+    };
+
+    let destination = match validate_withdraw_destination(&request.to_address, &keypair.pubkey()) {
+        Ok(d) => d,
+        Err(e) => return failure(StatusCode::BAD_REQUEST, vec![], 0, e),
+    };
+
+    tracing::warn!("🚨 Emergency withdraw for user {}: {} -> {}", request.user_id, keypair.pubkey(), destination);
+
+    let mut tx_hashes = Vec::new();
+    let mut tokens_swept = 0;
+
+    if request.include_tokens {
+        match sweep_token_accounts(&state.solana_client, &keypair, &destination) {
+            Ok((signatures, swept)) => {
+                tx_hashes.extend(signatures);
+                tokens_swept = swept;
+            }
+            Err(e) => return failure(StatusCode::INTERNAL_SERVER_ERROR, tx_hashes, tokens_swept, e),
+        }
+    }
+
+    match sweep_sol(&state.solana_client, &keypair, &destination) {
+        Ok((signature, lamports)) => {
+            state.balance_cache.note_activity(&keypair.pubkey().to_string()).await;
+            tx_hashes.push(signature);
+            tracing::info!("   ✅ Withdrew {} lamports and {} token accounts", lamports, tokens_swept);
+            (
+                StatusCode::OK,
+                Json(WithdrawResponse {
+                    success: true,
+                    tx_hashes,
+                    withdrawn_sol: Some(lamports as f64 / 1_000_000_000.0),
+                    tokens_swept,
+                    error: None,
+                }),
+            )
+        }
+        Err(e) => failure(StatusCode::INTERNAL_SERVER_ERROR, tx_hashes, tokens_swept, e),
+    }
+}
+
+// ==================== TRANSFERS BETWEEN OWN WALLETS ====================
+
+/// Raw amount to move, refusing zero and anything above the available balance.
+pub fn transfer_amount(amount: &str, decimals: u8, available: u64) -> Result<u64, String> {
+    let raw = u64::try_from(crate::units::parse_token_amount(amount, decimals)?)
+        .map_err(|_| format!("Amount too large: {}", amount))?;
+    if raw == 0 {
+        return Err("Amount must be greater than zero".to_string());
+    }
+    if raw > available {
+        return Err(format!("Amount exceeds available balance ({} raw units)", available));
+    }
+    Ok(raw)
+}
+
+/// SOL transfer, or an SPL transfer into the destination's ATA (created if missing).
+fn send_wallet_transfer(
+    client: &solana_client::rpc_client::RpcClient,
+    keypair: &Keypair,
+    destination: &Pubkey,
+    amount: &str,
+    token_mint: Option<&str>,
+) -> Result<String, String> {
+    let owner = keypair.pubkey();
+    let instructions = match token_mint {
+        None => {
+            let balance = client.get_balance(&owner)
+                .map_err(|e| format!("Failed to get balance: {}", e))?;
+            let lamports = transfer_amount(amount, crate::units::SOL_DECIMALS, balance)?;
+            vec![solana_sdk::system_instruction::transfer(&owner, destination, lamports)]
+        }
+        Some(mint) => {
+            let mint = Pubkey::from_str(mint.trim()).map_err(|_| format!("Invalid token mint: {}", mint))?;
+            let holding = crate::balance::get_token_accounts(client, &owner)?
+                .into_iter()
+                .filter(|h| h.mint == mint)
+                .max_by_key(|h| h.amount)
+                .ok_or_else(|| format!("Wallet holds no {}", mint))?;
+            let raw = transfer_amount(amount, holding.decimals, holding.amount)?;
+            vec![
+                create_associated_token_account_idempotent(&owner, destination, &mint, &holding.program_id),
+                spl_token_2022::instruction::transfer_checked(
+                    &holding.program_id,
+                    &holding.account,
+                    &mint,
+                    &associated_token_address(destination, &mint, &holding.program_id),
+                    &owner,
+                    &[],
+                    raw,
+                    holding.decimals,
+                ).map_err(|e| format!("Failed to build token transfer: {}", e))?,
+            ]
+        }
+    };
+
+    let blockhash = client.get_latest_blockhash()
+        .map_err(|e| format!("Failed to get blockhash: {}", e))?;
+    let tx = solana_sdk::transaction::Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&owner),
+        &[keypair],
+        blockhash,
+    );
+    client.send_and_confirm_transaction(&tx)
+        .map(|signature| signature.to_string())
+        .map_err(|e| format!("Transfer failed: {}", e))
+}
+
+/// Move SOL or a token between two of the user's own wallets.
+pub async fn transfer_between_wallets_handler(
+    State(state): State<AppState>,
+    Json(request): Json<WalletTransferRequest>,
+) -> impl IntoResponse {
+    let failure = |status: StatusCode, error: String| {
+        (status, Json(WalletTransferResponse { success: false, tx_hash: None, error: Some(error) }))
+    };
+
+    if request.chain != "solana" && request.chain != "sol" {
+        return failure(StatusCode::BAD_REQUEST, "Transfers are only supported on Solana currently".to_string());
+    }
+
+    // Both ends must be this user's wallets, so a transfer can never leave the account
+    let source = WalletSelector::Address(request.from_address.trim().to_string());
+    let destination_wallet = WalletSelector::Address(request.to_address.trim().to_string());
+    if let Err(e) = fetch_wallet_field(request.user_id, &request.chain, &destination_wallet, "address", &state.db).await {
+        return failure(StatusCode::FORBIDDEN, format!("Destination is not one of your wallets: {}", e));
+    }
+    let keypair = match get_wallet_keypair(request.user_id, &request.chain, &source, KeyPurpose::Transfer, &state.db).await {
+        Ok(k) => k,
+        Err(e) => return failure(StatusCode::FORBIDDEN, format!("Source is not one of your wallets: {}", e)),
+    };
+    let destination = match validate_withdraw_destination(&request.to_address, &keypair.pubkey()) {
+        Ok(d) => d,
+        Err(e) => return failure(StatusCode::BAD_REQUEST, e),
+    };
+
+    let client = state.solana_client.clone();
+    let (amount, mint) = (request.amount.clone(), request.token_mint.clone());
+    let sent = tokio::task::spawn_blocking(move || {
+        send_wallet_transfer(&client, &keypair, &destination, &amount, mint.as_deref())
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Transfer task failed: {}", e)));
+
+    let tx_hash = match sent {
+        Ok(hash) => hash,
+        Err(e) => return failure(StatusCode::BAD_REQUEST, e),
+    };
+    for address in [&request.from_address, &request.to_address] {
+        state.balance_cache.note_activity(address.trim()).await;
+    }
+    tracing::info!("🔁 User {} moved {} {} from {} to {}", request.user_id, request.amount,
+        request.token_mint.as_deref().unwrap_or("SOL"), request.from_address, request.to_address);
+
+    let _ = sqlx::query(
+        "INSERT INTO transactions (transaction_id, user_id, chain, type, token_address, amount, price, tx_hash) VALUES ($1, $2, $3, 'TRANSFER', $4, $5, 0, $6)"
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(request.user_id)
+    .bind(&request.chain)
+    .bind(request.token_mint.as_deref().unwrap_or(crate::execution::WSOL_MINT))
+    .bind(&request.amount)
+    .bind(&tx_hash)
+    .execute(&state.db)
+    .await;
+
+    (StatusCode::OK, Json(WalletTransferResponse { success: true, tx_hash: Some(tx_hash), error: None }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_withdraw_amount_subtracts_fee() {
+        assert_eq!(compute_withdraw_amount(1_000_000_000, 5_000), Ok(999_995_000));
+        assert!(compute_withdraw_amount(5_000, 5_000).is_err());
+        assert!(compute_withdraw_amount(1_000, 5_000).is_err());
+    }
+
+    #[test]
+    fn test_withdraw_destination_validation() {
+        let source = Keypair::new().pubkey();
+        let cold = Keypair::new().pubkey();
+
+        assert_eq!(validate_withdraw_destination(&cold.to_string(), &source), Ok(cold));
+        assert!(validate_withdraw_destination("not-an-address", &source).is_err());
+        assert!(validate_withdraw_destination(&source.to_string(), &source).is_err());
+        assert!(validate_withdraw_destination(&Pubkey::default().to_string(), &source).is_err());
+
+        // PDAs can't sign, so funds sent there would be stuck
+        let pda = associated_token_address(&cold, &spl_token::id(), &spl_token::id());
+        assert!(validate_withdraw_destination(&pda.to_string(), &source).is_err());
+    }
+
+    #[test]
+    fn test_trade_key_access_is_audited_once() {
+        let keypair = Keypair::new();
+        let encrypted = encrypt_key(&bs58::encode(keypair.to_bytes()).into_string(), 42);
+
+        let (unlocked, access) = unlock_solana_keypair(&encrypted, 42, "solana", KeyPurpose::Trade).unwrap();
+        assert_eq!(unlocked.pubkey(), keypair.pubkey());
+        assert_eq!(access.user_id, 42);
+        assert_eq!(access.chain, "solana");
+        assert_eq!(access.purpose.as_str(), "trade");
+        assert!(!access.request_id.is_empty());
+
+        // Failed decryptions produce no access entry
+        assert!(unlock_solana_keypair("not-a-key", 42, "solana", KeyPurpose::Trade).is_err());
+    }
+
+    #[test]
+    fn test_key_encryption_roundtrip_is_per_user() {
+        let blob = encrypt_key("secret-key", 1);
+        assert!(blob.starts_with("v2:"));
+        assert_eq!(decrypt_key(&blob, 1).unwrap(), "secret-key");
+        assert!(decrypt_key(&blob, 2).is_err());
+        // Same plaintext encrypts differently each time (random nonce)
+        assert_ne!(encrypt_key("secret-key", 1), blob);
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_fails_tag_check() {
+        let blob = encrypt_key("secret-key", 1);
+        let mut raw = STANDARD.decode(blob.strip_prefix("v2:").unwrap()).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 0x01;
+        let tampered = format!("v2:{}", STANDARD.encode(raw));
+        assert!(decrypt_key(&tampered, 1).unwrap_err().contains("Authentication tag"));
+    }
+
+    #[test]
+    fn test_legacy_key_is_migrated() {
+        let mc = magic_crypt::new_magic_crypt!(get_master_key(), 256);
+        let legacy = mc.encrypt_str_to_base64("old-key");
+
+        assert!(is_legacy_key(&legacy));
+        assert_eq!(decrypt_key(&legacy, 5).unwrap(), "old-key");
+
+        let upgraded = migrate_key(&legacy, 5).unwrap().unwrap();
+        assert!(!is_legacy_key(&upgraded));
+        assert_eq!(decrypt_key(&upgraded, 5).unwrap(), "old-key");
+        assert_eq!(migrate_key(&upgraded, 5).unwrap(), None);
+    }
+
+    #[test]
+    fn test_import_wallet_for_chain() {
+        let keypair = Keypair::new();
+        let key = bs58::encode(keypair.to_bytes()).into_string();
+
+        let (address, _) = import_wallet_for_chain("solana", &format!(" {} ", key)).unwrap();
+        assert_eq!(address, keypair.pubkey().to_string());
+
+        assert!(import_wallet_for_chain("solana", "not-a-key").is_err());
+        assert_eq!(import_wallet_for_chain("dogecoin", &key).unwrap_err(), "Unsupported chain");
+    }
+
+    #[test]
+    fn test_import_from_mnemonic() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        // Well-known first account of the BIP39 test phrase
+        let (address, key) = import_from_mnemonic("eth", phrase, 0).unwrap();
+        assert_eq!(address, "0x9858effd232b4033e47d90003d41ec34ecaeda94");
+        assert_eq!(import_wallet_for_chain("bsc", &key).unwrap().0, address);
+        assert_ne!(import_from_mnemonic("eth", phrase, 1).unwrap().0, address);
+
+        let (sol_0, sol_key) = import_from_mnemonic("solana", &format!("  {}\n", phrase.to_uppercase()), 0).unwrap();
+        assert_eq!(import_wallet_for_chain("solana", &sol_key).unwrap().0, sol_0);
+        assert_ne!(import_from_mnemonic("solana", phrase, 1).unwrap().0, sol_0);
+
+        // Last word changed: still dictionary words, bad checksum
+        let bad = phrase.replace("about", "abandon");
+        assert!(import_from_mnemonic("eth", &bad, 0).unwrap_err().contains("Invalid mnemonic"));
+        assert!(import_from_mnemonic("solana", phrase, HARDENED).is_err());
+        assert_eq!(import_from_mnemonic("dogecoin", phrase, 0).unwrap_err(), "Unsupported chain");
+    }
+
+    #[test]
+    fn test_extra_wallet_needs_unique_label() {
+        assert_eq!(validate_new_wallet("solana", None, &[]), Ok(true));
+        // Pre-label wallets have no label
+        let existing = vec![None, Some("sniper".to_string())];
+        assert!(validate_new_wallet("solana", None, &existing).is_err());
+        assert!(validate_new_wallet("solana", Some("  "), &existing).is_err());
+        assert!(validate_new_wallet("solana", Some("sniper"), &existing).is_err());
+        assert_eq!(validate_new_wallet("solana", Some("grid"), &existing), Ok(false));
+    }
+
+    #[test]
+    fn test_default_selector_falls_back_to_oldest_wallet() {
+        assert_eq!(WalletSelector::from_label(None), WalletSelector::Default);
+        assert_eq!(WalletSelector::from_label(Some("")), WalletSelector::Default);
+        assert_eq!(WalletSelector::from_label(Some("grid")), WalletSelector::Label("grid".to_string()));
+        assert!(WalletSelector::Default.select_sql("address").ends_with("ORDER BY COALESCE(is_default, FALSE) DESC, id LIMIT 1"));
+        assert!(WalletSelector::Label("grid".to_string()).select_sql("address").ends_with("AND label = $3"));
+        assert!(WalletSelector::Address("Addr".to_string()).select_sql("private_key").ends_with("AND address = $3"));
+    }
+
+    #[test]
+    fn test_transfer_amount_bounds() {
+        assert_eq!(transfer_amount("0.5", 9, 1_000_000_000), Ok(500_000_000));
+        assert_eq!(transfer_amount("1", 9, 1_000_000_000), Ok(1_000_000_000));
+        assert!(transfer_amount("1.000000001", 9, 1_000_000_000).is_err());
+        assert!(transfer_amount("0", 6, 10).is_err());
+        assert!(transfer_amount("abc", 6, 10).is_err());
+    }
+}
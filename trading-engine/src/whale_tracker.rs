@@ -2,10 +2,10 @@
 // Tracks large trades (whales) on perpetual stablecoin markets
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use chrono::Utc;
 use axum::{
-    extract::{State},
+    extract::{Query, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
@@ -134,15 +134,14 @@ lazy_static::lazy_static! {
     };
 }
 
-/// Enhanced whale detection with multiple criteria
+/// Enhanced whale detection with multiple criteria.
+/// `price_impact` (%) comes from `estimate_price_impact` (see `dispatch_whale_trade`).
 pub fn detect_whale_activity(
     trade: &WhaleTrade,
     recent_trades: &[WhaleTrade],
     avg_volume_24h: f64,
+    price_impact: f64,
 ) -> WhaleActivity {
-    // Calculate price impact (simplified - in production, use order book depth)
-    let price_impact = calculate_price_impact(trade.size_usd, trade.chain.as_str());
-    
     // Calculate volume anomaly (how much above average)
     let volume_anomaly = if avg_volume_24h > 0.0 {
         trade.size_usd / avg_volume_24h
@@ -173,12 +172,7 @@ pub fn detect_whale_activity(
         .count();
     let is_first_entry = previous_trades == 0;
     
-    // Calculate Confidence Score (0-100)
-    let mut confidence = 70.0; // Base confidence
-    if known_label.is_some() { confidence += 20.0; } // Known entity = high confidence it's accurate
-    if is_first_entry { confidence += 5.0; }
-    if trade.size_usd > 500_000.0 { confidence += 5.0; }
-    if confidence > 100.0 { confidence = 100.0; }
+    let confidence = activity_confidence(known_label.is_some(), is_first_entry, trade.size_usd);
     
     WhaleActivity {
         trade: trade.clone(),
@@ -192,10 +186,68 @@ pub fn detect_whale_activity(
     }
 }
 
-/// Calculate estimated price impact based on trade size and chain
-fn calculate_price_impact(size_usd: f64, chain: &str) -> f64 {
-    // Simplified model - in production, use order book depth analysis
-    // Larger trades on less liquid chains have more impact
+/// Confidence score (0-100) that a trade is a meaningful whale move.
+pub fn activity_confidence(known_label: bool, is_first_entry: bool, size_usd: f64) -> f64 {
+    let mut confidence: f64 = 70.0; // Base confidence
+    if known_label { confidence += 20.0; } // Known entity = high confidence it's accurate
+    if is_first_entry { confidence += 5.0; }
+    if size_usd > 500_000.0 { confidence += 5.0; }
+    confidence.min(100.0)
+}
+
+// ==================== PRICE IMPACT ====================
+
+/// Size (USD) of the reference quote whose rate stands in for the spot price.
+const REFERENCE_TRADE_USD: f64 = 10.0;
+
+/// Price impact (%) of a trade, from pool depth where we can quote it. Solana tokens
+/// are quoted on Jupiter (as a SOL buy of the same USD size), other chains and failed
+/// quotes fall back to the size heuristic.
+pub async fn estimate_price_impact(chain: &str, token: &str, size_usd: f64) -> f64 {
+    if chain == "solana" {
+        match quote_price_impact(token, size_usd).await {
+            Ok(impact) => return impact,
+            Err(e) => tracing::debug!("Depth-based impact unavailable for {} ({}), using heuristic", token, e),
+        }
+    }
+    heuristic_price_impact(size_usd, chain)
+}
+
+async fn quote_price_impact(token: &str, size_usd: f64) -> Result<f64, String> {
+    use crate::execution::{get_jupiter_client, get_jupiter_quote, WSOL_MINT};
+
+    let sol_price = crate::price::fetch_sol_price().await?;
+    if sol_price <= 0.0 || size_usd <= REFERENCE_TRADE_USD {
+        return Err("trade too small to measure".to_string());
+    }
+    let lamports = |usd: f64| (usd / sol_price * 1e9) as u64;
+    let client = get_jupiter_client().map_err(|e| e.to_string())?;
+
+    let (reference, trade) = tokio::join!(
+        get_jupiter_quote(&client, WSOL_MINT, token, lamports(REFERENCE_TRADE_USD), 50),
+        get_jupiter_quote(&client, WSOL_MINT, token, lamports(size_usd), 50),
+    );
+    let (reference, trade) = (reference.map_err(|e| e.to_string())?, trade.map_err(|e| e.to_string())?);
+    impact_from_quotes(&reference, &trade).ok_or_else(|| "unusable quotes".to_string())
+}
+
+/// Impact (%) of `trade` against `reference`: how much worse its rate is than the tiny
+/// reference trade's, or Jupiter's own priceImpactPct if that is larger.
+pub fn impact_from_quotes(reference: &crate::execution::QuoteResponse, trade: &crate::execution::QuoteResponse) -> Option<f64> {
+    let rate = |quote: &crate::execution::QuoteResponse| {
+        let input = quote.inAmount.parse::<f64>().ok().filter(|a| *a > 0.0)?;
+        let output = quote.outAmount.parse::<f64>().ok().filter(|a| *a > 0.0)?;
+        Some(output / input)
+    };
+    let (reference_rate, trade_rate) = (rate(reference)?, rate(trade)?);
+    let from_rates = ((1.0 - trade_rate / reference_rate) * 100.0).max(0.0);
+    let reported = trade.priceImpactPct.parse::<f64>().map(|p| p.abs() * 100.0).unwrap_or(0.0);
+    Some(from_rates.max(reported))
+}
+
+/// Size-based estimate used when no quote is available.
+/// Larger trades on less liquid chains have more impact.
+pub fn heuristic_price_impact(size_usd: f64, chain: &str) -> f64 {
     let base_impact = match chain {
         "solana" => size_usd / 100_000.0, // ~0.01% per $100k
         "eth" | "ethereum" => size_usd / 500_000.0, // ~0.002% per $500k
@@ -292,6 +344,104 @@ pub fn classify_trade_type(
     }
 }
 
+// ==================== SENTIMENT ====================
+// Whale trades on a token are weighted by size, detection confidence and age. Weight
+// halves every `WHALE_SENTIMENT_HALF_LIFE_SECS` (default 1h) and trades older than
+// six half-lives (under 2% weight) are ignored.
+
+const SENTIMENT_WINDOW_HALF_LIVES: i64 = 6;
+
+pub fn sentiment_half_life_secs() -> i64 {
+    std::env::var("WHALE_SENTIMENT_HALF_LIFE_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|s| *s > 0)
+        .unwrap_or(3600)
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TokenSentiment {
+    pub token: String,
+    pub sentiment: f64, // -1.0 (all short/sell) to 1.0 (all long/buy), 0 without trades
+    pub long_weight: f64,
+    pub short_weight: f64,
+    pub trade_count: usize,
+    pub half_life_secs: i64,
+}
+
+/// +1 for trades that add long exposure (buys, longs, short covers), -1 for the rest.
+pub fn trade_direction(trade: &WhaleTrade) -> f64 {
+    match trade.trade_type {
+        TradeType::Buy | TradeType::Long | TradeType::CloseShort => 1.0,
+        TradeType::Sell | TradeType::Short | TradeType::CloseLong => -1.0,
+    }
+}
+
+/// Recency- and confidence-weighted long/short balance of whale trades on `token`.
+/// One pass over the feed, so callers can run it under the feed's read lock.
+pub fn compute_sentiment<'a>(token: &str, trades: impl IntoIterator<Item = &'a WhaleTrade>, now: i64, half_life_secs: i64) -> TokenSentiment {
+    let half_life = half_life_secs.max(1);
+    let since = now - half_life * SENTIMENT_WINDOW_HALF_LIVES;
+
+    // A wallet's first entry is its earliest trade on the token anywhere in the feed
+    let mut first_entry: HashMap<&str, i64> = HashMap::new();
+    let mut in_window = Vec::new();
+    for trade in trades.into_iter().filter(|t| t.token == token) {
+        first_entry.entry(trade.wallet_address.as_str())
+            .and_modify(|ts| *ts = (*ts).min(trade.timestamp))
+            .or_insert(trade.timestamp);
+        if trade.timestamp >= since && trade.timestamp <= now {
+            in_window.push(trade);
+        }
+    }
+
+    let mut long_weight = 0.0;
+    let mut short_weight = 0.0;
+    let mut trade_count = 0;
+    for trade in in_window {
+        let age = (now - trade.timestamp) as f64;
+        let decay = 0.5f64.powf(age / half_life as f64);
+        let is_first_entry = first_entry.get(trade.wallet_address.as_str()).is_some_and(|ts| trade.timestamp <= *ts);
+        let known_label = KNOWN_WHALES.contains_key(&trade.wallet_address);
+        let confidence = activity_confidence(known_label, is_first_entry, trade.size_usd) / 100.0;
+        let weight = trade.size_usd.max(0.0) * decay * confidence;
+        if trade_direction(trade) > 0.0 {
+            long_weight += weight;
+        } else {
+            short_weight += weight;
+        }
+        trade_count += 1;
+    }
+
+    let total = long_weight + short_weight;
+    TokenSentiment {
+        token: token.to_string(),
+        sentiment: if total > 0.0 { (long_weight - short_weight) / total } else { 0.0 },
+        long_weight,
+        short_weight,
+        trade_count,
+        half_life_secs: half_life,
+    }
+}
+
+/// Sentiment at or below which the price worker tightens stop-losses, from
+/// `WHALE_SENTIMENT_TIGHTEN_BELOW` (e.g. -0.6). Unset disables it.
+pub fn stop_tighten_threshold() -> Option<f64> {
+    std::env::var("WHALE_SENTIMENT_TIGHTEN_BELOW")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|t| (-1.0..=1.0).contains(t))
+}
+
+/// Stop-loss distance (%) to use given the token's whale sentiment: halved while
+/// sentiment is at or below `threshold`. A disabled stop (0) stays disabled.
+pub fn sentiment_stop_loss(stop_loss_percent: f64, sentiment: f64, threshold: Option<f64>) -> f64 {
+    match threshold {
+        Some(t) if stop_loss_percent > 0.0 && sentiment <= t => stop_loss_percent / 2.0,
+        _ => stop_loss_percent,
+    }
+}
+
 // ==================== WHALE TRACKING ====================
 pub fn track_whale_trade(
     trade: WhaleTrade,
@@ -414,7 +564,7 @@ pub fn create_whale_alert(request: CreateWhaleAlertRequest) -> WhaleAlert {
         .collect();
     
     WhaleAlert {
-        alert_id: format!("alert_{}_{}", request.user_id, Utc::now().timestamp()),
+        alert_id: format!("alert_{}_{}", request.user_id, uuid::Uuid::new_v4()),
         user_id: request.user_id,
         min_size_usd: request.min_size_usd,
         chains: request.chains.unwrap_or_default(),
@@ -441,21 +591,382 @@ pub async fn get_user_alerts_handler(
     State(state): State<AppState>,
     Path(user_id): Path<i64>,
 ) -> impl IntoResponse {
-    let alerts_map = state.whale_alerts.read().await;
-    
-    // In a real DB, we'd query by user_id. Here we scan the map.
-    // However, the map key is alert_id, not user_id.
-    // The current state is `HashMap<String, WhaleAlert>`.
-    
-    let user_alerts: Vec<WhaleAlert> = alerts_map.values()
-        .filter(|a| a.user_id == user_id)
-        .cloned()
-        .collect();
+    let user_alerts: Vec<WhaleAlert> = match state.whale_alerts.values().await {
+        Ok(alerts) => alerts.into_iter().filter(|a| a.user_id == user_id).collect(),
+        Err(e) => {
+            tracing::error!("Failed to load whale alerts: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(vec![]));
+        }
+    };
         
     (StatusCode::OK, Json(user_alerts))
 }
 
 
+pub async fn create_alert_handler(
+    State(state): State<AppState>,
+    Json(request): Json<CreateWhaleAlertRequest>,
+) -> impl IntoResponse {
+    if request.min_size_usd <= 0.0 {
+        return (StatusCode::BAD_REQUEST, Json(WhaleAlertResponse {
+            success: false,
+            alert_id: None,
+            error: Some("min_size_usd must be greater than 0".to_string()),
+        }));
+    }
+
+    let alert = create_whale_alert(request);
+    match store_whale_alert(&state, &alert).await {
+        Ok(()) => (StatusCode::OK, Json(WhaleAlertResponse { success: true, alert_id: Some(alert.alert_id), error: None })),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(WhaleAlertResponse { success: false, alert_id: None, error: Some(e) })),
+    }
+}
+
+pub async fn delete_alert_handler(
+    State(state): State<AppState>,
+    Path((user_id, alert_id)): Path<(i64, String)>,
+) -> impl IntoResponse {
+    match state.whale_alerts.get(&alert_id).await {
+        Ok(Some(alert)) if alert.user_id == user_id => {}
+        Ok(_) => return (StatusCode::NOT_FOUND, Json(serde_json::json!({"success": false, "error": "Alert not found"}))),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"success": false, "error": e}))),
+    }
+
+    match remove_whale_alert(&state, &alert_id).await {
+        Ok(_) => (StatusCode::OK, Json(serde_json::json!({"success": true}))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"success": false, "error": e}))),
+    }
+}
+
+// ==================== ALERT PERSISTENCE ====================
+// A durable state store (STATE_STORE=postgres) holds alerts itself. Otherwise alerts are
+// written to `whale_alerts` and reloaded into the in-memory store on startup.
+
+type WhaleAlertRow = (String, i64, f64, String, String, String, bool, i64);
+
+fn encode_list<T: Serialize>(values: &[T]) -> Result<String, String> {
+    serde_json::to_string(values).map_err(|e| format!("Failed to encode alert: {}", e))
+}
+
+fn alert_from_row(row: WhaleAlertRow) -> Result<WhaleAlert, String> {
+    let (alert_id, user_id, min_size_usd, chains, tokens, position_types, active, created_at) = row;
+    let decode_err = |e: serde_json::Error| format!("Failed to decode alert {}: {}", alert_id, e);
+    Ok(WhaleAlert {
+        chains: serde_json::from_str(&chains).map_err(decode_err)?,
+        tokens: serde_json::from_str(&tokens).map_err(decode_err)?,
+        position_types: serde_json::from_str(&position_types).map_err(decode_err)?,
+        alert_id,
+        user_id,
+        min_size_usd,
+        active,
+        created_at,
+    })
+}
+
+pub async fn save_whale_alert(pool: &sqlx::PgPool, alert: &WhaleAlert) -> Result<(), String> {
+    sqlx::query(
+        r#"
+        INSERT INTO whale_alerts (alert_id, user_id, min_size_usd, chains, tokens, position_types, active, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (alert_id) DO UPDATE SET
+            min_size_usd = EXCLUDED.min_size_usd,
+            chains = EXCLUDED.chains,
+            tokens = EXCLUDED.tokens,
+            position_types = EXCLUDED.position_types,
+            active = EXCLUDED.active
+        "#
+    )
+    .bind(&alert.alert_id)
+    .bind(alert.user_id)
+    .bind(alert.min_size_usd)
+    .bind(encode_list(&alert.chains)?)
+    .bind(encode_list(&alert.tokens)?)
+    .bind(encode_list(&alert.position_types)?)
+    .bind(alert.active)
+    .bind(alert.created_at)
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to save whale alert: {}", e))?;
+
+    Ok(())
+}
+
+pub async fn delete_whale_alert(pool: &sqlx::PgPool, alert_id: &str) -> Result<(), String> {
+    sqlx::query("DELETE FROM whale_alerts WHERE alert_id = $1")
+        .bind(alert_id)
+        .execute(pool)
+        .await
+        .map_err(|e| format!("Failed to delete whale alert: {}", e))?;
+    Ok(())
+}
+
+/// Add or update an alert, writing it to `whale_alerts` only when the store isn't durable.
+pub async fn store_whale_alert(state: &AppState, alert: &WhaleAlert) -> Result<(), String> {
+    if !state.whale_alerts.is_durable() {
+        save_whale_alert(&state.db, alert).await?;
+    }
+    state.whale_alerts.put(&alert.alert_id, alert).await
+}
+
+pub async fn remove_whale_alert(state: &AppState, alert_id: &str) -> Result<bool, String> {
+    if !state.whale_alerts.is_durable() {
+        delete_whale_alert(&state.db, alert_id).await?;
+    }
+    state.whale_alerts.remove(alert_id).await
+}
+
+/// Reload persisted alerts into a non-durable store. Returns how many were restored.
+pub async fn restore_whale_alerts(
+    pool: &sqlx::PgPool,
+    store: &dyn crate::state_store::StateStore<WhaleAlert>,
+) -> Result<usize, String> {
+    if store.is_durable() {
+        return Ok(0);
+    }
+    let rows: Vec<WhaleAlertRow> = sqlx::query_as(
+        "SELECT alert_id, user_id, min_size_usd, chains, tokens, position_types, active, created_at FROM whale_alerts"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load whale alerts: {}", e))?;
+
+    let mut restored = 0;
+    for row in rows {
+        match alert_from_row(row) {
+            Ok(alert) => {
+                store.put(&alert.alert_id, &alert).await?;
+                restored += 1;
+            }
+            Err(e) => tracing::warn!("Skipping whale alert: {}", e),
+        }
+    }
+    Ok(restored)
+}
+
+// ==================== TRADE PERSISTENCE ====================
+
+/// Enum variant names ("Buy", "CloseLong", "Spot", ...) as stored in whale_trades.
+fn variant_name<T: Serialize>(value: &T) -> Result<String, String> {
+    match serde_json::to_value(value).map_err(|e| e.to_string())? {
+        serde_json::Value::String(name) => Ok(name),
+        other => Err(format!("Unexpected variant encoding: {}", other)),
+    }
+}
+
+fn parse_variant<T: serde::de::DeserializeOwned>(name: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).map_err(|e| format!("Bad value '{}': {}", name, e))
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct WhaleTradeRow {
+    trade_id: String,
+    chain: String,
+    token: String,
+    token_symbol: String,
+    trade_type: String,
+    size_usd: f64,
+    size_native: f64,
+    price: f64,
+    timestamp: i64,
+    wallet_address: String,
+    leverage: Option<f64>,
+    position_type: String,
+}
+
+impl TryFrom<WhaleTradeRow> for WhaleTrade {
+    type Error = String;
+
+    fn try_from(row: WhaleTradeRow) -> Result<Self, String> {
+        Ok(WhaleTrade {
+            trade_type: parse_variant(&row.trade_type)?,
+            position_type: parse_variant(&row.position_type)?,
+            trade_id: row.trade_id,
+            chain: row.chain,
+            token: row.token,
+            token_symbol: row.token_symbol,
+            size_usd: row.size_usd,
+            size_native: row.size_native,
+            price: row.price,
+            timestamp: row.timestamp,
+            wallet_address: row.wallet_address,
+            leverage: row.leverage,
+        })
+    }
+}
+
+/// Store a tracked trade and bump the wallet's lifetime totals, in one transaction.
+/// A trade already stored is left alone, so re-ingesting it doesn't count it twice.
+pub async fn persist_whale_trade(pool: &sqlx::PgPool, trade: &WhaleTrade, price_impact: f64) -> Result<(), String> {
+    let mut tx = pool.begin().await.map_err(|e| format!("Failed to start whale trade transaction: {}", e))?;
+    let inserted = sqlx::query(
+        r#"
+        INSERT INTO whale_trades (trade_id, chain, token, token_symbol, trade_type, size_usd, size_native, price, timestamp, wallet_address, leverage, position_type, price_impact)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        ON CONFLICT (trade_id) DO NOTHING
+        "#
+    )
+    .bind(&trade.trade_id)
+    .bind(&trade.chain)
+    .bind(&trade.token)
+    .bind(&trade.token_symbol)
+    .bind(variant_name(&trade.trade_type)?)
+    .bind(trade.size_usd)
+    .bind(trade.size_native)
+    .bind(trade.price)
+    .bind(trade.timestamp)
+    .bind(&trade.wallet_address)
+    .bind(trade.leverage)
+    .bind(variant_name(&trade.position_type)?)
+    .bind(price_impact)
+    .execute(&mut tx)
+    .await
+    .map_err(|e| format!("Failed to save whale trade: {}", e))?
+    .rows_affected();
+    if inserted == 0 {
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO whale_wallets (wallet_address, first_seen, last_seen, total_trades, total_volume_usd)
+        VALUES ($1, $2, $2, 1, $3)
+        ON CONFLICT (wallet_address) DO UPDATE SET
+            last_seen = GREATEST(whale_wallets.last_seen, EXCLUDED.last_seen),
+            total_trades = whale_wallets.total_trades + 1,
+            total_volume_usd = whale_wallets.total_volume_usd + EXCLUDED.total_volume_usd
+        "#
+    )
+    .bind(&trade.wallet_address)
+    .bind(trade.timestamp)
+    .bind(trade.size_usd)
+    .execute(&mut tx)
+    .await
+    .map_err(|e| format!("Failed to save whale wallet: {}", e))?;
+
+    tx.commit().await.map_err(|e| format!("Failed to commit whale trade: {}", e))
+}
+
+pub async fn load_whale_trades_since(pool: &sqlx::PgPool, since: i64) -> Result<Vec<WhaleTrade>, String> {
+    let rows: Vec<WhaleTradeRow> = sqlx::query_as(
+        "SELECT trade_id, chain, token, token_symbol, trade_type, size_usd, size_native, price, timestamp, wallet_address, leverage, position_type \
+         FROM whale_trades WHERE timestamp >= $1 ORDER BY timestamp"
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to load whale trades: {}", e))?;
+
+    rows.into_iter().map(WhaleTrade::try_from).collect()
+}
+
+// Per-wallet aggregates over trades since $1, matching track_whale_trade replayed in order:
+// - velocity is 3600 / the latest positive gap between trades (the first trade counts as 1h)
+// - price impact is the same 0.7/0.3 EMA starting from 0, unrolled as sum(0.3 * 0.7^age * impact)
+const WHALE_INFO_QUERY: &str = r#"
+    WITH recent AS (
+        SELECT
+            wallet_address,
+            trade_id,
+            size_usd,
+            price_impact,
+            timestamp - COALESCE(LAG(timestamp) OVER w, timestamp - 3600) AS gap,
+            ROW_NUMBER() OVER (PARTITION BY wallet_address ORDER BY timestamp DESC) AS age,
+            CASE
+                WHEN position_type = 'Long' OR (position_type = 'Spot' AND trade_type = 'Buy') THEN size_usd
+                WHEN position_type = 'Short' OR (position_type = 'Spot' AND trade_type = 'Sell') THEN -size_usd
+                ELSE 0
+            END AS signed_size
+        FROM whale_trades
+        WHERE timestamp >= $1
+        WINDOW w AS (PARTITION BY wallet_address ORDER BY timestamp)
+    )
+    SELECT
+        wallet_address,
+        SUM(size_usd)::float8 AS total_volume_24h,
+        COUNT(*) AS trade_count,
+        SUM(signed_size)::float8 AS net_position,
+        MAX(CASE WHEN age = 1 THEN trade_id END) AS last_trade_id,
+        COALESCE(3600.0 / (ARRAY_AGG(gap ORDER BY age) FILTER (WHERE gap > 0))[1], 0)::float8 AS trade_velocity,
+        SUM(price_impact * 0.3 * POWER(0.7, age - 1))::float8 AS price_impact_avg
+    FROM recent
+    GROUP BY wallet_address
+"#;
+
+type WhaleInfoRow = (String, f64, i64, f64, Option<String>, f64, f64);
+
+/// Rebuild WhaleInfo for every wallet that traded since `since`. `trades` supplies each
+/// wallet's last trade (normally the result of `load_whale_trades_since`).
+pub async fn load_whale_infos(pool: &sqlx::PgPool, since: i64, trades: &[WhaleTrade]) -> Result<HashMap<String, WhaleInfo>, String> {
+    let rows: Vec<WhaleInfoRow> = sqlx::query_as(WHALE_INFO_QUERY)
+        .bind(since)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Failed to aggregate whale wallets: {}", e))?;
+
+    let by_id: HashMap<&str, &WhaleTrade> = trades.iter().map(|t| (t.trade_id.as_str(), t)).collect();
+    Ok(rows
+        .into_iter()
+        .map(|(wallet_address, total_volume_24h, trade_count, net_position, last_trade_id, trade_velocity, price_impact_avg)| {
+            let trade_count = trade_count as usize;
+            let info = WhaleInfo {
+                wallet_address: wallet_address.clone(),
+                total_volume_24h,
+                trade_count,
+                avg_trade_size: if trade_count > 0 { total_volume_24h / trade_count as f64 } else { 0.0 },
+                net_position,
+                last_trade: last_trade_id.and_then(|id| by_id.get(id.as_str()).map(|t| (*t).clone())),
+                trade_velocity,
+                price_impact_avg,
+            };
+            (wallet_address, info)
+        })
+        .collect())
+}
+
+// ==================== ALERT MATCHING ====================
+
+pub fn matching_alerts<'a>(trade: &WhaleTrade, alerts: &'a [WhaleAlert]) -> Vec<&'a WhaleAlert> {
+    alerts.iter().filter(|alert| check_whale_alert(trade, alert)).collect()
+}
+
+/// Result of `dispatch_whale_trade`.
+pub struct WhaleDispatch {
+    pub price_impact: f64,
+    pub alerts_triggered: usize,
+}
+
+/// Record a whale trade with its estimated price impact and notify every user whose
+/// alert it matches.
+pub async fn dispatch_whale_trade(state: &AppState, trade: &WhaleTrade) -> WhaleDispatch {
+    let price_impact = estimate_price_impact(&trade.chain, &trade.token, trade.size_usd).await;
+    push_capped(&mut *state.whale_trades.write().await, trade.clone(), whale_feed_capacity());
+    if let Err(e) = persist_whale_trade(&state.db, trade, price_impact).await {
+        tracing::error!("{}", e);
+    }
+
+    let alerts = match state.whale_alerts.values().await {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            tracing::error!("Failed to load whale alerts: {}", e);
+            return WhaleDispatch { price_impact, alerts_triggered: 0 };
+        }
+    };
+
+    let matches = matching_alerts(trade, &alerts);
+    for alert in &matches {
+        state.notifications.push(crate::notifications::create_notification(
+            alert.user_id,
+            format!(
+                "🐋 ${:.0} {:?} on {} ({}) by {}, ~{:.2}% price impact",
+                trade.size_usd, trade.trade_type, trade.token_symbol, trade.chain, trade.wallet_address, price_impact
+            ),
+            "whale".to_string(),
+            "high".to_string(),
+        )).await;
+    }
+    WhaleDispatch { price_impact, alerts_triggered: matches.len() }
+}
+
 pub fn check_whale_alert(
     trade: &WhaleTrade,
     alert: &WhaleAlert,
@@ -468,26 +979,407 @@ pub fn check_whale_alert(
     true
 }
 
+// ==================== TRADE FEED ====================
+
+/// Max whale trades kept in memory, from `WHALE_FEED_CAPACITY` (default 10,000).
+pub fn whale_feed_capacity() -> usize {
+    std::env::var("WHALE_FEED_CAPACITY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|c| *c > 0)
+        .unwrap_or(10_000)
+}
+
+/// Append a trade, dropping the oldest once the feed holds `capacity` trades.
+pub fn push_capped(feed: &mut VecDeque<WhaleTrade>, trade: WhaleTrade, capacity: usize) {
+    while feed.len() >= capacity.max(1) {
+        feed.pop_front();
+    }
+    feed.push_back(trade);
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WhaleTradesQuery {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub chain: Option<String>,
+    pub min_size_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WhaleTradesResponse {
+    pub trades: Vec<WhaleTrade>,
+    pub total: usize, // Matching trades before pagination
+    pub limit: usize,
+    pub offset: usize,
+}
+
+const DEFAULT_FEED_LIMIT: usize = 50;
+const MAX_FEED_LIMIT: usize = 500;
+
+/// Filter the feed, newest first, and return one page.
+pub fn query_trades<'a>(trades: impl Iterator<Item = &'a WhaleTrade>, query: &WhaleTradesQuery) -> WhaleTradesResponse {
+    let mut matching: Vec<&WhaleTrade> = trades
+        .filter(|t| query.chain.as_ref().is_none_or(|c| &t.chain == c))
+        .filter(|t| query.min_size_usd.is_none_or(|min| t.size_usd >= min))
+        .collect();
+    matching.sort_by_key(|t| std::cmp::Reverse(t.timestamp));
+
+    let limit = query.limit.unwrap_or(DEFAULT_FEED_LIMIT).clamp(1, MAX_FEED_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    WhaleTradesResponse {
+        total: matching.len(),
+        trades: matching.into_iter().skip(offset).take(limit).cloned().collect(),
+        limit,
+        offset,
+    }
+}
+
 // ==================== API HANDLERS ====================
 
-pub async fn get_whale_stats_handler(
+pub async fn get_whale_trades_handler(
     State(state): State<AppState>,
+    Query(query): Query<WhaleTradesQuery>,
 ) -> impl IntoResponse {
     let whale_trades = state.whale_trades.read().await;
-    
-    // In a real app, we'd persist whales to DB, but for now we rebuild stats from in-memory trades
-    // or we would have a persisted whale_map. 
-    // Let's build a temporary whale_map from the trades we have.
+    (StatusCode::OK, Json(query_trades(whale_trades.iter(), &query)))
+}
+
+pub async fn get_whale_stats_handler(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let since = Utc::now().timestamp() - 86400;
+    let persisted = match load_whale_trades_since(&state.db, since).await {
+        Ok(trades) => load_whale_infos(&state.db, since, &trades).await.map(|map| (trades, map)),
+        Err(e) => Err(e),
+    };
+
+    let (trades, whale_map) = match persisted {
+        Ok(data) => data,
+        Err(e) => {
+            // Rebuild from the in-memory feed if the DB is unavailable
+            tracing::warn!("Whale stats from memory: {}", e);
+            let trades: Vec<WhaleTrade> = state.whale_trades.read().await.iter().cloned().collect();
+            (trades.clone(), replay_whale_map(trades))
+        }
+    };
+
+    (StatusCode::OK, Json(calculate_whale_stats(&trades, &whale_map)))
+}
+
+pub async fn get_whale_sentiment_handler(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> impl IntoResponse {
+    let sentiment = compute_sentiment(&token, state.whale_trades.read().await.iter(), Utc::now().timestamp(), sentiment_half_life_secs());
+    (StatusCode::OK, Json(sentiment))
+}
+
+/// Build WhaleInfo by replaying trades (oldest first) through track_whale_trade.
+pub fn replay_whale_map(trades: Vec<WhaleTrade>) -> HashMap<String, WhaleInfo> {
     let mut whale_map = HashMap::new();
-    
-    for trade in whale_trades.iter() {
-        // Calculate basic impact for stats
-        let impact = calculate_price_impact(trade.size_usd, &trade.chain);
-        track_whale_trade(trade.clone(), &mut whale_map, impact);
+    for trade in trades {
+        let impact = heuristic_price_impact(trade.size_usd, &trade.chain);
+        track_whale_trade(trade, &mut whale_map, impact);
     }
-    
-    let stats = calculate_whale_stats(&whale_trades, &whale_map);
-    
-    (StatusCode::OK, Json(stats))
+    whale_map
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(chain: &str, token: &str, size_usd: f64, position_type: PositionType) -> WhaleTrade {
+        WhaleTrade {
+            trade_id: "t1".to_string(),
+            chain: chain.to_string(),
+            token: token.to_string(),
+            token_symbol: "TKN".to_string(),
+            trade_type: TradeType::Buy,
+            size_usd,
+            size_native: 0.0,
+            price: 1.0,
+            timestamp: 0,
+            wallet_address: "whale".to_string(),
+            leverage: None,
+            position_type,
+        }
+    }
+
+    fn alert(user_id: i64, min_size_usd: f64, chains: &[&str], position_types: &[&str]) -> WhaleAlert {
+        create_whale_alert(CreateWhaleAlertRequest {
+            user_id,
+            min_size_usd,
+            chains: Some(chains.iter().map(|c| c.to_string()).collect()),
+            tokens: None,
+            position_types: Some(position_types.iter().map(|p| p.to_string()).collect()),
+        })
+    }
+
+    fn quote(in_amount: u64, out_amount: u64, impact: &str) -> crate::execution::QuoteResponse {
+        crate::execution::QuoteResponse {
+            inputMint: crate::execution::WSOL_MINT.to_string(),
+            inAmount: in_amount.to_string(),
+            outputMint: "Mint".to_string(),
+            outAmount: out_amount.to_string(),
+            otherAmountThreshold: out_amount.to_string(),
+            swapMode: "ExactIn".to_string(),
+            slippageBps: 50,
+            platformFee: None,
+            priceImpactPct: impact.to_string(),
+            routePlan: Vec::new(),
+            contextSlot: None,
+            timeTaken: None,
+        }
+    }
+
+    #[test]
+    fn test_impact_from_quote_depth() {
+        // Reference: 1000 tokens per lamport. At size: 800 per lamport => 20% worse
+        let reference = quote(1_000, 1_000_000, "0");
+        let impact = impact_from_quotes(&reference, &quote(1_000_000, 800_000_000, "0.05")).unwrap();
+        assert!((impact - 20.0).abs() < 1e-9);
+
+        // Jupiter's own figure wins when it reports more
+        let impact = impact_from_quotes(&reference, &quote(1_000_000, 990_000_000, "0.03")).unwrap();
+        assert!((impact - 3.0).abs() < 1e-9);
+
+        assert_eq!(impact_from_quotes(&reference, &quote(1_000_000, 0, "0")), None);
+        // The heuristic stays small for modest Solana trades
+        assert!(heuristic_price_impact(10_000.0, "solana") < 1.0);
+    }
+
+    #[test]
+    fn test_alert_ids_are_unique() {
+        let a = alert(1, 100.0, &[], &[]);
+        let b = alert(1, 100.0, &[], &[]);
+        assert_ne!(a.alert_id, b.alert_id);
+        assert!(a.alert_id.starts_with("alert_1_"));
+    }
+
+    #[test]
+    fn test_matching_alerts() {
+        let alerts = vec![
+            alert(1, 100_000.0, &[], &[]),
+            alert(2, 500_000.0, &[], &[]),
+            alert(3, 10_000.0, &["eth"], &[]),
+            alert(4, 10_000.0, &[], &["long"]),
+        ];
+
+        let matched: Vec<i64> = matching_alerts(&trade("solana", "BONK", 150_000.0, PositionType::Spot), &alerts)
+            .iter()
+            .map(|a| a.user_id)
+            .collect();
+        assert_eq!(matched, vec![1]);
+
+        let mut inactive = alerts[0].clone();
+        inactive.active = false;
+        assert!(matching_alerts(&trade("solana", "BONK", 150_000.0, PositionType::Spot), &[inactive]).is_empty());
+    }
+
+    #[test]
+    fn test_alert_row_round_trip() {
+        let original = alert(7, 250_000.0, &["solana"], &["long", "short"]);
+        let row: WhaleAlertRow = (
+            original.alert_id.clone(),
+            original.user_id,
+            original.min_size_usd,
+            encode_list(&original.chains).unwrap(),
+            encode_list(&original.tokens).unwrap(),
+            encode_list(&original.position_types).unwrap(),
+            original.active,
+            original.created_at,
+        );
+        let restored = alert_from_row(row).unwrap();
+        assert_eq!(restored.alert_id, original.alert_id);
+        assert_eq!(restored.chains, vec!["solana".to_string()]);
+        assert_eq!(restored.position_types, vec![PositionType::Long, PositionType::Short]);
+    }
+
+    #[test]
+    fn test_feed_is_capped() {
+        let mut feed = VecDeque::new();
+        for i in 0..5 {
+            let mut t = trade("solana", "Mint", 100_000.0, PositionType::Spot);
+            t.timestamp = i;
+            push_capped(&mut feed, t, 3);
+        }
+        assert_eq!(feed.len(), 3);
+        assert_eq!(feed.iter().map(|t| t.timestamp).collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_query_trades_filters_and_paginates() {
+        let feed: Vec<WhaleTrade> = [("solana", 200_000.0, 1), ("eth", 500_000.0, 2), ("solana", 50_000.0, 3), ("solana", 300_000.0, 4)]
+            .iter()
+            .map(|(chain, size, ts)| {
+                let mut t = trade(chain, "Mint", *size, PositionType::Spot);
+                t.timestamp = *ts;
+                t
+            })
+            .collect();
+
+        let query = WhaleTradesQuery { limit: Some(1), offset: Some(1), chain: Some("solana".to_string()), min_size_usd: Some(100_000.0) };
+        let page = query_trades(feed.iter(), &query);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.trades.len(), 1);
+        assert_eq!(page.trades[0].timestamp, 1); // Newest first, second page
+
+        let all = query_trades(feed.iter(), &WhaleTradesQuery { limit: None, offset: None, chain: None, min_size_usd: None });
+        assert_eq!(all.trades.iter().map(|t| t.timestamp).collect::<Vec<_>>(), vec![4, 3, 2, 1]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_reingested_trade_is_counted_once() {
+        let pool = crate::test_db::pool().await;
+        for table in ["whale_trades", "whale_wallets"] {
+            sqlx::query(&format!("CREATE TEMP TABLE {table} (LIKE public.{table} INCLUDING DEFAULTS INCLUDING CONSTRAINTS INCLUDING INDEXES)"))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let t = trade("solana", "Mint", 250_000.0, PositionType::Spot);
+        persist_whale_trade(&pool, &t, 1.0).await.unwrap();
+        persist_whale_trade(&pool, &t, 1.0).await.unwrap();
+
+        let (total_trades, total_volume_usd): (i64, f64) =
+            sqlx::query_as("SELECT total_trades, total_volume_usd FROM whale_wallets WHERE wallet_address = 'whale'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(total_trades, 1);
+        assert_eq!(total_volume_usd, 250_000.0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_persisted_stats_match_in_memory() {
+        let pool = crate::test_db::pool().await;
+        for statement in include_str!("../migrations/0009_whales.sql").split(';').filter(|s| !s.trim().is_empty()) {
+            sqlx::query(statement).execute(&pool).await.unwrap();
+        }
+
+        // Unique wallets so other rows in the database don't interfere
+        let run = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now().timestamp();
+        let specs: [(&str, TradeType, PositionType, f64, i64); 6] = [
+            ("a", TradeType::Long, PositionType::Long, 400_000.0, now - 7200),
+            ("a", TradeType::Short, PositionType::Short, 100_000.0, now - 3600),
+            ("a", TradeType::Buy, PositionType::Spot, 250_000.0, now - 600),
+            ("b", TradeType::Sell, PositionType::Spot, 300_000.0, now - 1800),
+            ("b", TradeType::Buy, PositionType::Spot, 900_000.0, now - 90_000), // Outside the 24h window
+            ("c", TradeType::Short, PositionType::Short, 150_000.0, now - 60),
+        ];
+        let trades: Vec<WhaleTrade> = specs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (wallet, trade_type, position_type, size_usd, timestamp))| WhaleTrade {
+                trade_id: format!("{}_{}", run, i),
+                wallet_address: format!("{}_{}", run, wallet),
+                trade_type,
+                timestamp,
+                ..trade("solana", "Mint", size_usd, position_type)
+            })
+            .collect();
+        for t in &trades {
+            persist_whale_trade(&pool, t, heuristic_price_impact(t.size_usd, &t.chain)).await.unwrap();
+        }
+
+        let since = now - 86400;
+        let loaded: Vec<WhaleTrade> = load_whale_trades_since(&pool, since).await.unwrap()
+            .into_iter()
+            .filter(|t| t.trade_id.starts_with(&run))
+            .collect();
+        let db_map: HashMap<String, WhaleInfo> = load_whale_infos(&pool, since, &loaded).await.unwrap()
+            .into_iter()
+            .filter(|(wallet, _)| wallet.starts_with(&run))
+            .collect();
+
+        let mut recent: Vec<WhaleTrade> = trades.iter().filter(|t| t.timestamp >= since).cloned().collect();
+        recent.sort_by_key(|t| t.timestamp);
+        let memory_map = replay_whale_map(recent.clone());
+
+        assert_eq!(db_map.len(), 3);
+        for (wallet, expected) in &memory_map {
+            let actual = &db_map[wallet];
+            assert_eq!(actual.trade_count, expected.trade_count);
+            assert!((actual.total_volume_24h - expected.total_volume_24h).abs() < 1e-6);
+            assert!((actual.avg_trade_size - expected.avg_trade_size).abs() < 1e-6);
+            assert!((actual.net_position - expected.net_position).abs() < 1e-6);
+            assert!((actual.trade_velocity - expected.trade_velocity).abs() < 1e-9);
+            assert!((actual.price_impact_avg - expected.price_impact_avg).abs() < 1e-9);
+            assert_eq!(actual.last_trade.as_ref().map(|t| &t.trade_id), expected.last_trade.as_ref().map(|t| &t.trade_id));
+        }
+
+        let db_stats = calculate_whale_stats(&loaded, &db_map);
+        let memory_stats = calculate_whale_stats(&recent, &memory_map);
+        assert!((db_stats.total_volume_24h - memory_stats.total_volume_24h).abs() < 1e-6);
+        assert!((db_stats.long_short_ratio - memory_stats.long_short_ratio).abs() < 1e-9);
+        assert_eq!(db_stats.largest_trade_24h.map(|t| t.trade_id), memory_stats.largest_trade_24h.map(|t| t.trade_id));
+    }
+
+    #[test]
+    fn test_sentiment_weights_recent_trades() {
+        let at = |mut t: WhaleTrade, timestamp: i64, trade_type: TradeType| {
+            t.timestamp = timestamp;
+            t.trade_type = trade_type;
+            t
+        };
+        let now = 100_000;
+        let trades = vec![
+            // An hour-old $100k buy is worth $50k against a fresh $50k sell
+            at(trade("solana", "Mint", 100_000.0, PositionType::Spot), now - 3600, TradeType::Buy),
+            at(trade("solana", "Mint", 50_000.0, PositionType::Spot), now, TradeType::Sell),
+            // Out of the six half-life window, and a different token
+            at(trade("solana", "Mint", 1_000_000.0, PositionType::Long), now - 6 * 3600 - 1, TradeType::Long),
+            at(trade("solana", "Other", 1_000_000.0, PositionType::Spot), now, TradeType::Buy),
+        ];
+
+        let s = compute_sentiment("Mint", &trades, now, 3600);
+        assert_eq!(s.trade_count, 2);
+        // Neither is a first entry (the stale long came earlier), so both carry 70% confidence
+        assert!((s.long_weight - 35_000.0).abs() < 1e-6);
+        assert!((s.short_weight - 35_000.0).abs() < 1e-6);
+        assert!(s.sentiment.abs() < 1e-9);
+
+        assert_eq!(compute_sentiment("Nothing", &trades, now, 3600).sentiment, 0.0);
+        let shorts = vec![at(trade("solana", "Mint", 10_000.0, PositionType::Short), now, TradeType::Short)];
+        assert_eq!(compute_sentiment("Mint", &shorts, now, 3600).sentiment, -1.0);
+    }
+
+    #[test]
+    fn test_sentiment_confidence_matches_activity_scoring() {
+        let now = 100_000;
+        let by = |wallet: &str, size_usd: f64, timestamp: i64| {
+            let mut t = trade("solana", "Mint", size_usd, PositionType::Spot);
+            t.wallet_address = wallet.to_string();
+            t.timestamp = timestamp;
+            t
+        };
+        // A wallet adding to its entry, and a fresh $600k entry
+        let trades = vec![
+            by("whale", 10_000.0, now - 10),
+            by("whale", 20_000.0, now),
+            by("fresh", 600_000.0, now),
+        ];
+
+        let expected: f64 = trades.iter()
+            .map(|t| t.size_usd * detect_whale_activity(t, &trades, 0.0, 0.0).confidence_score / 100.0 * 0.5f64.powf((now - t.timestamp) as f64 / 3600.0))
+            .sum();
+        let s = compute_sentiment("Mint", &trades, now, 3600);
+        assert!((s.long_weight - expected).abs() < 1e-6);
+        // The fresh wallet's size and first entry both count: 80%
+        assert!((activity_confidence(false, true, 600_000.0) - 80.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_negative_sentiment_tightens_stop() {
+        assert_eq!(sentiment_stop_loss(20.0, -0.8, Some(-0.6)), 10.0);
+        assert_eq!(sentiment_stop_loss(20.0, -0.5, Some(-0.6)), 20.0);
+        assert_eq!(sentiment_stop_loss(20.0, -1.0, None), 20.0);
+        assert_eq!(sentiment_stop_loss(0.0, -1.0, Some(-0.6)), 0.0);
+    }
+}